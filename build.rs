@@ -2,7 +2,48 @@ use clap::CommandFactory;
 use std::fs;
 use std::path::PathBuf;
 
-include!("src/cli.rs");
+// The binary's argv helpers in src/cli.rs are never called here
+#[allow(dead_code)]
+mod cli {
+    include!("src/cli.rs");
+}
+use cli::Cli;
+
+/// The man page only needs the shape of the arguments, so what src/cli.rs takes from
+/// the library is stood in for here
+mod types {
+    /// `--group-by`, parsed for real by the library's `types::GroupBy`
+    #[derive(Clone, Debug)]
+    pub struct GroupBy;
+
+    impl std::str::FromStr for GroupBy {
+        type Err = std::convert::Infallible;
+
+        fn from_str(_: &str) -> Result<Self, Self::Err> {
+            Ok(GroupBy)
+        }
+    }
+
+    /// SIZE flags such as `--max-memory`, parsed for real by the library's
+    /// `types::memory::parse_size_kib`
+    pub mod memory {
+        pub fn parse_size_kib(_: &str) -> Result<u64, String> {
+            Ok(0)
+        }
+    }
+
+    /// `--sort-by` and `--csv-sort`, parsed for real by the library's `types::ProcessSort`
+    #[derive(Clone, Debug)]
+    pub struct ProcessSort;
+
+    impl std::str::FromStr for ProcessSort {
+        type Err = std::convert::Infallible;
+
+        fn from_str(_: &str) -> Result<Self, Self::Err> {
+            Ok(ProcessSort)
+        }
+    }
+}
 
 fn main() -> std::io::Result<()> {
     // Set build-time environment variables for version info
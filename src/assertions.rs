@@ -0,0 +1,327 @@
+use crate::budget::{self, Budget};
+use crate::cli::ExitCodePolicy;
+use crate::compare;
+use crate::reporter::{describe_memory_limit, format_memory, stderr_color_choice};
+use crate::types::{JobProfile, KillReason, ThresholdCheck, Verdict, exit_codes};
+use std::io::Write;
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+/// Post-run checks that can override the exit code memwatch returns
+#[derive(Debug, Clone)]
+pub struct AssertionConfig {
+    /// Fail when no memory data was captured at all
+    pub fail_on_empty: bool,
+    /// Memory budgets loaded from `--budgets`
+    pub budgets: Vec<Budget>,
+    /// Exit code used when the peak is above `--fail-above`
+    pub fail_above_exit_code: i32,
+    /// `--baseline-fail-above`: the increase over the baseline's peak, in percent,
+    /// that fails the run
+    pub baseline_fail_above_percent: Option<f64>,
+}
+
+impl Default for AssertionConfig {
+    fn default() -> Self {
+        Self {
+            fail_on_empty: false,
+            budgets: Vec::new(),
+            fail_above_exit_code: exit_codes::THRESHOLD_EXCEEDED,
+            baseline_fail_above_percent: None,
+        }
+    }
+}
+
+/// A failed post-run check
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertionFailure {
+    pub exit_code: i32,
+    pub message: String,
+}
+
+/// Result of evaluating all checks
+#[derive(Debug, Default)]
+pub struct Evaluation {
+    pub failures: Vec<AssertionFailure>,
+    /// Problems worth reporting that do not fail the run
+    pub warnings: Vec<String>,
+}
+
+/// Compare a peak against the `--warn-above` / `--fail-above` thresholds (exceeding
+/// means strictly above). Failing takes precedence over warning.
+pub fn check_thresholds(
+    peak_kib: u64,
+    warn_above_kib: Option<u64>,
+    fail_above_kib: Option<u64>,
+) -> ThresholdCheck {
+    let verdict = if fail_above_kib.is_some_and(|limit| peak_kib > limit) {
+        Verdict::Fail
+    } else if warn_above_kib.is_some_and(|limit| peak_kib > limit) {
+        Verdict::Warn
+    } else {
+        Verdict::Pass
+    };
+
+    ThresholdCheck {
+        warn_above_kib,
+        fail_above_kib,
+        verdict,
+    }
+}
+
+/// Evaluate every configured check against a finished profile
+pub fn evaluate(profile: &JobProfile, config: &AssertionConfig) -> Evaluation {
+    let mut failures = Vec::new();
+    let mut warnings = Vec::new();
+
+    if profile.killed_reason == Some(KillReason::MemoryLimit) {
+        failures.push(AssertionFailure {
+            exit_code: exit_codes::MEMORY_LIMIT_EXCEEDED,
+            message: format!(
+                "Job killed for exceeding {}",
+                describe_memory_limit(profile)
+            ),
+        });
+    }
+
+    if let Some(ref check) = profile.thresholds {
+        let peak = profile.max_total_rss_kib;
+        match (check.verdict, check.fail_above_kib, check.warn_above_kib) {
+            (Verdict::Fail, Some(limit), _) => failures.push(AssertionFailure {
+                exit_code: config.fail_above_exit_code,
+                message: format!(
+                    "Peak total RSS {} exceeded --fail-above {} by {}",
+                    format_memory(peak),
+                    format_memory(limit),
+                    format_memory(peak - limit)
+                ),
+            }),
+            (Verdict::Warn, _, Some(limit)) => warnings.push(format!(
+                "Peak total RSS {} exceeded --warn-above {} by {}",
+                format_memory(peak),
+                format_memory(limit),
+                format_memory(peak - limit)
+            )),
+            _ => {}
+        }
+    }
+
+    if let Some(ref baseline) = profile.baseline_comparison
+        && let Some(limit) = config.baseline_fail_above_percent
+        && let Some(change) =
+            compare::percent_change(profile.max_total_rss_kib as f64, baseline.peak_kib as f64)
+        && change > limit
+    {
+        failures.push(AssertionFailure {
+            exit_code: exit_codes::THRESHOLD_EXCEEDED,
+            message: format!(
+                "Peak total RSS {} is {:.1}% above the baseline's {} (--baseline-fail-above {}%)",
+                format_memory(profile.max_total_rss_kib),
+                change,
+                format_memory(baseline.peak_kib),
+                limit
+            ),
+        });
+    }
+
+    if !config.budgets.is_empty() {
+        let report = budget::check(profile, &config.budgets);
+        for pattern in report.unmatched_patterns {
+            warnings.push(format!(
+                "Budget '{}' did not match the command (check for typos)",
+                pattern
+            ));
+        }
+        for violation in report.violations {
+            failures.push(AssertionFailure {
+                exit_code: exit_codes::THRESHOLD_EXCEEDED,
+                message: format!("Budget exceeded: {}", violation.message),
+            });
+        }
+    }
+
+    if profile.timed_out {
+        failures.push(AssertionFailure {
+            exit_code: exit_codes::TIMED_OUT,
+            message: format!(
+                "Job killed after exceeding --timeout {}s",
+                profile.timeout_seconds.unwrap_or(0.0)
+            ),
+        });
+    }
+
+    if config.fail_on_empty && profile.max_total_rss_kib == 0 {
+        failures.push(AssertionFailure {
+            exit_code: exit_codes::NO_DATA,
+            message: "No memory data was captured (--fail-on-empty)".to_string(),
+        });
+    }
+
+    Evaluation { failures, warnings }
+}
+
+/// Print warnings in yellow and failures in red on stderr
+pub fn print_evaluation(evaluation: &Evaluation) {
+    let mut stderr = StandardStream::stderr(stderr_color_choice());
+
+    for warning in &evaluation.warnings {
+        let _ = stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
+        let _ = write!(stderr, "Warning: {}", warning);
+        let _ = stderr.reset();
+        let _ = writeln!(stderr);
+    }
+    for failure in &evaluation.failures {
+        let _ = stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red)));
+        let _ = write!(stderr, "Error: {}", failure.message);
+        let _ = stderr.reset();
+        let _ = writeln!(stderr);
+    }
+}
+
+/// Exit code to return under `policy`.
+///
+/// With `Child`, a non-zero child exit code is passed through untouched and the first
+/// failed check only applies when the child succeeded. With `Memwatch`, the first failed
+/// check wins regardless of the child. `AlwaysZero` ignores both.
+pub fn resolve_exit_code(
+    policy: ExitCodePolicy,
+    child_exit_code: i32,
+    failures: &[AssertionFailure],
+) -> i32 {
+    let failure_code = failures.first().map(|f| f.exit_code);
+
+    match policy {
+        ExitCodePolicy::Child if child_exit_code != 0 => child_exit_code,
+        ExitCodePolicy::Child | ExitCodePolicy::Memwatch => failure_code.unwrap_or(child_exit_code),
+        ExitCodePolicy::AlwaysZero => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::BaselineComparison;
+
+    #[test]
+    fn test_fail_on_empty() {
+        let empty = JobProfile::default();
+        let config = AssertionConfig {
+            fail_on_empty: true,
+            ..Default::default()
+        };
+
+        let failures = evaluate(&empty, &config).failures;
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            resolve_exit_code(ExitCodePolicy::Child, 0, &failures),
+            exit_codes::NO_DATA
+        );
+
+        // Disabled check never fails
+        assert!(
+            evaluate(&empty, &AssertionConfig::default())
+                .failures
+                .is_empty()
+        );
+
+        let captured = JobProfile {
+            max_total_rss_kib: 1024,
+            ..Default::default()
+        };
+        let failures = evaluate(&captured, &config).failures;
+        assert!(failures.is_empty());
+        assert_eq!(resolve_exit_code(ExitCodePolicy::Child, 7, &failures), 7);
+    }
+
+    #[test]
+    fn test_fail_and_warn_above() {
+        let mut profile = JobProfile {
+            max_total_rss_kib: 3 * 1024,
+            ..Default::default()
+        };
+        let config = AssertionConfig {
+            fail_above_exit_code: 42,
+            ..Default::default()
+        };
+
+        profile.thresholds = Some(check_thresholds(3 * 1024, Some(1024), Some(2048)));
+        assert_eq!(profile.thresholds.as_ref().unwrap().verdict, Verdict::Fail);
+        let evaluation = evaluate(&profile, &config);
+        assert_eq!(evaluation.failures[0].exit_code, 42);
+        assert!(evaluation.failures[0].message.contains("by 1.0 MiB"));
+        assert!(evaluation.warnings.is_empty());
+
+        profile.thresholds = Some(check_thresholds(3 * 1024, Some(1024), Some(4096)));
+        let evaluation = evaluate(&profile, &config);
+        assert!(evaluation.failures.is_empty());
+        assert_eq!(evaluation.warnings.len(), 1);
+
+        // Exactly at the threshold is not above it
+        let check = check_thresholds(3 * 1024, Some(3 * 1024), None);
+        assert_eq!(check.verdict, Verdict::Pass);
+    }
+
+    #[test]
+    fn test_baseline_fail_above() {
+        let profile = JobProfile {
+            max_total_rss_kib: 1100,
+            baseline_comparison: Some(BaselineComparison {
+                path: "old.json".to_string(),
+                peak_kib: 1000,
+                duration_seconds: 1.0,
+                group_peaks_kib: Default::default(),
+            }),
+            ..Default::default()
+        };
+        let config = |baseline_fail_above_percent| AssertionConfig {
+            baseline_fail_above_percent,
+            ..Default::default()
+        };
+
+        let failures = evaluate(&profile, &config(Some(5.0))).failures;
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].exit_code, exit_codes::THRESHOLD_EXCEEDED);
+        assert!(failures[0].message.contains("10.0% above"));
+
+        // A 10% increase is not above a 10% limit, and without a limit nothing fails
+        assert!(evaluate(&profile, &config(Some(10.0))).failures.is_empty());
+        assert!(evaluate(&profile, &config(None)).failures.is_empty());
+
+        // Without a baseline there is nothing to compare against
+        let unbaselined = JobProfile {
+            baseline_comparison: None,
+            ..profile
+        };
+        assert!(
+            evaluate(&unbaselined, &config(Some(5.0)))
+                .failures
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_resolve_exit_code_policies() {
+        let failures = vec![AssertionFailure {
+            exit_code: exit_codes::THRESHOLD_EXCEEDED,
+            message: "Budget exceeded".to_string(),
+        }];
+
+        // Child: a failing child keeps its own code, a successful one picks up the failure
+        assert_eq!(resolve_exit_code(ExitCodePolicy::Child, 2, &failures), 2);
+        assert_eq!(
+            resolve_exit_code(ExitCodePolicy::Child, 0, &failures),
+            exit_codes::THRESHOLD_EXCEEDED
+        );
+
+        // Memwatch: failed checks dominate
+        assert_eq!(
+            resolve_exit_code(ExitCodePolicy::Memwatch, 2, &failures),
+            exit_codes::THRESHOLD_EXCEEDED
+        );
+        assert_eq!(resolve_exit_code(ExitCodePolicy::Memwatch, 2, &[]), 2);
+
+        assert_eq!(
+            resolve_exit_code(ExitCodePolicy::AlwaysZero, 2, &failures),
+            0
+        );
+    }
+}
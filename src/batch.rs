@@ -0,0 +1,137 @@
+use crate::reporter::{color_choice, format_duration, format_memory};
+use crate::types::JobProfile;
+use anyhow::{Context, Result};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+/// Parse a batch file: one command line per row, shell-quoted.
+/// Blank lines and lines starting with `#` are ignored.
+pub fn parse_commands(content: &str) -> Result<Vec<Vec<String>>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_num, line)| parse_command(line).context(format!("Line {}", line_num)))
+        .collect()
+}
+
+/// Split one shell-quoted command line into arguments
+pub fn parse_command(line: &str) -> Result<Vec<String>> {
+    let args =
+        shell_words::split(line).context(format!("Invalid command line: {}", line.trim()))?;
+    if args.is_empty() {
+        anyhow::bail!("Empty command");
+    }
+    Ok(args)
+}
+
+/// Index of the run with the largest peak; runs are sequential, so this is also the
+/// peak across the whole batch
+pub fn overall_peak(profiles: &[JobProfile]) -> Option<usize> {
+    profiles
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, p)| (p.max_total_rss_kib, std::cmp::Reverse(*i)))
+        .map(|(i, _)| i)
+}
+
+/// Print every run's peak side by side, plus the batch totals
+pub fn print_batch_summary(profiles: &[JobProfile]) {
+    let mut stdout = StandardStream::stdout(color_choice());
+
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true));
+    print!("\nBATCH SUMMARY");
+    let _ = stdout.reset();
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    print!(" ({} commands)", profiles.len());
+    let _ = stdout.reset();
+    println!();
+
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    println!(
+        "  {:>3}  {:>12}  {:>8}  {:>4}  COMMAND",
+        "#", "TOTAL PEAK", "DURATION", "EXIT"
+    );
+    let _ = stdout.reset();
+
+    let worst = overall_peak(profiles);
+    for (i, profile) in profiles.iter().enumerate() {
+        print!("  {:>3}  ", i + 1);
+
+        let color = if Some(i) == worst {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(color)));
+        print!("{:>12}  ", format_memory(profile.max_total_rss_kib));
+        let _ = stdout.reset();
+
+        print!("{:>8}  ", format_duration(profile.duration_seconds));
+        match profile.exit_code {
+            Some(code) => print!("{:>4}  ", code),
+            None => print!("{:>4}  ", "-"),
+        }
+        println!("{}", profile.command.join(" "));
+    }
+
+    if let Some(worst) = worst {
+        let total_duration: f64 = profiles.iter().map(|p| p.duration_seconds).sum();
+
+        println!();
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
+        print!(
+            "  Overall peak:    {}",
+            format_memory(profiles[worst].max_total_rss_kib)
+        );
+        let _ = stdout.reset();
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+        print!(" (#{})", worst + 1);
+        let _ = stdout.reset();
+        println!();
+        println!("  Total duration:  {}", format_duration(total_duration));
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let content = r#"
+# warm-up
+cargo build --release
+
+python3 -c "print('a b')"
+  ./bench --size 'large input'
+"#;
+
+        let commands = parse_commands(content).unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                vec!["cargo", "build", "--release"],
+                vec!["python3", "-c", "print('a b')"],
+                vec!["./bench", "--size", "large input"],
+            ]
+        );
+
+        let err = parse_commands("ok\necho \"unterminated\n").unwrap_err();
+        assert!(format!("{:#}", err).contains("Line 2"));
+    }
+
+    #[test]
+    fn test_overall_peak_prefers_first_of_equal_peaks() {
+        let profile = |peak| JobProfile {
+            max_total_rss_kib: peak,
+            ..Default::default()
+        };
+        assert_eq!(
+            overall_peak(&[profile(10), profile(30), profile(30), profile(5)]),
+            Some(1)
+        );
+        assert_eq!(overall_peak(&[]), None);
+    }
+}
@@ -0,0 +1,353 @@
+//! Benchmark mode (`run --runs N`): the same command profiled several times in a row,
+//! so the spread of the peak is visible rather than a single noisy number.
+
+use crate::batch::overall_peak;
+use crate::reporter::{color_choice, format_duration, format_memory};
+use crate::stats::Stats;
+use crate::types::JobProfile;
+use anyhow::Result;
+use serde::Serialize;
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+/// `--runs`, `--warmup`, and `--runs-fail-fast`
+#[derive(Debug, Clone, Copy)]
+pub struct BenchmarkOptions {
+    pub runs: u32,
+    /// Warmup runs done beforehand by `run_warmups`; only recorded here
+    pub warmup: usize,
+    pub fail_fast: bool,
+    /// Print a line on stderr as each run finishes
+    pub progress: bool,
+}
+
+/// Every measured run plus the statistics over them, as printed by `--json`
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub runs: Vec<JobProfile>,
+    pub aggregate: Aggregate,
+}
+
+/// Statistics over the measured runs; run numbers are 1-based
+#[derive(Debug, Clone, Serialize)]
+pub struct Aggregate {
+    pub runs: usize,
+    pub warmup: usize,
+    pub peak_rss_kib: Stats,
+    pub duration_seconds: Stats,
+    /// Run with the largest peak (the first of equal peaks)
+    pub worst_run: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_runs: Vec<usize>,
+    /// Whether --runs-fail-fast stopped before every run was done
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stopped_early: bool,
+}
+
+impl BenchmarkReport {
+    /// The run with the largest peak
+    pub fn worst(&self) -> &JobProfile {
+        &self.runs[self.aggregate.worst_run - 1]
+    }
+
+    /// The first non-zero exit code among the runs, or 0
+    pub fn exit_code(&self) -> i32 {
+        self.runs
+            .iter()
+            .map(|p| {
+                p.exit_code.unwrap_or(if p.interrupted {
+                    p.interrupt_exit_code()
+                } else {
+                    0
+                })
+            })
+            .find(|&code| code != 0)
+            .unwrap_or(0)
+    }
+}
+
+/// Statistics over `runs`, which must not be empty
+pub fn aggregate(runs: &[JobProfile], warmup: usize, stopped_early: bool) -> Aggregate {
+    let peaks: Vec<f64> = runs.iter().map(|p| p.max_total_rss_kib as f64).collect();
+    let durations: Vec<f64> = runs.iter().map(|p| p.duration_seconds).collect();
+
+    Aggregate {
+        runs: runs.len(),
+        warmup,
+        peak_rss_kib: Stats::from_values(&peaks).expect("at least one run"),
+        duration_seconds: Stats::from_values(&durations).expect("at least one run"),
+        worst_run: overall_peak(runs).expect("at least one run") + 1,
+        failed_runs: runs
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.exit_code.is_some_and(|code| code != 0))
+            .map(|(i, _)| i + 1)
+            .collect(),
+        stopped_early,
+    }
+}
+
+/// Run the command `count` times with `run_once` before anything is measured,
+/// stopping at the first run that fails, since its measurements would be of a
+/// different job. `run_once` returns the exit code, or None if a signal ended it
+pub fn run_warmups(
+    count: usize,
+    progress: bool,
+    mut run_once: impl FnMut() -> Result<Option<i32>>,
+) -> Result<()> {
+    for i in 0..count {
+        if progress {
+            eprintln!("Warmup run {}/{}...", i + 1, count);
+        }
+        match run_once()? {
+            Some(0) => {}
+            Some(code) => {
+                anyhow::bail!(
+                    "Warmup run {}/{} exited with code {}; not measuring",
+                    i + 1,
+                    count,
+                    code
+                )
+            }
+            None => anyhow::bail!(
+                "Warmup run {}/{} was killed by a signal; not measuring",
+                i + 1,
+                count
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Profile the command `options.runs` times with `profile_once`. A failed run is
+/// reported on stderr; with `fail_fast` it is also the last one
+pub fn run_benchmark(
+    options: BenchmarkOptions,
+    mut profile_once: impl FnMut() -> Result<JobProfile>,
+) -> Result<BenchmarkReport> {
+    let mut runs = Vec::with_capacity(options.runs as usize);
+    let mut stopped_early = false;
+    for i in 0..options.runs {
+        let profile = profile_once()?;
+        let failed = profile.exit_code.is_some_and(|code| code != 0);
+        if failed {
+            eprintln!(
+                "Run {}/{} exited with code {}",
+                i + 1,
+                options.runs,
+                profile.exit_code.unwrap_or_default()
+            );
+        } else if options.progress {
+            eprintln!(
+                "Run {}/{}: peak {} in {}",
+                i + 1,
+                options.runs,
+                format_memory(profile.max_total_rss_kib),
+                format_duration(profile.duration_seconds)
+            );
+        }
+        let interrupted = profile.interrupted;
+        runs.push(profile);
+
+        if interrupted {
+            eprintln!("Stopping after run {} was interrupted", i + 1);
+            stopped_early = i + 1 < options.runs;
+            break;
+        }
+        if failed && options.fail_fast && i + 1 < options.runs {
+            eprintln!("Stopping after run {} (--runs-fail-fast)", i + 1);
+            stopped_early = true;
+            break;
+        }
+    }
+
+    let aggregate = aggregate(&runs, options.warmup, stopped_early);
+    Ok(BenchmarkReport { runs, aggregate })
+}
+
+/// Print every run's peak and duration, then min/median/max/stddev over them
+pub fn print_benchmark_summary(report: &BenchmarkReport) {
+    let mut stdout = StandardStream::stdout(color_choice());
+    let aggregate = &report.aggregate;
+
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true));
+    print!("\nBENCHMARK");
+    let _ = stdout.reset();
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    print!(" ({} runs", aggregate.runs);
+    if aggregate.warmup > 0 {
+        print!(", {} warmup", aggregate.warmup);
+    }
+    print!(")");
+    let _ = stdout.reset();
+    println!();
+    if let Some(first) = report.runs.first() {
+        println!("  Command: {}", first.command.join(" "));
+    }
+
+    println!();
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    println!(
+        "  {:>3}  {:>12}  {:>8}  {:>4}",
+        "#", "TOTAL PEAK", "DURATION", "EXIT"
+    );
+    let _ = stdout.reset();
+    for (i, profile) in report.runs.iter().enumerate() {
+        print!("  {:>3}  ", i + 1);
+        let color = if i + 1 == aggregate.worst_run {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(color)));
+        print!("{:>12}  ", format_memory(profile.max_total_rss_kib));
+        let _ = stdout.reset();
+        print!("{:>8}  ", format_duration(profile.duration_seconds));
+        match profile.exit_code {
+            Some(code) => println!("{:>4}", code),
+            None => println!("{:>4}", "-"),
+        }
+    }
+
+    println!();
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    println!(
+        "  {:<10}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "", "MIN", "MEDIAN", "MAX", "STDDEV"
+    );
+    let _ = stdout.reset();
+    let peak = &aggregate.peak_rss_kib;
+    let memory = |kib: f64| format_memory(kib.round() as u64);
+    println!(
+        "  {:<10}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "Total peak",
+        memory(peak.min),
+        memory(peak.median),
+        memory(peak.max),
+        memory(peak.stddev)
+    );
+    let duration = &aggregate.duration_seconds;
+    let duration_stddev = format!("{:.2}s", duration.stddev);
+    println!(
+        "  {:<10}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "Duration",
+        format_duration(duration.min),
+        format_duration(duration.median),
+        format_duration(duration.max),
+        duration_stddev
+    );
+
+    println!();
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)));
+    print!(
+        "  Worst run:  #{} ({})",
+        aggregate.worst_run,
+        format_memory(report.worst().max_total_rss_kib)
+    );
+    let _ = stdout.reset();
+    println!();
+    if !aggregate.failed_runs.is_empty() {
+        let failed: Vec<String> = aggregate
+            .failed_runs
+            .iter()
+            .map(|run| format!("#{}", run))
+            .collect();
+        println!("  Failed runs: {}", failed.join(", "));
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::exit_codes;
+
+    fn run(peak: u64, duration: f64, exit_code: i32) -> JobProfile {
+        JobProfile {
+            max_total_rss_kib: peak,
+            duration_seconds: duration,
+            exit_code: Some(exit_code),
+            ..Default::default()
+        }
+    }
+
+    fn options(runs: u32, warmup: usize, fail_fast: bool) -> BenchmarkOptions {
+        BenchmarkOptions {
+            runs,
+            warmup,
+            fail_fast,
+            progress: false,
+        }
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut results = vec![run(100, 1.0, 0), run(300, 3.0, 0), run(200, 2.0, 0)].into_iter();
+        let report = run_benchmark(options(3, 1, false), || Ok(results.next().unwrap())).unwrap();
+
+        let aggregate = &report.aggregate;
+        assert_eq!((aggregate.runs, aggregate.warmup), (3, 1));
+        assert_eq!(aggregate.peak_rss_kib.min, 100.0);
+        assert_eq!(aggregate.peak_rss_kib.median, 200.0);
+        assert_eq!(aggregate.peak_rss_kib.max, 300.0);
+        assert_eq!(aggregate.peak_rss_kib.stddev, 100.0);
+        assert_eq!(aggregate.duration_seconds.median, 2.0);
+        assert_eq!(aggregate.worst_run, 2);
+        assert_eq!(report.worst().max_total_rss_kib, 300);
+        assert_eq!(report.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_failed_warmup_aborts() {
+        let mut runs = 0;
+        run_warmups(2, false, || {
+            runs += 1;
+            Ok(Some(0))
+        })
+        .unwrap();
+        assert_eq!(runs, 2);
+
+        let mut codes = vec![Some(0), Some(3), Some(0)].into_iter();
+        let err = run_warmups(3, false, || Ok(codes.next().unwrap())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Warmup run 2/3 exited with code 3; not measuring"
+        );
+        // The third warmup never ran
+        assert_eq!(codes.len(), 1);
+
+        let err = run_warmups(1, false, || Ok(None)).unwrap_err();
+        assert!(err.to_string().contains("killed by a signal"));
+    }
+
+    #[test]
+    fn test_failed_runs() {
+        let results = || vec![run(100, 1.0, 0), run(200, 1.0, 2), run(300, 1.0, 1)].into_iter();
+
+        // Every run happens; the first failure decides the exit code
+        let mut all = results();
+        let report = run_benchmark(options(3, 0, false), || Ok(all.next().unwrap())).unwrap();
+        assert_eq!(report.aggregate.failed_runs, vec![2, 3]);
+        assert!(!report.aggregate.stopped_early);
+        assert_eq!(report.exit_code(), 2);
+
+        let mut all = results();
+        let report = run_benchmark(options(3, 0, true), || Ok(all.next().unwrap())).unwrap();
+        assert_eq!(report.runs.len(), 2);
+        assert_eq!(report.aggregate.failed_runs, vec![2]);
+        assert!(report.aggregate.stopped_early);
+        assert_eq!(report.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_interrupted_run_stops_the_benchmark() {
+        let interrupted = JobProfile {
+            interrupted: true,
+            ..Default::default()
+        };
+        let mut all = vec![run(100, 1.0, 0), interrupted, run(300, 1.0, 0)].into_iter();
+        let report = run_benchmark(options(3, 0, false), || Ok(all.next().unwrap())).unwrap();
+        assert_eq!(report.runs.len(), 2);
+        assert!(report.aggregate.stopped_early);
+        assert_eq!(report.exit_code(), exit_codes::INTERRUPTED);
+    }
+}
@@ -0,0 +1,127 @@
+//! `cargo memwatch`: profile a cargo invocation without quoting it into `memwatch run`.
+//!
+//! Cargo runs this binary as `cargo-memwatch memwatch <args>`, so everything after the
+//! memwatch flags is passed to cargo as-is: `cargo memwatch --json test --release -p app`.
+
+use clap::Parser;
+use memwatch::assertions::{self, AssertionConfig};
+use memwatch::cargo;
+use memwatch::cli::{ExitCodePolicy, parse_interval};
+use memwatch::csv_writer;
+use memwatch::inspector;
+use memwatch::logging;
+use memwatch::progress;
+use memwatch::reporter;
+use memwatch::sampler;
+use memwatch::types::{ProcessOrder, exit_codes, memory};
+use std::process;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "cargo-memwatch", bin_name = "cargo memwatch")]
+#[command(about = "Profile the memory of a cargo command, split into build and run phases")]
+#[command(version)]
+struct Args {
+    /// Sampling interval, e.g. 50ms, 1s, or 2m (a bare number is milliseconds)
+    #[arg(short, long, value_name = "DURATION", default_value = "500ms", value_parser = parse_interval)]
+    interval: Duration,
+
+    /// Output JSON instead of human-readable text
+    #[arg(long)]
+    json: bool,
+
+    /// Export per-process peak RSS to a CSV file
+    #[arg(long, value_name = "FILE")]
+    csv: Option<String>,
+
+    /// Exit with code 3 when the peak is above SIZE (e.g. 2GiB) and cargo succeeded
+    #[arg(long, value_name = "SIZE", value_parser = memory::parse_size_kib)]
+    fail_above: Option<u64>,
+
+    /// Report the run phase's peak (the target and its children, without cargo and the
+    /// compilers) as the job's peak, so --fail-above ignores the build
+    #[arg(long)]
+    exclude_build: bool,
+
+    /// Log what memwatch is doing to stderr (-v debug, -vv trace)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// The cargo subcommand and its arguments, e.g. `test --release -p app`
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+    cargo_args: Vec<String>,
+}
+
+fn main() {
+    // Drop the `memwatch` that cargo inserts when run as `cargo memwatch`
+    let mut argv: Vec<String> = std::env::args().collect();
+    if argv.get(1).map(String::as_str) == Some("memwatch") {
+        argv.remove(1);
+    }
+    let args = Args::parse_from(argv);
+    logging::init(args.verbose);
+
+    match run(args) {
+        Ok(exit_code) => process::exit(exit_code),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(exit_codes::ERROR);
+        }
+    }
+}
+
+fn run(args: Args) -> anyhow::Result<i32> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut command = vec![cargo];
+    command.extend(args.cargo_args);
+
+    // The split needs every process's RSS at every sample
+    let options = sampler::SamplerOptions {
+        interval: args.interval,
+        track_timeline: true,
+        per_process_timeline: true,
+        ..Default::default()
+    };
+    let inspector = inspector::create_inspector();
+    let mut profile = sampler::run_and_observe(
+        command,
+        &options,
+        &inspector,
+        &mut progress::Console::new(None),
+    )?;
+
+    cargo::apply_build_phase(&mut profile, args.exclude_build);
+    if args.fail_above.is_some() {
+        profile.thresholds = Some(assertions::check_thresholds(
+            profile.max_total_rss_kib,
+            None,
+            args.fail_above,
+        ));
+    }
+
+    if args.json {
+        reporter::print_json(&profile, false)?;
+    } else {
+        reporter::print_summary(
+            &profile,
+            reporter::SummaryOptions {
+                width: Some(reporter::summary_width()),
+                ..Default::default()
+            },
+        );
+    }
+    if let Some(path) = args.csv {
+        csv_writer::export_process_csv(&profile, &path, ProcessOrder::default())?;
+        if !args.json {
+            eprintln!("Per-process CSV exported to: {}", path);
+        }
+    }
+
+    let evaluation = assertions::evaluate(&profile, &AssertionConfig::default());
+    assertions::print_evaluation(&evaluation);
+    Ok(assertions::resolve_exit_code(
+        ExitCodePolicy::Child,
+        profile.exit_code.unwrap_or(0),
+        &evaluation.failures,
+    ))
+}
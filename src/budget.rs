@@ -0,0 +1,226 @@
+use crate::error::MemwatchError;
+use crate::reporter::format_memory;
+use crate::types::{JobProfile, memory};
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+/// Raw budget file layout:
+///
+/// ```toml
+/// [[budget]]
+/// command = "cargo test"   # regex matched against the full command line
+/// max_total = "4GiB"       # peak total RSS of the job
+/// max_process = "1GiB"     # peak RSS of any single process
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BudgetFileRaw {
+    #[serde(default)]
+    budget: Vec<BudgetEntryRaw>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BudgetEntryRaw {
+    command: String,
+    max_total: Option<String>,
+    max_process: Option<String>,
+}
+
+/// One budget: limits that apply to commands matching a pattern
+#[derive(Debug, Clone)]
+pub struct Budget {
+    pub pattern: String,
+    regex: Regex,
+    pub max_total_kib: Option<u64>,
+    pub max_process_kib: Option<u64>,
+}
+
+/// A single exceeded limit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub pattern: String,
+    pub message: String,
+}
+
+/// Outcome of checking a profile against a set of budgets
+#[derive(Debug, Default)]
+pub struct BudgetReport {
+    pub violations: Vec<Violation>,
+    /// Patterns that matched nothing (often a typo), each listed once
+    pub unmatched_patterns: Vec<String>,
+}
+
+/// Load and validate a budgets file
+pub fn load(path: &str) -> Result<Vec<Budget>> {
+    let content = std::fs::read_to_string(path).context(MemwatchError::read_failed(
+        "Failed to read budgets file",
+        path,
+    ))?;
+    parse(&content).context(format!("Invalid budgets file: {}", path))
+}
+
+fn parse(content: &str) -> Result<Vec<Budget>> {
+    let raw: BudgetFileRaw = toml::from_str(content)?;
+
+    raw.budget
+        .into_iter()
+        .map(|entry| {
+            let regex = Regex::new(&entry.command).context(MemwatchError::invalid_regex(
+                format!(
+                    "Invalid command pattern '{}': must be valid regex",
+                    entry.command
+                ),
+                &entry.command,
+            ))?;
+            let parse_limit = |limit: Option<String>, key: &str| -> Result<Option<u64>> {
+                limit
+                    .map(|s| memory::parse_size_kib(&s))
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("{} for '{}': {}", key, entry.command, e))
+            };
+
+            Ok(Budget {
+                max_total_kib: parse_limit(entry.max_total.clone(), "max_total")?,
+                max_process_kib: parse_limit(entry.max_process.clone(), "max_process")?,
+                pattern: entry.command,
+                regex,
+            })
+        })
+        .collect()
+}
+
+/// Check a finished profile against every budget whose pattern matches its command
+pub fn check(profile: &JobProfile, budgets: &[Budget]) -> BudgetReport {
+    let command = profile.command.join(" ");
+    let mut report = BudgetReport::default();
+
+    let (matched, unmatched): (Vec<&Budget>, Vec<&Budget>) =
+        budgets.iter().partition(|b| b.regex.is_match(&command));
+    for budget in unmatched {
+        if !report.unmatched_patterns.contains(&budget.pattern) {
+            report.unmatched_patterns.push(budget.pattern.clone());
+        }
+    }
+
+    for budget in matched {
+        if let Some(limit) = budget.max_total_kib
+            && profile.max_total_rss_kib > limit
+        {
+            report.violations.push(Violation {
+                pattern: budget.pattern.clone(),
+                message: format!(
+                    "total peak {} exceeds max_total {} (budget '{}')",
+                    format_memory(profile.max_total_rss_kib),
+                    format_memory(limit),
+                    budget.pattern
+                ),
+            });
+        }
+
+        if let Some(limit) = budget.max_process_kib {
+            for proc in profile.processes.iter().filter(|p| p.max_rss_kib > limit) {
+                report.violations.push(Violation {
+                    pattern: budget.pattern.clone(),
+                    message: format!(
+                        "pid {} ({}) peak {} exceeds max_process {} (budget '{}')",
+                        proc.pid,
+                        proc.command,
+                        format_memory(proc.max_rss_kib),
+                        format_memory(limit),
+                        budget.pattern
+                    ),
+                });
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ProcessStats;
+
+    const BUDGETS: &str = r#"
+[[budget]]
+command = "^cargo test"
+max_total = "4GiB"
+max_process = "1GiB"
+
+[[budget]]
+command = "^make"
+max_total = "2GB"
+"#;
+
+    fn profile(total_kib: u64, process_kib: &[u64]) -> JobProfile {
+        JobProfile {
+            command: vec!["cargo".to_string(), "test".to_string()],
+            max_total_rss_kib: total_kib,
+            processes: process_kib
+                .iter()
+                .enumerate()
+                .map(|(i, &rss)| ProcessStats {
+                    pid: i as i32 + 100,
+                    command: "rustc".to_string(),
+                    max_rss_kib: rss,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_budgets() {
+        let budgets = parse(BUDGETS).unwrap();
+        assert_eq!(budgets.len(), 2);
+        assert_eq!(budgets[0].max_total_kib, Some(4 * 1024 * 1024));
+        assert_eq!(budgets[0].max_process_kib, Some(1024 * 1024));
+        assert_eq!(budgets[1].max_total_kib, Some(1_953_125));
+        assert_eq!(budgets[1].max_process_kib, None);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_sizes_and_keys() {
+        assert!(parse("[[budget]]\ncommand = \"x\"\nmax_total = \"huge\"\n").is_err());
+        assert!(parse("[[budget]]\ncommand = \"x\"\nmax_totl = \"1GiB\"\n").is_err());
+        assert!(parse("[[budget]]\ncommand = \"[\"\n").is_err());
+    }
+
+    #[test]
+    fn test_check_reports_every_violation() {
+        let budgets = parse(BUDGETS).unwrap();
+        let gib = 1024 * 1024;
+
+        let report = check(&profile(5 * gib, &[2 * gib, gib / 2, 3 * gib]), &budgets);
+
+        assert_eq!(report.violations.len(), 3);
+        assert!(report.violations[0].message.contains("max_total"));
+        assert!(report.violations[1].message.contains("pid 100"));
+        assert!(report.violations[2].message.contains("pid 102"));
+        assert_eq!(report.unmatched_patterns, vec!["^make"]);
+    }
+
+    #[test]
+    fn test_check_warns_once_per_unmatched_pattern() {
+        let budgets = parse(&format!(
+            "{}\n[[budget]]\ncommand = \"^make\"\nmax_process = \"1GiB\"\n",
+            BUDGETS
+        ))
+        .unwrap();
+
+        let report = check(&profile(1024, &[512, 256, 128]), &budgets);
+
+        assert_eq!(report.unmatched_patterns, vec!["^make"]);
+    }
+
+    #[test]
+    fn test_check_within_budget() {
+        let budgets = parse(BUDGETS).unwrap();
+        let report = check(&profile(1024, &[512]), &budgets);
+        assert!(report.violations.is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+//! `cargo memwatch`: splitting a cargo job into its build and run phases.
+//!
+//! `cargo test` and `cargo run` compile before they start the target, and the
+//! compilers often dominate the peak. The split point is the first sample that
+//! contains a process which is not part of the build (the test binary, the program,
+//! or anything it starts); from then on only those processes count towards the run
+//! peak, so cargo waiting on its child is left out too.
+
+use crate::types::{BuildPhase, JobProfile, extract_command_name, sum_present};
+use std::collections::HashSet;
+
+/// Programs cargo runs while building, matched against the command name
+const BUILD_PROGRAMS: &[&str] = &[
+    "cargo",
+    "rustc",
+    "rustdoc",
+    "clippy-driver",
+    "sccache",
+    "cc",
+    "c++",
+    "gcc",
+    "g++",
+    "clang",
+    "clang++",
+    "cc1",
+    "cc1plus",
+    "as",
+    "ld",
+    "ld.lld",
+    "ld64.lld",
+    "lld",
+    "rust-lld",
+    "collect2",
+    "ar",
+    "make",
+];
+
+/// Whether a command line belongs to the build rather than the target being run
+pub fn is_build_process(command: &str) -> bool {
+    let name = extract_command_name(command);
+    BUILD_PROGRAMS.contains(&name.as_str()) || name.starts_with("build-script-")
+}
+
+/// Split `profile` into build and run phases. Needs the per-process timeline; returns
+/// None without one
+pub fn split_build_phase(profile: &JobProfile) -> Option<BuildPhase> {
+    let timeline = profile.timeline.as_ref()?;
+    let build_pids: HashSet<i32> = profile
+        .processes
+        .iter()
+        .filter(|p| is_build_process(&p.command))
+        .map(|p| p.pid)
+        .collect();
+
+    let mut phase = BuildPhase {
+        build_end_seconds: None,
+        build_peak_kib: 0,
+        run_peak_kib: 0,
+        build_excluded: false,
+    };
+    for point in timeline {
+        let per_process = point.process_rss_kib.as_ref()?;
+        let run_rss_kib: u64 = per_process
+            .iter()
+            .filter(|(pid, _)| !build_pids.contains(pid))
+            .map(|(_, rss)| rss)
+            .sum();
+
+        if phase.build_end_seconds.is_none()
+            && per_process.keys().any(|pid| !build_pids.contains(pid))
+        {
+            phase.build_end_seconds = Some(point.elapsed_seconds);
+        }
+        match phase.build_end_seconds {
+            Some(_) => phase.run_peak_kib = phase.run_peak_kib.max(run_rss_kib),
+            None => phase.build_peak_kib = phase.build_peak_kib.max(point.total_rss_kib),
+        }
+    }
+    Some(phase)
+}
+
+/// Record the build/run split in `profile`; with `exclude_build` the job totals only
+/// count the run, so the headline peak and thresholds only see the target. CPU time
+/// is not kept per process, so `total_cpu_seconds` still covers both phases
+pub fn apply_build_phase(profile: &mut JobProfile, exclude_build: bool) {
+    let Some(mut phase) = split_build_phase(profile) else {
+        return;
+    };
+    if exclude_build {
+        phase.build_excluded = true;
+        let run: Vec<_> = profile
+            .processes
+            .iter()
+            .filter(|p| !is_build_process(&p.command))
+            .collect();
+        let sum_of_peaks_kib = run.iter().map(|p| p.max_rss_kib).sum::<u64>();
+        profile.io_read_bytes = sum_present(run.iter().map(|p| p.io_read_bytes));
+        profile.io_write_bytes = sum_present(run.iter().map(|p| p.io_write_bytes));
+        profile.private_total_kib = sum_present(run.iter().map(|p| p.private_kib));
+        profile.simultaneous_peak_kib = phase.run_peak_kib;
+        profile.sum_of_peaks_kib = sum_of_peaks_kib.max(phase.run_peak_kib);
+        // Picks the new headline peak and updates the shares and baseline from it
+        profile.apply_peak_mode(profile.peak_mode);
+    }
+    profile.build_phase = Some(phase);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{PeakMode, ProcessStats, TimelinePoint};
+    use chrono::Utc;
+
+    fn profile(samples: &[&[(i32, u64)]]) -> JobProfile {
+        let commands = [
+            (1, "cargo test --release"),
+            (
+                2,
+                "/home/me/.rustup/toolchains/stable/bin/rustc --crate-name app",
+            ),
+            (3, "target/release/deps/app-1a2b3c --nocapture"),
+        ];
+        let timeline = samples
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| TimelinePoint {
+                timestamp: Utc::now(),
+                elapsed_seconds: i as f64,
+                total_rss_kib: sample.iter().map(|(_, rss)| rss).sum(),
+                process_count: sample.len(),
+                process_rss_kib: Some(sample.iter().copied().collect()),
+                smoothed_rss_kib: None,
+            })
+            .collect();
+
+        JobProfile {
+            max_total_rss_kib: 900,
+            timeline: Some(timeline),
+            processes: commands
+                .iter()
+                .map(|&(pid, command)| ProcessStats {
+                    pid,
+                    command: command.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_build_process() {
+        assert!(is_build_process("cargo test"));
+        assert!(is_build_process("/usr/bin/cc -O2 foo.c"));
+        assert!(is_build_process(
+            "target/debug/build/ring-0a1b/build-script-build"
+        ));
+        assert!(!is_build_process("target/debug/deps/app-1a2b3c"));
+        assert!(!is_build_process("python3 bench.py"));
+    }
+
+    #[test]
+    fn test_split_at_first_non_build_process() {
+        let mut profile = profile(&[
+            &[(1, 100), (2, 800)],
+            &[(1, 100)],
+            &[(1, 100), (3, 300)],
+            &[(1, 100), (3, 500)],
+        ]);
+
+        apply_build_phase(&mut profile, false);
+        let phase = profile.build_phase.clone().unwrap();
+        assert_eq!(phase.build_end_seconds, Some(2.0));
+        assert_eq!(phase.build_peak_kib, 900);
+        // cargo waiting on the test binary is not part of the run peak
+        assert_eq!(phase.run_peak_kib, 500);
+        assert_eq!(profile.max_total_rss_kib, 900);
+
+        apply_build_phase(&mut profile, true);
+        assert!(profile.build_phase.clone().unwrap().build_excluded);
+        assert_eq!(profile.max_total_rss_kib, 500);
+        assert_eq!(profile.simultaneous_peak_kib, 500);
+
+        // Every total leaves the build out, whichever one is the headline
+        profile.processes[1].max_rss_kib = 800;
+        profile.processes[2].max_rss_kib = 500;
+        profile.processes[2].io_read_bytes = Some(4096);
+        profile.peak_mode = PeakMode::SumOfPeaks;
+        apply_build_phase(&mut profile, true);
+        assert_eq!(profile.sum_of_peaks_kib, 500);
+        assert_eq!(profile.max_total_rss_kib, 500);
+        assert_eq!(profile.io_read_bytes, Some(4096));
+    }
+
+    #[test]
+    fn test_build_only_job() {
+        let phase = split_build_phase(&profile(&[&[(1, 100), (2, 800)]])).unwrap();
+        assert_eq!(phase.build_end_seconds, None);
+        assert_eq!((phase.build_peak_kib, phase.run_peak_kib), (900, 0));
+
+        // Without a per-process timeline nothing can be split
+        assert!(split_build_phase(&JobProfile::default()).is_none());
+    }
+}
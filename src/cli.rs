@@ -1,10 +1,76 @@
-use clap::{Parser, Subcommand};
+use crate::types::{GroupBy, ProcessSort, memory};
+use clap::{Args, Command, Parser, Subcommand, ValueEnum, ValueHint};
+use std::ffi::OsString;
 
 #[derive(Parser)]
 #[command(name = "memwatch")]
 #[command(about = "Cross-platform job-level memory profiler", long_about = None)]
+#[command(
+    after_help = "Without a subcommand, arguments go to `run`: `memwatch -i 100 -- make` is `memwatch run -i 100 -- make`. To profile a program named like a subcommand, use `memwatch run -- <program>`."
+)]
 #[command(version)]
 pub struct Cli {
+    /// When to color output: auto (terminals only, honoring NO_COLOR and CLICOLOR_FORCE), always, or never
+    #[arg(long, global = true, value_enum, default_value = "auto")]
+    pub color: ColorWhen,
+
+    /// Use plain ASCII for glyphs such as ⚠, •, and … in reports (automatic when the
+    /// output is not a terminal); `plot` also draws its chart in ASCII
+    #[arg(long, global = true)]
+    pub ascii: bool,
+
+    /// Units for memory sizes: binary (KiB/MiB/GiB, picked per value; also `auto`),
+    /// si (kB/MB/GB), or always kib, mib, gib, or bytes
+    #[arg(
+        long,
+        alias = "unit",
+        global = true,
+        value_enum,
+        default_value = "binary"
+    )]
+    pub units: UnitsArg,
+
+    /// Base for automatically scaled sizes: 1024 (KiB/MiB/GiB) or 1000 (kB/MB/GB, as
+    /// `docker stats` shows them); `--unit-base 1000` is `--units si`
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "1024",
+        conflicts_with = "units"
+    )]
+    pub unit_base: UnitBase,
+
+    /// Config file with a [defaults] table for `run` options
+    /// (default: ~/.config/memwatch/config.toml)
+    #[arg(long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath, env = "MEMWATCH_CONFIG")]
+    pub config: Option<String>,
+
+    /// Log what memwatch is doing to stderr: -v for debug messages (each sample,
+    /// processes joining and leaving the job, filtering, unreadable /proc files),
+    /// -vv for trace messages. MEMWATCH_LOG overrides this, e.g. MEMWATCH_LOG=error
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// How fatal errors are printed on stderr: human (`Error: ...`) or json (one object
+    /// with a stable `code`, the full `message`, the `path`, `pattern`, or `command`
+    /// involved, and the `exit_code`). Codes and exit codes: invalid_argument and
+    /// invalid_regex exit 2; command_not_found exits 127 and permission_denied 126;
+    /// spawn_failed, read_failed, export_failed, and error exit 1
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "human",
+        env = "MEMWATCH_ERROR_FORMAT"
+    )]
+    pub error_format: ErrorFormat,
+
+    /// Read processes from this procfs mount instead of /proc (Linux only), e.g. a
+    /// container's /proc mounted on the host; it must exist before anything runs
+    #[arg(long, global = true, value_name = "DIR", value_hint = ValueHint::DirPath, env = "MEMWATCH_PROC_PATH")]
+    pub proc_path: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -20,44 +86,1166 @@ impl Cli {
     }
 }
 
+/// Insert `run` into `args` when they name no subcommand, so `memwatch -- make` and
+/// `memwatch -i 100 -- make` mean `memwatch run ...`. Top-level options stay in front
+/// of it. A bare `memwatch`, help and version requests are left for clap to handle, and
+/// a command named like a subcommand still needs the explicit `memwatch run -- run`
+pub fn insert_default_subcommand(cmd: &Command, mut args: Vec<OsString>) -> Vec<OsString> {
+    let mut i = 1;
+    while let Some(token) = args.get(i).and_then(|token| token.to_str()) {
+        if matches!(token, "-h" | "--help" | "-V" | "--version") {
+            return args;
+        }
+        if !token.starts_with('-') {
+            if token == "help" || cmd.find_subcommand(token).is_some() {
+                return args;
+            }
+            break;
+        }
+        // Anything but a top-level option belongs to `run`, including `--`
+        let long = token.strip_prefix("--").unwrap_or_default();
+        let (name, inline_value) = match long.split_once('=') {
+            Some((name, _)) => (name, true),
+            None => (long, false),
+        };
+        let Some(arg) = cmd.get_arguments().find(|arg| arg.get_long() == Some(name)) else {
+            break;
+        };
+        i += if inline_value || !arg.get_action().takes_values() {
+            1
+        } else {
+            2
+        };
+    }
+
+    if i < args.len() {
+        args.insert(i, OsString::from("run"));
+    }
+    args
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Run a command and profile its memory usage
-    Run {
-        /// Sampling interval in milliseconds
-        #[arg(short, long, default_value = "500")]
-        interval: u64,
+    /// Run a command and profile its memory usage (the default subcommand)
+    Run(Box<RunArgs>),
+
+    /// Profile several commands one after another and summarize them together
+    Batch(BatchArgs),
+
+    /// Combine profiles recorded separately (e.g. one per node) into one job view
+    Merge(MergeArgs),
+
+    /// Aggregate peak memory and duration statistics across many saved profiles
+    Summarize(SummarizeArgs),
+
+    /// Detect peak memory regressions across a history of saved profiles
+    Trend(TrendArgs),
+
+    /// List runs saved with `run --save`, or show one of them
+    History(HistoryArgs),
+
+    /// Chart a saved profile's memory over time in the terminal
+    Plot(PlotArgs),
+
+    /// Live view of system memory grouped by process tree
+    Top(TopArgs),
+
+    /// List the processes a profile of a running PID would track and show, then exit
+    Probe(ProbeArgs),
+
+    /// Show the memory of every running process once, like a filtered `ps`
+    Snapshot(SnapshotArgs),
+
+    /// Convert a saved profile to another format
+    Convert(ConvertArgs),
+
+    /// Print the JSON Schema of the profile format written by --json, or of another
+    /// JSON output
+    Schema {
+        /// Output to describe
+        #[arg(value_enum, default_value = "profile")]
+        format: SchemaFormat,
+    },
+
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+
+    /// Evaluate memory budgets
+    #[command(subcommand)]
+    Budget(BudgetCommand),
+
+    /// Inspect the config file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Sampling interval, e.g. 50ms, 1s, or 2m (a bare number is milliseconds)
+    #[arg(short, long, value_name = "DURATION", default_value = "500ms", env = "MEMWATCH_INTERVAL", value_parser = parse_interval)]
+    pub interval: std::time::Duration,
+
+    /// Sampling interval in microseconds, for chasing peaks of very short-lived commands
+    /// (overrides --interval; sub-millisecond sampling adds significant overhead)
+    #[arg(long, value_name = "MICROS", conflicts_with = "interval")]
+    pub interval_us: Option<u64>,
+
+    /// Output JSON instead of human-readable text
+    #[arg(long)]
+    pub json: bool,
+
+    /// Also write the JSON profile to FILE (replaced atomically), independent of --json
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub json_out: Option<String>,
+
+    /// Write --json and --json-out on a single line instead of pretty-printed
+    #[arg(long)]
+    pub json_compact: bool,
+
+    /// Suppress human-readable output (useful with --json)
+    #[arg(long)]
+    pub quiet: bool,
+
+    /// Print only a single stable, grep-friendly summary line:
+    /// memwatch: peak=<kib> duration=<s> samples=<n> exit=<code> command="<cmd>"
+    #[arg(long, conflicts_with_all = ["json", "quiet"])]
+    pub oneline: bool,
+
+    /// Rows to show in each summary table; the rest are summed in a trailer line
+    /// (0 shows all; JSON and CSV are always complete)
+    #[arg(long, value_name = "N", default_value = "20")]
+    pub top: usize,
+
+    /// Order of the per-process table in the summary
+    #[arg(long, alias = "sort", value_name = "KEY", default_value = "rss")]
+    pub sort_by: ProcessSort,
+
+    /// Reverse --sort-by (and --csv-sort); ties still list the lowest pid first
+    #[arg(long, alias = "sort-reverse")]
+    pub reverse: bool,
+
+    /// Show the resolved executable path in place of the program name in the summary,
+    /// e.g. to tell apart `python` processes from different environments (Linux only)
+    #[arg(long)]
+    pub resolve_exe: bool,
+
+    /// Print commands in the summary in full instead of shortening long ones (in the
+    /// middle) to fit the terminal, or 100 columns when stdout is not one
+    #[arg(long)]
+    pub full_command: bool,
+
+    /// Order of the --csv export (peak RSS unless given)
+    #[arg(long, value_name = "KEY", requires = "csv")]
+    pub csv_sort: Option<ProcessSort>,
+
+    /// Name this run in every output (summary header, JSON, CSV comments), e.g. a commit
+    /// SHA; also names export files written into a directory
+    #[arg(long, value_name = "STRING")]
+    pub label: Option<String>,
+
+    /// Attach metadata to the run, e.g. --tag commit=abc123 --tag node=n01 (repeatable);
+    /// stored in JSON and CSV comments, and usable by history/summarize --tag
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_tag)]
+    pub tag: Vec<(String, String)>,
+
+    /// Also write the human-readable summary (without colors) to FILE
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub output: Option<String>,
+
+    /// Write the summary only to the --output file, not the terminal
+    #[arg(long, requires = "output")]
+    pub output_only: bool,
+
+    /// Export per-process peak RSS to CSV file
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub csv: Option<String>,
+
+    /// Export time-series memory data to CSV file
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub timeline: Option<String>,
+
+    /// Add an N-point moving average of total RSS to the timeline (odd N, at most 99;
+    /// records a timeline even without --timeline). Reported peaks stay raw
+    #[arg(long, value_name = "N", value_parser = parse_smooth)]
+    pub smooth: Option<u64>,
+
+    /// Record every process's RSS at each sample (stored in the JSON timeline)
+    #[arg(long)]
+    pub per_process_timeline: bool,
+
+    /// Keep every sample's per-process RSS in the JSON profile (raw_samples); output grows
+    /// with processes × samples, so this is off by default
+    #[arg(long)]
+    pub raw_samples: bool,
+
+    /// Export per-process RSS over time as a wide CSV (one column per process) for stacked plots
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, requires = "per_process_timeline")]
+    pub stacked_csv: Option<String>,
+
+    /// Suppress command output (hide stdout/stderr from the profiled command)
+    #[arg(long)]
+    pub silent: bool,
+
+    /// Save the command's stdout/stderr to files in DIR instead of showing them
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with_all = ["silent", "pty"])]
+    pub capture_output: Option<String>,
+
+    /// Run the command on a pseudo-terminal so it sees a TTY (colors, progress bars,
+    /// interactive prompts)
+    #[arg(long, conflicts_with_all = ["silent", "detach_after_samples"])]
+    pub pty: bool,
+
+    /// Run the command line through a shell as `sh -c '<command>'`, so pipelines, globs
+    /// and redirections work; quote it as one argument to keep its quoting exactly
+    #[arg(long)]
+    pub shell: bool,
+
+    /// Shell used by --shell
+    #[arg(long, value_name = "SHELL", default_value = "/bin/sh", requires = "shell", value_hint = ValueHint::ExecutablePath)]
+    pub shell_path: String,
+
+    /// Run the command in this directory
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    pub cwd: Option<String>,
+
+    /// Set an environment variable for the command (repeatable)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env_pair)]
+    pub env: Vec<(String, String)>,
+
+    /// Load environment variables for the command from a dotenv-style file (--env wins on conflicts)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub env_file: Option<String>,
+
+    /// Start the command from an empty environment instead of inheriting memwatch's
+    #[arg(long)]
+    pub env_clear: bool,
+
+    /// Record the values of --env/--env-file variables in the profile, not just their names
+    #[arg(long)]
+    pub capture_env: bool,
+
+    /// Also list processes whose RSS was always 0 (usually processes that exited before
+    /// their first memory read)
+    #[arg(long)]
+    pub include_zero: bool,
+
+    /// How the PROCESS GROUPS table groups processes: basename (of the executable),
+    /// first-arg (basename of the first non-option argument, e.g. the script of
+    /// `python3 train.py`), or a regex, optionally written regex:<pattern> (its first
+    /// capture group, matched against the full command line, e.g. `python3 \S*/(\w+)\.py`;
+    /// unmatched commands fall back to the basename)
+    #[arg(long, value_name = "MODE|REGEX", default_value = "basename")]
+    pub group_by: GroupBy,
+
+    /// Exclude processes matching a regex pattern from output (repeatable; matching any
+    /// one excludes; can be combined with --include)
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Only include processes matching a regex pattern in output (repeatable; matching
+    /// any one includes; can be combined with --exclude)
+    #[arg(long, value_name = "PATTERN")]
+    pub include: Vec<String>,
+
+    /// Hide launcher and wrapper processes (`sh -c`, mpirun/orted/hydra_pmi_proxy, cargo,
+    /// memwatch) from the tables; their memory still counts in the total
+    #[arg(long)]
+    pub hide_wrappers: bool,
+
+    /// Leave memwatch itself and the --shell wrapper out of the job, totals included; the
+    /// command's own processes below them are still tracked. By default the root
+    /// command (the shell, under --shell) counts like any other process
+    #[arg(long)]
+    pub exclude_self: bool,
+
+    /// Omit processes whose peak stays below SIZE (e.g. 10MiB) from tables, JSON and
+    /// CSV, after --include/--exclude; their memory still counts in the totals
+    #[arg(long, value_name = "SIZE", value_parser = memory::parse_size_kib)]
+    pub min_rss: Option<u64>,
+
+    /// Omit processes observed for less than DURATION (e.g. 500ms, 2s) from tables, JSON
+    /// and CSV, after --min-rss; their memory still counts in the totals
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub min_lifetime: Option<std::time::Duration>,
+
+    /// Only sample processes owned by this user, given as a name or UID (repeatable);
+    /// unlike --include this also limits the totals
+    #[arg(long, value_name = "USER")]
+    pub user: Vec<String>,
+
+    /// Do not sample processes owned by this user, given as a name or UID (repeatable)
+    #[arg(long, value_name = "USER")]
+    pub exclude_user: Vec<String>,
+
+    /// Only sample this job process and its descendants (repeatable); useful with
+    /// --track-by session|pgid, which can pull in processes that already run
+    #[arg(long, value_name = "PID")]
+    pub pid: Vec<i32>,
+
+    /// Do not sample this job process or any of its descendants (repeatable)
+    #[arg(long, value_name = "PID")]
+    pub exclude_pid: Vec<i32>,
+
+    /// Follow the process tree only N levels below the command (0 is the command
+    /// alone, 1 adds its direct children); deeper processes are not counted at all
+    #[arg(long, value_name = "N")]
+    pub depth: Option<usize>,
+
+    /// If no data is captured, rerun the command at progressively shorter intervals
+    /// (e.g. 500ms → 100ms → 20ms). The command runs again on every retry, so only use
+    /// this with idempotent commands
+    #[arg(long)]
+    pub auto_interval: bool,
+
+    /// Benchmark mode: run the command N times in a row and report min/median/max/stddev
+    /// of the peak and duration. Checks and exports apply to the run with the largest peak
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "oneline"
+    )]
+    pub runs: u32,
+
+    /// Run the command M extra times first, without sampling, e.g. to warm the page
+    /// cache; works with a single run too. A failing warmup run stops memwatch
+    #[arg(long, value_name = "M", default_value = "0")]
+    pub warmup: usize,
+
+    /// Stop benchmarking after the first run that exits non-zero (by default every run
+    /// happens, and the first failure's exit code is returned)
+    #[arg(long)]
+    pub runs_fail_fast: bool,
+
+    /// Save the finished profile to the run history (see `memwatch history`)
+    #[arg(long)]
+    pub save: bool,
+
+    /// Report the total peak above the job's RSS at the first sample (startup memory)
+    #[arg(long)]
+    pub subtract_baseline: bool,
+
+    /// Which peak the headline total reports (both are always in the JSON)
+    #[arg(long, value_enum, default_value = "simultaneous")]
+    pub peak_mode: PeakModeArg,
+
+    /// Record per-NUMA-node resident memory at each process's peak (Linux only; reads
+    /// /proc/[pid]/numa_maps, which is expensive for large processes)
+    #[arg(long)]
+    pub numa: bool,
+
+    /// Record each process's storage I/O (read_bytes/write_bytes from /proc/[pid]/io)
+    /// at every sample (Linux only; processes whose counters are unreadable are skipped)
+    #[arg(long)]
+    pub show_io: bool,
+
+    /// Record how much of each process's memory is private and how much is shared,
+    /// at its peak, and report the job's private total (Linux only; reads
+    /// /proc/[pid]/smaps_rollup, which is expensive for large processes)
+    #[arg(long)]
+    pub show_shared: bool,
+
+    /// Don't print sampling failures as they happen; they are counted at the end of the
+    /// summary and listed in the JSON (sampling_errors)
+    #[arg(long)]
+    pub quiet_errors: bool,
+
+    /// Which processes belong to the job: the command's descendants (tree), or also
+    /// everything in its session or process group, which catches daemonized children
+    #[arg(long, value_name = "MODE", value_enum, default_value = "tree")]
+    pub track_by: TrackBy,
+
+    /// Check the finished profile against a budgets file; exits with code 3 on any violation
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub budgets: Option<String>,
+
+    /// Exit with code 66 when no memory data was captured (e.g. the command exited too fast)
+    #[arg(long)]
+    pub fail_on_empty: bool,
+
+    /// Kill the whole job tree (SIGTERM, then SIGKILL after --kill-grace) once its total
+    /// RSS exceeds SIZE, e.g. 4GiB; exits with code 10
+    #[arg(long, value_name = "SIZE", value_parser = memory::parse_size_kib)]
+    pub max_memory: Option<u64>,
+
+    /// Like --max-memory, with the limit given as PERCENT of the machine's total RAM
+    /// (e.g. 75), so one setting fits a laptop and a large CI runner alike
+    #[arg(long, value_name = "PERCENT", conflicts_with = "max_memory", value_parser = parse_percent)]
+    pub max_rss_pct: Option<f64>,
+
+    /// Kill the whole job tree (SIGTERM, then SIGKILL after --kill-grace) once it has
+    /// run for DURATION, e.g. 90s, 10m, or 1h; exits with code 124
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub timeout: Option<std::time::Duration>,
+
+    /// Stop the run once the job's total RSS has stayed within --idle-epsilon for
+    /// DURATION (e.g. 5m), for services that settle into a steady state
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub idle_stop: Option<std::time::Duration>,
+
+    /// How far the total RSS may move and still count as flat for --idle-stop
+    #[arg(
+        long,
+        value_name = "SIZE",
+        default_value = "1MiB",
+        requires = "idle_stop",
+        value_parser = memory::parse_size_kib
+    )]
+    pub idle_epsilon: u64,
+
+    /// What --idle-stop does once memory is flat: kill the job (SIGTERM, then SIGKILL
+    /// after --kill-grace) or detach and leave it running
+    #[arg(
+        long,
+        value_name = "ACTION",
+        value_enum,
+        default_value = "kill",
+        requires = "idle_stop"
+    )]
+    pub idle_action: IdleAction,
+
+    /// Start sampling only once the command has run for DURATION (e.g. 30s), skipping
+    /// its startup phase
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub delay: Option<std::time::Duration>,
+
+    /// Keep sampling processes the command leaves running after it exits (e.g. daemonized
+    /// or backgrounded children) until they exit or --drain-timeout elapses
+    #[arg(long)]
+    pub drain: bool,
+
+    /// Longest time --drain keeps sampling after the command exits
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "30s",
+        requires = "drain",
+        value_parser = parse_duration
+    )]
+    pub drain_timeout: std::time::Duration,
+
+    /// Like --drain, but keep sampling the processes left behind for as long as any of
+    /// them runs, bounded only by --timeout (e.g. mpirun or launchers that return early)
+    #[arg(long, conflicts_with = "drain")]
+    pub wait_for_children: bool,
+
+    /// Stop sampling after N samples and wait for the command to exit without sampling
+    /// (the reported peak may be underestimated)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_samples: Option<u64>,
+
+    /// With --max-samples, return as soon as the limit is reached and leave the command running
+    #[arg(long, requires = "max_samples")]
+    pub detach_after_samples: bool,
+
+    /// With --max-samples, stop the command once the limit is reached (SIGTERM, then
+    /// SIGKILL after --kill-grace), for fixed-length captures of long-running services
+    #[arg(
+        long,
+        requires = "max_samples",
+        conflicts_with = "detach_after_samples"
+    )]
+    pub kill_after_samples: bool,
+
+    /// Seconds killed processes get to exit after SIGTERM before they are sent SIGKILL
+    #[arg(long, value_name = "SECS", default_value = "5")]
+    pub kill_grace: u64,
+
+    /// On Ctrl-C or SIGTERM, stop sampling and report without passing the signal on to
+    /// the command, which is left running and stays in memwatch's process group (by
+    /// default it runs in a group of its own, which gets the signal)
+    #[arg(long)]
+    pub no_forward_signals: bool,
+
+    /// POST a JSON notification to URL (http:// only) when the job's total RSS first
+    /// exceeds --webhook-threshold; failures are reported once and never stop the run
+    #[arg(long, value_name = "URL", requires = "webhook_threshold")]
+    pub webhook: Option<String>,
+
+    /// Total RSS that triggers --webhook, e.g. 8GiB
+    #[arg(long, value_name = "SIZE", requires = "webhook", value_parser = memory::parse_size_kib)]
+    pub webhook_threshold: Option<u64>,
+
+    /// Notify again whenever the total climbs back above --webhook-threshold after
+    /// dropping below it
+    #[arg(long, requires = "webhook")]
+    pub webhook_repeat: bool,
+
+    /// Print a line on stderr the first time the job's total RSS rises above SIZE
+    /// (e.g. 1GiB); repeat for several levels. Crossings are recorded in the JSON profile
+    #[arg(long, value_name = "SIZE", value_parser = memory::parse_size_kib)]
+    pub alert: Vec<u64>,
+
+    /// Print a warning when the total peak RSS is above SIZE (e.g. 2GiB); the exit code is unchanged
+    #[arg(long, value_name = "SIZE", value_parser = memory::parse_size_kib)]
+    pub warn_above: Option<u64>,
+
+    /// Fail when the total peak RSS is above SIZE (e.g. 4GiB), exiting with --fail-above-code
+    #[arg(long, value_name = "SIZE", value_parser = memory::parse_size_kib)]
+    pub fail_above: Option<u64>,
+
+    /// Exit code used when --fail-above is exceeded
+    #[arg(
+        long,
+        value_name = "CODE",
+        default_value = "3",
+        requires = "fail_above"
+    )]
+    pub fail_above_code: i32,
+
+    /// Compare the run with a profile saved earlier (--json or --json-out): the summary
+    /// shows the change of the total peak, duration, and each process group
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub baseline: Option<String>,
+
+    /// Fail (exit 3) when the total peak is more than PCT percent above --baseline's
+    #[arg(long, value_name = "PCT", requires = "baseline")]
+    pub baseline_fail_above: Option<f64>,
+
+    /// Which exit code memwatch returns: the child's (checks only apply when it
+    /// succeeded), memwatch's own (failed checks always win), or always 0
+    #[arg(long, value_enum, value_name = "POLICY", default_value = "child")]
+    pub exit_code: ExitCodePolicy,
+
+    /// Command to run (everything after --)
+    #[arg(trailing_var_arg = true, required = true, value_hint = ValueHint::CommandWithArguments)]
+    pub command: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct MergeArgs {
+    /// Profile JSON files to merge (written with --json)
+    #[arg(required = true, num_args = 2.., value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub files: Vec<String>,
+
+    /// Output JSON instead of human-readable text
+    #[arg(long)]
+    pub json: bool,
+
+    /// Warn when profile start times differ by more than this many seconds
+    #[arg(long, value_name = "SECS", default_value = "60")]
+    pub max_skew: f64,
+}
+
+#[derive(Args)]
+pub struct SummarizeArgs {
+    /// Profile JSON files, directories containing them, or glob patterns
+    #[arg(required = true, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    pub inputs: Vec<String>,
+
+    /// Only include profiles recorded with this tag (repeatable; all must match)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_tag)]
+    pub tag: Vec<(String, String)>,
+
+    /// Output JSON instead of human-readable text
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct TrendArgs {
+    /// Profile JSON files, directories containing them, or glob patterns
+    #[arg(
+        required_unless_present = "history",
+        value_name = "PATH",
+        value_hint = ValueHint::AnyPath
+    )]
+    pub inputs: Vec<String>,
+
+    /// Analyze runs saved with `run --save` instead of files
+    #[arg(long, conflicts_with = "inputs")]
+    pub history: bool,
+
+    /// Number of previous runs whose median forms the baseline
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u64).range(1..))]
+    pub window: u64,
+
+    /// Flag a regression when the latest peak exceeds the baseline by more than this percent
+    #[arg(long, value_name = "PERCENT", default_value = "10")]
+    pub threshold: f64,
+
+    /// Exit with code 3 when any command regressed (for CI)
+    #[arg(long)]
+    pub fail_on_regression: bool,
+
+    /// Output JSON instead of human-readable text
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub action: Option<HistoryAction>,
+
+    /// Maximum number of runs to list (most recent first)
+    #[arg(long, default_value = "20")]
+    pub limit: usize,
+
+    /// Only list runs whose command matches this regex
+    #[arg(long, value_name = "PATTERN")]
+    pub r#match: Option<String>,
+
+    /// Only list runs recorded with this tag (repeatable; all must match)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_tag)]
+    pub tag: Vec<(String, String)>,
+
+    /// Output JSON instead of human-readable text
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryAction {
+    /// Re-render the full summary of a saved run
+    Show {
+        /// Run id (or a unique prefix of it)
+        id: String,
 
         /// Output JSON instead of human-readable text
         #[arg(long)]
         json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BudgetCommand {
+    /// Check a saved profile against a budgets file; exits with code 3 on any violation
+    Check {
+        /// Profile JSON file (written with --json)
+        #[arg(value_hint = ValueHint::FilePath)]
+        profile: String,
 
-        /// Suppress human-readable output (useful with --json)
+        /// Budgets file (TOML)
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, default_value = "memwatch-budgets.toml")]
+        budgets: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the `run` options set by the config file or the environment, and where
+    /// each value comes from
+    Show {
+        /// Include options left at their built-in defaults
         #[arg(long)]
-        quiet: bool,
+        all: bool,
+    },
+}
 
-        /// Export per-process peak RSS to CSV file
-        #[arg(long, value_name = "FILE")]
-        csv: Option<String>,
+#[derive(Args)]
+pub struct BatchArgs {
+    /// File with one command line per row (shell quoting; # starts a comment)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, required_unless_present = "cmd")]
+    pub file: Option<String>,
 
-        /// Export time-series memory data to CSV file
-        #[arg(long, value_name = "FILE")]
-        timeline: Option<String>,
+    /// Command line to profile (repeatable; runs after any commands from --file)
+    #[arg(long, value_name = "COMMAND", value_hint = ValueHint::CommandString)]
+    pub cmd: Vec<String>,
 
-        /// Suppress command output (hide stdout/stderr from the profiled command)
-        #[arg(long)]
-        silent: bool,
+    /// Sampling interval, e.g. 50ms, 1s, or 2m (a bare number is milliseconds)
+    #[arg(short, long, value_name = "DURATION", default_value = "500ms", env = "MEMWATCH_INTERVAL", value_parser = parse_interval)]
+    pub interval: std::time::Duration,
 
-        /// Exclude processes matching regex pattern from output (can be combined with --include)
-        #[arg(long, value_name = "PATTERN")]
-        exclude: Option<String>,
+    /// Output a JSON array of profiles instead of the summary table
+    #[arg(long)]
+    pub json: bool,
 
-        /// Only include processes matching regex pattern in output (can be combined with --exclude)
-        #[arg(long, value_name = "PATTERN")]
-        include: Option<String>,
+    /// Suppress the commands' stdout/stderr
+    #[arg(long)]
+    pub silent: bool,
 
-        /// Command to run (everything after --)
-        #[arg(trailing_var_arg = true, required = true)]
-        command: Vec<String>,
-    },
+    /// Stop at the first command that exits non-zero
+    #[arg(long)]
+    pub fail_fast: bool,
+}
+
+#[derive(Args)]
+pub struct PlotArgs {
+    /// Profile JSON file (recorded with --timeline)
+    #[arg(
+        required_unless_present = "last",
+        conflicts_with = "last",
+        value_hint = ValueHint::FilePath
+    )]
+    pub profile: Option<String>,
+
+    /// Plot the most recent run saved with --save
+    #[arg(long)]
+    pub last: bool,
+
+    /// Overlay the top 3 processes (needs a profile recorded with --per-process-timeline)
+    #[arg(long)]
+    pub per_process: bool,
+
+    /// Chart width in columns (defaults to the terminal width)
+    #[arg(long)]
+    pub width: Option<usize>,
+
+    /// Chart height in rows
+    #[arg(long, default_value = "12", value_parser = clap::value_parser!(u16).range(2..))]
+    pub height: u16,
+}
+
+#[derive(Args)]
+pub struct ProbeArgs {
+    /// Root process of the job to inspect
+    #[arg(long)]
+    pub pid: i32,
+
+    /// Which processes belong to the job, as for `run --track-by`
+    #[arg(long, value_name = "MODE", value_enum, default_value = "tree")]
+    pub track_by: TrackBy,
+
+    /// Exclude processes matching a regex pattern (repeatable), as for `run --exclude`
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Only include processes matching a regex pattern (repeatable), as for `run --include`
+    #[arg(long, value_name = "PATTERN")]
+    pub include: Vec<String>,
+
+    /// Hide launcher and wrapper processes, as for `run --hide-wrappers`
+    #[arg(long)]
+    pub hide_wrappers: bool,
+
+    /// Only list processes owned by this user (repeatable), as for `run --user`
+    #[arg(long, value_name = "USER")]
+    pub user: Vec<String>,
+
+    /// Leave out processes owned by this user (repeatable), as for `run --exclude-user`
+    #[arg(long, value_name = "USER")]
+    pub exclude_user: Vec<String>,
+
+    /// Leave out this process and its descendants (repeatable), as for `run --exclude-pid`
+    #[arg(long, value_name = "PID")]
+    pub exclude_pid: Vec<i32>,
+
+    /// Follow the tree only N levels below the root, as for `run --depth`
+    #[arg(long, value_name = "N")]
+    pub depth: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct SnapshotArgs {
+    /// Exclude processes matching a regex pattern (repeatable), as for `run --exclude`
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Only include processes matching a regex pattern (repeatable), as for `run --include`
+    #[arg(long, value_name = "PATTERN")]
+    pub include: Vec<String>,
+
+    /// Omit processes using less than SIZE (e.g. 10MiB), after --include/--exclude
+    #[arg(long, value_name = "SIZE", value_parser = memory::parse_size_kib)]
+    pub min_rss: Option<u64>,
+
+    /// Rows to show, largest first (0 shows every process; --json always has all)
+    #[arg(long, value_name = "N", default_value = "20")]
+    pub top: usize,
+
+    /// Print the processes as JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct TopArgs {
+    /// Refresh interval in milliseconds
+    #[arg(short, long, default_value = "1000", value_parser = clap::value_parser!(u64).range(50..))]
+    pub interval: u64,
+
+    /// Initial sort order (press s to cycle while running)
+    #[arg(long, value_enum, default_value = "rss")]
+    pub sort: TopSort,
+}
+
+/// Parse a `KEY=VALUE` pair for `--env`
+fn parse_env_pair(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        Some(_) => Err(format!("missing variable name in '{}'", s)),
+        None => Err(format!("expected KEY=VALUE, got '{}'", s)),
+    }
+}
+
+/// Parse a `KEY=VALUE` pair for `--tag`; the key may not be empty, and the first `=`
+/// ends it
+fn parse_tag(s: &str) -> Result<(String, String), String> {
+    // Tags are written on one line, as in the `# Tags:` comment of CSV exports
+    if s.contains(['\n', '\r']) {
+        return Err(format!(
+            "tag '{}' must not contain a line break",
+            s.escape_debug()
+        ));
+    }
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        Some(_) => Err(format!("missing tag name in '{}'", s)),
+        None => Err(format!("expected KEY=VALUE, got '{}'", s)),
+    }
+}
+
+/// A percentage above 0 and at most 100
+fn parse_percent(s: &str) -> Result<f64, String> {
+    let pct: f64 = s
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", s))?;
+    if pct > 0.0 && pct <= 100.0 {
+        Ok(pct)
+    } else {
+        Err("must be above 0 and at most 100".to_string())
+    }
+}
+
+/// Parse a `--smooth` window: odd, so the average is centred on a sample, and at most 99
+fn parse_smooth(s: &str) -> Result<u64, String> {
+    let window: u64 = s
+        .parse()
+        .map_err(|_| format!("'{}' is not a whole number", s))?;
+    if !(1..=99).contains(&window) {
+        return Err("must be between 1 and 99".to_string());
+    }
+    if window % 2 == 0 {
+        return Err("must be odd".to_string());
+    }
+    Ok(window)
+}
+
+/// Parse a duration such as `90s`, `10m`, `1h`, `500ms`, or `250us`. A bare number
+/// is seconds, and fractional values like `1.5h` are allowed
+pub fn parse_duration(input: &str) -> Result<std::time::Duration, String> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let value: f64 = number.parse().map_err(|_| {
+        format!(
+            "invalid duration '{}': expected e.g. 500ms, 90s, 10m, or 1h",
+            input
+        )
+    })?;
+    let seconds_per_unit = match unit.trim().to_lowercase().as_str() {
+        "us" | "µs" => 0.000_001,
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        other => {
+            return Err(format!(
+                "invalid duration '{}': unknown unit '{}' (use us, ms, s, m, or h)",
+                input, other
+            ));
+        }
+    };
+
+    std::time::Duration::try_from_secs_f64(value * seconds_per_unit)
+        .map_err(|_| format!("invalid duration '{}': out of range", input))
+}
+
+/// Parse a sampling interval with `parse_duration`, except that a bare number is
+/// milliseconds, as `--interval` took before it accepted units
+pub fn parse_interval(s: &str) -> Result<std::time::Duration, String> {
+    let interval = match s.trim().parse::<f64>() {
+        Ok(_) => parse_duration(&format!("{}ms", s.trim())),
+        Err(_) => parse_duration(s),
+    }?;
+    if interval.is_zero() {
+        return Err("interval must be greater than 0".to_string());
+    }
+    Ok(interval)
+}
+
+/// How `memwatch run` chooses its exit code when the child's status and
+/// memwatch's own checks disagree
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExitCodePolicy {
+    /// The child's exit code; failed checks only override a successful child
+    Child,
+    /// Failed checks take precedence over the child's exit code
+    Memwatch,
+    /// Always exit 0 (failures are still reported on stderr)
+    AlwaysZero,
+}
+
+/// Settings for `--color`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorWhen {
+    /// Color terminals only, unless NO_COLOR or CLICOLOR_FORCE say otherwise
+    #[default]
+    Auto,
+    /// Always emit color escapes, even when piped
+    Always,
+    /// Never emit color escapes
+    Never,
+}
+
+/// Settings for `--error-format`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// `Error: <message>`
+    #[default]
+    Human,
+    /// A single JSON object per error
+    Json,
+}
+
+/// Settings for `--idle-action`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IdleAction {
+    /// Stop the job, as --timeout does
+    Kill,
+    /// Report and leave the job running
+    Detach,
+}
+
+/// Settings for `--track-by`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TrackBy {
+    /// Descendants of the command, followed by parent PID
+    Tree,
+    /// Descendants, plus every process in the command's session
+    Session,
+    /// Descendants, plus every process in the command's process group
+    Pgid,
+}
+
+/// Settings for `--units`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum UnitsArg {
+    /// Powers of 1024, with the unit picked per value
+    #[value(alias = "auto")]
+    Binary,
+    /// Powers of 1000, with the unit picked per value
+    Si,
+    /// Always KiB
+    Kib,
+    /// Always MiB
+    Mib,
+    /// Always GiB
+    Gib,
+    /// Always bytes, exactly
+    Bytes,
+}
+
+/// Settings for `--unit-base`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum UnitBase {
+    /// Powers of 1024 (IEC)
+    #[value(name = "1024")]
+    Iec,
+    /// Powers of 1000 (SI)
+    #[value(name = "1000")]
+    Si,
+}
+
+/// Definitions of the job's total peak
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PeakModeArg {
+    /// Largest total RSS observed in a single sample
+    Simultaneous,
+    /// Sum of every process's own peak, a worst case that may never have occurred
+    SumOfPeaks,
+}
+
+/// JSON outputs `memwatch schema` describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SchemaFormat {
+    /// Profiles written by `run --json`
+    Profile,
+    /// `snapshot --json`
+    Snapshot,
+}
+
+/// Sort orders for `memwatch top`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TopSort {
+    /// Aggregate tree RSS
+    Rss,
+    /// Number of processes in the tree
+    Procs,
+    /// Root command name
+    Name,
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Profile JSON file (written with --json)
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub input: String,
+
+    /// Output format
+    #[arg(long, value_enum)]
+    pub to: ConvertFormat,
+
+    /// Output file or directory (a directory gets files named after the input);
+    /// defaults to stdout, or the current directory for --to csv
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    pub out: Option<String>,
+}
+
+/// Formats `memwatch convert` can produce
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ConvertFormat {
+    /// Pretty-printed JSON profile
+    Json,
+    /// YAML profile
+    Yaml,
+    /// Markdown report
+    Md,
+    /// Per-process CSV plus timeline CSV (when the profile has a timeline)
+    Csv,
+    /// Timeline CSV only
+    Timeline,
+    /// Wide per-process CSV for stacked plots (needs --per-process-timeline data)
+    Stacked,
+}
+
+/// Shells `memwatch completions` can generate scripts for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("1.5h"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("3 days").is_err());
+        assert!(parse_duration("1e400").is_err());
+        assert!(
+            parse_duration(&"9".repeat(400))
+                .unwrap_err()
+                .contains("out of range")
+        );
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("500"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_interval("50ms"), Ok(Duration::from_millis(50)));
+        assert_eq!(parse_interval("250us"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_interval("250µs"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_interval("1s"), Ok(Duration::from_secs(1)));
+        assert_eq!(parse_interval("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_interval("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_interval(" 100 ms "), Ok(Duration::from_millis(100)));
+
+        assert!(parse_interval("0").unwrap_err().contains("greater than 0"));
+        assert!(parse_interval("0s").unwrap_err().contains("greater than 0"));
+        assert_eq!(parse_interval("1h"), Ok(Duration::from_secs(3600)));
+        assert!(
+            parse_interval("5d")
+                .unwrap_err()
+                .contains("unknown unit 'd'")
+        );
+        assert!(parse_interval("fast").is_err());
+        assert!(parse_interval("").is_err());
+        assert!(parse_interval("-5ms").is_err());
+        assert!(parse_interval("1e400s").is_err());
+    }
+
+    #[test]
+    fn test_parse_smooth() {
+        assert_eq!(parse_smooth("5"), Ok(5));
+        assert_eq!(parse_smooth("99"), Ok(99));
+        assert!(parse_smooth("4").unwrap_err().contains("odd"));
+        assert!(parse_smooth("0").is_err());
+        assert!(parse_smooth("101").is_err());
+        assert!(parse_smooth("five").is_err());
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        assert_eq!(
+            parse_tag(" node =n03"),
+            Ok(("node".to_string(), "n03".to_string()))
+        );
+        assert_eq!(parse_tag("note=a=b").unwrap().1, "a=b");
+        assert!(parse_tag("=x").is_err());
+        assert!(parse_tag("node").is_err());
+        assert!(
+            parse_tag("note=two\nlines")
+                .unwrap_err()
+                .contains("line break")
+        );
+        assert!(parse_tag("note=crlf\r").is_err());
+    }
+
+    #[test]
+    fn test_insert_default_subcommand() {
+        let insert = |argv: &[&str]| {
+            let args = argv.iter().map(OsString::from).collect();
+            insert_default_subcommand(&Cli::command(), args)
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        assert_eq!(
+            insert(&["memwatch", "--", "ls", "-l"]),
+            "memwatch run -- ls -l"
+        );
+        assert_eq!(
+            insert(&["memwatch", "-i", "100", "--", "make"]),
+            "memwatch run -i 100 -- make"
+        );
+        assert_eq!(
+            insert(&["memwatch", "--color", "never", "--units=si", "--", "make"]),
+            "memwatch --color never --units=si run -- make"
+        );
+        assert_eq!(insert(&["memwatch", "sleep", "1"]), "memwatch run sleep 1");
+
+        // Explicit subcommands, a program named `run`, help, and no arguments are untouched
+        assert_eq!(
+            insert(&["memwatch", "run", "--", "make"]),
+            "memwatch run -- make"
+        );
+        assert_eq!(
+            insert(&["memwatch", "run", "--", "run"]),
+            "memwatch run -- run"
+        );
+        assert_eq!(
+            insert(&["memwatch", "--color", "never", "top"]),
+            "memwatch --color never top"
+        );
+        assert_eq!(insert(&["memwatch", "help", "run"]), "memwatch help run");
+        assert_eq!(insert(&["memwatch", "--help"]), "memwatch --help");
+        assert_eq!(insert(&["memwatch"]), "memwatch");
+
+        // Flags after `--` still belong to the command
+        let argv = insert(&["memwatch", "-i", "100", "--", "make", "-j4"]);
+        let Commands::Run(args) = Cli::try_parse_from(argv.split(' ')).unwrap().command else {
+            unreachable!()
+        };
+        assert_eq!(args.interval, Duration::from_millis(100));
+        assert_eq!(args.command, vec!["make", "-j4"]);
+    }
 }
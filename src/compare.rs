@@ -0,0 +1,169 @@
+//! Comparing a run with a profile saved earlier (`run --baseline`).
+//!
+//! The comparison is numeric only: a baseline from another memwatch version or of a
+//! different command is still compared, with a warning, since the peak and duration
+//! mean the same thing either way.
+
+use crate::reporter::format_memory;
+use crate::types::{
+    BaselineComparison, GroupBy, JobProfile, SCHEMA_VERSION, compute_process_groups,
+    extract_command_name,
+};
+
+/// Keep the figures of `baseline` (loaded from `path`) that the summary compares against.
+/// Its processes are regrouped when it was grouped other than by `group_by`, so groups
+/// match by the same key
+pub fn from_baseline(baseline: &JobProfile, path: &str, group_by: &GroupBy) -> BaselineComparison {
+    let regrouped = (baseline.group_by != *group_by)
+        .then(|| compute_process_groups(&baseline.processes, baseline.include_zero_rss, group_by));
+    BaselineComparison {
+        path: path.to_string(),
+        peak_kib: baseline.max_total_rss_kib,
+        duration_seconds: baseline.duration_seconds,
+        group_peaks_kib: regrouped
+            .as_ref()
+            .unwrap_or(&baseline.process_groups)
+            .iter()
+            .map(|g| (g.command_name.clone(), g.total_peak_rss_kib))
+            .collect(),
+    }
+}
+
+/// Reasons the comparison may be misleading
+pub fn warnings(profile: &JobProfile, baseline: &JobProfile, path: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if baseline.schema_version != SCHEMA_VERSION {
+        warnings.push(format!(
+            "Baseline {} has schema version {} (this memwatch writes {}); comparing anyway",
+            path, baseline.schema_version, SCHEMA_VERSION
+        ));
+    }
+
+    let program = |p: &JobProfile| extract_command_name(&p.command.join(" "));
+    if program(profile) != program(baseline) {
+        warnings.push(format!(
+            "Baseline {} profiled '{}', not '{}'; comparing anyway",
+            path,
+            baseline.command.join(" "),
+            profile.command.join(" ")
+        ));
+    }
+    warnings
+}
+
+/// Change from `baseline` to `current` in percent, or None without a baseline value
+pub fn percent_change(current: f64, baseline: f64) -> Option<f64> {
+    (baseline > 0.0).then(|| (current - baseline) / baseline * 100.0)
+}
+
+/// A size change such as `+0.4 GiB / +8.3%`
+pub fn format_memory_delta(current_kib: u64, baseline_kib: u64) -> String {
+    let sign = if current_kib < baseline_kib { '-' } else { '+' };
+    let amount = format_memory(current_kib.abs_diff(baseline_kib));
+    with_percent(
+        format!("{}{}", sign, amount),
+        percent_change(current_kib as f64, baseline_kib as f64),
+    )
+}
+
+/// A duration change such as `-1.5s / -12.0%`
+pub fn format_duration_delta(current_seconds: f64, baseline_seconds: f64) -> String {
+    with_percent(
+        format!("{:+.1}s", current_seconds - baseline_seconds),
+        percent_change(current_seconds, baseline_seconds),
+    )
+}
+
+fn with_percent(amount: String, percent: Option<f64>) -> String {
+    match percent {
+        Some(percent) => format!("{} / {:+.1}%", amount, percent),
+        None => amount,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ProcessGroup, ProcessStats};
+
+    #[test]
+    fn test_format_deltas() {
+        let kib = |gib: f64| (gib * 1024.0 * 1024.0) as u64;
+        assert_eq!(
+            format_memory_delta(kib(5.2), kib(4.8)),
+            "+409.6 MiB / +8.3%"
+        );
+        assert_eq!(format_memory_delta(1024, 2048), "-1.0 MiB / -50.0%");
+        assert_eq!(format_memory_delta(512, 0), "+512 KiB");
+
+        assert_eq!(format_duration_delta(11.0, 12.5), "-1.5s / -12.0%");
+        assert_eq!(format_duration_delta(3.0, 0.0), "+3.0s");
+    }
+
+    #[test]
+    fn test_baseline_is_regrouped_by_the_current_key() {
+        let process = |pid: i32, command: &str, max_rss_kib: u64| ProcessStats {
+            pid,
+            command: command.to_string(),
+            max_rss_kib,
+            ..Default::default()
+        };
+        let processes = vec![
+            process(1, "python3 /srv/train.py", 300),
+            process(2, "python3 /srv/eval.py", 100),
+        ];
+        let baseline = JobProfile {
+            process_groups: compute_process_groups(&processes, false, &GroupBy::Basename),
+            processes,
+            ..Default::default()
+        };
+
+        let by_basename = from_baseline(&baseline, "old.json", &GroupBy::Basename);
+        assert_eq!(by_basename.group_peaks_kib["python3"], 400);
+
+        let by_script: GroupBy = r"/(\w+)\.py".parse().unwrap();
+        let by_script = from_baseline(&baseline, "old.json", &by_script);
+        assert_eq!(by_script.group_peaks_kib.len(), 2);
+        assert_eq!(by_script.group_peaks_kib["train"], 300);
+        assert_eq!(by_script.group_peaks_kib["eval"], 100);
+    }
+
+    #[test]
+    fn test_baseline_figures_and_warnings() {
+        let profile = |command: &str| JobProfile {
+            command: command.split(' ').map(String::from).collect(),
+            max_total_rss_kib: 4096,
+            duration_seconds: 2.0,
+            process_groups: vec![ProcessGroup {
+                command_name: "cc1plus".to_string(),
+                count: 3,
+                total_peak_rss_kib: 3072,
+            }],
+            ..Default::default()
+        };
+        let baseline = JobProfile {
+            schema_version: SCHEMA_VERSION,
+            ..profile("/usr/bin/make -j8")
+        };
+
+        let comparison = from_baseline(&baseline, "old.json", &GroupBy::Basename);
+        assert_eq!(comparison.peak_kib, 4096);
+        assert_eq!(comparison.group_peaks_kib["cc1plus"], 3072);
+
+        // Arguments may change; the program may not
+        let current = |command: &str| JobProfile {
+            schema_version: SCHEMA_VERSION,
+            ..profile(command)
+        };
+        assert!(warnings(&current("make -j4"), &baseline, "old.json").is_empty());
+        let warned = warnings(&current("ninja"), &baseline, "old.json");
+        assert_eq!(warned.len(), 1);
+        assert!(warned[0].contains("profiled '/usr/bin/make -j8', not 'ninja'"));
+
+        let old = JobProfile {
+            schema_version: SCHEMA_VERSION + 1,
+            ..baseline.clone()
+        };
+        assert!(warnings(&baseline, &old, "old.json")[0].contains("schema version"));
+    }
+}
@@ -0,0 +1,35 @@
+use crate::cli::{Cli, CompletionShell};
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::io::Write;
+
+/// Write the completion script for `shell` covering the full CLI definition
+pub fn generate(shell: CompletionShell, out: &mut impl Write) {
+    let shell = match shell {
+        CompletionShell::Bash => Shell::Bash,
+        CompletionShell::Zsh => Shell::Zsh,
+        CompletionShell::Fish => Shell::Fish,
+        CompletionShell::Powershell => Shell::PowerShell,
+    };
+
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, name, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_generate_every_shell() {
+        for shell in CompletionShell::value_variants() {
+            let mut buf = Vec::new();
+            generate(*shell, &mut buf);
+            let script = String::from_utf8(buf).unwrap();
+            assert!(script.contains("run"), "{:?} script lacks `run`", shell);
+            assert!(script.contains("memwatch"), "{:?} script lacks name", shell);
+        }
+    }
+}
@@ -0,0 +1,966 @@
+//! Configuration handling: defaults read from the config file and memwatch's own
+//! environment (including color settings), and dotenv-style files for the profiled
+//! command's environment.
+//!
+//! Precedence is CLI flag > environment variable > config file > built-in default.
+//! `MEMWATCH_INTERVAL` is handled by clap (`env` on `--interval`); the variables here
+//! need post-processing because they do not map onto a single flag. Config file values
+//! become the defaults of the clap arguments they name, so clap applies them last.
+
+use crate::cli::{ColorWhen, RunArgs};
+use crate::error::MemwatchError;
+use crate::types::Units;
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use termcolor::ColorChoice;
+use toml::Spanned;
+
+/// Default output format for `run`: `text`, `json`, or `oneline`
+pub const ENV_FORMAT: &str = "MEMWATCH_FORMAT";
+
+/// Disables colored output when set to any non-empty value
+pub const ENV_NO_COLOR: &str = "MEMWATCH_NO_COLOR";
+
+/// The cross-tool equivalent of `MEMWATCH_NO_COLOR` (https://no-color.org)
+const NO_COLOR: &str = "NO_COLOR";
+
+/// Forces colors even when output is not a terminal, unless set to 0
+const CLICOLOR_FORCE: &str = "CLICOLOR_FORCE";
+
+/// Variables read outside clap that still beat the config file, and the options
+/// they decide
+const ENV_OVERRIDES: &[(&str, &[&str])] = &[
+    (ENV_FORMAT, &["json", "oneline"]),
+    (ENV_NO_COLOR, &["color"]),
+    (NO_COLOR, &["color"]),
+    (CLICOLOR_FORCE, &["color"]),
+];
+
+/// Config file location below `$XDG_CONFIG_HOME`, or `~/.config` when it is unset
+const CONFIG_FILE: &str = "memwatch/config.toml";
+
+/// `--color`, set once from `main`
+static COLOR: OnceLock<ColorWhen> = OnceLock::new();
+
+/// `--units`, set once from `main`
+static UNITS: OnceLock<Units> = OnceLock::new();
+
+/// `--ascii`, set once from `main`
+static ASCII: OnceLock<bool> = OnceLock::new();
+
+/// Output formats `MEMWATCH_FORMAT` can select
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Oneline,
+}
+
+fn parse_format(value: &str) -> Result<OutputFormat> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        "oneline" => Ok(OutputFormat::Oneline),
+        _ => anyhow::bail!(
+            "Invalid {} '{}': expected text, json, or oneline",
+            ENV_FORMAT,
+            value
+        ),
+    }
+}
+
+/// Fill in `run` options from environment variables read through `lookup`,
+/// leaving anything chosen explicitly on the command line alone
+pub fn apply_env_defaults(
+    args: &mut RunArgs,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    if let Some(value) = lookup(ENV_FORMAT) {
+        let format = parse_format(&value)?;
+        // Any explicit output flag wins over the environment
+        if !args.json && !args.oneline {
+            match format {
+                OutputFormat::Text => {}
+                OutputFormat::Json => args.json = true,
+                // --quiet conflicts with --oneline, so honor the explicit flag
+                OutputFormat::Oneline if args.quiet => {}
+                OutputFormat::Oneline => args.oneline = true,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Raw config file layout:
+///
+/// ```toml
+/// [defaults]
+/// interval = "100ms"              # any `run` option, by its long flag name
+/// timeline = "memory.csv"
+/// exclude = ["cargo", "rustc"]    # repeatable options take an array
+/// hide-wrappers = true            # flags take true or false
+/// units = "si"
+/// ```
+#[derive(Debug, Deserialize)]
+struct ConfigFileRaw {
+    #[serde(default)]
+    defaults: BTreeMap<Spanned<String>, Spanned<toml::Value>>,
+}
+
+/// A `[defaults]` entry: the clap argument it sets and the values it defaults to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigDefault {
+    pub id: String,
+    pub values: Vec<String>,
+    /// Line in the config file, for messages
+    pub line: usize,
+}
+
+/// Defaults loaded from the config file
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// The file that was read, if one was found
+    pub path: Option<PathBuf>,
+    pub defaults: Vec<ConfigDefault>,
+}
+
+/// One row of `config show`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectiveOption {
+    pub name: String,
+    pub value: String,
+    pub source: String,
+}
+
+/// Find the config file: `--config` or `MEMWATCH_CONFIG`, then the XDG location.
+///
+/// Returns the path and whether it was asked for explicitly. The command line is
+/// pre-parsed without the config so `--config` is only found where clap would find it.
+pub fn config_path(
+    cmd: &Command,
+    args: &[OsString],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Option<(PathBuf, bool)> {
+    let explicit = cmd
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(args)
+        .ok()
+        .and_then(|matches| matches.get_one::<String>("config").cloned());
+    if let Some(path) = explicit {
+        return Some((PathBuf::from(path), true));
+    }
+
+    let set = |name: &str| lookup(name).filter(|value| !value.is_empty());
+    let base = set("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| set("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some((base.join(CONFIG_FILE), false))
+}
+
+/// Read the config file, if any. A missing file is only an error when it was named
+/// explicitly; unknown keys are warned about and bad values are errors.
+pub fn load_config(
+    cmd: &Command,
+    args: &[OsString],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Config> {
+    let Some((path, explicit)) = config_path(cmd, args, lookup) else {
+        return Ok(Config::default());
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Config::default());
+        }
+        Err(e) => {
+            return Err(e).context(MemwatchError::read_failed(
+                "Failed to read config file",
+                path.display(),
+            ));
+        }
+    };
+
+    // Keep the line number in the top-level message
+    let (defaults, warnings) = parse_config(&contents, cmd)
+        .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))?;
+    for warning in warnings {
+        eprintln!("Warning: {} in config file {}", warning, path.display());
+    }
+
+    Ok(Config {
+        path: Some(path),
+        defaults,
+    })
+}
+
+/// Parse a config file against the options of `cmd`, returning its defaults and
+/// warnings for keys that are not options
+pub fn parse_config(contents: &str, cmd: &Command) -> Result<(Vec<ConfigDefault>, Vec<String>)> {
+    let line_of = |offset: usize| contents[..offset].matches('\n').count() + 1;
+    let mut warnings = Vec::new();
+
+    // toml's own errors already name the line
+    let table: toml::Table = toml::from_str(contents)?;
+    for key in table.keys().filter(|key| *key != "defaults") {
+        warnings.push(format!("unknown key '{}' outside [defaults]", key));
+    }
+
+    let raw: ConfigFileRaw = toml::from_str(contents)?;
+    let mut defaults = Vec::new();
+    for (key, value) in raw.defaults {
+        let line = line_of(key.span().start);
+        let key = key.into_inner();
+        let Some(arg) = find_option(cmd, &key) else {
+            warnings.push(format!("unknown key '{}' (line {})", key, line));
+            continue;
+        };
+
+        let line = line_of(value.span().start);
+        let values = config_values(arg, value.get_ref())
+            .map_err(|e| anyhow::anyhow!("line {}: '{}' {}", line, key, e))?;
+        check_values(cmd, arg, &values).map_err(|e| anyhow::anyhow!("line {}: {}", line, e))?;
+
+        defaults.push(ConfigDefault {
+            id: arg.get_id().to_string(),
+            values,
+            line,
+        });
+    }
+    Ok((defaults, warnings))
+}
+
+/// The `run` or global option a config key names, by long flag or field name
+fn find_option<'a>(cmd: &'a Command, key: &str) -> Option<&'a Arg> {
+    let id = key.replace('-', "_");
+    let run = cmd.find_subcommand("run")?;
+    run.get_arguments()
+        .chain(cmd.get_arguments())
+        .filter(|arg| !arg.is_positional() && arg.get_id() != "config")
+        .find(|arg| arg.get_id() == id.as_str())
+}
+
+fn is_flag(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::SetTrue | ArgAction::SetFalse)
+}
+
+/// Turn a TOML value into the strings clap would have seen on the command line
+fn config_values(arg: &Arg, value: &toml::Value) -> Result<Vec<String>, String> {
+    let scalar = |value: &toml::Value| match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err("expects a string, number, or boolean".to_string()),
+    };
+
+    match value {
+        toml::Value::Boolean(_) => scalar(value).map(|v| vec![v]),
+        _ if is_flag(arg) => Err("is a flag and expects true or false".to_string()),
+        toml::Value::Array(items) if matches!(arg.get_action(), ArgAction::Append) => {
+            items.iter().map(scalar).collect()
+        }
+        toml::Value::Array(_) => Err("takes a single value, not an array".to_string()),
+        _ => scalar(value).map(|v| vec![v]),
+    }
+}
+
+/// Validate values by passing them to clap as if they were given on the command line
+fn check_values(cmd: &Command, arg: &Arg, values: &[String]) -> Result<(), String> {
+    let Some(long) = arg.get_long().filter(|_| !is_flag(arg)) else {
+        return Ok(());
+    };
+
+    let mut argv = vec!["memwatch".to_string(), "run".to_string()];
+    argv.extend(values.iter().map(|value| format!("--{}={}", long, value)));
+    argv.extend(["--".to_string(), "true".to_string()]);
+
+    match cmd.clone().try_get_matches_from(argv) {
+        Err(e)
+            if matches!(
+                e.kind(),
+                clap::error::ErrorKind::InvalidValue | clap::error::ErrorKind::ValueValidation
+            ) =>
+        {
+            let message = e.to_string();
+            let first = message.lines().next().unwrap_or_default();
+            Err(first.trim_start_matches("error: ").to_string())
+        }
+        // Missing companions like --csv for --csv-sort are not the value's fault
+        _ => Ok(()),
+    }
+}
+
+/// The variable that decides `id` instead of the config file, if one is set
+fn env_override(id: &str, lookup: &impl Fn(&str) -> Option<String>) -> Option<&'static str> {
+    ENV_OVERRIDES
+        .iter()
+        .find(|(name, ids)| ids.contains(&id) && lookup(name).is_some_and(|v| !v.is_empty()))
+        .map(|(name, _)| *name)
+}
+
+/// Make config values the defaults of the arguments they set
+fn with_defaults(mut cmd: Command, defaults: &[&ConfigDefault]) -> Command {
+    for default in defaults {
+        let set = |arg: Arg| arg.default_values(default.values.clone());
+        if cmd
+            .get_arguments()
+            .any(|arg| arg.get_id() == default.id.as_str())
+        {
+            cmd = cmd.mut_arg(&default.id, set);
+        } else {
+            cmd = cmd.mut_subcommand("run", |run| run.mut_arg(&default.id, set));
+        }
+    }
+    cmd
+}
+
+/// Parse the command line with the config file's values as defaults.
+///
+/// A config value is dropped when the environment decides the option, or when an
+/// option given on the command line conflicts with it, so `--oneline` still works
+/// with `json = true` in the file.
+pub fn get_matches(
+    cmd: Command,
+    config: &Config,
+    args: &[OsString],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<ArgMatches, clap::Error> {
+    let mut active: Vec<&ConfigDefault> = config
+        .defaults
+        .iter()
+        .filter(|default| env_override(&default.id, &lookup).is_none())
+        .collect();
+    let matches = with_defaults(cmd.clone(), &active).try_get_matches_from(args)?;
+
+    let (Some(("run", run_matches)), Some(run)) =
+        (matches.subcommand(), cmd.find_subcommand("run"))
+    else {
+        return Ok(matches);
+    };
+    let explicit: Vec<&Arg> = run
+        .get_arguments()
+        .filter(|arg| {
+            run_matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+        })
+        .collect();
+    let conflicts = |a: &Arg, b: &Arg| {
+        run.get_arg_conflicts_with(a)
+            .iter()
+            .any(|c| c.get_id() == b.get_id())
+            || run
+                .get_arg_conflicts_with(b)
+                .iter()
+                .any(|c| c.get_id() == a.get_id())
+    };
+
+    let before = active.len();
+    active.retain(|default| {
+        run.get_arguments()
+            .find(|arg| arg.get_id() == default.id.as_str())
+            .is_none_or(|arg| !explicit.iter().any(|given| conflicts(arg, given)))
+    });
+    if active.len() == before {
+        return Ok(matches);
+    }
+    with_defaults(cmd, &active).try_get_matches_from(args)
+}
+
+/// The effective `run` options with no flags given, and where each value comes from.
+/// Options left at their built-in defaults are only listed with `all`.
+pub fn effective_options(
+    cmd: Command,
+    config: &Config,
+    lookup: impl Fn(&str) -> Option<String>,
+    all: bool,
+) -> Result<Vec<EffectiveOption>> {
+    let args: Vec<OsString> = ["memwatch", "run", "--", "true"].map(OsString::from).into();
+    let matches = get_matches(cmd.clone(), config, &args, &lookup)?;
+    let Some(("run", run_matches)) = matches.subcommand() else {
+        unreachable!("parsed a run command line");
+    };
+    let run = cmd.find_subcommand("run").context("no run command")?;
+
+    let mut options = Vec::new();
+    let args = run
+        .get_arguments()
+        .chain(cmd.get_arguments())
+        .filter(|arg| !arg.is_positional() && arg.get_id() != "config");
+    for arg in args {
+        let id = arg.get_id().as_str();
+        let from_config = config.defaults.iter().find(|default| default.id == id);
+        let value = run_matches
+            .get_raw(id)
+            .map(|values| {
+                values
+                    .map(|v| v.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_else(|| "(unset)".to_string());
+
+        let source = match (run_matches.value_source(id), from_config) {
+            (Some(ValueSource::EnvVariable), _) => format!(
+                "environment ({})",
+                arg.get_env().unwrap_or_default().to_string_lossy()
+            ),
+            (_, Some(default)) => match env_override(id, &lookup) {
+                Some(name) => format!("{} (overrides config line {})", name, default.line),
+                None => format!("config (line {})", default.line),
+            },
+            _ if all => "default".to_string(),
+            _ => continue,
+        };
+        options.push(EffectiveOption {
+            name: arg.get_long().unwrap_or(id).to_string(),
+            value,
+            source,
+        });
+    }
+
+    // Variables that are not tied to a single flag
+    for (name, _) in ENV_OVERRIDES {
+        if let Some(value) = lookup(name).filter(|v| !v.is_empty()) {
+            options.push(EffectiveOption {
+                name: name.to_string(),
+                value,
+                source: "environment".to_string(),
+            });
+        }
+    }
+    Ok(options)
+}
+
+/// Parse a dotenv-style file: `KEY=VALUE` lines, with blank lines, `#` comments,
+/// an optional `export ` prefix, and single- or double-quoted values allowed
+pub fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let Some((key, value)) = line.split_once('=') else {
+            anyhow::bail!("line {}: expected KEY=VALUE, got '{}'", index + 1, line);
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            anyhow::bail!("line {}: missing variable name", index + 1);
+        }
+
+        let value = value.trim();
+        let unquoted = ['"', '\'']
+            .iter()
+            .find_map(|&quote| value.strip_prefix(quote)?.strip_suffix(quote))
+            .unwrap_or(value);
+        vars.push((key.to_string(), unquoted.to_string()));
+    }
+    Ok(vars)
+}
+
+/// Combine `--env-file` and `--env` into the variables to set for the command.
+///
+/// File entries come first so `--env` overrides them; a key set more than once keeps
+/// its first position and its last value.
+pub fn load_child_env(
+    env_file: Option<&str>,
+    overrides: &[(String, String)],
+) -> Result<Vec<(String, String)>> {
+    let from_file = match env_file {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)
+                .context(MemwatchError::read_failed("Failed to read env file", path))?;
+            // Keep the line number in the top-level message
+            parse_env_file(&contents)
+                .map_err(|e| anyhow::anyhow!("Invalid env file {}: {}", path, e))?
+        }
+        None => Vec::new(),
+    };
+
+    let mut vars: Vec<(String, String)> = Vec::new();
+    for (key, value) in from_file.into_iter().chain(overrides.iter().cloned()) {
+        match vars.iter_mut().find(|(existing, _)| *existing == key) {
+            Some(entry) => entry.1 = value,
+            None => vars.push((key, value)),
+        }
+    }
+    Ok(vars)
+}
+
+/// Record the `--color` setting for the rest of the run
+pub fn set_color(when: ColorWhen) {
+    let _ = COLOR.set(when);
+}
+
+/// Record the `--units` setting for the rest of the run
+pub fn set_units(units: Units) {
+    let _ = UNITS.set(units);
+}
+
+/// Units memory sizes are displayed in
+pub fn units() -> Units {
+    UNITS.get().copied().unwrap_or_default()
+}
+
+/// Record the `--ascii` setting for the rest of the run
+pub fn set_ascii(forced: bool) {
+    let _ = ASCII.set(forced);
+}
+
+/// Whether `--ascii` was given
+pub fn ascii_forced() -> bool {
+    ASCII.get().copied().unwrap_or(false)
+}
+
+/// Whether output to a stream swaps glyphs for ASCII: with `--ascii`, or when the
+/// stream is not a terminal (CI logs and files often mangle them)
+pub fn ascii_for(is_terminal: bool) -> bool {
+    ascii_forced() || !is_terminal
+}
+
+/// Color choice for a stream, given whether it is a terminal
+pub fn color_choice_for(is_terminal: bool) -> ColorChoice {
+    resolve_color(
+        COLOR.get().copied().unwrap_or_default(),
+        is_terminal,
+        |name| std::env::var(name).ok(),
+    )
+}
+
+/// Combine `--color`, the environment, and whether the stream is a terminal.
+///
+/// An explicit `always`/`never` wins. Under `auto`, `NO_COLOR` (or `MEMWATCH_NO_COLOR`)
+/// turns colors off, then `CLICOLOR_FORCE` turns them on even when piped, and otherwise
+/// only terminals get colors.
+pub fn resolve_color(
+    when: ColorWhen,
+    is_terminal: bool,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> ColorChoice {
+    let set = |name: &str| lookup(name).is_some_and(|value| !value.is_empty());
+
+    match when {
+        ColorWhen::Always => ColorChoice::AlwaysAnsi,
+        ColorWhen::Never => ColorChoice::Never,
+        ColorWhen::Auto if set(ENV_NO_COLOR) || set(NO_COLOR) => ColorChoice::Never,
+        ColorWhen::Auto
+            if set(CLICOLOR_FORCE) && lookup(CLICOLOR_FORCE).as_deref() != Some("0") =>
+        {
+            ColorChoice::AlwaysAnsi
+        }
+        // termcolor's Auto still honors TERM=dumb
+        ColorWhen::Auto if is_terminal => ColorChoice::Auto,
+        ColorWhen::Auto => ColorChoice::Never,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Commands, UnitBase, UnitsArg};
+    use clap::{CommandFactory, FromArgMatches, Parser};
+    use std::time::Duration;
+
+    fn run_args(argv: &[&str]) -> RunArgs {
+        match Cli::try_parse_from(argv).unwrap().command {
+            Commands::Run(args) => *args,
+            _ => unreachable!(),
+        }
+    }
+
+    fn config(contents: &str) -> Config {
+        let (defaults, warnings) = parse_config(contents, &Cli::command()).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        Config {
+            path: None,
+            defaults,
+        }
+    }
+
+    fn parse_with_config(
+        config: &Config,
+        argv: &[&str],
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Cli {
+        let args: Vec<OsString> = argv.iter().map(OsString::from).collect();
+        let matches = get_matches(Cli::command(), config, &args, lookup).unwrap();
+        Cli::from_arg_matches(&matches).unwrap()
+    }
+
+    fn config_run_args(
+        config: &Config,
+        argv: &[&str],
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> RunArgs {
+        match parse_with_config(config, argv, lookup).command {
+            Commands::Run(args) => *args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let contents = "# shared flags\n[defaults]\ninterval = 100\nper-process-timeline = true\nexclude = [\"cargo\", \"rustc\"]\nmin_rss = \"10MiB\"\nunits = \"si\"\nbogus = 1\n\n[extra]\n";
+        let (defaults, warnings) = parse_config(contents, &Cli::command()).unwrap();
+
+        let by_id = |id: &str| defaults.iter().find(|d| d.id == id).unwrap();
+        assert_eq!(by_id("interval").values, vec!["100"]);
+        assert_eq!(by_id("interval").line, 3);
+        assert_eq!(by_id("per_process_timeline").values, vec!["true"]);
+        assert_eq!(by_id("exclude").values, vec!["cargo", "rustc"]);
+        assert_eq!(by_id("min_rss").values, vec!["10MiB"]);
+        assert_eq!(by_id("units").values, vec!["si"]);
+        assert_eq!(defaults.len(), 5);
+        assert_eq!(
+            warnings,
+            vec![
+                "unknown key 'extra' outside [defaults]".to_string(),
+                "unknown key 'bogus' (line 8)".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        let err = |contents: &str| {
+            parse_config(contents, &Cli::command())
+                .unwrap_err()
+                .to_string()
+        };
+
+        let message = err("[defaults]\n\ninterval = \"fast\"\n");
+        assert!(message.starts_with("line 3:"), "{}", message);
+        assert!(message.contains("invalid interval 'fast'"), "{}", message);
+
+        assert!(err("[defaults]\nunits = \"lightyears\"\n").starts_with("line 2:"));
+        assert!(err("[defaults]\njson = \"yes\"\n").contains("expects true or false"));
+        assert!(err("[defaults]\ninterval = [\"1s\"]\n").contains("not an array"));
+        assert!(err("[defaults]\nexclude = [[\"a\"]]\n").contains("expects a string"));
+        assert!(err("[defaults\n").contains("line 1"));
+    }
+
+    #[test]
+    fn test_config_precedence() {
+        let config = config(
+            "[defaults]\ninterval = \"100ms\"\nexclude = [\"cargo\"]\nhide-wrappers = true\njson = true\nunits = \"si\"\n",
+        );
+        let no_env = |_: &str| None;
+
+        // Config beats the built-in defaults
+        let cli = parse_with_config(&config, &["memwatch", "run", "--", "true"], no_env);
+        assert!(matches!(cli.units, UnitsArg::Si));
+        let args = match cli.command {
+            Commands::Run(args) => *args,
+            _ => unreachable!(),
+        };
+        assert_eq!(args.interval, Duration::from_millis(100));
+        assert_eq!(args.exclude, vec!["cargo"]);
+        assert!(args.hide_wrappers && args.json);
+
+        // The command line beats the config, and replaces repeatable options
+        let args = config_run_args(
+            &config,
+            &[
+                "memwatch",
+                "run",
+                "-i",
+                "2s",
+                "--exclude",
+                "make",
+                "--",
+                "true",
+            ],
+            no_env,
+        );
+        assert_eq!(args.interval, Duration::from_secs(2));
+        assert_eq!(args.exclude, vec!["make"]);
+        let cli = parse_with_config(
+            &config,
+            &["memwatch", "--units", "kib", "run", "--", "true"],
+            no_env,
+        );
+        assert!(matches!(cli.units, UnitsArg::Kib));
+
+        // A conflicting flag on the command line drops the config value
+        let args = config_run_args(
+            &config,
+            &["memwatch", "run", "--oneline", "--", "true"],
+            no_env,
+        );
+        assert!(args.oneline && !args.json);
+
+        // The environment beats the config
+        let text = |name: &str| (name == ENV_FORMAT).then(|| "text".to_string());
+        let mut args = config_run_args(&config, &["memwatch", "run", "--", "true"], text);
+        apply_env_defaults(&mut args, text).unwrap();
+        assert!(!args.json && !args.oneline);
+
+        // Without a config everything stays at the built-in defaults
+        let args = config_run_args(
+            &Config::default(),
+            &["memwatch", "run", "--", "true"],
+            no_env,
+        );
+        assert!(args.exclude.is_empty() && !args.hide_wrappers && !args.json);
+    }
+
+    #[test]
+    fn test_config_path() {
+        let cmd = Cli::command();
+        let args = |argv: &[&str]| argv.iter().map(OsString::from).collect::<Vec<_>>();
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        let home = env(&[("HOME", "/home/me")]);
+
+        assert_eq!(
+            config_path(
+                &cmd,
+                &args(&["memwatch", "--config", "a.toml", "run", "--", "true"]),
+                home
+            ),
+            Some((PathBuf::from("a.toml"), true))
+        );
+        assert_eq!(
+            config_path(
+                &cmd,
+                &args(&["memwatch", "run", "--config=b.toml", "--", "true"]),
+                home
+            ),
+            Some((PathBuf::from("b.toml"), true))
+        );
+        // After the command starts, --config belongs to it
+        assert_eq!(
+            config_path(
+                &cmd,
+                &args(&["memwatch", "run", "--", "tool", "--config", "c.toml"]),
+                home
+            ),
+            Some((
+                PathBuf::from("/home/me/.config/memwatch/config.toml"),
+                false
+            ))
+        );
+        assert_eq!(
+            config_path(
+                &cmd,
+                &args(&["memwatch", "run", "--", "true"]),
+                env(&[("HOME", "/home/me"), ("XDG_CONFIG_HOME", "/xdg")])
+            ),
+            Some((PathBuf::from("/xdg/memwatch/config.toml"), false))
+        );
+        assert_eq!(
+            config_path(&cmd, &args(&["memwatch", "run", "--", "true"]), env(&[])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_effective_options() {
+        let config = config("[defaults]\ninterval = 100\njson = true\n");
+        let format = |name: &str| (name == ENV_FORMAT).then(|| "oneline".to_string());
+
+        let options = effective_options(Cli::command(), &config, format, false).unwrap();
+        let source = |name: &str| {
+            options
+                .iter()
+                .find(|o| o.name == name)
+                .map(|o| o.source.clone())
+        };
+        assert_eq!(source("interval").as_deref(), Some("config (line 2)"));
+        assert_eq!(
+            source("json").as_deref(),
+            Some("MEMWATCH_FORMAT (overrides config line 3)")
+        );
+        assert_eq!(source("MEMWATCH_FORMAT").as_deref(), Some("environment"));
+        assert_eq!(source("top"), None);
+
+        let all = effective_options(Cli::command(), &config, |_| None, true).unwrap();
+        assert!(all.iter().any(|o| o.name == "top" && o.source == "default"));
+    }
+
+    #[test]
+    fn test_format_from_env() {
+        let env = |format: &'static str| {
+            move |name: &str| (name == ENV_FORMAT).then(|| format.to_string())
+        };
+
+        let mut args = run_args(&["memwatch", "run", "--", "true"]);
+        apply_env_defaults(&mut args, env("json")).unwrap();
+        assert!(args.json);
+
+        let mut args = run_args(&["memwatch", "run", "--", "true"]);
+        apply_env_defaults(&mut args, env("ONELINE")).unwrap();
+        assert!(args.oneline && !args.json);
+
+        // Explicit flags win
+        let mut args = run_args(&["memwatch", "run", "--oneline", "--", "true"]);
+        apply_env_defaults(&mut args, env("json")).unwrap();
+        assert!(args.oneline && !args.json);
+
+        let mut args = run_args(&["memwatch", "run", "--", "true"]);
+        assert!(apply_env_defaults(&mut args, env("xml")).is_err());
+
+        // Unset leaves the defaults
+        let mut args = run_args(&["memwatch", "run", "--", "true"]);
+        apply_env_defaults(&mut args, |_| None).unwrap();
+        assert!(!args.json && !args.oneline);
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let contents = "# build settings\n\nRUST_LOG=debug\nexport OMP_NUM_THREADS = 4\nGREETING=\"hello world\"\nEMPTY=\nURL='a=b'\n";
+        assert_eq!(
+            parse_env_file(contents).unwrap(),
+            vec![
+                ("RUST_LOG".to_string(), "debug".to_string()),
+                ("OMP_NUM_THREADS".to_string(), "4".to_string()),
+                ("GREETING".to_string(), "hello world".to_string()),
+                ("EMPTY".to_string(), String::new()),
+                ("URL".to_string(), "a=b".to_string()),
+            ]
+        );
+
+        let err = parse_env_file("A=1\nNOT_A_PAIR\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+        assert!(parse_env_file("=value").is_err());
+    }
+
+    #[test]
+    fn test_load_child_env_overrides() {
+        let path = std::env::temp_dir().join(format!("memwatch-env-{}.env", std::process::id()));
+        std::fs::write(&path, "A=1\nB=2\n").unwrap();
+
+        let vars = load_child_env(
+            path.to_str(),
+            &[
+                ("B".to_string(), "3".to_string()),
+                ("C".to_string(), "4".to_string()),
+            ],
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            vars.unwrap(),
+            vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "3".to_string()),
+                ("C".to_string(), "4".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_color() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert_eq!(
+            resolve_color(ColorWhen::Auto, true, env(&[])),
+            ColorChoice::Auto
+        );
+        assert_eq!(
+            resolve_color(ColorWhen::Auto, false, env(&[])),
+            ColorChoice::Never
+        );
+        assert_eq!(
+            resolve_color(ColorWhen::Auto, true, env(&[("NO_COLOR", "1")])),
+            ColorChoice::Never
+        );
+        assert_eq!(
+            resolve_color(ColorWhen::Auto, true, env(&[("MEMWATCH_NO_COLOR", "1")])),
+            ColorChoice::Never
+        );
+        // An empty NO_COLOR does not count
+        assert_eq!(
+            resolve_color(ColorWhen::Auto, true, env(&[("NO_COLOR", "")])),
+            ColorChoice::Auto
+        );
+        assert_eq!(
+            resolve_color(ColorWhen::Auto, false, env(&[("CLICOLOR_FORCE", "1")])),
+            ColorChoice::AlwaysAnsi
+        );
+        assert_eq!(
+            resolve_color(ColorWhen::Auto, false, env(&[("CLICOLOR_FORCE", "0")])),
+            ColorChoice::Never
+        );
+
+        // Explicit settings beat the environment
+        let both = env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]);
+        assert_eq!(
+            resolve_color(ColorWhen::Always, false, both),
+            ColorChoice::AlwaysAnsi
+        );
+        assert_eq!(
+            resolve_color(ColorWhen::Never, true, both),
+            ColorChoice::Never
+        );
+    }
+
+    #[test]
+    fn test_unit_aliases() {
+        let units = |argv: &[&str]| Cli::try_parse_from(argv).unwrap().units;
+
+        assert_eq!(units(&["memwatch", "run", "--", "true"]), UnitsArg::Binary);
+        assert_eq!(
+            units(&["memwatch", "--unit", "auto", "run", "--", "true"]),
+            UnitsArg::Binary
+        );
+        assert_eq!(
+            units(&["memwatch", "run", "--unit", "bytes", "--", "true"]),
+            UnitsArg::Bytes
+        );
+        assert_eq!(
+            units(&["memwatch", "--units", "mib", "run", "--", "true"]),
+            UnitsArg::Mib
+        );
+    }
+
+    #[test]
+    fn test_unit_base() {
+        let parse = |argv: &[&str]| Cli::try_parse_from(argv).map(|cli| cli.unit_base);
+
+        assert_eq!(
+            parse(&["memwatch", "run", "--", "true"]).unwrap(),
+            UnitBase::Iec
+        );
+        assert_eq!(
+            parse(&["memwatch", "--unit-base", "1000", "run", "--", "true"]).unwrap(),
+            UnitBase::Si
+        );
+        assert!(parse(&["memwatch", "--unit-base", "1024", "run", "--", "true"]).is_ok());
+        assert!(parse(&["memwatch", "--unit-base", "100", "run", "--", "true"]).is_err());
+        assert!(
+            parse(&[
+                "memwatch",
+                "--units",
+                "mib",
+                "--unit-base",
+                "1000",
+                "run",
+                "--",
+                "true"
+            ])
+            .is_err()
+        );
+    }
+}
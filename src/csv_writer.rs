@@ -1,10 +1,41 @@
-use crate::types::{JobProfile, memory};
+use crate::config;
+use crate::error::MemwatchError;
+use crate::types::{JobProfile, ProcessOrder, Units, extract_command_name, memory};
 use anyhow::{Context, Result};
 use std::fs::File;
 use std::io::Write;
 
-/// Write filter metadata as CSV comment header
-fn write_filter_comment(file: &mut File, profile: &JobProfile, include_stats: bool) -> Result<()> {
+const NO_TIMELINE: &str =
+    "Profile has no timeline data (record it with `memwatch run --timeline <FILE>`)";
+
+const NO_PROCESS_TIMELINE: &str = "Profile has no per-process timeline data (record it with `memwatch run --per-process-timeline`)";
+
+/// Suffix and size of the convenience column next to each `*_kib` column: MB under
+/// `--units si`, MiB otherwise
+fn convenience_unit(units: Units) -> (&'static str, f64) {
+    match units {
+        Units::Si => ("mb", 1e6 / 1024.0),
+        _ => ("mib", memory::KIB_PER_MIB),
+    }
+}
+
+/// Write the run label and filter metadata as CSV comment header
+fn write_comments<W: Write>(file: &mut W, profile: &JobProfile, include_stats: bool) -> Result<()> {
+    if let Some(ref label) = profile.label {
+        writeln!(file, "# Label: {}", label)?;
+    }
+    if !profile.tags.is_empty() {
+        writeln!(file, "# Tags: {}", profile.format_tags())?;
+    }
+    for pause in &profile.pauses {
+        writeln!(
+            file,
+            "# Paused: {:.1}s to {:.1}s (no samples)",
+            pause.start_elapsed_seconds,
+            pause.start_elapsed_seconds + pause.duration_seconds
+        )?;
+    }
+
     if let Some(ref filter) = profile.filter {
         write!(file, "# Filter: {}", filter.to_csv_comment())?;
 
@@ -21,6 +52,20 @@ fn write_filter_comment(file: &mut File, profile: &JobProfile, include_stats: bo
             } else {
                 writeln!(file)?;
             }
+            if profile.below_min_rss_count > 0 {
+                writeln!(
+                    file,
+                    "# Omitted: {} processes below min_rss_kib (totaling {} KiB)",
+                    profile.below_min_rss_count, profile.below_min_rss_total_kib
+                )?;
+            }
+            if profile.short_lived_count > 0 {
+                writeln!(
+                    file,
+                    "# Omitted: {} processes below min_lifetime_seconds (totaling {} KiB)",
+                    profile.short_lived_count, profile.short_lived_total_kib
+                )?;
+            }
         } else {
             writeln!(file)?;
             writeln!(
@@ -37,32 +82,68 @@ fn write_filter_comment(file: &mut File, profile: &JobProfile, include_stats: bo
 }
 
 /// Export per-process peak RSS to CSV
-pub fn export_process_csv(profile: &JobProfile, path: &str) -> Result<()> {
-    let mut file =
-        File::create(path).context(format!("Failed to create per-process CSV file: {}", path))?;
+pub fn export_process_csv(profile: &JobProfile, path: &str, order: ProcessOrder) -> Result<()> {
+    let mut file = File::create(path).context(MemwatchError::export_failed(
+        "Failed to create per-process CSV file",
+        path,
+    ))?;
+    write_process_csv(profile, &mut file, order)
+}
 
-    write_filter_comment(&mut file, profile, true)?;
+/// Write per-process peak RSS as CSV, rows in `order`
+pub fn write_process_csv<W: Write>(
+    profile: &JobProfile,
+    file: &mut W,
+    order: ProcessOrder,
+) -> Result<()> {
+    write_comments(file, profile, true)?;
 
-    // Write header
-    writeln!(
+    // Write header; I/O columns only for profiles recorded with --show-io
+    let show_io = profile.io_read_bytes.is_some();
+    let (suffix, kib_per_unit) = convenience_unit(config::units());
+    write!(
         file,
-        "pid,ppid,command,max_rss_kib,max_rss_mib,first_seen,last_seen"
+        "pid,ppid,command,max_rss_kib,max_rss_{},first_seen,last_seen,start_time,age_seconds,lifetime_seconds,first_rss_kib,rss_growth_kib,avg_rss_kib,exe_path",
+        suffix
     )?;
+    if show_io {
+        write!(file, ",io_read_bytes,io_write_bytes")?;
+    }
+    writeln!(file)?;
 
-    // Write each process (filter out processes with 0 RSS)
-    for proc in profile.processes.iter().filter(|p| p.max_rss_kib > 0) {
-        let max_rss_mib = proc.max_rss_kib as f64 / memory::KIB_PER_MIB;
-        writeln!(
+    // Write each process (0-RSS processes only with --include-zero)
+    let column = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+    for proc in profile.sorted_processes(order) {
+        let max_rss = proc.max_rss_kib as f64 / kib_per_unit;
+        write!(
             file,
-            "{},{},\"{}\",{},{:.2},{},{}",
+            "{},{},\"{}\",{},{:.2},{},{},{},{},{:.3},{},{},{},\"{}\"",
             proc.pid,
             proc.ppid,
             escape_csv(&proc.command),
             proc.max_rss_kib,
-            max_rss_mib,
+            max_rss,
             proc.first_seen.to_rfc3339(),
-            proc.last_seen.to_rfc3339()
+            proc.last_seen.to_rfc3339(),
+            proc.start_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            proc.age_seconds
+                .map(|a| format!("{:.3}", a))
+                .unwrap_or_default(),
+            proc.lifetime_seconds,
+            column(proc.first_rss_kib),
+            column(proc.rss_growth_kib),
+            column(proc.avg_rss_kib),
+            escape_csv(proc.exe_path.as_deref().unwrap_or_default())
         )?;
+        if show_io {
+            write!(
+                file,
+                ",{},{}",
+                column(proc.io_read_bytes),
+                column(proc.io_write_bytes)
+            )?;
+        }
+        writeln!(file)?;
     }
 
     Ok(())
@@ -70,32 +151,114 @@ pub fn export_process_csv(profile: &JobProfile, path: &str) -> Result<()> {
 
 /// Export timeline data to CSV
 pub fn export_timeline_csv(profile: &JobProfile, path: &str) -> Result<()> {
-    let mut file =
-        File::create(path).context(format!("Failed to create timeline CSV file: {}", path))?;
+    // Check before creating the file so a missing timeline leaves nothing behind
+    if profile.timeline.is_none() {
+        anyhow::bail!(NO_TIMELINE);
+    }
+
+    let mut file = File::create(path).context(MemwatchError::export_failed(
+        "Failed to create timeline CSV file",
+        path,
+    ))?;
+    write_timeline_csv(profile, &mut file)
+}
 
-    let timeline = profile.timeline.as_ref()
-        .context("Timeline data not available. This is a bug - timeline should be tracked when --timeline is used.")?;
+/// Write timeline data as CSV; fails if the profile was recorded without `--timeline`
+pub fn write_timeline_csv<W: Write>(profile: &JobProfile, file: &mut W) -> Result<()> {
+    let timeline = profile.timeline.as_ref().context(NO_TIMELINE)?;
 
-    write_filter_comment(&mut file, profile, false)?;
+    write_comments(file, profile, false)?;
+
+    // The smoothed column only appears for timelines recorded with --smooth
+    let smoothed = timeline.iter().any(|p| p.smoothed_rss_kib.is_some());
 
     // Write header
-    writeln!(
+    let (suffix, kib_per_unit) = convenience_unit(config::units());
+    write!(
         file,
-        "timestamp,elapsed_seconds,total_rss_kib,total_rss_mib,process_count"
+        "timestamp,elapsed_seconds,total_rss_kib,total_rss_{},process_count",
+        suffix
     )?;
+    if smoothed {
+        write!(file, ",smoothed_rss_kib")?;
+    }
+    writeln!(file)?;
 
     // Write each timeline point
     for point in timeline {
-        let total_rss_mib = point.total_rss_kib as f64 / memory::KIB_PER_MIB;
-        writeln!(
+        let total_rss = point.total_rss_kib as f64 / kib_per_unit;
+        write!(
             file,
             "{},{:.3},{},{:.2},{}",
             point.timestamp.to_rfc3339(),
             point.elapsed_seconds,
             point.total_rss_kib,
-            total_rss_mib,
+            total_rss,
             point.process_count
         )?;
+        if smoothed {
+            write!(file, ",{}", point.smoothed_rss_kib.unwrap_or_default())?;
+        }
+        writeln!(file)?;
+    }
+
+    Ok(())
+}
+
+/// Export per-process RSS over time as a wide CSV for stacked-area plots
+pub fn export_stacked_csv(profile: &JobProfile, path: &str) -> Result<()> {
+    if !has_process_timeline(profile) {
+        anyhow::bail!(NO_PROCESS_TIMELINE);
+    }
+
+    let mut file = File::create(path).context(MemwatchError::export_failed(
+        "Failed to create stacked CSV file",
+        path,
+    ))?;
+    write_stacked_csv(profile, &mut file)
+}
+
+fn has_process_timeline(profile: &JobProfile) -> bool {
+    profile
+        .timeline
+        .as_ref()
+        .is_some_and(|t| t.iter().all(|p| p.process_rss_kib.is_some()))
+}
+
+/// Write one row per sample: `elapsed_seconds` followed by one RSS column per process.
+///
+/// Columns follow the displayed processes (filters applied, largest peak first) and
+/// are named `pid_<N>_<command>`; samples where a process was not alive are 0.
+pub fn write_stacked_csv<W: Write>(profile: &JobProfile, file: &mut W) -> Result<()> {
+    if !has_process_timeline(profile) {
+        anyhow::bail!(NO_PROCESS_TIMELINE);
+    }
+    let timeline = profile.timeline.as_deref().unwrap_or_default();
+
+    let columns: Vec<_> = profile.displayed_processes().collect();
+
+    write_comments(file, profile, true)?;
+
+    // Write header
+    write!(file, "elapsed_seconds")?;
+    for proc in &columns {
+        let name: String = extract_command_name(&proc.command)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        write!(file, ",pid_{}_{}", proc.pid, name)?;
+    }
+    writeln!(file)?;
+
+    // Write each sample, zero-filling processes that were not alive
+    for point in timeline {
+        let rss = point.process_rss_kib.as_ref();
+        write!(file, "{:.3}", point.elapsed_seconds)?;
+        for proc in &columns {
+            let kib = rss.and_then(|m| m.get(&proc.pid)).copied().unwrap_or(0);
+            write!(file, ",{}", kib)?;
+        }
+        writeln!(file)?;
     }
 
     Ok(())
@@ -117,4 +280,108 @@ mod tests {
         assert_eq!(escape_csv("hello \"world\""), "hello \"\"world\"\"");
         assert_eq!(escape_csv("test"), "test");
     }
+
+    #[test]
+    fn test_write_csv_to_buffer() {
+        let profile = JobProfile {
+            processes: vec![crate::types::ProcessStats {
+                pid: 42,
+                command: "say \"hi\"".to_string(),
+                max_rss_kib: 2048,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        write_process_csv(&profile, &mut buf, ProcessOrder::default()).unwrap();
+        let csv = String::from_utf8(buf).unwrap();
+        assert!(csv.starts_with("pid,ppid,command,"));
+        assert!(csv.contains("42,0,\"say \"\"hi\"\"\",2048,2.00,"));
+
+        let err = write_timeline_csv(&profile, &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("--timeline"));
+    }
+
+    #[test]
+    fn test_include_zero_rss() {
+        let mut profile = JobProfile {
+            processes: vec![
+                crate::types::ProcessStats {
+                    pid: 1,
+                    command: "worker".to_string(),
+                    max_rss_kib: 2048,
+                    ..Default::default()
+                },
+                crate::types::ProcessStats {
+                    pid: 2,
+                    command: "kworker".to_string(),
+                    max_rss_kib: 0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let write = |profile: &JobProfile| {
+            let mut buf = Vec::new();
+            write_process_csv(profile, &mut buf, ProcessOrder::default()).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+
+        assert!(!write(&profile).contains("kworker"));
+
+        profile.include_zero_rss = true;
+        assert!(write(&profile).contains("2,0,\"kworker\",0,"));
+    }
+
+    #[test]
+    fn test_write_stacked_csv_zero_fills() {
+        use crate::types::{ProcessStats, TimelinePoint};
+        use chrono::Utc;
+
+        let process = |pid: i32, command: &str, max_rss_kib: u64| ProcessStats {
+            pid,
+            command: command.to_string(),
+            max_rss_kib,
+            ..Default::default()
+        };
+        let point = |elapsed_seconds: f64, rss: &[(i32, u64)]| TimelinePoint {
+            timestamp: Utc::now(),
+            elapsed_seconds,
+            total_rss_kib: rss.iter().map(|(_, kib)| kib).sum(),
+            process_count: rss.len(),
+            process_rss_kib: Some(rss.iter().copied().collect()),
+            smoothed_rss_kib: None,
+        };
+
+        let mut profile = JobProfile {
+            processes: vec![
+                process(20, "/usr/bin/python3 job.py", 300),
+                process(10, "sh", 100),
+            ],
+            timeline: Some(vec![
+                point(0.0, &[(10, 100)]),
+                point(0.5, &[(10, 100), (20, 300)]),
+                point(1.0, &[(20, 250)]),
+            ]),
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        write_stacked_csv(&profile, &mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "elapsed_seconds,pid_20_python3,pid_10_sh\n\
+             0.000,0,100\n\
+             0.500,300,100\n\
+             1.000,250,0\n"
+        );
+
+        // A plain --timeline profile lacks the per-process data
+        for point in profile.timeline.as_mut().unwrap() {
+            point.process_rss_kib = None;
+        }
+        let err = write_stacked_csv(&profile, &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("--per-process-timeline"));
+    }
 }
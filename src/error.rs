@@ -0,0 +1,338 @@
+//! Fatal errors and how they are reported (`--error-format`).
+//!
+//! Errors travel as `anyhow::Error` like everywhere else. Where a failure is worth
+//! telling apart, a `MemwatchError` is attached as context: humans still see the
+//! same `Error: ...` line, while `--error-format json` finds the `MemwatchError` in
+//! the chain and reports its stable code and the path, pattern, or command involved.
+
+use crate::cli::ErrorFormat;
+use crate::types::exit_codes;
+use serde_json::{Map, Value, json};
+use std::fmt;
+
+/// A classified failure; displays as the message humans see
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemwatchError {
+    /// The command could not be started
+    SpawnFailed { message: String, command: String },
+    /// The command does not exist; `suggestion` is a similarly named one on PATH
+    CommandNotFound {
+        message: String,
+        command: String,
+        suggestion: Option<String>,
+    },
+    /// The command exists but may not be executed
+    PermissionDenied { message: String, command: String },
+    /// A regex given as an argument or in a file does not compile
+    InvalidRegex { message: String, pattern: String },
+    /// An argument was rejected (by clap, or after parsing)
+    InvalidArgument { message: String },
+    /// An input file (profile, config, batch list, budgets) could not be read or parsed
+    ReadFailed { message: String, path: String },
+    /// An output file or directory could not be written
+    ExportFailed { message: String, path: String },
+}
+
+impl MemwatchError {
+    pub fn spawn_failed(message: impl Into<String>, command: impl fmt::Display) -> Self {
+        MemwatchError::SpawnFailed {
+            message: message.into(),
+            command: command.to_string(),
+        }
+    }
+
+    /// `command not found: <command>`, as a shell would put it
+    pub fn command_not_found(command: impl fmt::Display, suggestion: Option<String>) -> Self {
+        MemwatchError::CommandNotFound {
+            message: format!("command not found: {}", command),
+            command: command.to_string(),
+            suggestion,
+        }
+    }
+
+    /// `permission denied: <command>`, as a shell would put it
+    pub fn permission_denied(command: impl fmt::Display) -> Self {
+        MemwatchError::PermissionDenied {
+            message: format!("permission denied: {}", command),
+            command: command.to_string(),
+        }
+    }
+
+    pub fn invalid_regex(message: impl Into<String>, pattern: impl fmt::Display) -> Self {
+        MemwatchError::InvalidRegex {
+            message: message.into(),
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// `what` failed on `path`, as in `Failed to read profile: run.json`
+    pub fn read_failed(what: &str, path: impl fmt::Display) -> Self {
+        MemwatchError::ReadFailed {
+            message: format!("{}: {}", what, path),
+            path: path.to_string(),
+        }
+    }
+
+    /// `what` failed on `path`, as in `Failed to create file: out/run.md`
+    pub fn export_failed(what: &str, path: impl fmt::Display) -> Self {
+        MemwatchError::ExportFailed {
+            message: format!("{}: {}", what, path),
+            path: path.to_string(),
+        }
+    }
+
+    /// Stable identifier of the kind of failure, as written in JSON
+    pub fn code(&self) -> &'static str {
+        match self {
+            MemwatchError::SpawnFailed { .. } => "spawn_failed",
+            MemwatchError::CommandNotFound { .. } => "command_not_found",
+            MemwatchError::PermissionDenied { .. } => "permission_denied",
+            MemwatchError::InvalidRegex { .. } => "invalid_regex",
+            MemwatchError::InvalidArgument { .. } => "invalid_argument",
+            MemwatchError::ReadFailed { .. } => "read_failed",
+            MemwatchError::ExportFailed { .. } => "export_failed",
+        }
+    }
+
+    /// Exit code for the failure: `USAGE` for rejected arguments, the shell's 127 and
+    /// 126 for a command that is missing or not executable, else `ERROR`
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MemwatchError::InvalidRegex { .. } | MemwatchError::InvalidArgument { .. } => {
+                exit_codes::USAGE
+            }
+            MemwatchError::CommandNotFound { .. } => exit_codes::COMMAND_NOT_FOUND,
+            MemwatchError::PermissionDenied { .. } => exit_codes::NOT_EXECUTABLE,
+            _ => exit_codes::ERROR,
+        }
+    }
+
+    /// The path, pattern, or command involved, keyed as in JSON
+    fn fields(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            MemwatchError::SpawnFailed { command, .. }
+            | MemwatchError::PermissionDenied { command, .. } => vec![("command", command)],
+            MemwatchError::CommandNotFound {
+                command,
+                suggestion,
+                ..
+            } => {
+                let mut fields = vec![("command", command.as_str())];
+                if let Some(suggestion) = suggestion {
+                    fields.push(("suggestion", suggestion.as_str()));
+                }
+                fields
+            }
+            MemwatchError::InvalidRegex { pattern, .. } => vec![("pattern", pattern)],
+            MemwatchError::InvalidArgument { .. } => Vec::new(),
+            MemwatchError::ReadFailed { path, .. } | MemwatchError::ExportFailed { path, .. } => {
+                vec![("path", path)]
+            }
+        }
+    }
+}
+
+impl fmt::Display for MemwatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemwatchError::SpawnFailed { message, .. }
+            | MemwatchError::CommandNotFound { message, .. }
+            | MemwatchError::PermissionDenied { message, .. }
+            | MemwatchError::InvalidRegex { message, .. }
+            | MemwatchError::InvalidArgument { message }
+            | MemwatchError::ReadFailed { message, .. }
+            | MemwatchError::ExportFailed { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for MemwatchError {}
+
+/// Code of failures nothing classified
+const UNCLASSIFIED: &str = "error";
+
+/// The JSON object `--error-format json` prints for `error`, and the exit code
+pub fn to_json(error: &anyhow::Error) -> (Value, i32) {
+    let classified = error.downcast_ref::<MemwatchError>();
+    let exit_code = classified.map_or(exit_codes::ERROR, MemwatchError::exit_code);
+
+    let mut object = Map::new();
+    object.insert(
+        "code".to_string(),
+        json!(classified.map_or(UNCLASSIFIED, MemwatchError::code)),
+    );
+    // The whole chain, since there is no later line to carry the causes
+    object.insert("message".to_string(), json!(format!("{:#}", error)));
+    for (key, value) in classified.map(MemwatchError::fields).unwrap_or_default() {
+        object.insert(key.to_string(), json!(value));
+    }
+    object.insert("exit_code".to_string(), json!(exit_code));
+    (Value::Object(object), exit_code)
+}
+
+/// Print a fatal error on stderr in `format` and exit with its exit code
+pub fn exit_with(error: anyhow::Error, format: ErrorFormat) -> ! {
+    let exit_code = match format {
+        ErrorFormat::Human => {
+            let classified = error.downcast_ref::<MemwatchError>();
+            match classified {
+                // Says all there is to say, like the shell's own message would
+                Some(
+                    missing @ (MemwatchError::CommandNotFound { .. }
+                    | MemwatchError::PermissionDenied { .. }),
+                ) => eprintln!("Error: {}", missing),
+                _ => eprintln!("Error: {}", error),
+            }
+            if let Some(MemwatchError::CommandNotFound {
+                suggestion: Some(suggestion),
+                ..
+            }) = classified
+            {
+                eprintln!("  Did you mean '{}'?", suggestion);
+            }
+            classified.map_or(exit_codes::ERROR, MemwatchError::exit_code)
+        }
+        ErrorFormat::Json => {
+            let (object, exit_code) = to_json(&error);
+            eprintln!("{}", object);
+            exit_code
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+/// Report a clap error (usage mistakes, but also `--help` and `--version`, which
+/// clap prints itself) and exit
+pub fn exit_with_clap(error: clap::Error, format: ErrorFormat) -> ! {
+    if format == ErrorFormat::Json && error.use_stderr() {
+        // A value parser's classified error, such as an invalid --group-by regex,
+        // keeps its own code
+        let source = std::error::Error::source(&error);
+        if let Some(classified) = source.and_then(|e| e.downcast_ref::<MemwatchError>()) {
+            exit_with(classified.clone().into(), format);
+        }
+        let message = error.to_string().trim_end().to_string();
+        exit_with(MemwatchError::InvalidArgument { message }.into(), format);
+    }
+    error.exit();
+}
+
+/// Environment variable for `--error-format`
+pub const ERROR_FORMAT_ENV: &str = "MEMWATCH_ERROR_FORMAT";
+
+/// `--error-format` as given in raw arguments or `MEMWATCH_ERROR_FORMAT`, for errors
+/// that happen before the arguments are parsed (including those that fail to parse)
+pub fn requested_format(
+    args: &[std::ffi::OsString],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> ErrorFormat {
+    let mut tokens = args.iter().skip(1).filter_map(|arg| arg.to_str());
+    let mut requested = lookup(ERROR_FORMAT_ENV);
+    while let Some(token) = tokens.next() {
+        match token {
+            "--" => break,
+            "--error-format" => requested = tokens.next().map(String::from),
+            _ => {
+                if let Some(value) = token.strip_prefix("--error-format=") {
+                    requested = Some(value.to_string());
+                }
+            }
+        }
+    }
+    match requested.as_deref() {
+        Some("json") => ErrorFormat::Json,
+        _ => ErrorFormat::Human,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_json_carries_code_and_context() {
+        let spawn: anyhow::Result<()> = Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .context(MemwatchError::spawn_failed(
+                "Failed to execute: pytohn",
+                "pytohn",
+            ))
+            .context("Failed to start command");
+        let (object, exit_code) = to_json(&spawn.unwrap_err());
+        assert_eq!(object["code"], "spawn_failed");
+        assert_eq!(object["command"], "pytohn");
+        assert_eq!(exit_code, exit_codes::ERROR);
+        assert!(
+            object["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("Failed to start command: Failed to execute: pytohn: ")
+        );
+
+        let regex: anyhow::Error =
+            MemwatchError::invalid_regex("Invalid exclude pattern '(': must be valid regex", "(")
+                .into();
+        let (object, exit_code) = to_json(&regex);
+        assert_eq!(object["code"], "invalid_regex");
+        assert_eq!(object["pattern"], "(");
+        assert_eq!(exit_code, exit_codes::USAGE);
+
+        let export: anyhow::Result<()> = Err(anyhow::anyhow!("disk full")).context(
+            MemwatchError::export_failed("Failed to create file", "out/a.md"),
+        );
+        let (object, _) = to_json(&export.unwrap_err());
+        assert_eq!(object["code"], "export_failed");
+        assert_eq!(object["path"], "out/a.md");
+        assert_eq!(
+            object["message"],
+            "Failed to create file: out/a.md: disk full"
+        );
+
+        let missing: anyhow::Result<()> = Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .context(MemwatchError::command_not_found(
+                "pytohn",
+                Some("python3".to_string()),
+            ))
+            .context("Failed to start command");
+        let (object, exit_code) = to_json(&missing.unwrap_err());
+        assert_eq!(object["code"], "command_not_found");
+        assert_eq!(object["command"], "pytohn");
+        assert_eq!(object["suggestion"], "python3");
+        assert_eq!(exit_code, exit_codes::COMMAND_NOT_FOUND);
+
+        let denied: anyhow::Error = MemwatchError::permission_denied("./run.sh").into();
+        let (object, exit_code) = to_json(&denied);
+        assert_eq!(object["code"], "permission_denied");
+        assert_eq!(object["message"], "permission denied: ./run.sh");
+        assert_eq!(exit_code, exit_codes::NOT_EXECUTABLE);
+
+        let (object, exit_code) = to_json(&anyhow::anyhow!("something else"));
+        assert_eq!(object["code"], "error");
+        assert_eq!(exit_code, exit_codes::ERROR);
+    }
+
+    #[test]
+    fn test_requested_format() {
+        let args = |line: &str| -> Vec<OsString> { line.split(' ').map(OsString::from).collect() };
+        let no_env = |_: &str| None;
+
+        assert_eq!(
+            requested_format(&args("memwatch --error-format json run -- x"), no_env),
+            ErrorFormat::Json
+        );
+        assert_eq!(
+            requested_format(&args("memwatch run --error-format=json -- x"), no_env),
+            ErrorFormat::Json
+        );
+        // Arguments of the profiled command are not memwatch's
+        assert_eq!(
+            requested_format(&args("memwatch run -- x --error-format json"), no_env),
+            ErrorFormat::Human
+        );
+        assert_eq!(
+            requested_format(&args("memwatch run -- x"), |_| Some("json".to_string())),
+            ErrorFormat::Json
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use crate::cli::ConvertFormat;
+use crate::csv_writer;
+use crate::error::MemwatchError;
+use crate::reporter::{describe_filter, format_duration, format_memory};
+use crate::types::{JobProfile, ProcessOrder};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File extension used when writing a format into a directory
+fn extension(format: ConvertFormat) -> &'static str {
+    match format {
+        ConvertFormat::Json => "json",
+        ConvertFormat::Yaml => "yaml",
+        ConvertFormat::Md => "md",
+        ConvertFormat::Csv | ConvertFormat::Timeline | ConvertFormat::Stacked => "csv",
+    }
+}
+
+/// Write a single-file format to any writer
+pub fn write_profile<W: Write>(
+    profile: &JobProfile,
+    format: ConvertFormat,
+    writer: &mut W,
+) -> Result<()> {
+    match format {
+        ConvertFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, profile)?;
+            writeln!(writer)?;
+        }
+        ConvertFormat::Yaml => serde_yaml::to_writer(writer, profile)?,
+        ConvertFormat::Md => write_markdown(profile, writer)?,
+        ConvertFormat::Timeline => csv_writer::write_timeline_csv(profile, writer)?,
+        ConvertFormat::Stacked => csv_writer::write_stacked_csv(profile, writer)?,
+        ConvertFormat::Csv => {
+            csv_writer::write_process_csv(profile, writer, ProcessOrder::default())?
+        }
+    }
+    Ok(())
+}
+
+/// Convert a profile, writing to `out` (a file, or a directory where files are named
+/// after `stem`) or to stdout when `out` is None. Returns the files written.
+///
+/// `csv` produces two files (per-process and timeline), so it always writes into a
+/// directory, defaulting to the current one.
+pub fn convert(
+    profile: &JobProfile,
+    format: ConvertFormat,
+    stem: &str,
+    out: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    // Formats that can fail on missing data are rendered once up front, so a failed
+    // conversion never leaves an empty file behind
+    if matches!(format, ConvertFormat::Timeline | ConvertFormat::Stacked) {
+        write_profile(profile, format, &mut std::io::sink())?;
+    }
+
+    if format == ConvertFormat::Csv {
+        let dir = Path::new(out.unwrap_or("."));
+        if dir.is_file() {
+            anyhow::bail!("--to csv writes several files; --out must be a directory");
+        }
+        std::fs::create_dir_all(dir).context(MemwatchError::export_failed(
+            "Failed to create directory",
+            dir.display(),
+        ))?;
+
+        let process_path = dir.join(format!("{}.csv", stem));
+        csv_writer::export_process_csv(
+            profile,
+            &process_path.to_string_lossy(),
+            ProcessOrder::default(),
+        )?;
+        let mut written = vec![process_path];
+
+        if profile.timeline.is_some() {
+            let timeline_path = dir.join(format!("{}-timeline.csv", stem));
+            csv_writer::export_timeline_csv(profile, &timeline_path.to_string_lossy())?;
+            written.push(timeline_path);
+        } else {
+            eprintln!("Warning: Profile has no timeline data; wrote the per-process CSV only");
+        }
+        return Ok(written);
+    }
+
+    let Some(out) = out else {
+        let stdout = std::io::stdout();
+        write_profile(profile, format, &mut stdout.lock())?;
+        return Ok(Vec::new());
+    };
+
+    let path = if Path::new(out).is_dir() || out.ends_with('/') {
+        std::fs::create_dir_all(out).context(MemwatchError::export_failed(
+            "Failed to create directory",
+            out,
+        ))?;
+        let suffix = match format {
+            ConvertFormat::Timeline => "-timeline",
+            ConvertFormat::Stacked => "-stacked",
+            _ => "",
+        };
+        Path::new(out).join(format!("{}{}.{}", stem, suffix, extension(format)))
+    } else {
+        PathBuf::from(out)
+    };
+
+    let mut file = File::create(&path).context(MemwatchError::export_failed(
+        "Failed to create file",
+        path.display(),
+    ))?;
+    write_profile(profile, format, &mut file)?;
+    Ok(vec![path])
+}
+
+/// Escape text for a markdown table cell
+fn escape_md(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+/// Render a profile as a markdown report
+fn write_markdown<W: Write>(profile: &JobProfile, w: &mut W) -> Result<()> {
+    writeln!(w, "# memwatch: `{}`", profile.command.join(" "))?;
+    writeln!(w)?;
+    writeln!(w, "| | |")?;
+    writeln!(w, "|---|---|")?;
+    if let Some(ref label) = profile.label {
+        writeln!(w, "| Label | {} |", escape_md(label))?;
+    }
+    if !profile.tags.is_empty() {
+        writeln!(w, "| Tags | {} |", escape_md(&profile.format_tags()))?;
+    }
+    writeln!(w, "| Start | {} |", profile.start_time.to_rfc3339())?;
+    writeln!(
+        w,
+        "| Duration | {} |",
+        format_duration(profile.duration_seconds)
+    )?;
+    writeln!(w, "| Samples | {} |", profile.samples)?;
+    writeln!(
+        w,
+        "| Total peak | {} |",
+        format_memory(profile.max_total_rss_kib)
+    )?;
+    if let Some(code) = profile.exit_code {
+        writeln!(w, "| Exit code | {} |", code)?;
+    }
+    if let Some(ref filter) = profile.filter {
+        for line in describe_filter(filter) {
+            writeln!(w, "| Filter | {} |", escape_md(&line))?;
+        }
+    }
+
+    writeln!(w)?;
+    writeln!(w, "## Per-process peaks")?;
+    writeln!(w)?;
+    writeln!(w, "| PID | Memory | Peak at | Command |")?;
+    writeln!(w, "|---:|---:|---:|---|")?;
+    for proc in profile.displayed_processes() {
+        let elapsed_secs = (proc.peak_time - profile.start_time).num_milliseconds() as f64 / 1000.0;
+        writeln!(
+            w,
+            "| {} | {} | {:.1}s | `{}` |",
+            proc.pid,
+            format_memory(proc.max_rss_kib),
+            elapsed_secs,
+            escape_md(&proc.command)
+        )?;
+    }
+
+    if profile.process_groups.len() > 1 {
+        writeln!(w)?;
+        writeln!(w, "## Process groups")?;
+        writeln!(w)?;
+        writeln!(w, "| Command | Processes | Total peak |")?;
+        writeln!(w, "|---|---:|---:|")?;
+        for group in &profile.process_groups {
+            writeln!(
+                w,
+                "| `{}` | {} | {} |",
+                escape_md(&group.command_name),
+                group.count,
+                format_memory(group.total_peak_rss_kib)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ProcessStats;
+
+    fn profile() -> JobProfile {
+        JobProfile {
+            command: vec!["sh".to_string(), "-c".to_string(), "a | b".to_string()],
+            max_total_rss_kib: 4096,
+            processes: vec![ProcessStats {
+                pid: 7,
+                command: "grep a|b".to_string(),
+                max_rss_kib: 4096,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_markdown_report() {
+        let mut buf = Vec::new();
+        write_profile(&profile(), ConvertFormat::Md, &mut buf).unwrap();
+        let md = String::from_utf8(buf).unwrap();
+
+        assert!(md.starts_with("# memwatch: `sh -c a | b`"));
+        assert!(md.contains("| Total peak | 4.0 MiB |"));
+        assert!(md.contains("| 7 | 4.0 MiB | 0.0s | `grep a\\|b` |"));
+    }
+
+    #[test]
+    fn test_yaml_round_trips() {
+        let mut buf = Vec::new();
+        write_profile(&profile(), ConvertFormat::Yaml, &mut buf).unwrap();
+        let parsed: JobProfile = serde_yaml::from_slice(&buf).unwrap();
+        assert_eq!(parsed.processes[0].command, "grep a|b");
+    }
+
+    #[test]
+    fn test_timeline_format_requires_timeline() {
+        let dir = std::env::temp_dir().join(format!("memwatch-convert-{}", std::process::id()));
+        let out = format!("{}/", dir.display());
+
+        let err = convert(&profile(), ConvertFormat::Timeline, "p", Some(&out)).unwrap_err();
+        assert!(err.to_string().contains("no timeline data"));
+        // Nothing was created for the failed conversion
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::error::MemwatchError;
+use crate::reporter::{color_choice, format_duration, format_memory};
+use crate::types::JobProfile;
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+/// Environment variable overriding the history location
+pub const HISTORY_DIR_ENV: &str = "MEMWATCH_HISTORY_DIR";
+
+/// A saved run
+pub struct HistoryEntry {
+    pub id: String,
+    pub path: PathBuf,
+    pub profile: JobProfile,
+}
+
+/// Directory holding saved runs: `$MEMWATCH_HISTORY_DIR`, else
+/// `$XDG_DATA_HOME/memwatch/history`, else `~/.local/share/memwatch/history`
+pub fn history_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os(HISTORY_DIR_ENV) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let data_home = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = std::env::var_os("HOME")
+                .context("Cannot locate history directory: HOME is not set")?;
+            PathBuf::from(home).join(".local").join("share")
+        }
+    };
+
+    Ok(data_home.join("memwatch").join("history"))
+}
+
+/// Save a finished profile to the history store, returning its id.
+///
+/// Every run is its own file in a per-day directory (`YYYY-MM-DD/<id>.json`), written
+/// to a temporary file and renamed into place, so concurrent memwatch processes never
+/// write to the same file and readers never observe partial writes.
+pub fn save(profile: &JobProfile) -> Result<String> {
+    save_in(&history_dir()?, profile)
+}
+
+fn save_in(root: &Path, profile: &JobProfile) -> Result<String> {
+    let day_dir = root.join(profile.start_time.format("%Y-%m-%d").to_string());
+    fs::create_dir_all(&day_dir).context(MemwatchError::export_failed(
+        "Failed to create history directory",
+        day_dir.display(),
+    ))?;
+
+    // The pid keeps concurrent processes apart; the suffix handles several saves
+    // from one process within the same second
+    let base_id = format!(
+        "{}-{}",
+        profile.start_time.format("%Y%m%d-%H%M%S"),
+        std::process::id()
+    );
+    let mut id = base_id.clone();
+    let mut n = 2;
+    while day_dir.join(format!("{}.json", id)).exists() {
+        id = format!("{}-{}", base_id, n);
+        n += 1;
+    }
+
+    let json = serde_json::to_string_pretty(profile)?;
+    write_atomic(&day_dir.join(format!("{}.json", id)), json.as_bytes())?;
+
+    Ok(id)
+}
+
+/// Write a file by writing a sibling temporary file and renaming it into place
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .context(format!("Invalid output path: {}", path.display()))?;
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let mut file = fs::File::create(&tmp_path).context(MemwatchError::export_failed(
+        "Failed to create file",
+        tmp_path.display(),
+    ))?;
+    file.write_all(contents)
+        .and_then(|_| file.sync_all())
+        .context(MemwatchError::export_failed(
+            "Failed to write file",
+            tmp_path.display(),
+        ))?;
+
+    fs::rename(&tmp_path, path).context(MemwatchError::export_failed(
+        "Failed to write file",
+        path.display(),
+    ))
+}
+
+/// Load all saved runs, most recent first. Unreadable entries are skipped with a warning.
+pub fn load_all() -> Result<Vec<HistoryEntry>> {
+    load_all_in(&history_dir()?)
+}
+
+fn load_all_in(root: &Path) -> Result<Vec<HistoryEntry>> {
+    let mut entries = Vec::new();
+    if !root.exists() {
+        return Ok(entries);
+    }
+
+    for day in fs::read_dir(root).context(MemwatchError::read_failed(
+        "Failed to read history directory",
+        root.display(),
+    ))? {
+        let day = day?.path();
+        if !day.is_dir() {
+            continue;
+        }
+
+        for file in fs::read_dir(&day)? {
+            let path = file?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let Some(id) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+
+            match JobProfile::load_json(&path.to_string_lossy()) {
+                Ok(profile) => entries.push(HistoryEntry { id, path, profile }),
+                Err(e) => eprintln!("Warning: Skipping history entry {}: {}", id, e),
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| {
+        b.profile
+            .start_time
+            .cmp(&a.profile.start_time)
+            .then_with(|| b.id.cmp(&a.id))
+    });
+    Ok(entries)
+}
+
+/// Find a saved run by id or unique id prefix
+pub fn find(entries: Vec<HistoryEntry>, id: &str) -> Result<HistoryEntry> {
+    let mut matches: Vec<HistoryEntry> = entries
+        .into_iter()
+        .filter(|e| e.id.starts_with(id))
+        .collect();
+
+    match matches.len() {
+        0 => anyhow::bail!("No history entry matches id '{}'", id),
+        1 => Ok(matches.remove(0)),
+        n => anyhow::bail!("Id '{}' is ambiguous ({} entries match)", id, n),
+    }
+}
+
+/// Print the list of saved runs
+pub fn print_history(entries: &[HistoryEntry]) {
+    let mut stdout = StandardStream::stdout(color_choice());
+
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    println!(
+        "  {:22}  {:19}  {:>10}  {:>8}  {:>4}  COMMAND",
+        "ID", "DATE", "PEAK", "DURATION", "EXIT"
+    );
+    let _ = stdout.reset();
+
+    for entry in entries {
+        let profile = &entry.profile;
+
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+        print!("  {:22}  ", entry.id);
+        let _ = stdout.reset();
+
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
+        print!(
+            "{:19}  ",
+            profile.start_time.format("%Y-%m-%d %H:%M:%S").to_string()
+        );
+        let _ = stdout.reset();
+
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
+        print!("{:>10}  ", format_memory(profile.max_total_rss_kib));
+        let _ = stdout.reset();
+
+        print!("{:>8}  ", format_duration(profile.duration_seconds));
+        match profile.exit_code {
+            Some(code) => print!("{:>4}  ", code),
+            None => print!("{:>4}  ", "-"),
+        }
+        println!("{}", profile.command.join(" "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_save_and_load_history() {
+        let root = std::env::temp_dir().join(format!("memwatch-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let older = JobProfile {
+            command: vec!["make".to_string()],
+            start_time: Utc::now() - Duration::days(1),
+            max_total_rss_kib: 100,
+            ..Default::default()
+        };
+        let newer = JobProfile {
+            command: vec!["cargo".to_string(), "test".to_string()],
+            start_time: Utc::now(),
+            max_total_rss_kib: 200,
+            ..Default::default()
+        };
+
+        let older_id = save_in(&root, &older).unwrap();
+        let newer_id = save_in(&root, &newer).unwrap();
+
+        let entries = load_all_in(&root).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, newer_id);
+        assert_eq!(entries[1].id, older_id);
+        assert_eq!(entries[1].profile.max_total_rss_kib, 100);
+
+        let found = find(load_all_in(&root).unwrap(), &older_id).unwrap();
+        assert_eq!(found.profile.command, vec!["make"]);
+        assert!(find(load_all_in(&root).unwrap(), "nope").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
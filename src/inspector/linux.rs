@@ -1,22 +1,87 @@
-use crate::types::ProcessSample;
+use crate::types::{IoBytes, MemorySplit, ProcessSample, SystemMemory};
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::debug;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
 
-use super::ProcessInspector;
+use super::{DEFAULT_PROC_PATH, ProcessInspector};
 
 /// Linux process inspector using /proc filesystem
-pub struct LinuxProcessInspector;
+pub struct LinuxProcessInspector {
+    /// Where procfs is read from (`/proc`, or `--proc-path`)
+    proc_path: PathBuf,
+    /// System boot time, needed to turn `/proc/[pid]/stat` start ticks into timestamps
+    boot_time: Option<DateTime<Utc>>,
+    /// Clock ticks per second (`USER_HZ`)
+    ticks_per_second: f64,
+    /// Executable paths from the last snapshot, by PID, so each is resolved only once
+    exe_cache: Mutex<HashMap<i32, CachedExe>>,
+}
+
+/// A resolved `/proc/[pid]/exe`, with what tells whether it still applies
+struct CachedExe {
+    /// Start time in clock ticks since boot; a different one means the PID was reused
+    start_ticks: u64,
+    /// Command line; a different one means the process exec'd another program
+    command: String,
+    path: Option<String>,
+}
+
+/// The fields memwatch uses from `/proc/[pid]/stat`
+struct ProcStat {
+    ppid: i32,
+    comm: String,
+    process_group: i32,
+    session: i32,
+    /// Start time in clock ticks since boot
+    start_ticks: u64,
+    /// User plus system CPU time in clock ticks
+    cpu_ticks: u64,
+}
+
+/// The fields memwatch uses from `/proc/[pid]/status`
+#[derive(Default)]
+struct ProcStatus {
+    rss_kib: u64,
+    /// Peak RSS (`VmHWM`)
+    hwm_kib: Option<u64>,
+    /// Real UID
+    uid: Option<u32>,
+}
+
+impl Default for LinuxProcessInspector {
+    fn default() -> Self {
+        Self::new(DEFAULT_PROC_PATH)
+    }
+}
 
 impl LinuxProcessInspector {
-    pub fn new() -> Self {
-        Self
+    /// Inspect processes through the procfs mounted at `proc_path`
+    pub fn new(proc_path: impl Into<PathBuf>) -> Self {
+        let proc_path = proc_path.into();
+        // SAFETY: sysconf has no preconditions
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+
+        Self {
+            boot_time: read_boot_time(&proc_path).ok(),
+            proc_path,
+            ticks_per_second: if ticks > 0 { ticks as f64 } else { 100.0 },
+            exe_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Path of a file in a process's directory, such as `/proc/[pid]/stat`
+    fn pid_path(&self, pid: i32, file: &str) -> PathBuf {
+        self.proc_path.join(pid.to_string()).join(file)
     }
 
-    fn read_proc_stat(&self, pid: i32) -> Result<(i32, String)> {
-        let stat_path = format!("/proc/{}/stat", pid);
-        let stat_content =
-            fs::read_to_string(&stat_path).context(format!("Failed to read {}", stat_path))?;
+    fn read_proc_stat(&self, pid: i32) -> Result<ProcStat> {
+        let stat_path = self.pid_path(pid, "stat");
+        let stat_content = fs::read_to_string(&stat_path)
+            .context(format!("Failed to read {}", stat_path.display()))?;
 
         // Parse /proc/[pid]/stat format:
         // pid (comm) state ppid ...
@@ -31,43 +96,68 @@ impl LinuxProcessInspector {
         let after_comm = &stat_content[end_paren + 1..].trim();
         let fields: Vec<&str> = after_comm.split_whitespace().collect();
 
-        if fields.len() < 2 {
+        if fields.len() < 20 {
             anyhow::bail!("Invalid stat format: not enough fields");
         }
 
-        // Field 0 is state, field 1 is ppid
+        // Field 0 is state, field 1 is ppid, fields 2 and 3 are pgrp and session,
+        // fields 11 and 12 are utime and stime, field 19 is starttime (3rd to 6th,
+        // 14th, 15th and 22nd in proc(5))
         let ppid = fields[1].parse::<i32>().context("Failed to parse ppid")?;
+        let process_group = fields[2].parse::<i32>().context("Failed to parse pgrp")?;
+        let session = fields[3]
+            .parse::<i32>()
+            .context("Failed to parse session")?;
+        let utime = fields[11].parse::<u64>().context("Failed to parse utime")?;
+        let stime = fields[12].parse::<u64>().context("Failed to parse stime")?;
+        let start_ticks = fields[19]
+            .parse::<u64>()
+            .context("Failed to parse starttime")?;
 
         let comm = stat_content[start_paren + 1..end_paren].to_string();
 
-        Ok((ppid, comm))
+        Ok(ProcStat {
+            ppid,
+            comm,
+            process_group,
+            session,
+            start_ticks,
+            cpu_ticks: utime + stime,
+        })
     }
 
-    fn read_proc_status_rss(&self, pid: i32) -> Result<u64> {
-        let status_path = format!("/proc/{}/status", pid);
-        let status_content =
-            fs::read_to_string(&status_path).context(format!("Failed to read {}", status_path))?;
+    fn start_time_from_ticks(&self, start_ticks: u64) -> Option<DateTime<Utc>> {
+        let millis = (ticks_to_seconds(start_ticks, self.ticks_per_second) * 1000.0) as i64;
+        self.boot_time
+            .map(|boot| boot + chrono::Duration::milliseconds(millis))
+    }
+
+    fn read_proc_status(&self, pid: i32) -> Result<ProcStatus> {
+        let status_path = self.pid_path(pid, "status");
+        let status_content = fs::read_to_string(&status_path)
+            .context(format!("Failed to read {}", status_path.display()))?;
+
+        // If VmRSS is not found, the process might not have RSS (kernel threads)
+        let mut status = ProcStatus::default();
 
         for line in status_content.lines() {
-            if line.starts_with("VmRSS:") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    let rss_kib = parts[1]
-                        .parse::<u64>()
-                        .context("Failed to parse VmRSS value")?;
-                    return Ok(rss_kib);
-                }
+            if let Some(value) = line.strip_prefix("VmRSS:") {
+                status.rss_kib = parse_status_kib(value).context("Failed to parse VmRSS value")?;
+            } else if let Some(value) = line.strip_prefix("VmHWM:") {
+                status.hwm_kib = parse_status_kib(value).ok();
+            } else if let Some(value) = line.strip_prefix("Uid:") {
+                // Real, effective, saved and filesystem UIDs; the real one owns the process
+                status.uid = value.split_whitespace().next().and_then(|u| u.parse().ok());
             }
         }
 
-        // If VmRSS is not found, the process might not have RSS (kernel threads)
-        Ok(0)
+        Ok(status)
     }
 
     fn read_cmdline(&self, pid: i32) -> Result<String> {
-        let cmdline_path = format!("/proc/{}/cmdline", pid);
-        let cmdline_content =
-            fs::read(&cmdline_path).context(format!("Failed to read {}", cmdline_path))?;
+        let cmdline_path = self.pid_path(pid, "cmdline");
+        let cmdline_content = fs::read(&cmdline_path)
+            .context(format!("Failed to read {}", cmdline_path.display()))?;
 
         if cmdline_content.is_empty() {
             // Kernel thread or empty cmdline - use comm from stat
@@ -84,14 +174,51 @@ impl LinuxProcessInspector {
 
         Ok(cmdline)
     }
+
+    /// Resolve the `/proc/[pid]/exe` link to the executable's path.
+    ///
+    /// None for kernel threads and for processes of other users, whose link cannot be
+    /// read without privileges. A binary replaced since it started keeps a ` (deleted)` suffix.
+    fn read_exe(&self, pid: i32) -> Option<String> {
+        fs::read_link(self.pid_path(pid, "exe"))
+            .ok()
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    /// The executable of a process, reusing the path from the previous snapshot unless
+    /// the PID now belongs to another process or program
+    fn cached_exe(
+        &self,
+        previous: &mut HashMap<i32, CachedExe>,
+        pid: i32,
+        start_ticks: u64,
+        command: &str,
+    ) -> CachedExe {
+        match previous.remove(&pid) {
+            Some(cached) if cached.start_ticks == start_ticks && cached.command == command => {
+                cached
+            }
+            _ => CachedExe {
+                start_ticks,
+                command: command.to_string(),
+                path: self.read_exe(pid),
+            },
+        }
+    }
 }
 
 impl ProcessInspector for LinuxProcessInspector {
     fn snapshot_all(&self) -> Result<Vec<ProcessSample>> {
-        let proc_path = Path::new("/proc");
         let mut processes = Vec::new();
+        // Only processes still running are carried over to the next snapshot
+        let mut exe_cache = self
+            .exe_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut previous = std::mem::take(&mut *exe_cache);
 
-        let entries = fs::read_dir(proc_path).context("Failed to read /proc directory")?;
+        let entries = fs::read_dir(&self.proc_path)
+            .context(format!("Failed to read {}", self.proc_path.display()))?;
 
         for entry in entries {
             let entry = match entry {
@@ -111,31 +238,206 @@ impl ProcessInspector for LinuxProcessInspector {
             };
 
             // Try to read process info, skip if we can't (process may have exited)
-            let (ppid, comm) = match self.read_proc_stat(pid) {
-                Ok(info) => info,
-                Err(_) => continue,
+            let stat = match self.read_proc_stat(pid) {
+                Ok(stat) => stat,
+                Err(e) => {
+                    debug!("Skipping pid {}: {:#}", pid, e);
+                    continue;
+                }
             };
 
-            let rss_kib = match self.read_proc_status_rss(pid) {
-                Ok(rss) => rss,
-                Err(_) => continue,
+            let status = match self.read_proc_status(pid) {
+                Ok(status) => status,
+                Err(e) => {
+                    debug!("Skipping pid {}: {:#}", pid, e);
+                    continue;
+                }
             };
 
             let cmdline = match self.read_cmdline(pid) {
                 Ok(cmd) if !cmd.is_empty() => cmd,
-                _ => comm.clone(),
+                _ => stat.comm,
             };
+            let exe = self.cached_exe(&mut previous, pid, stat.start_ticks, &cmdline);
 
             processes.push(ProcessSample {
                 pid,
-                ppid,
-                rss_kib,
+                ppid: stat.ppid,
+                rss_kib: status.rss_kib,
                 command: cmdline,
+                start_time: self.start_time_from_ticks(stat.start_ticks),
+                hwm_kib: status.hwm_kib,
+                cpu_seconds: Some(ticks_to_seconds(stat.cpu_ticks, self.ticks_per_second)),
+                process_group: Some(stat.process_group),
+                session: Some(stat.session),
+                uid: status.uid,
+                exe_path: exe.path.clone(),
             });
+            exe_cache.insert(pid, exe);
         }
 
         Ok(processes)
     }
+
+    fn numa_rss_kib(&self, pid: i32) -> Option<HashMap<u32, u64>> {
+        // Absent on kernels built without CONFIG_NUMA; unreadable if the process exited
+        let content = read_optional(&self.pid_path(pid, "numa_maps"))?;
+        Some(parse_numa_maps(&content))
+    }
+
+    fn io_bytes(&self, pid: i32) -> Option<IoBytes> {
+        // Only readable for processes we may ptrace (EACCES otherwise)
+        let content = read_optional(&self.pid_path(pid, "io"))?;
+        parse_proc_io(&content)
+    }
+
+    fn memory_split(&self, pid: i32) -> Option<MemorySplit> {
+        // Walks every mapping in the kernel, so only read at a process's new peaks
+        let content = read_optional(&self.pid_path(pid, "smaps_rollup"))?;
+        parse_smaps_rollup(&content)
+    }
+
+    fn system_memory(&self) -> Result<SystemMemory> {
+        let path = self.proc_path.join("meminfo");
+        let content =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        parse_meminfo(&content).context(format!("No MemTotal in {}", path.display()))
+    }
+}
+
+/// Read an optional /proc file, logging why it was unreadable
+fn read_optional(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .map_err(|e| debug!("Failed to read {}: {}", path.display(), e))
+        .ok()
+}
+
+/// Convert a `/proc/[pid]/stat` time in clock ticks to seconds
+fn ticks_to_seconds(ticks: u64, ticks_per_second: f64) -> f64 {
+    ticks as f64 / ticks_per_second
+}
+
+/// Parse the value of a `/proc/[pid]/status` memory line such as `   1234 kB`
+fn parse_status_kib(value: &str) -> Result<u64> {
+    value
+        .split_whitespace()
+        .next()
+        .context("Missing value")?
+        .parse::<u64>()
+        .context("Invalid value")
+}
+
+/// Parse `read_bytes` and `write_bytes` from `/proc/[pid]/io`
+fn parse_proc_io(content: &str) -> Option<IoBytes> {
+    let mut read_bytes = None;
+    let mut write_bytes = None;
+
+    for line in content.lines() {
+        if let Some(value) = line.strip_prefix("read_bytes:") {
+            read_bytes = value.trim().parse().ok();
+        } else if let Some(value) = line.strip_prefix("write_bytes:") {
+            write_bytes = value.trim().parse().ok();
+        }
+    }
+
+    Some(IoBytes {
+        read_bytes: read_bytes?,
+        write_bytes: write_bytes?,
+    })
+}
+
+/// Parse `MemTotal` and `MemAvailable` from `/proc/meminfo`. Kernels before 3.14 have
+/// no `MemAvailable`
+fn parse_meminfo(content: &str) -> Option<SystemMemory> {
+    let mut total_kib = None;
+    let mut available_kib = None;
+
+    for line in content.lines() {
+        if let Some(value) = line.strip_prefix("MemTotal:") {
+            total_kib = parse_status_kib(value).ok();
+        } else if let Some(value) = line.strip_prefix("MemAvailable:") {
+            available_kib = parse_status_kib(value).ok();
+        }
+    }
+
+    Some(SystemMemory {
+        total_kib: total_kib?,
+        available_kib,
+    })
+}
+
+/// Sum the `Private_*` and `Shared_*` lines (clean and dirty) of `/proc/[pid]/smaps_rollup`
+fn parse_smaps_rollup(content: &str) -> Option<MemorySplit> {
+    let mut private_kib = None;
+    let mut shared_kib = None;
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let total = if key.starts_with("Private_") {
+            &mut private_kib
+        } else if key.starts_with("Shared_") {
+            &mut shared_kib
+        } else {
+            continue;
+        };
+        let kib = parse_status_kib(value).ok()?;
+        *total = Some(total.unwrap_or(0) + kib);
+    }
+
+    Some(MemorySplit {
+        private_kib: private_kib?,
+        shared_kib: shared_kib?,
+    })
+}
+
+/// Sum resident pages per NUMA node from `/proc/[pid]/numa_maps`.
+///
+/// Each line describes one mapping with `N<node>=<pages>` counts; pages are
+/// `kernelpagesize_kB` in size (4 KiB unless the mapping uses huge pages).
+fn parse_numa_maps(content: &str) -> HashMap<u32, u64> {
+    let mut nodes: HashMap<u32, u64> = HashMap::new();
+
+    for line in content.lines() {
+        let mut page_kib = 4;
+        let mut counts = Vec::new();
+
+        for field in line.split_whitespace() {
+            if let Some(size) = field.strip_prefix("kernelpagesize_kB=") {
+                page_kib = size.parse().unwrap_or(page_kib);
+            } else if let Some((node, pages)) = field
+                .strip_prefix('N')
+                .and_then(|rest| rest.split_once('='))
+                && let (Ok(node), Ok(pages)) = (node.parse::<u32>(), pages.parse::<u64>())
+            {
+                counts.push((node, pages));
+            }
+        }
+
+        for (node, pages) in counts {
+            *nodes.entry(node).or_default() += pages * page_kib;
+        }
+    }
+
+    nodes
+}
+
+/// Read the boot time (`btime`, whole seconds since the epoch) from /proc/stat
+fn read_boot_time(proc_path: &Path) -> Result<DateTime<Utc>> {
+    let stat_path = proc_path.join("stat");
+    let content = fs::read_to_string(&stat_path)
+        .context(format!("Failed to read {}", stat_path.display()))?;
+
+    let btime = content
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .context("No btime in /proc/stat")?
+        .trim()
+        .parse::<i64>()
+        .context("Failed to parse btime")?;
+
+    DateTime::from_timestamp(btime, 0).context("btime out of range")
 }
 
 #[cfg(test)]
@@ -145,17 +447,262 @@ mod tests {
     #[test]
     #[cfg(target_os = "linux")]
     fn test_read_self() {
-        let inspector = LinuxProcessInspector::new();
+        let inspector = LinuxProcessInspector::default();
         let pid = std::process::id() as i32;
 
-        let (ppid, comm) = inspector.read_proc_stat(pid).unwrap();
-        assert!(ppid > 0);
-        assert!(!comm.is_empty());
-
-        let rss = inspector.read_proc_status_rss(pid).unwrap();
-        assert!(rss > 0);
+        let stat = inspector.read_proc_stat(pid).unwrap();
+        assert!(stat.ppid > 0);
+        assert!(!stat.comm.is_empty());
+        assert_eq!(stat.process_group, unsafe { libc::getpgid(0) });
+        assert_eq!(stat.session, unsafe { libc::getsid(0) });
+        assert!(ticks_to_seconds(stat.cpu_ticks, inspector.ticks_per_second) >= 0.0);
+
+        // We started before now (allowing for btime's whole-second precision)
+        let start = inspector.start_time_from_ticks(stat.start_ticks).unwrap();
+        assert!(start <= Utc::now() + chrono::Duration::seconds(1));
+
+        let status = inspector.read_proc_status(pid).unwrap();
+        assert!(status.rss_kib > 0);
+        assert!(status.hwm_kib.unwrap() >= status.rss_kib);
+        assert_eq!(status.uid, Some(unsafe { libc::getuid() }));
+
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(
+            inspector.read_exe(pid),
+            Some(exe.to_string_lossy().into_owned())
+        );
+        // PID 0 has no /proc entry
+        assert_eq!(inspector.read_exe(0), None);
 
         let cmdline = inspector.read_cmdline(pid).unwrap();
         assert!(!cmdline.is_empty());
     }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_exe_is_read_once_per_process() {
+        let inspector = LinuxProcessInspector::default();
+        let pid = std::process::id() as i32;
+        let own_exe = |inspector: &LinuxProcessInspector| {
+            let processes = inspector.snapshot_all().unwrap();
+            processes
+                .into_iter()
+                .find(|p| p.pid == pid)
+                .unwrap()
+                .exe_path
+        };
+
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(
+            own_exe(&inspector),
+            Some(exe.to_string_lossy().into_owned())
+        );
+
+        // A cached path is used as long as the process is the same
+        let mark = |start_ticks: Option<u64>| {
+            let mut cache = inspector.exe_cache.lock().unwrap();
+            let cached = cache.get_mut(&pid).unwrap();
+            cached.path = Some("cached".to_string());
+            if let Some(ticks) = start_ticks {
+                cached.start_ticks = ticks;
+            }
+        };
+        mark(None);
+        assert_eq!(own_exe(&inspector), Some("cached".to_string()));
+
+        // Another start time means the PID was reused, so the link is read again
+        mark(Some(0));
+        assert_eq!(
+            own_exe(&inspector),
+            Some(exe.to_string_lossy().into_owned())
+        );
+
+        // Processes that are gone are dropped from the cache
+        inspector.exe_cache.lock().unwrap().insert(
+            -1,
+            CachedExe {
+                start_ticks: 0,
+                command: "gone".to_string(),
+                path: None,
+            },
+        );
+        own_exe(&inspector);
+        assert!(!inspector.exe_cache.lock().unwrap().contains_key(&-1));
+    }
+
+    #[test]
+    fn test_ticks_to_seconds() {
+        assert_eq!(ticks_to_seconds(0, 100.0), 0.0);
+        assert_eq!(ticks_to_seconds(4230, 100.0), 42.3);
+        assert_eq!(ticks_to_seconds(250, 250.0), 1.0);
+        assert_eq!(ticks_to_seconds(1, 1000.0), 0.001);
+    }
+
+    #[test]
+    fn test_parse_proc_io() {
+        let content = "\
+rchar: 323934931
+wchar: 323929600
+syscr: 632687
+syscw: 632675
+read_bytes: 4096
+write_bytes: 323932160
+cancelled_write_bytes: 0
+";
+
+        assert_eq!(
+            parse_proc_io(content),
+            Some(IoBytes {
+                read_bytes: 4096,
+                write_bytes: 323932160,
+            })
+        );
+        // Kernels without task I/O accounting only have the char counts
+        assert_eq!(parse_proc_io("rchar: 1\nwchar: 2\n"), None);
+    }
+
+    #[test]
+    fn test_parse_smaps_rollup() {
+        let content = "\
+55d0c8a00000-7ffd3b3c1000 ---p 00000000 00:00 0                          [rollup]
+Rss:               12340 kB
+Pss:                8123 kB
+Pss_Anon:           6000 kB
+Pss_File:           2123 kB
+Pss_Shmem:             0 kB
+Shared_Clean:       5000 kB
+Shared_Dirty:        200 kB
+Private_Clean:      1140 kB
+Private_Dirty:      6000 kB
+Referenced:        12000 kB
+Anonymous:          6000 kB
+Swap:                  0 kB
+SwapPss:               0 kB
+Locked:                0 kB
+";
+
+        assert_eq!(
+            parse_smaps_rollup(content),
+            Some(MemorySplit {
+                private_kib: 7140,
+                shared_kib: 5200,
+            })
+        );
+        // Kernel threads have an empty rollup
+        assert_eq!(parse_smaps_rollup(""), None);
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let content = "MemTotal:        8052348 kB
+MemFree:          412676 kB
+MemAvailable:    5360812 kB
+Buffers:          201044 kB
+SwapTotal:       2097148 kB
+";
+        assert_eq!(
+            parse_meminfo(content),
+            Some(SystemMemory {
+                total_kib: 8_052_348,
+                available_kib: Some(5_360_812),
+            })
+        );
+        // Old kernels have no MemAvailable
+        assert_eq!(
+            parse_meminfo("MemTotal:  1024 kB\nMemFree:  512 kB\n"),
+            Some(SystemMemory {
+                total_kib: 1024,
+                available_kib: None,
+            })
+        );
+        assert_eq!(parse_meminfo("MemFree:  512 kB\n"), None);
+    }
+
+    #[test]
+    fn test_parse_numa_maps() {
+        let content = "\
+55d0c8a00000 default file=/usr/bin/cat mapped=4 N0=4 kernelpagesize_kB=4
+55d0ca4c0000 default heap anon=300 dirty=300 N0=100 N1=200 kernelpagesize_kB=4
+7f3e80000000 bind:1 anon=2 dirty=2 N1=2 kernelpagesize_kB=2048
+7f3e9c000000 default
+7ffd3b3a0000 default stack anon=3 dirty=3 N0=3 kernelpagesize_kB=4
+";
+
+        let nodes = parse_numa_maps(content);
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[&0], (4 + 100 + 3) * 4);
+        assert_eq!(nodes[&1], 200 * 4 + 2 * 2048);
+        assert!(parse_numa_maps("").is_empty());
+    }
+
+    /// A fake procfs (tests/fixtures/proc): init (1), kthreadd (2), a shell (100)
+    /// running python (101), and 102, which exited after the directory was listed
+    /// and left nothing but its stat file
+    fn fixture() -> LinuxProcessInspector {
+        LinuxProcessInspector::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/proc"))
+    }
+
+    #[test]
+    fn test_snapshot_from_fixture() {
+        let inspector = fixture();
+        assert_eq!(
+            inspector.boot_time,
+            DateTime::from_timestamp(1_760_000_000, 0)
+        );
+
+        let mut processes = inspector.snapshot_all().unwrap();
+        processes.sort_by_key(|p| p.pid);
+        let pids: Vec<i32> = processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, vec![1, 2, 100, 101]);
+
+        let kthreadd = &processes[1];
+        assert_eq!(kthreadd.command, "kthreadd");
+        assert_eq!((kthreadd.rss_kib, kthreadd.hwm_kib), (0, None));
+
+        let python = &processes[3];
+        assert_eq!(python.ppid, 100);
+        assert_eq!(python.command, "python3 train.py --epochs 3");
+        assert_eq!((python.rss_kib, python.hwm_kib), (204_800, Some(307_200)));
+        assert_eq!(python.uid, Some(1000));
+        assert_eq!(
+            (python.process_group, python.session),
+            (Some(100), Some(100))
+        );
+        assert_eq!(
+            python.cpu_seconds,
+            Some(ticks_to_seconds(1500, inspector.ticks_per_second))
+        );
+        assert_eq!(python.start_time, inspector.start_time_from_ticks(15_000));
+        assert_eq!(python.exe_path, None);
+
+        let io = inspector.io_bytes(101).unwrap();
+        assert_eq!((io.read_bytes, io.write_bytes), (4_096_000, 8192));
+        let split = inspector.memory_split(101).unwrap();
+        assert_eq!((split.private_kib, split.shared_kib), (190_000, 14_800));
+        assert!(inspector.io_bytes(100).is_none());
+        assert!(inspector.numa_rss_kib(101).is_none());
+        assert_eq!(
+            inspector.system_memory().unwrap(),
+            SystemMemory {
+                total_kib: 16_384_000,
+                available_kib: Some(8_192_000),
+            }
+        );
+    }
+
+    #[test]
+    fn test_probe_job_from_fixture() {
+        let snapshot = crate::sampler::probe_job(
+            100,
+            crate::cli::TrackBy::Tree,
+            &crate::types::FilterConfig::default(),
+            &fixture(),
+        )
+        .unwrap();
+        let pids: Vec<i32> = snapshot.processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids.len(), 2);
+        assert!(pids.contains(&100) && pids.contains(&101));
+        assert_eq!(snapshot.total_rss_kib, 4000 + 204_800);
+    }
 }
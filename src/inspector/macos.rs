@@ -1,10 +1,12 @@
-use crate::types::ProcessSample;
+use crate::types::{ProcessSample, SystemMemory};
 use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
 use std::process::Command;
 
 use super::ProcessInspector;
 
 /// macOS process inspector using ps command
+#[derive(Default)]
 pub struct MacProcessInspector;
 
 impl MacProcessInspector {
@@ -15,8 +17,11 @@ impl MacProcessInspector {
 
 impl ProcessInspector for MacProcessInspector {
     fn snapshot_all(&self) -> Result<Vec<ProcessSample>> {
+        // -ww: without it ps cuts `command` to the terminal width (or 80 columns)
         let output = Command::new("ps")
-            .args(["-axo", "pid,ppid,rss,command"])
+            .args(["-ww", "-axo", "pid,ppid,pgid,ruid,rss,time,lstart,command"])
+            // lstart is printed in the C locale format parsed below
+            .env("LC_ALL", "C")
             .output()
             .context("Failed to execute ps command")?;
 
@@ -26,8 +31,66 @@ impl ProcessInspector for MacProcessInspector {
 
         let stdout = String::from_utf8(output.stdout).context("ps output was not valid UTF-8")?;
 
-        parse_ps_output(&stdout)
+        let mut processes = parse_ps_output(&stdout)?;
+        for proc in &mut processes {
+            // ps's `sess` column is a kernel address on macOS, not the session ID
+            // SAFETY: getsid has no preconditions
+            let session = unsafe { libc::getsid(proc.pid) };
+            proc.session = (session >= 0).then_some(session);
+        }
+        Ok(processes)
     }
+
+    fn system_memory(&self) -> Result<SystemMemory> {
+        let output = Command::new("sysctl")
+            .args(["-n", "hw.memsize"])
+            .output()
+            .context("Failed to execute sysctl")?;
+        if !output.status.success() {
+            anyhow::bail!("sysctl hw.memsize failed with status: {}", output.status);
+        }
+        let bytes = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<u64>()
+            .context("Failed to parse hw.memsize")?;
+
+        // Optional: the total alone still gives the summary its context
+        let available_kib = Command::new("vm_stat")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| parse_vm_stat(&String::from_utf8_lossy(&output.stdout)));
+        Ok(SystemMemory {
+            total_kib: bytes / 1024,
+            available_kib,
+        })
+    }
+}
+
+/// Available memory from `vm_stat`: free, inactive, and speculative pages, which the
+/// kernel hands out without swapping
+fn parse_vm_stat(output: &str) -> Option<u64> {
+    let page_size: u64 = output
+        .lines()
+        .next()?
+        .split("page size of ")
+        .nth(1)?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    let pages = |name: &str| -> Option<u64> {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(name))?
+            .trim()
+            .trim_end_matches('.')
+            .parse()
+            .ok()
+    };
+    let available =
+        pages("Pages free:")? + pages("Pages inactive:")? + pages("Pages speculative:")?;
+    Some(available * page_size / 1024)
 }
 
 fn parse_ps_output(output: &str) -> Result<Vec<ProcessSample>> {
@@ -44,7 +107,7 @@ fn parse_ps_output(output: &str) -> Result<Vec<ProcessSample>> {
             continue;
         }
 
-        // Parse: PID PPID RSS COMMAND
+        // Parse: PID PPID PGID RUID RSS TIME LSTART COMMAND
         // First, split by whitespace to get all parts
         let mut parts = line.split_whitespace();
 
@@ -62,6 +125,9 @@ fn parse_ps_output(output: &str) -> Result<Vec<ProcessSample>> {
             None => continue,
         };
 
+        let process_group = parts.next().and_then(|p| p.parse::<i32>().ok());
+        let uid = parts.next().and_then(|u| u.parse::<u32>().ok());
+
         let rss_kib = match parts.next() {
             Some(r) => r
                 .parse::<u64>()
@@ -69,6 +135,12 @@ fn parse_ps_output(output: &str) -> Result<Vec<ProcessSample>> {
             None => continue,
         };
 
+        let cpu_seconds = parts.next().and_then(parse_cpu_time);
+
+        // lstart is five fields, e.g. "Mon Nov 20 18:02:34 2025"
+        let lstart = parts.by_ref().take(5).collect::<Vec<_>>().join(" ");
+        let start_time = parse_lstart(&lstart);
+
         // Rest of the line is the command
         let command = parts.collect::<Vec<_>>().join(" ");
         if command.is_empty() {
@@ -85,22 +157,66 @@ fn parse_ps_output(output: &str) -> Result<Vec<ProcessSample>> {
             ppid,
             rss_kib,
             command,
+            start_time,
+            hwm_kib: None,
+            cpu_seconds,
+            process_group,
+            session: None,
+            uid,
+            exe_path: None,
         });
     }
 
     Ok(processes)
 }
 
+/// Parse a `ps -o time` value (user plus system time) such as `0:00.03`,
+/// `12:34:56.78` or `2-03:04:05` into seconds
+fn parse_cpu_time(time: &str) -> Option<f64> {
+    let (days, clock) = match time.split_once('-') {
+        Some((days, clock)) => (days.parse::<f64>().ok()?, clock),
+        None => (0.0, time),
+    };
+
+    let mut seconds = 0.0;
+    for field in clock.split(':') {
+        seconds = seconds * 60.0 + field.parse::<f64>().ok()?;
+    }
+    Some(days * 86400.0 + seconds)
+}
+
+/// Parse a `ps -o lstart` timestamp (local time, C locale)
+fn parse_lstart(lstart: &str) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(lstart, "%a %b %e %H:%M:%S %Y").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_vm_stat() {
+        let output = "Mach Virtual Memory Statistics: (page size of 16384 bytes)
+Pages free:                               12000.
+Pages active:                            400000.
+Pages inactive:                          300000.
+Pages speculative:                         8000.
+Pages throttled:                              0.
+";
+        assert_eq!(parse_vm_stat(output), Some(320_000 * 16));
+        assert_eq!(parse_vm_stat("Pages free: 1.\n"), None);
+    }
+
     #[test]
     fn test_parse_ps_output() {
-        let output = r#"  PID  PPID  RSS COMMAND
-    1     0   1234 /sbin/launchd
-  123     1   5678 /usr/bin/safari
-  456   123  91011 /Applications/Safari.app/Contents/MacOS/Safari --flag
+        let output = r#"  PID  PPID  PGID  RUID  RSS      TIME STARTED                      COMMAND
+    1     0     1     0   1234  12:34.56 Mon Nov 17 09:00:01 2025     /sbin/launchd
+  123     1   123   501   5678   0:00.03 Thu Nov 20 18:02:34 2025     /usr/bin/safari
+  456   123   123   501  91011   1:02.50 Thu Nov  6 18:02:35 2025     /Applications/Safari.app/Contents/MacOS/Safari --flag
 "#;
 
         let processes = parse_ps_output(output).unwrap();
@@ -110,6 +226,8 @@ mod tests {
         assert_eq!(processes[0].ppid, 0);
         assert_eq!(processes[0].rss_kib, 1234);
         assert_eq!(processes[0].command, "/sbin/launchd");
+        assert!(processes[0].start_time.is_some());
+        assert!((processes[0].cpu_seconds.unwrap() - 754.56).abs() < 1e-9);
 
         assert_eq!(processes[1].pid, 123);
         assert_eq!(processes[1].ppid, 1);
@@ -118,6 +236,21 @@ mod tests {
         assert_eq!(processes[2].pid, 456);
         assert_eq!(processes[2].ppid, 123);
         assert_eq!(processes[2].rss_kib, 91011);
+        assert_eq!(processes[2].process_group, Some(123));
+        assert_eq!(processes[0].uid, Some(0));
+        assert_eq!(processes[2].uid, Some(501));
+        assert_eq!(processes[2].cpu_seconds, Some(62.5));
         assert!(processes[2].command.contains("--flag"));
+        // Single-digit days are space padded
+        assert!(processes[2].start_time.is_some());
+    }
+
+    #[test]
+    fn test_parse_cpu_time() {
+        assert_eq!(parse_cpu_time("0:00.03"), Some(0.03));
+        assert!((parse_cpu_time("12:34.56").unwrap() - 754.56).abs() < 1e-9);
+        assert_eq!(parse_cpu_time("1:02:03"), Some(3723.0));
+        assert_eq!(parse_cpu_time("2-00:00:01"), Some(172801.0));
+        assert_eq!(parse_cpu_time("-"), None);
     }
 }
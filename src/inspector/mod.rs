@@ -1,5 +1,8 @@
-use crate::types::ProcessSample;
+use crate::error::MemwatchError;
+use crate::types::{IoBytes, MemorySplit, ProcessSample, SystemMemory};
 use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
 
 #[cfg(target_os = "linux")]
 mod linux;
@@ -11,13 +14,120 @@ pub use linux::LinuxProcessInspector as PlatformInspector;
 #[cfg(target_os = "macos")]
 pub use macos::MacProcessInspector as PlatformInspector;
 
+/// Where Linux procfs is read from unless `--proc-path` says otherwise
+pub const DEFAULT_PROC_PATH: &str = "/proc";
+
 /// Trait for inspecting process information across different platforms
 pub trait ProcessInspector {
     /// Return a snapshot of all processes on the system
     fn snapshot_all(&self) -> Result<Vec<ProcessSample>>;
+
+    /// Resident memory of a process per NUMA node (node id -> KiB).
+    /// Returns None where the platform or kernel has no NUMA information.
+    fn numa_rss_kib(&self, _pid: i32) -> Option<HashMap<u32, u64>> {
+        None
+    }
+
+    /// Cumulative storage I/O of a process. Returns None where the platform does not
+    /// report it or the process's counters are not readable
+    fn io_bytes(&self, _pid: i32) -> Option<IoBytes> {
+        None
+    }
+
+    /// Resident memory of a process split into private and shared pages. Returns None
+    /// where the platform does not report it or the process's mappings are not readable
+    fn memory_split(&self, _pid: i32) -> Option<MemorySplit> {
+        None
+    }
+
+    /// Total and available physical memory of the whole system
+    fn system_memory(&self) -> Result<SystemMemory> {
+        anyhow::bail!("System memory is not known on this platform")
+    }
+}
+
+/// Resolve a `--user` argument (a user name or numeric UID) to a UID
+pub fn resolve_user(spec: &str) -> Result<u32> {
+    if let Ok(uid) = spec.parse::<u32>() {
+        return Ok(uid);
+    }
+
+    let name = std::ffi::CString::new(spec)
+        .map_err(|_| anyhow::anyhow!("Invalid user name '{}'", spec))?;
+    // SAFETY: name is a valid C string; the returned entry is only read before any
+    // other passwd call on this thread
+    let entry = unsafe { libc::getpwnam(name.as_ptr()) };
+    if entry.is_null() {
+        anyhow::bail!("Unknown user '{}'", spec);
+    }
+    Ok(unsafe { (*entry).pw_uid })
 }
 
 /// Create a platform-specific process inspector
 pub fn create_inspector() -> PlatformInspector {
-    PlatformInspector::new()
+    PlatformInspector::default()
+}
+
+/// Create the inspector for `--proc-path` (`/proc` when None). The directory is
+/// checked here, so a mistyped path fails before any command is started
+pub fn create_inspector_at(proc_path: Option<&Path>) -> Result<PlatformInspector> {
+    let Some(path) = proc_path else {
+        return Ok(create_inspector());
+    };
+    let message = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => return open_proc_path(path),
+        Ok(_) => format!("--proc-path {} is not a directory", path.display()),
+        Err(e) => format!("Cannot read --proc-path {}: {}", path.display(), e),
+    };
+    Err(MemwatchError::InvalidArgument {
+        message: format!("{} (expected a procfs mount such as /proc)", message),
+    }
+    .into())
+}
+
+#[cfg(target_os = "linux")]
+fn open_proc_path(path: &Path) -> Result<PlatformInspector> {
+    Ok(PlatformInspector::new(path))
+}
+
+#[cfg(target_os = "macos")]
+fn open_proc_path(_path: &Path) -> Result<PlatformInspector> {
+    Err(MemwatchError::InvalidArgument {
+        message: "--proc-path is only supported on Linux; macOS has no procfs".to_string(),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_user() {
+        assert_eq!(resolve_user("0").unwrap(), 0);
+        assert_eq!(resolve_user("root").unwrap(), 0);
+        assert_eq!(resolve_user("4242").unwrap(), 4242);
+
+        let err = resolve_user("no-such-user-memwatch").unwrap_err();
+        assert_eq!(err.to_string(), "Unknown user 'no-such-user-memwatch'");
+    }
+
+    #[test]
+    fn test_create_inspector_at_checks_the_path() {
+        let err = create_inspector_at(Some(Path::new("/nonexistent/memwatch-proc")))
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string()
+                .starts_with("Cannot read --proc-path /nonexistent/memwatch-proc: ")
+        );
+        assert_eq!(
+            err.downcast_ref::<MemwatchError>().map(MemwatchError::code),
+            Some("invalid_argument")
+        );
+
+        let file = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let err = create_inspector_at(Some(Path::new(file))).err().unwrap();
+        assert!(err.to_string().contains("is not a directory"));
+    }
 }
@@ -1,6 +1,29 @@
+pub mod assertions;
+pub mod batch;
+pub mod bench;
+pub mod budget;
+pub mod cargo;
 pub mod cli;
+pub mod compare;
+pub mod completions;
+pub mod config;
 pub mod csv_writer;
+pub mod error;
+pub mod export;
+pub mod history;
 pub mod inspector;
+pub mod logging;
+pub mod merge;
+pub mod plot;
+pub mod progress;
+pub mod pty;
 pub mod reporter;
 pub mod sampler;
+pub mod schema;
+pub mod signals;
+pub mod stats;
+pub mod summarize;
+pub mod top;
+pub mod trend;
 pub mod types;
+pub mod webhook;
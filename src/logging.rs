@@ -0,0 +1,58 @@
+//! Diagnostics on stderr through the `log` crate (`-v`/`--verbose`).
+//!
+//! Warnings print as they always have (`Warning: ...`); `-v` adds debug messages
+//! about sampling, job membership, filtering, and unreadable /proc files, and `-vv`
+//! adds trace messages. `MEMWATCH_LOG` (an `env_logger` filter such as `error` or
+//! `memwatch::sampler=debug`) overrides the level, so warnings can be silenced too.
+//! Nothing is logged to stdout, which stays reserved for `--json` and friends.
+
+use log::{Level, LevelFilter};
+use std::io::Write;
+use std::time::Instant;
+
+/// Environment variable holding an `env_logger` filter that overrides `-v`
+pub const LOG_ENV: &str = "MEMWATCH_LOG";
+
+/// Level for a `-v` count: warnings by default, debug with `-v`, trace with `-vv`
+pub fn level_filter(verbosity: u8) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Install the stderr logger; call once, before anything is logged
+pub fn init(verbosity: u8) {
+    let started = Instant::now();
+    env_logger::Builder::new()
+        .filter_level(level_filter(verbosity))
+        .parse_env(LOG_ENV)
+        .target(env_logger::Target::Stderr)
+        .format(move |buf, record| match record.level() {
+            Level::Error => writeln!(buf, "Error: {}", record.args()),
+            Level::Warn => writeln!(buf, "Warning: {}", record.args()),
+            level => writeln!(
+                buf,
+                "[{:>8.3}s {:<5} {}] {}",
+                started.elapsed().as_secs_f64(),
+                level,
+                record.target(),
+                record.args()
+            ),
+        })
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_filter() {
+        assert_eq!(level_filter(0), LevelFilter::Warn);
+        assert_eq!(level_filter(1), LevelFilter::Debug);
+        assert_eq!(level_filter(2), LevelFilter::Trace);
+        assert_eq!(level_filter(5), LevelFilter::Trace);
+    }
+}
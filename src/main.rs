@@ -1,102 +1,972 @@
+use anyhow::Context;
+use clap::parser::ValueSource;
 use clap::{CommandFactory, FromArgMatches};
-use memwatch::cli::{Cli, Commands};
+use memwatch::assertions::{self, AssertionConfig};
+use memwatch::batch;
+use memwatch::bench;
+use memwatch::budget;
+use memwatch::cli::{
+    self, BatchArgs, BudgetCommand, Cli, Commands, ConfigCommand, ConvertArgs, HistoryAction,
+    HistoryArgs, IdleAction, MergeArgs, PeakModeArg, PlotArgs, ProbeArgs, RunArgs, SchemaFormat,
+    SnapshotArgs, SummarizeArgs, TrackBy, TrendArgs, UnitBase, UnitsArg,
+};
+use memwatch::compare;
+use memwatch::completions;
+use memwatch::config;
 use memwatch::csv_writer;
-use memwatch::inspector;
+use memwatch::error::{self, MemwatchError};
+use memwatch::export;
+use memwatch::history;
+use memwatch::inspector::{self, ProcessInspector};
+use memwatch::logging;
+use memwatch::merge;
+use memwatch::plot;
+use memwatch::progress;
 use memwatch::reporter;
 use memwatch::sampler;
+use memwatch::schema;
+use memwatch::summarize;
+use memwatch::top;
+use memwatch::trend;
+use memwatch::types::{self, ProcessOrder, exit_codes};
+use memwatch::webhook;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
 
 fn main() {
-    // Create command with extended version info and parse
-    let matches = Cli::command()
-        .long_version(Cli::get_long_version())
-        .get_matches();
+    // Create command with extended version info, then parse with the config file's defaults;
+    // arguments without a subcommand go to `run`
+    let command = Cli::command().long_version(Cli::get_long_version());
+    let args = cli::insert_default_subcommand(&command, std::env::args_os().collect());
+    let env = |name: &str| std::env::var(name).ok();
+    // Known before parsing, for errors that happen before or during it
+    let error_format = error::requested_format(&args, env);
+    let config_file = config::load_config(&command, &args, env)
+        .unwrap_or_else(|e| error::exit_with(e, error_format));
+    let matches = config::get_matches(command.clone(), &config_file, &args, env)
+        .unwrap_or_else(|e| error::exit_with_clap(e, error_format));
 
-    let cli = Cli::from_arg_matches(&matches)
-        .map_err(|e| e.exit())
-        .unwrap();
+    let cli =
+        Cli::from_arg_matches(&matches).unwrap_or_else(|e| error::exit_with_clap(e, error_format));
+    logging::init(cli.verbose);
+    config::set_color(cli.color);
+    config::set_ascii(cli.ascii);
+    config::set_units(match cli.units {
+        UnitsArg::Binary if cli.unit_base == UnitBase::Si => types::Units::Si,
+        UnitsArg::Binary => types::Units::Binary,
+        UnitsArg::Si => types::Units::Si,
+        UnitsArg::Kib => types::Units::Kib,
+        UnitsArg::Mib => types::Units::Mib,
+        UnitsArg::Gib => types::Units::Gib,
+        UnitsArg::Bytes => types::Units::Bytes,
+    });
 
+    let proc_path = cli.proc_path.as_deref().map(Path::new);
+    // run and batch sample the command they start on this host, whose PIDs another
+    // procfs would not match. MEMWATCH_PROC_PATH and config defaults only apply to
+    // the commands that inspect existing processes
+    if matches!(cli.command, Commands::Run(_) | Commands::Batch(_))
+        && matches.value_source("proc_path") == Some(ValueSource::CommandLine)
+    {
+        error::exit_with(
+            MemwatchError::InvalidArgument {
+                message: "--proc-path cannot be used with run or batch; use probe, snapshot, or top to read another procfs".to_string(),
+            }
+            .into(),
+            cli.error_format,
+        );
+    }
     match cli.command {
-        Commands::Run {
-            interval,
-            json,
-            quiet,
-            csv,
-            timeline,
-            silent,
-            exclude,
-            include,
-            command,
-        } => {
-            match run_command(
-                command, interval, json, quiet, csv, timeline, silent, exclude, include,
-            ) {
+        Commands::Run(args) => {
+            match run_command(*args) {
                 Ok(exit_code) => {
-                    // Exit with the child process's exit code
+                    // Exit code chosen by the --exit-code policy
                     process::exit(exit_code);
                 }
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    process::exit(1);
-                }
+                Err(e) => error::exit_with(e, cli.error_format),
+            }
+        }
+        Commands::Batch(args) => match batch_command(args) {
+            Ok(exit_code) => process::exit(exit_code),
+            Err(e) => error::exit_with(e, cli.error_format),
+        },
+        Commands::Merge(args) => {
+            if let Err(e) = merge_command(args) {
+                error::exit_with(e, cli.error_format);
+            }
+        }
+        Commands::Summarize(args) => {
+            if let Err(e) = summarize_command(args) {
+                error::exit_with(e, cli.error_format);
+            }
+        }
+        Commands::Trend(args) => match trend_command(args) {
+            Ok(exit_code) => process::exit(exit_code),
+            Err(e) => error::exit_with(e, cli.error_format),
+        },
+        Commands::Plot(args) => {
+            if let Err(e) = plot_command(args) {
+                error::exit_with(e, cli.error_format);
+            }
+        }
+        Commands::Top(args) => {
+            let result = inspector::create_inspector_at(proc_path).and_then(|inspector| {
+                top::run(&inspector, Duration::from_millis(args.interval), args.sort)
+            });
+            if let Err(e) = result {
+                error::exit_with(e, cli.error_format);
+            }
+        }
+        Commands::Probe(args) => {
+            if let Err(e) = probe_command(args, proc_path) {
+                error::exit_with(e, cli.error_format);
+            }
+        }
+        Commands::Snapshot(args) => {
+            if let Err(e) = snapshot_command(args, proc_path) {
+                error::exit_with(e, cli.error_format);
+            }
+        }
+        Commands::Convert(args) => {
+            if let Err(e) = convert_command(args) {
+                error::exit_with(e, cli.error_format);
+            }
+        }
+        Commands::Schema { format } => {
+            let schema = match format {
+                SchemaFormat::Profile => schema::profile_schema(),
+                SchemaFormat::Snapshot => schema::snapshot_schema(),
+            };
+            println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+        }
+        Commands::Completions { shell } => {
+            completions::generate(shell, &mut std::io::stdout());
+        }
+        Commands::Budget(BudgetCommand::Check { profile, budgets }) => {
+            match budget_check_command(&profile, &budgets) {
+                Ok(exit_code) => process::exit(exit_code),
+                Err(e) => error::exit_with(e, cli.error_format),
+            }
+        }
+        Commands::History(args) => {
+            if let Err(e) = history_command(args) {
+                error::exit_with(e, cli.error_format);
+            }
+        }
+        Commands::Config(ConfigCommand::Show { all }) => {
+            if let Err(e) = config_show_command(command, &config_file, all) {
+                error::exit_with(e, cli.error_format);
             }
         }
     }
 }
 
-fn run_command(
-    command: Vec<String>,
-    interval_ms: u64,
-    json: bool,
-    quiet: bool,
-    csv_path: Option<String>,
-    timeline_path: Option<String>,
-    silent: bool,
-    exclude: Option<String>,
-    include: Option<String>,
-) -> anyhow::Result<i32> {
+fn config_show_command(
+    command: clap::Command,
+    config_file: &config::Config,
+    all: bool,
+) -> anyhow::Result<()> {
+    match &config_file.path {
+        Some(path) => println!("Config file: {}", path.display()),
+        None => println!("Config file: none"),
+    }
+
+    let options =
+        config::effective_options(command, config_file, |name| std::env::var(name).ok(), all)?;
+    if options.is_empty() {
+        println!("No options set by the config file or the environment");
+        return Ok(());
+    }
+
+    let name_width = options.iter().map(|o| o.name.len()).max().unwrap_or(0);
+    let value_width = options.iter().map(|o| o.value.len()).max().unwrap_or(0);
+    println!();
+    for option in options {
+        println!(
+            "  {:<name_width$}  {:<value_width$}  {}",
+            option.name, option.value, option.source
+        );
+    }
+    Ok(())
+}
+
+fn run_command(mut args: RunArgs) -> anyhow::Result<i32> {
+    config::apply_env_defaults(&mut args, |name| std::env::var(name).ok())?;
+
+    let mut max_memory_kib = args.max_memory;
+    // 0 is the default and keeps every process
+    let min_rss_kib = args.min_rss.filter(|&kib| kib > 0);
+    let min_lifetime_seconds = args
+        .min_lifetime
+        .filter(|duration| !duration.is_zero())
+        .map(|duration| duration.as_secs_f64());
+    let (tags, repeated_tags) = types::collect_tags(&args.tag);
+    for key in repeated_tags {
+        eprintln!(
+            "Warning: --tag {} was given more than once; using '{}'",
+            key, tags[&key]
+        );
+    }
+    let timeout = args.timeout;
+    let idle_stop = args.idle_stop.map(|after| sampler::IdleStop {
+        after,
+        epsilon_kib: args.idle_epsilon,
+        detach: args.idle_action == IdleAction::Detach,
+    });
+    let delay = args.delay;
+    let drain = args.drain.then_some(args.drain_timeout);
+    let warn_above_kib = args.warn_above;
+    let fail_above_kib = args.fail_above;
+    let mut alert_levels_kib = args.alert.clone();
+    alert_levels_kib.sort_unstable();
+    alert_levels_kib.dedup();
+    let webhook = match (&args.webhook, args.webhook_threshold) {
+        (Some(url), Some(threshold_kib)) => Some(webhook::WebhookConfig {
+            url: webhook::parse_url(url)?,
+            threshold_kib,
+            repeat: args.webhook_repeat,
+        }),
+        _ => None,
+    };
+
+    // Load budgets and the baseline up front so a broken file fails before the command runs
+    let budgets = match args.budgets {
+        Some(ref path) => budget::load(path)?,
+        None => Vec::new(),
+    };
+
+    let baseline = match args.baseline {
+        Some(ref path) => Some(types::JobProfile::load_json(path)?),
+        None => None,
+    };
+
+    let env_vars = config::load_child_env(args.env_file.as_deref(), &args.env)?;
+
     // Create platform-specific inspector
     let inspector = inspector::create_inspector();
 
+    // --max-rss-pct becomes an absolute limit for this machine
+    if let Some(pct) = args.max_rss_pct {
+        let total = inspector
+            .system_memory()
+            .context("--max-rss-pct needs the system's total memory, which could not be read")?
+            .total_kib;
+        max_memory_kib = Some((total as f64 * pct / 100.0) as u64);
+    }
+
+    let cwd = args
+        .cwd
+        .as_deref()
+        .map(sampler::resolve_working_dir)
+        .transpose()
+        .map_err(|e| MemwatchError::InvalidArgument {
+            message: format!("invalid value for '--cwd': {}", e),
+        })?;
+
     // Track timeline if requested
-    let track_timeline = timeline_path.is_some();
+    let track_timeline = args.timeline.is_some() || args.smooth.is_some();
+
+    let interval = match args.interval_us {
+        Some(us) => Duration::from_micros(us),
+        None => args.interval,
+    };
+    warn_slow_interval(interval);
+
+    if args.numa && !cfg!(target_os = "linux") {
+        eprintln!("Warning: --numa is only supported on Linux; ignoring");
+    }
+    if args.show_io && !cfg!(target_os = "linux") {
+        eprintln!("Warning: --show-io is only supported on Linux; ignoring");
+    }
+    if args.show_shared && !cfg!(target_os = "linux") {
+        eprintln!("Warning: --show-shared is only supported on Linux; ignoring");
+    }
 
-    // Run and profile the command
-    let profile = sampler::run_and_profile(
-        command,
-        interval_ms,
+    let options = sampler::SamplerOptions {
+        interval,
         track_timeline,
-        silent,
-        exclude,
-        include,
-        &inspector,
-    )?;
+        per_process_timeline: args.per_process_timeline,
+        raw_samples: args.raw_samples,
+        silent: args.silent,
+        pty: args.pty,
+        capture_output: args.capture_output.as_ref().map(PathBuf::from),
+        include_zero: args.include_zero,
+        group_by: args.group_by.clone(),
+        env: sampler::ChildEnv {
+            clear: args.env_clear,
+            vars: env_vars.clone(),
+        },
+        shell: args.shell.then(|| args.shell_path.clone()),
+        exclude_self: args.exclude_self,
+        cwd,
+        filter: types::FilterConfig {
+            exclude_patterns: args.exclude.clone(),
+            include_patterns: args.include.clone(),
+            hide_wrappers: args.hide_wrappers,
+            min_rss_kib,
+            min_lifetime_seconds,
+            uids: resolve_users(&args.user)?,
+            exclude_uids: resolve_users(&args.exclude_user)?,
+            users: args.user.clone(),
+            exclude_users: args.exclude_user.clone(),
+            pids: args.pid.clone(),
+            exclude_pids: args.exclude_pid.clone(),
+            max_depth: args.depth,
+        },
+        subtract_baseline: args.subtract_baseline,
+        numa: args.numa,
+        show_io: args.show_io,
+        show_shared: args.show_shared,
+        quiet_errors: args.quiet_errors,
+        track_by: args.track_by,
+        max_memory_kib,
+        timeout,
+        idle_stop,
+        delay: delay.unwrap_or(Duration::ZERO),
+        drain,
+        wait_for_children: args.wait_for_children,
+        max_samples: args.max_samples.map(|n| n as usize),
+        detach_after_samples: args.detach_after_samples,
+        kill_after_samples: args.kill_after_samples,
+        kill_grace: Duration::from_secs(args.kill_grace),
+        forward_signals: !args.no_forward_signals,
+        webhook,
+        alert_levels_kib,
+        partial_outputs: sampler::PartialOutputs {
+            json: args
+                .json_out
+                .as_deref()
+                .map(|path| partial_path(path, &args, ".json")),
+            json_compact: args.json_compact,
+            timeline: args
+                .timeline
+                .as_deref()
+                .map(|path| partial_path(path, &args, "-timeline.csv")),
+        },
+    };
+
+    let show_progress =
+        !args.quiet && !args.json && !args.oneline && std::io::stderr().is_terminal();
+
+    let schedule = if args.auto_interval {
+        sampler::auto_interval_schedule(interval)
+    } else {
+        vec![interval]
+    };
+
+    // Run and profile the command, retrying at shorter intervals if requested
+    let profile_once = || -> anyhow::Result<types::JobProfile> {
+        let mut attempt = 0;
+        let mut profile = loop {
+            let interval = schedule[attempt];
+            attempt += 1;
+
+            let mut console =
+                progress::Console::new(show_progress.then(|| progress::Progress::start(timeout)));
+            let mut profile = sampler::run_and_observe(
+                args.command.clone(),
+                &sampler::SamplerOptions {
+                    interval,
+                    ..options.clone()
+                },
+                &inspector,
+                &mut console,
+            )?;
+
+            if args.auto_interval {
+                profile.auto_interval_attempts = Some(attempt);
+            }
+
+            match schedule.get(attempt) {
+                Some(next) if profile.max_total_rss_kib == 0 => {
+                    eprintln!(
+                        "No memory data captured at {:?} interval; rerunning command at {:?} (attempt {}/{})",
+                        interval,
+                        next,
+                        attempt + 1,
+                        schedule.len()
+                    );
+                }
+                _ => break profile,
+            }
+        };
+
+        if let Some(window) = args.smooth {
+            profile.apply_smoothing(window as usize);
+        }
+        profile.apply_peak_mode(match args.peak_mode {
+            PeakModeArg::Simultaneous => types::PeakMode::Simultaneous,
+            PeakModeArg::SumOfPeaks => types::PeakMode::SumOfPeaks,
+        });
+        profile.label = args.label.clone();
+        profile.tags = tags.clone();
+        profile.units = config::units();
+        profile.env_cleared = args.env_clear;
+        profile.env_overrides = env_vars
+            .iter()
+            .map(|(key, value)| types::EnvOverride {
+                key: key.clone(),
+                value: args.capture_env.then(|| value.clone()),
+            })
+            .collect();
+        profile.warmup_runs = args.warmup;
+        profile.memory_limit_pct = args.max_rss_pct;
+        Ok(profile)
+    };
+
+    bench::run_warmups(args.warmup, !args.quiet, || {
+        sampler::run_unsampled(&args.command, &options, &inspector)
+    })?;
+
+    // With --runs, checks and exports describe the run with the largest peak
+    let benchmark = if args.runs > 1 {
+        Some(bench::run_benchmark(
+            bench::BenchmarkOptions {
+                runs: args.runs,
+                warmup: args.warmup,
+                fail_fast: args.runs_fail_fast,
+                progress: !args.quiet && !args.json,
+            },
+            &profile_once,
+        )?)
+    } else {
+        None
+    };
+    let mut profile = match benchmark {
+        Some(ref report) => report.worst().clone(),
+        None => profile_once()?,
+    };
+
+    if let (Some(baseline), Some(path)) = (&baseline, &args.baseline) {
+        for warning in compare::warnings(&profile, baseline, path) {
+            eprintln!("Warning: {}", warning);
+        }
+        profile.baseline_comparison =
+            Some(compare::from_baseline(baseline, path, &profile.group_by));
+    }
+    if warn_above_kib.is_some() || fail_above_kib.is_some() {
+        profile.thresholds = Some(assertions::check_thresholds(
+            profile.max_total_rss_kib,
+            warn_above_kib,
+            fail_above_kib,
+        ));
+    }
+
+    // Capture exit code before consuming profile. A job memwatch killed has no exit
+    // code of its own; the kill is reported as a failed check instead, except for
+    // --kill-after-samples, which is a planned stop. After Ctrl-C or SIGTERM it is the
+    // command's own exit code, or 130/143 if it had none
+    let child_exit_code = match (&benchmark, &profile.killed_reason) {
+        (Some(report), _) => report.exit_code(),
+        (None, _) if profile.interrupted => {
+            profile.exit_code.unwrap_or(profile.interrupt_exit_code())
+        }
+        (None, Some(_)) => 0,
+        (None, None) => profile.exit_code.unwrap_or(0),
+    };
+
+    // Output results (--top 0 shows every row)
+    let summary_options = reporter::SummaryOptions {
+        top: (args.top > 0).then_some(args.top),
+        order: ProcessOrder {
+            key: args.sort_by,
+            reverse: args.reverse,
+        },
+        resolve_exe: args.resolve_exe,
+        width: (!args.full_command).then(reporter::summary_width),
+    };
+    if let Some(ref report) = benchmark {
+        if args.json {
+            reporter::print_json(report, args.json_compact)?;
+        } else if !args.quiet && !args.output_only {
+            bench::print_benchmark_summary(report);
+        }
+    } else if args.json {
+        reporter::print_json(&profile, args.json_compact)?;
+    } else if args.oneline {
+        println!("{}", reporter::format_oneline(&profile));
+    } else if !args.quiet && !args.output_only {
+        reporter::print_summary(&profile, summary_options);
+    }
+
+    // Export notices would clutter scripted output
+    let notices = !args.quiet && !args.json && !args.oneline;
 
-    // Capture exit code before consuming profile
-    let exit_code = profile.exit_code.unwrap_or(0);
+    if let Some(path) = args.output {
+        let path = output_path(path, &profile, ".txt");
+        reporter::export_summary(&profile, &path, summary_options)?;
+        if notices {
+            eprintln!("Summary written to: {}", path);
+        }
+    }
 
-    // Output results
-    if json {
-        reporter::print_json(&profile)?;
-    } else if !quiet {
-        reporter::print_summary(&profile);
+    if let Some(path) = args.json_out {
+        let path = output_path(path, &profile, ".json");
+        reporter::export_json(&profile, &path, args.json_compact)?;
+        if notices {
+            eprintln!("JSON profile written to: {}", path);
+        }
+    }
+
+    if let Some(ref captured) = profile.captured_output
+        && notices
+    {
+        eprintln!(
+            "Command output saved to: {} (stdout), {} (stderr)",
+            captured.stdout, captured.stderr
+        );
     }
 
     // Export CSV if requested
-    if let Some(path) = csv_path {
-        csv_writer::export_process_csv(&profile, &path)?;
-        if !quiet && !json {
+    if let Some(path) = args.csv {
+        let path = output_path(path, &profile, ".csv");
+        let order = ProcessOrder {
+            key: args.csv_sort.unwrap_or_default(),
+            reverse: args.csv_sort.is_some() && args.reverse,
+        };
+        csv_writer::export_process_csv(&profile, &path, order)?;
+        if notices {
             eprintln!("Per-process CSV exported to: {}", path);
         }
     }
 
     // Export timeline if requested
-    if let Some(path) = timeline_path {
+    if let Some(path) = args.timeline {
+        let path = output_path(path, &profile, "-timeline.csv");
         csv_writer::export_timeline_csv(&profile, &path)?;
-        if !quiet && !json {
+        if notices {
             eprintln!("Timeline CSV exported to: {}", path);
         }
     }
 
+    // Export stacked per-process timeline if requested
+    if let Some(path) = args.stacked_csv {
+        let path = output_path(path, &profile, "-stacked.csv");
+        csv_writer::export_stacked_csv(&profile, &path)?;
+        if notices {
+            eprintln!("Stacked per-process CSV exported to: {}", path);
+        }
+    }
+
+    if args.save {
+        let id = history::save(&profile)?;
+        if notices {
+            eprintln!("Saved to history: {}", id);
+        }
+    }
+
+    let evaluation = assertions::evaluate(
+        &profile,
+        &AssertionConfig {
+            fail_on_empty: args.fail_on_empty,
+            budgets,
+            fail_above_exit_code: args.fail_above_code,
+            baseline_fail_above_percent: args.baseline_fail_above,
+        },
+    );
+    assertions::print_evaluation(&evaluation);
+
+    Ok(assertions::resolve_exit_code(
+        args.exit_code,
+        child_exit_code,
+        &evaluation.failures,
+    ))
+}
+
+/// Intervals this long usually mean seconds were passed where milliseconds were meant
+const SLOW_INTERVAL: Duration = Duration::from_secs(10);
+
+fn warn_slow_interval(interval: Duration) {
+    if interval > SLOW_INTERVAL {
+        eprintln!(
+            "Warning: Sampling every {:?} will miss most peaks; bare --interval values are milliseconds",
+            interval
+        );
+    }
+}
+
+/// `path`, or a file named after the run inside it when `path` is a directory
+fn output_path(path: String, profile: &types::JobProfile, suffix: &str) -> String {
+    if !std::path::Path::new(&path).is_dir() {
+        return path;
+    }
+    std::path::Path::new(&path)
+        .join(format!("{}{}", profile.file_stem(), suffix))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Where an intermediate summary (SIGUSR1) writes the data so far: the final output
+/// path with `.partial` appended, so a directory resolves to the same file name
+fn partial_path(path: &str, args: &RunArgs, suffix: &str) -> String {
+    let named = types::JobProfile {
+        command: args.command.clone(),
+        label: args.label.clone(),
+        ..Default::default()
+    };
+    format!("{}.partial", output_path(path.to_string(), &named, suffix))
+}
+
+fn merge_command(args: MergeArgs) -> anyhow::Result<()> {
+    let labels = merge::labels_for_paths(&args.files);
+
+    let mut inputs = Vec::with_capacity(args.files.len());
+    for (path, label) in args.files.iter().zip(labels) {
+        let profile = memwatch::types::JobProfile::load_json(path)?;
+        inputs.push(merge::LabeledProfile { label, profile });
+    }
+
+    let (profile, warnings) = merge::merge_profiles(inputs, args.max_skew)?;
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if args.json {
+        reporter::print_json(&profile, false)?;
+    } else {
+        reporter::print_summary(
+            &profile,
+            reporter::SummaryOptions {
+                width: Some(reporter::summary_width()),
+                ..Default::default()
+            },
+        );
+    }
+
+    Ok(())
+}
+
+fn summarize_command(args: SummarizeArgs) -> anyhow::Result<()> {
+    let (paths, mut skipped) = summarize::expand_inputs(&args.inputs);
+    let (mut profiles, mut load_skipped) = summarize::load_profiles(&paths);
+    skipped.append(&mut load_skipped);
+
+    for file in &skipped {
+        eprintln!("Warning: Skipping {}: {}", file.path, file.error);
+    }
+
+    if profiles.is_empty() {
+        anyhow::bail!("No valid profiles found to summarize");
+    }
+    profiles.retain(|(_, profile)| profile.has_tags(&args.tag));
+    if profiles.is_empty() {
+        anyhow::bail!("No profiles match the given --tag filters");
+    }
+
+    let summary = summarize::RunSummary {
+        groups: summarize::summarize(&profiles),
+        skipped,
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        summarize::print_run_summary(&summary);
+    }
+
+    Ok(())
+}
+
+fn trend_command(args: TrendArgs) -> anyhow::Result<i32> {
+    let profiles: Vec<_> = if args.history {
+        history::load_all()?
+            .into_iter()
+            .map(|e| e.profile)
+            .collect()
+    } else {
+        let (paths, skipped) = summarize::expand_inputs(&args.inputs);
+        let (profiles, load_skipped) = summarize::load_profiles(&paths);
+
+        for file in skipped.iter().chain(&load_skipped) {
+            eprintln!("Warning: Skipping {}: {}", file.path, file.error);
+        }
+
+        profiles.into_iter().map(|(_, p)| p).collect()
+    };
+
+    if profiles.is_empty() {
+        anyhow::bail!("No valid profiles found to analyze");
+    }
+
+    let trends = trend::analyze(profiles, args.window as usize, args.threshold);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&trends)?);
+    } else {
+        trend::print_trends(&trends, args.threshold);
+    }
+
+    let regressed = trends
+        .iter()
+        .any(|t| t.verdict == trend::Verdict::Regression);
+    if args.fail_on_regression && regressed {
+        Ok(exit_codes::THRESHOLD_EXCEEDED)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Resolve `--user`/`--exclude-user` names to UIDs once, before anything runs
+fn resolve_users(specs: &[String]) -> anyhow::Result<Vec<u32>> {
+    specs
+        .iter()
+        .map(|spec| inspector::resolve_user(spec))
+        .collect()
+}
+
+fn probe_command(args: ProbeArgs, proc_path: Option<&Path>) -> anyhow::Result<()> {
+    let filter = types::FilterConfig {
+        exclude_patterns: args.exclude,
+        include_patterns: args.include,
+        hide_wrappers: args.hide_wrappers,
+        min_rss_kib: None,
+        min_lifetime_seconds: None,
+        uids: resolve_users(&args.user)?,
+        exclude_uids: resolve_users(&args.exclude_user)?,
+        users: args.user,
+        exclude_users: args.exclude_user,
+        pids: Vec::new(),
+        exclude_pids: args.exclude_pid,
+        max_depth: args.depth,
+    };
+    let inspector = inspector::create_inspector_at(proc_path)?;
+    let snapshot = sampler::probe_job(args.pid, args.track_by, &filter, &inspector)?;
+    let processes = snapshot.processes.clone();
+
+    // Run the snapshot through the same path as a real profile so the filters match
+    let mut state = types::JobState::new(false, false);
+    state.update(snapshot);
+    let profile = state.into_profile(vec![], Duration::ZERO, None, filter)?;
+
+    reporter::print_probe(args.pid, &processes, &profile);
+    Ok(())
+}
+
+fn snapshot_command(args: SnapshotArgs, proc_path: Option<&Path>) -> anyhow::Result<()> {
+    // 0 keeps every process, as for `run`
+    let min_rss_kib = args.min_rss.filter(|&kib| kib > 0);
+    let filter = types::FilterConfig {
+        exclude_patterns: args.exclude,
+        include_patterns: args.include,
+        min_rss_kib,
+        ..Default::default()
+    };
+    let inspector = inspector::create_inspector_at(proc_path)?;
+    let processes = inspector.snapshot_all()?;
+    let process_count = processes.len();
+
+    // One sample of every process, filtered the way a profile would be
+    let mut state = types::JobState::new(false, false);
+    state.update(types::JobSnapshot {
+        timestamp: chrono::Utc::now(),
+        total_rss_kib: processes.iter().map(|p| p.rss_kib).sum(),
+        processes,
+        reparented: Vec::new(),
+    });
+    let profile = state.into_profile(vec![], Duration::ZERO, None, filter)?;
+
+    if args.json {
+        reporter::print_json(
+            &types::SystemSnapshot::from_profile(&profile, process_count),
+            false,
+        )?;
+    } else {
+        reporter::print_snapshot(&profile, process_count, (args.top > 0).then_some(args.top));
+    }
+    Ok(())
+}
+
+fn history_command(args: HistoryArgs) -> anyhow::Result<()> {
+    let entries = history::load_all()?;
+
+    if let Some(HistoryAction::Show { id, json }) = args.action {
+        let entry = history::find(entries, &id)?;
+        if json {
+            reporter::print_json(&entry.profile, false)?;
+        } else {
+            reporter::print_summary(
+                &entry.profile,
+                reporter::SummaryOptions {
+                    width: Some(reporter::summary_width()),
+                    ..Default::default()
+                },
+            );
+        }
+        return Ok(());
+    }
+
+    let pattern = match args.r#match {
+        Some(ref p) => Some(regex::Regex::new(p).context(MemwatchError::invalid_regex(
+            format!("Invalid match pattern '{}': must be valid regex", p),
+            p,
+        ))?),
+        None => None,
+    };
+
+    let entries: Vec<_> = entries
+        .into_iter()
+        .filter(|e| {
+            pattern
+                .as_ref()
+                .is_none_or(|re| re.is_match(&e.profile.command.join(" ")))
+                && e.profile.has_tags(&args.tag)
+        })
+        .take(args.limit)
+        .collect();
+
+    if args.json {
+        let runs: Vec<_> = entries
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "id": e.id,
+                    "command": e.profile.command,
+                    "start_time": e.profile.start_time,
+                    "duration_seconds": e.profile.duration_seconds,
+                    "max_total_rss_kib": e.profile.max_total_rss_kib,
+                    "exit_code": e.profile.exit_code,
+                    "tags": e.profile.tags,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&runs)?);
+    } else if entries.is_empty() {
+        println!("No saved runs. Use `memwatch run --save -- <command>` to record one.");
+    } else {
+        history::print_history(&entries);
+    }
+
+    Ok(())
+}
+
+fn batch_command(args: BatchArgs) -> anyhow::Result<i32> {
+    let mut commands = match args.file {
+        Some(ref path) => {
+            let content = std::fs::read_to_string(path).context(MemwatchError::read_failed(
+                "Failed to read batch file",
+                path,
+            ))?;
+            batch::parse_commands(&content).context(format!("Invalid batch file: {}", path))?
+        }
+        None => Vec::new(),
+    };
+    for cmd in &args.cmd {
+        commands.push(batch::parse_command(cmd)?);
+    }
+    if commands.is_empty() {
+        anyhow::bail!("No commands to run");
+    }
+
+    warn_slow_interval(args.interval);
+    let inspector = inspector::create_inspector();
+    let options = sampler::SamplerOptions {
+        interval: args.interval,
+        silent: args.silent,
+        // Nothing is killed in a batch; this is only how long a Ctrl-C gets
+        kill_grace: Duration::from_secs(5),
+        ..Default::default()
+    };
+
+    // Each command is profiled with its own sampler state
+    let mut profiles = Vec::new();
+    let mut exit_code = 0;
+    for (i, command) in commands.iter().enumerate() {
+        if !args.json {
+            eprintln!("[{}/{}] {}", i + 1, commands.len(), command.join(" "));
+        }
+        let profile = sampler::run_and_observe(
+            command.clone(),
+            &options,
+            &inspector,
+            &mut progress::Console::new(None),
+        )?;
+
+        // The batch fails with the first non-zero exit code
+        let code = profile.exit_code.unwrap_or(0);
+        if exit_code == 0 {
+            exit_code = code;
+        }
+        let interrupted = profile.interrupted.then(|| profile.interrupt_exit_code());
+        profiles.push(profile);
+
+        if let Some(interrupt_exit_code) = interrupted {
+            eprintln!("Stopping after command {} was interrupted", i + 1);
+            exit_code = interrupt_exit_code;
+            break;
+        }
+        if code != 0 && args.fail_fast {
+            eprintln!("Stopping after command {} exited with code {}", i + 1, code);
+            break;
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&profiles)?);
+    } else {
+        batch::print_batch_summary(&profiles);
+    }
+
     Ok(exit_code)
 }
+
+fn plot_command(args: PlotArgs) -> anyhow::Result<()> {
+    let profile = match args.profile {
+        Some(ref path) => memwatch::types::JobProfile::load_json(path)?,
+        None => {
+            history::load_all()?
+                .into_iter()
+                .next()
+                .context("No saved runs in history (record one with `memwatch run --save`)")?
+                .profile
+        }
+    };
+
+    plot::print_plot(
+        &profile,
+        &plot::PlotOptions {
+            width: args.width.unwrap_or_else(plot::terminal_width),
+            height: args.height as usize,
+            ascii: config::ascii_forced() || !plot::locale_supports_unicode(),
+            per_process: args.per_process,
+        },
+    );
+    Ok(())
+}
+
+fn convert_command(args: ConvertArgs) -> anyhow::Result<()> {
+    let profile = memwatch::types::JobProfile::load_json(&args.input)?;
+    let stem = std::path::Path::new(&args.input)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "profile".to_string());
+
+    for path in export::convert(&profile, args.to, &stem, args.out.as_deref())? {
+        eprintln!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn budget_check_command(profile_path: &str, budgets_path: &str) -> anyhow::Result<i32> {
+    let budgets = budget::load(budgets_path)?;
+    let profile = memwatch::types::JobProfile::load_json(profile_path)?;
+
+    let report = budget::check(&profile, &budgets);
+    for pattern in &report.unmatched_patterns {
+        eprintln!(
+            "Warning: Budget '{}' did not match the command (check for typos)",
+            pattern
+        );
+    }
+
+    if report.violations.is_empty() {
+        println!("All budgets satisfied for: {}", profile.command.join(" "));
+        Ok(0)
+    } else {
+        for violation in &report.violations {
+            println!("Budget exceeded: {}", violation.message);
+        }
+        Ok(exit_codes::THRESHOLD_EXCEEDED)
+    }
+}
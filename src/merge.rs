@@ -0,0 +1,548 @@
+//! Combine profiles recorded separately (e.g. one memwatch per MPI node) into one job view.
+//!
+//! # Clock alignment
+//!
+//! Timestamps are used as-is: each node's wall clock is trusted, and timelines are
+//! aligned by absolute timestamp. Nodes synchronized with NTP are typically within
+//! milliseconds of each other, which is far below any useful sampling interval.
+//! When the start times of the inputs differ by more than the skew threshold, a
+//! warning is emitted because either the runs did not start together or the clocks
+//! disagree, and the summed total peak may be misleading.
+
+use crate::types::{
+    GroupBy, JobProfile, PeakMode, SCHEMA_VERSION, SourceSummary, TimelinePoint, Units,
+    compute_process_groups, percent_of, sum_present,
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+
+/// A profile loaded from disk together with the label identifying its source
+pub struct LabeledProfile {
+    pub label: String,
+    pub profile: JobProfile,
+}
+
+/// Derive unique source labels from file names (file stem, suffixed on collisions)
+pub fn labels_for_paths(paths: &[String]) -> Vec<String> {
+    let mut labels: Vec<String> = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let stem = Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone());
+
+        let mut label = stem.clone();
+        let mut n = 2;
+        while labels.contains(&label) {
+            label = format!("{}-{}", stem, n);
+            n += 1;
+        }
+        labels.push(label);
+    }
+
+    labels
+}
+
+/// Merge several profiles into one.
+///
+/// Returns the merged profile plus any warnings (clock skew, missing timelines) that
+/// the caller should surface to the user.
+pub fn merge_profiles(
+    inputs: Vec<LabeledProfile>,
+    max_skew_seconds: f64,
+) -> Result<(JobProfile, Vec<String>)> {
+    if inputs.len() < 2 {
+        anyhow::bail!("At least two profiles are required to merge");
+    }
+
+    let mut warnings = Vec::new();
+
+    let start_time = inputs
+        .iter()
+        .map(|i| i.profile.start_time)
+        .min()
+        .expect("inputs is non-empty");
+    let end_time = inputs
+        .iter()
+        .map(|i| i.profile.end_time)
+        .max()
+        .expect("inputs is non-empty");
+    let latest_start = inputs
+        .iter()
+        .map(|i| i.profile.start_time)
+        .max()
+        .expect("inputs is non-empty");
+
+    let start_spread = seconds_between(start_time, latest_start);
+    if start_spread > max_skew_seconds {
+        warnings.push(format!(
+            "Profile start times differ by {:.1}s (threshold {:.1}s); clocks may be skewed or runs did not overlap, so the merged total peak may be misleading",
+            start_spread, max_skew_seconds
+        ));
+    }
+
+    let all_have_timeline = inputs.iter().all(|i| i.profile.timeline.is_some());
+    let (max_total_rss_kib, timeline) = if all_have_timeline {
+        let timeline = merge_timelines(&inputs, start_time);
+        let max_total = timeline.iter().map(|p| p.total_rss_kib).max().unwrap_or(0);
+        (max_total, Some(timeline))
+    } else {
+        warnings.push(
+            "Not every profile has timeline data (record with --timeline); the total peak assumes each source peaked while its run overlapped the others, which is an upper bound".to_string(),
+        );
+        (overlapping_peak_sum(&inputs), None)
+    };
+
+    let first_command = inputs[0].profile.command.clone();
+    let command = if inputs.iter().all(|i| i.profile.command == first_command) {
+        first_command
+    } else {
+        vec![format!("merge of {} profiles", inputs.len())]
+    };
+
+    let first_filter = inputs[0].profile.filter.clone();
+    let filter = if inputs.iter().all(|i| i.profile.filter == first_filter) {
+        first_filter
+    } else {
+        None
+    };
+    // Keep the tags every input agrees on, e.g. the commit but not the node
+    let mut tags = inputs[0].profile.tags.clone();
+    tags.retain(|key, value| {
+        inputs
+            .iter()
+            .all(|i| i.profile.tags.get(key) == Some(&*value))
+    });
+    let hidden_wrapper_count = inputs.iter().map(|i| i.profile.hidden_wrapper_count).sum();
+    let below_min_rss_count = inputs.iter().map(|i| i.profile.below_min_rss_count).sum();
+    let below_min_rss_total_kib = inputs
+        .iter()
+        .map(|i| i.profile.below_min_rss_total_kib)
+        .sum();
+    let short_lived_count = inputs.iter().map(|i| i.profile.short_lived_count).sum();
+    let short_lived_total_kib = inputs.iter().map(|i| i.profile.short_lived_total_kib).sum();
+    let filtered_process_count =
+        sum_options(inputs.iter().map(|i| i.profile.filtered_process_count));
+    let filtered_total_rss_kib =
+        sum_options(inputs.iter().map(|i| i.profile.filtered_total_rss_kib));
+
+    let interval_ms = inputs
+        .iter()
+        .map(|i| i.profile.interval_ms)
+        .max()
+        .unwrap_or(0);
+    let interval_us = inputs
+        .iter()
+        .map(|i| i.profile.interval_us)
+        .max()
+        .unwrap_or(0);
+    let samples = inputs.iter().map(|i| i.profile.samples).sum();
+    let include_zero_rss = inputs.iter().any(|i| i.profile.include_zero_rss);
+    // Groups are keyed the inputs' way when they agree
+    let group_by = match inputs.first() {
+        Some(first)
+            if inputs
+                .iter()
+                .all(|i| i.profile.group_by == first.profile.group_by) =>
+        {
+            first.profile.group_by.clone()
+        }
+        _ => GroupBy::Basename,
+    };
+    let sum_of_peaks_kib = inputs.iter().map(|i| i.profile.sum_of_peaks_kib).sum();
+    let total_cpu_seconds = inputs
+        .iter()
+        .filter_map(|i| i.profile.total_cpu_seconds)
+        .reduce(|a, b| a + b);
+    let io_read_bytes = sum_present(inputs.iter().map(|i| i.profile.io_read_bytes));
+    let io_write_bytes = sum_present(inputs.iter().map(|i| i.profile.io_write_bytes));
+    let private_total_kib = sum_present(inputs.iter().map(|i| i.profile.private_total_kib));
+
+    // Report the first failure, otherwise success if any source recorded a code
+    let exit_code = inputs
+        .iter()
+        .filter_map(|i| i.profile.exit_code)
+        .find(|&code| code != 0)
+        .or_else(|| inputs.iter().find_map(|i| i.profile.exit_code));
+
+    let mut sources = Vec::with_capacity(inputs.len());
+    let mut processes = Vec::new();
+    let mut sampling_errors = Vec::new();
+    let mut escaped_processes = Vec::new();
+
+    for LabeledProfile { label, profile } in inputs {
+        sources.push(SourceSummary {
+            label: label.clone(),
+            command: profile.command,
+            start_time: profile.start_time,
+            end_time: profile.end_time,
+            duration_seconds: profile.duration_seconds,
+            max_total_rss_kib: profile.max_total_rss_kib,
+            process_count: profile.processes.len(),
+            exit_code: profile.exit_code,
+        });
+
+        sampling_errors.extend(profile.sampling_errors.into_iter().map(|mut error| {
+            error.message = format!("[{}] {}", label, error.message);
+            error
+        }));
+
+        escaped_processes.extend(profile.escaped_processes);
+
+        for mut proc in profile.processes {
+            proc.source = Some(label.clone());
+            processes.push(proc);
+        }
+    }
+
+    processes.sort_by_key(|p| std::cmp::Reverse(p.max_rss_kib));
+    for proc in &mut processes {
+        proc.pct_of_job_peak = percent_of(proc.max_rss_kib, max_total_rss_kib);
+    }
+    let process_groups = compute_process_groups(&processes, include_zero_rss, &group_by);
+
+    Ok((
+        JobProfile {
+            schema_version: SCHEMA_VERSION,
+            command,
+            label: None,
+            tags,
+            start_time,
+            end_time,
+            duration_seconds: seconds_between(start_time, end_time),
+            interval_ms,
+            interval_us,
+            auto_interval_attempts: None,
+            warmup_runs: 0,
+            max_total_rss_kib,
+            peak_mode: PeakMode::Simultaneous,
+            units: Units::default(),
+            simultaneous_peak_kib: max_total_rss_kib,
+            sum_of_peaks_kib,
+            total_cpu_seconds,
+            io_read_bytes,
+            io_write_bytes,
+            private_total_kib,
+            // Each input's baseline was taken at a different moment, so none is kept
+            baseline_rss_kib: None,
+            max_total_above_baseline_kib: None,
+            samples,
+            processes,
+            process_groups,
+            timeline,
+            peak_snapshot: None,
+            raw_samples: None,
+            exit_code,
+            killed_reason: None,
+            memory_limit_kib: None,
+            memory_limit_pct: None,
+            system_total_kib: None,
+            system_available_kib: None,
+            system_available_end_kib: None,
+            timed_out: false,
+            idle_stopped: false,
+            idle_stop_seconds: None,
+            idle_epsilon_kib: None,
+            timeout_seconds: None,
+            delay_seconds: None,
+            exited_during_delay: false,
+            drain_timed_out: false,
+            drain_seconds: None,
+            sampling_truncated: false,
+            detached: false,
+            interrupted: false,
+            interrupt_signal: None,
+            terminated_by_signal: None,
+            terminated_by_signal_name: None,
+            thresholds: None,
+            captured_output: None,
+            include_zero_rss,
+            group_by,
+            working_dir: None,
+            shell: None,
+            self_excluded: false,
+            env_cleared: false,
+            env_overrides: Vec::new(),
+            escaped_process_count: escaped_processes.len(),
+            escaped_processes,
+            sampling_errors,
+            alerts: Vec::new(),
+            pauses: Vec::new(),
+            hidden_wrapper_count,
+            below_min_rss_count,
+            below_min_rss_total_kib,
+            short_lived_count,
+            short_lived_total_kib,
+            filter,
+            filtered_process_count,
+            filtered_total_rss_kib,
+            sources: Some(sources),
+            build_phase: None,
+            baseline_comparison: None,
+        },
+        warnings,
+    ))
+}
+
+fn seconds_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+fn sum_options<T: std::iter::Sum<T>>(values: impl Iterator<Item = Option<T>>) -> Option<T> {
+    let present: Vec<T> = values.flatten().collect();
+    if present.is_empty() {
+        None
+    } else {
+        Some(present.into_iter().sum())
+    }
+}
+
+/// Align timelines by wall-clock timestamp and sum them.
+///
+/// At every timestamp seen in any input, each source contributes its most recent
+/// sample at or before that instant, or nothing if it has not started yet or has
+/// already ended.
+fn merge_timelines(inputs: &[LabeledProfile], start_time: DateTime<Utc>) -> Vec<TimelinePoint> {
+    let mut timestamps: Vec<DateTime<Utc>> = inputs
+        .iter()
+        .flat_map(|i| i.profile.timeline.iter().flatten().map(|p| p.timestamp))
+        .collect();
+    timestamps.sort();
+    timestamps.dedup();
+
+    timestamps
+        .into_iter()
+        .map(|timestamp| {
+            let mut total_rss_kib = 0;
+            let mut process_count = 0;
+
+            for input in inputs {
+                let Some(points) = &input.profile.timeline else {
+                    continue;
+                };
+                if timestamp > input.profile.end_time {
+                    continue;
+                }
+                if let Some(point) = points.iter().rev().find(|p| p.timestamp <= timestamp) {
+                    total_rss_kib += point.total_rss_kib;
+                    process_count += point.process_count;
+                }
+            }
+
+            TimelinePoint {
+                timestamp,
+                elapsed_seconds: seconds_between(start_time, timestamp),
+                total_rss_kib,
+                process_count,
+                // PIDs from different hosts can collide, so per-process series are dropped
+                process_rss_kib: None,
+                smoothed_rss_kib: None,
+            }
+        })
+        .collect()
+}
+
+/// Upper bound on the merged peak without timelines: the largest sum of per-source
+/// peaks among sources whose [start, end] ranges overlap at some instant.
+fn overlapping_peak_sum(inputs: &[LabeledProfile]) -> u64 {
+    inputs
+        .iter()
+        .map(|candidate| {
+            // Any maximal overlap set contains the latest-starting member's start instant
+            let instant = candidate.profile.start_time;
+            inputs
+                .iter()
+                .filter(|i| i.profile.start_time <= instant && instant <= i.profile.end_time)
+                .map(|i| i.profile.max_total_rss_kib)
+                .sum()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ProcessStats;
+    use chrono::Duration;
+
+    fn profile_at(
+        start: DateTime<Utc>,
+        duration_secs: i64,
+        timeline: Option<Vec<(i64, u64)>>,
+        processes: Vec<(i32, &str, u64)>,
+    ) -> JobProfile {
+        let end = start + Duration::seconds(duration_secs);
+        let timeline = timeline.map(|points| {
+            points
+                .into_iter()
+                .map(|(offset, rss)| TimelinePoint {
+                    timestamp: start + Duration::seconds(offset),
+                    elapsed_seconds: offset as f64,
+                    total_rss_kib: rss,
+                    process_count: 1,
+                    process_rss_kib: None,
+                    smoothed_rss_kib: None,
+                })
+                .collect::<Vec<_>>()
+        });
+        let max_total_rss_kib = timeline
+            .iter()
+            .flatten()
+            .map(|p| p.total_rss_kib)
+            .max()
+            .unwrap_or(0);
+        let processes = processes
+            .into_iter()
+            .map(|(pid, command, rss)| ProcessStats {
+                pid,
+                ppid: 1,
+                command: command.to_string(),
+                max_rss_kib: rss,
+                first_seen: start,
+                last_seen: end,
+                peak_time: start,
+                ..Default::default()
+            })
+            .collect();
+
+        JobProfile {
+            command: vec!["mpirun".to_string()],
+            start_time: start,
+            end_time: end,
+            duration_seconds: duration_secs as f64,
+            interval_ms: 500,
+            interval_us: 500_000,
+            max_total_rss_kib,
+            samples: 3,
+            processes,
+            timeline,
+            exit_code: Some(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_labels_for_paths_deduplicates() {
+        let paths = vec![
+            "node1/profile.json".to_string(),
+            "node2/profile.json".to_string(),
+            "node3.json".to_string(),
+        ];
+        assert_eq!(
+            labels_for_paths(&paths),
+            vec!["profile", "profile-2", "node3"]
+        );
+    }
+
+    #[test]
+    fn test_merge_aligns_timelines_by_timestamp() {
+        let t0 = Utc::now();
+        let mut a = profile_at(
+            t0,
+            4,
+            Some(vec![(0, 100), (2, 300), (4, 100)]),
+            vec![(10, "worker", 300)],
+        );
+        // Second node starts one second later and peaks while the first is at 300
+        let mut b = profile_at(
+            t0 + Duration::seconds(1),
+            4,
+            Some(vec![(0, 50), (1, 200), (3, 50)]),
+            vec![(10, "worker", 200)],
+        );
+        let tag = |profile: &mut JobProfile, node: &str| {
+            profile
+                .tags
+                .insert("commit".to_string(), "abc123".to_string());
+            profile.tags.insert("node".to_string(), node.to_string());
+        };
+        tag(&mut a, "n01");
+        tag(&mut b, "n02");
+
+        let (merged, warnings) = merge_profiles(
+            vec![
+                LabeledProfile {
+                    label: "a".to_string(),
+                    profile: a,
+                },
+                LabeledProfile {
+                    label: "b".to_string(),
+                    profile: b,
+                },
+            ],
+            60.0,
+        )
+        .unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(merged.max_total_rss_kib, 500);
+        assert_eq!(merged.start_time, t0);
+        assert_eq!(merged.duration_seconds, 5.0);
+        assert_eq!(merged.processes.len(), 2);
+        assert_eq!(merged.format_tags(), "commit=abc123");
+        assert_eq!(merged.processes[0].source.as_deref(), Some("a"));
+        assert_eq!(merged.processes[1].source.as_deref(), Some("b"));
+        assert_eq!(merged.process_groups[0].count, 2);
+        assert_eq!(merged.sources.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_merge_without_timelines_sums_overlapping_peaks() {
+        let t0 = Utc::now();
+        let mut a = profile_at(t0, 10, None, vec![]);
+        a.max_total_rss_kib = 100;
+        let mut b = profile_at(t0 + Duration::seconds(5), 10, None, vec![]);
+        b.max_total_rss_kib = 200;
+        // Starts after `a` ended, so it only overlaps with `b`
+        let mut c = profile_at(t0 + Duration::seconds(12), 10, None, vec![]);
+        c.max_total_rss_kib = 50;
+
+        let inputs = vec![a, b, c]
+            .into_iter()
+            .enumerate()
+            .map(|(i, profile)| LabeledProfile {
+                label: i.to_string(),
+                profile,
+            })
+            .collect();
+
+        let (merged, warnings) = merge_profiles(inputs, 60.0).unwrap();
+
+        assert_eq!(merged.max_total_rss_kib, 300);
+        assert!(merged.timeline.is_none());
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_warns_on_start_skew() {
+        let t0 = Utc::now();
+        let a = profile_at(t0, 10, Some(vec![(0, 100)]), vec![]);
+        let b = profile_at(
+            t0 + Duration::seconds(600),
+            10,
+            Some(vec![(0, 100)]),
+            vec![],
+        );
+
+        let (_, warnings) = merge_profiles(
+            vec![
+                LabeledProfile {
+                    label: "a".to_string(),
+                    profile: a,
+                },
+                LabeledProfile {
+                    label: "b".to_string(),
+                    profile: b,
+                },
+            ],
+            60.0,
+        )
+        .unwrap();
+
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("start times differ"));
+    }
+}
@@ -0,0 +1,451 @@
+use crate::reporter::{color_choice, format_duration, format_memory};
+use crate::types::{JobProfile, PausePeriod, extract_command_name, memory};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const MARKERS: [char; 3] = ['●', '◆', '■'];
+const ASCII_MARKERS: [char; 3] = ['o', 'x', '+'];
+const SERIES_COLORS: [Color; 3] = [Color::Red, Color::Green, Color::Magenta];
+
+/// Number of processes overlaid by `--per-process`
+pub const TOP_PROCESSES: usize = 3;
+
+/// Chart rendering options
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    /// Total width in columns, including the Y-axis labels
+    pub width: usize,
+    /// Height of the plot area in rows
+    pub height: usize,
+    /// Use plain ASCII instead of block and marker characters
+    pub ascii: bool,
+    /// Overlay the top processes (needs per-process timeline data)
+    pub per_process: bool,
+}
+
+/// One character of the chart; `series` selects the overlay color, None is the total area
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    ch: char,
+    series: Option<usize>,
+}
+
+/// A process overlaid on the chart
+struct Overlay {
+    label: String,
+}
+
+/// Rendered chart, ready to print
+struct Chart {
+    rows: Vec<Vec<Cell>>,
+    top_kib: u64,
+    peak_column: usize,
+    overlays: Vec<Overlay>,
+}
+
+/// Terminal width from the controlling terminal, `$COLUMNS`, or 80
+pub fn terminal_width() -> usize {
+    // SAFETY: TIOCGWINSZ only writes into the winsize struct we pass
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+        && size.ws_col > 0
+    {
+        return size.ws_col as usize;
+    }
+
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c| c > 0)
+        .unwrap_or(80)
+}
+
+/// Whether the locale advertises UTF-8 (`LC_ALL`, then `LC_CTYPE`, then `LANG`)
+pub fn locale_supports_unicode() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|value| {
+            let value = value.to_ascii_lowercase();
+            value.contains("utf-8") || value.contains("utf8")
+        })
+}
+
+/// Resample (elapsed seconds, value) points into `buckets` columns spanning the run.
+///
+/// Each column takes the maximum of the samples falling in it so short spikes stay
+/// visible; columns without samples repeat the previous value.
+fn resample(points: &[(f64, u64)], buckets: usize) -> Vec<u64> {
+    let duration = points.iter().map(|(t, _)| *t).fold(0.0, f64::max);
+    let mut columns: Vec<Option<u64>> = vec![None; buckets];
+
+    for &(t, value) in points {
+        let idx = if duration > 0.0 {
+            ((t / duration * buckets as f64) as usize).min(buckets - 1)
+        } else {
+            0
+        };
+        columns[idx] = Some(columns[idx].map_or(value, |v| v.max(value)));
+    }
+
+    let mut last = 0;
+    columns
+        .into_iter()
+        .map(|v| {
+            last = v.unwrap_or(last);
+            last
+        })
+        .collect()
+}
+
+/// Empty the columns that fall entirely inside a pause, which has no samples, so the
+/// chart shows a gap instead of repeating the value before it
+fn blank_pauses(columns: &mut [u64], pauses: &[PausePeriod], duration: f64) {
+    if duration <= 0.0 {
+        return;
+    }
+    let width = duration / columns.len() as f64;
+    for (x, value) in columns.iter_mut().enumerate() {
+        let (start, end) = (x as f64 * width, (x + 1) as f64 * width);
+        if pauses.iter().any(|pause| {
+            start >= pause.start_elapsed_seconds
+                && end <= pause.start_elapsed_seconds + pause.duration_seconds
+        }) {
+            *value = 0;
+        }
+    }
+}
+
+/// Build the chart grid for a profile's timeline (None when it has no timeline)
+fn build_chart(profile: &JobProfile, plot_width: usize, options: &PlotOptions) -> Option<Chart> {
+    let timeline = profile.timeline.as_ref().filter(|t| !t.is_empty())?;
+    let height = options.height.max(1);
+
+    let totals: Vec<(f64, u64)> = timeline
+        .iter()
+        .map(|p| (p.elapsed_seconds, p.total_rss_kib))
+        .collect();
+    let mut columns = resample(&totals, plot_width);
+    let duration = totals.iter().map(|(t, _)| *t).fold(0.0, f64::max);
+    blank_pauses(&mut columns, &profile.pauses, duration);
+
+    let top_kib = columns.iter().copied().max().unwrap_or(0).max(1);
+    let peak_column = columns
+        .iter()
+        .position(|&v| v == top_kib)
+        .unwrap_or_default();
+
+    // Filled area for the total, in eighths of a row
+    let mut rows = vec![
+        vec![
+            Cell {
+                ch: ' ',
+                series: None
+            };
+            plot_width
+        ];
+        height
+    ];
+    for (x, &value) in columns.iter().enumerate() {
+        let eighths = (value as f64 / top_kib as f64 * (height * 8) as f64).round() as usize;
+        for (r, row) in rows.iter_mut().enumerate() {
+            let bottom = (height - 1 - r) * 8;
+            let filled = eighths.saturating_sub(bottom).min(8);
+            row[x].ch = match (filled, options.ascii) {
+                (0, _) => ' ',
+                (n, false) => BLOCKS[n - 1],
+                (n, true) if n >= 4 => '#',
+                (_, true) => '.',
+            };
+        }
+    }
+
+    // Per-process overlays, drawn as markers at each column's level
+    let mut overlays = Vec::new();
+    if options.per_process && timeline.iter().all(|p| p.process_rss_kib.is_some()) {
+        let mut top: Vec<_> = profile
+            .processes
+            .iter()
+            .filter(|p| p.max_rss_kib > 0)
+            .collect();
+        top.sort_by_key(|p| std::cmp::Reverse(p.max_rss_kib));
+
+        for (series, proc) in top.into_iter().take(TOP_PROCESSES).enumerate() {
+            let points: Vec<(f64, u64)> = timeline
+                .iter()
+                .map(|p| {
+                    let rss = p.process_rss_kib.as_ref();
+                    let kib = rss.and_then(|m| m.get(&proc.pid)).copied();
+                    (p.elapsed_seconds, kib.unwrap_or(0))
+                })
+                .collect();
+            let values = resample(&points, plot_width);
+
+            let marker = if options.ascii {
+                ASCII_MARKERS[series]
+            } else {
+                MARKERS[series]
+            };
+            for (x, &value) in values.iter().enumerate() {
+                if value == 0 {
+                    continue;
+                }
+                let level = (value as f64 / top_kib as f64 * height as f64).ceil() as usize;
+                let r = height - level.clamp(1, height);
+                rows[r][x] = Cell {
+                    ch: marker,
+                    series: Some(series),
+                };
+            }
+
+            overlays.push(Overlay {
+                label: format!(
+                    "pid {} {} (peak {})",
+                    proc.pid,
+                    extract_command_name(&proc.command),
+                    format_memory(proc.max_rss_kib)
+                ),
+            });
+        }
+    }
+
+    Some(Chart {
+        rows,
+        top_kib,
+        peak_column,
+        overlays,
+    })
+}
+
+/// Y-axis label, scaled to MiB or GiB depending on the chart's maximum
+fn axis_label(kib: u64, top_kib: u64) -> String {
+    if top_kib as f64 >= memory::KIB_PER_GIB {
+        format!("{:.1} GiB", kib as f64 / memory::KIB_PER_GIB)
+    } else {
+        format!("{:.1} MiB", kib as f64 / memory::KIB_PER_MIB)
+    }
+}
+
+/// Print a profile's memory curve, or its summary numbers when it has no timeline
+pub fn print_plot(profile: &JobProfile, options: &PlotOptions) {
+    let mut stdout = StandardStream::stdout(color_choice());
+
+    println!("\nJob: {}", profile.command.join(" "));
+    println!(
+        "Duration: {}  |  Samples: {}  |  Total peak: {}",
+        format_duration(profile.duration_seconds),
+        profile.samples,
+        format_memory(profile.max_total_rss_kib)
+    );
+
+    let label_width = 10;
+    let plot_width = options.width.saturating_sub(label_width + 2).max(10);
+
+    let Some(chart) = build_chart(profile, plot_width, options) else {
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
+        println!("\nThis profile has no timeline data to plot.");
+        let _ = stdout.reset();
+        println!("Rerun with `memwatch run --timeline <FILE> --json -- <command>` to record one.");
+        println!();
+        return;
+    };
+
+    if options.per_process && chart.overlays.is_empty() {
+        eprintln!(
+            "Warning: Profile has no per-process timeline data (record it with --per-process-timeline); plotting the total only"
+        );
+    }
+
+    let (vertical, corner, horizontal) = if options.ascii {
+        ('|', '+', '-')
+    } else {
+        ('┤', '└', '─')
+    };
+
+    println!();
+    let height = chart.rows.len();
+    for (r, row) in chart.rows.iter().enumerate() {
+        // Label the top row and the middle row
+        let label = if r == 0 {
+            axis_label(chart.top_kib, chart.top_kib)
+        } else if height > 2 && r == height / 2 {
+            axis_label(
+                chart.top_kib * (height - r) as u64 / height as u64,
+                chart.top_kib,
+            )
+        } else {
+            String::new()
+        };
+
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+        print!("{:>width$} {}", label, vertical, width = label_width);
+        let _ = stdout.reset();
+
+        for cell in row {
+            let color = match cell.series {
+                Some(series) => SERIES_COLORS[series],
+                None => Color::Cyan,
+            };
+            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(color)));
+            print!("{}", cell.ch);
+        }
+        let _ = stdout.reset();
+        println!();
+    }
+
+    // X axis with the peak marked underneath
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    println!(
+        "{:>width$} {}{}",
+        axis_label(0, chart.top_kib),
+        corner,
+        horizontal.to_string().repeat(plot_width),
+        width = label_width
+    );
+    let end_label = format!("{:.1}s", profile.duration_seconds);
+    println!(
+        "{:>width$}  0s{:>rest$}",
+        "",
+        end_label,
+        width = label_width,
+        rest = plot_width.saturating_sub(2)
+    );
+    let _ = stdout.reset();
+
+    let peak_text = format!(
+        "{} peak {}",
+        if options.ascii { '^' } else { '▲' },
+        format_memory(profile.max_total_rss_kib)
+    );
+    let offset = chart
+        .peak_column
+        .min(plot_width.saturating_sub(peak_text.chars().count()));
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)).set_bold(true));
+    println!(
+        "{:>width$}  {}{}",
+        "",
+        " ".repeat(offset),
+        peak_text,
+        width = label_width
+    );
+    let _ = stdout.reset();
+
+    // Legend for overlays
+    if !chart.overlays.is_empty() {
+        println!();
+        for (series, overlay) in chart.overlays.iter().enumerate() {
+            let marker = if options.ascii {
+                ASCII_MARKERS[series]
+            } else {
+                MARKERS[series]
+            };
+            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(SERIES_COLORS[series])));
+            print!("  {} ", marker);
+            let _ = stdout.reset();
+            println!("{}", overlay.label);
+        }
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ProcessStats, TimelinePoint};
+    use chrono::Utc;
+
+    fn profile(totals: &[u64], per_process: bool) -> JobProfile {
+        let timeline = totals
+            .iter()
+            .enumerate()
+            .map(|(i, &total)| TimelinePoint {
+                timestamp: Utc::now(),
+                elapsed_seconds: i as f64,
+                total_rss_kib: total,
+                process_count: 1,
+                process_rss_kib: per_process.then(|| [(7, total / 2)].into_iter().collect()),
+                smoothed_rss_kib: None,
+            })
+            .collect();
+
+        JobProfile {
+            timeline: Some(timeline),
+            processes: vec![ProcessStats {
+                pid: 7,
+                command: "worker".to_string(),
+                max_rss_kib: 100,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn options(ascii: bool, per_process: bool) -> PlotOptions {
+        PlotOptions {
+            width: 80,
+            height: 4,
+            ascii,
+            per_process,
+        }
+    }
+
+    #[test]
+    fn test_resample_keeps_spikes_and_fills_gaps() {
+        let points = [(0.0, 10), (0.1, 50), (0.2, 20), (4.0, 30)];
+        assert_eq!(resample(&points, 4), vec![50, 50, 50, 30]);
+    }
+
+    #[test]
+    fn test_blank_pauses() {
+        let pause = PausePeriod {
+            start: chrono::Utc::now(),
+            start_elapsed_seconds: 2.0,
+            duration_seconds: 4.5,
+        };
+        let mut columns = vec![10; 8];
+        blank_pauses(&mut columns, &[pause], 8.0);
+        // Only columns wholly inside 2.0s-6.5s are emptied
+        assert_eq!(columns, vec![10, 10, 0, 0, 0, 0, 10, 10]);
+    }
+
+    #[test]
+    fn test_chart_marks_peak_and_fills_area() {
+        let chart = build_chart(
+            &profile(&[0, 100, 200, 50], false),
+            4,
+            &options(false, false),
+        )
+        .unwrap();
+
+        assert_eq!(chart.top_kib, 200);
+        assert_eq!(chart.peak_column, 2);
+        // The peak column is full height, the empty column blank
+        assert!(chart.rows.iter().all(|row| row[2].ch == '█'));
+        assert!(chart.rows.iter().all(|row| row[0].ch == ' '));
+    }
+
+    #[test]
+    fn test_ascii_chart_has_only_ascii() {
+        let chart =
+            build_chart(&profile(&[30, 100, 200, 50], true), 8, &options(true, true)).unwrap();
+
+        assert_eq!(chart.overlays.len(), 1);
+        assert!(chart.rows.iter().flatten().all(|cell| cell.ch.is_ascii()));
+        assert!(
+            chart
+                .rows
+                .iter()
+                .flatten()
+                .any(|cell| cell.series == Some(0))
+        );
+    }
+
+    #[test]
+    fn test_no_timeline_has_no_chart() {
+        let mut p = profile(&[], false);
+        assert!(build_chart(&p, 10, &options(false, false)).is_none());
+        p.timeline = None;
+        assert!(build_chart(&p, 10, &options(false, false)).is_none());
+    }
+}
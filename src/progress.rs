@@ -0,0 +1,150 @@
+//! Progress display on stderr while a job is sampled (`run` on a terminal).
+//!
+//! With `--timeout` the job has a known upper bound, so a bar fills towards it;
+//! otherwise a spinner shows the elapsed time. Both show the current total and peak.
+//! `Console` drives the display from the sampler's events and prints the SIGUSR1
+//! summary and SIGUSR2 notices around it.
+
+use crate::reporter::{format_memory, print_partial_summary, print_pause};
+use crate::sampler::{SampleEvent, SampleObserver};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::time::Duration;
+
+/// How often the spinner redraws between samples
+const TICK: Duration = Duration::from_millis(120);
+
+/// The bar or spinner for one run, updated once per sample
+pub struct Progress {
+    bar: ProgressBar,
+    timeout: Option<Duration>,
+}
+
+impl Progress {
+    pub fn start(timeout: Option<Duration>) -> Self {
+        let bar = match timeout {
+            Some(timeout) => {
+                let bar = ProgressBar::new(timeout.as_millis() as u64);
+                bar.set_style(
+                    ProgressStyle::with_template(
+                        "{bar:30.cyan/blue} {elapsed_precise} of {prefix}  {msg}",
+                    )
+                    .expect("valid template")
+                    .progress_chars("=> "),
+                );
+                bar.set_prefix(format!("{:?}", timeout));
+                bar
+            }
+            None => {
+                let bar = ProgressBar::new_spinner();
+                bar.set_style(
+                    ProgressStyle::with_template("{spinner:.cyan} {elapsed_precise}  {msg}")
+                        .expect("valid template"),
+                );
+                bar
+            }
+        };
+        bar.enable_steady_tick(TICK);
+        Self { bar, timeout }
+    }
+
+    /// Show a sample's total RSS and the peak so far
+    pub fn observe(&self, elapsed: Duration, total_rss_kib: u64, peak_kib: u64) {
+        if let Some(timeout) = self.timeout {
+            self.bar.set_position(position(elapsed, timeout));
+        }
+        self.bar.set_message(message(total_rss_kib, peak_kib));
+    }
+
+    /// Hide the display while `print` writes to stderr, then draw it again
+    pub fn suspend<R>(&self, print: impl FnOnce() -> R) -> R {
+        self.bar.suspend(print)
+    }
+
+    /// Remove the display before the summary is printed
+    pub fn finish(self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Shows a run on stderr while it is sampled, with or without a progress display
+pub struct Console {
+    progress: Option<Progress>,
+}
+
+impl Console {
+    pub fn new(progress: Option<Progress>) -> Self {
+        Self { progress }
+    }
+
+    fn print(&self, print: impl FnOnce()) {
+        match &self.progress {
+            Some(progress) => progress.suspend(print),
+            None => print(),
+        }
+    }
+}
+
+impl SampleObserver for Console {
+    fn observe(&mut self, event: SampleEvent<'_>) {
+        match event {
+            SampleEvent::Sample {
+                elapsed,
+                total_rss_kib,
+                peak_kib,
+            } => {
+                if let Some(progress) = &self.progress {
+                    progress.observe(elapsed, total_rss_kib, peak_kib);
+                }
+            }
+            SampleEvent::Summary {
+                elapsed,
+                current_kib,
+                peak_kib,
+                processes,
+                written,
+            } => self.print(|| {
+                print_partial_summary(elapsed.as_secs_f64(), current_kib, peak_kib, processes);
+                for (what, path) in written {
+                    eprintln!("Partial {} written to: {}", what, path);
+                }
+            }),
+            SampleEvent::Pause {
+                elapsed_seconds,
+                resumed_after,
+            } => self.print(|| print_pause(elapsed_seconds, resumed_after)),
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(progress) = self.progress.take() {
+            progress.finish();
+        }
+    }
+}
+
+/// Bar position in milliseconds, capped at the timeout
+fn position(elapsed: Duration, timeout: Duration) -> u64 {
+    elapsed.min(timeout).as_millis() as u64
+}
+
+fn message(total_rss_kib: u64, peak_kib: u64) -> String {
+    format!(
+        "RSS {} (peak {})",
+        format_memory(total_rss_kib),
+        format_memory(peak_kib)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_and_message() {
+        let timeout = Duration::from_secs(10);
+        assert_eq!(position(Duration::from_millis(2500), timeout), 2500);
+        assert_eq!(position(Duration::from_secs(12), timeout), 10_000);
+
+        assert_eq!(message(512, 2048), "RSS 512 KiB (peak 2.0 MiB)");
+    }
+}
@@ -0,0 +1,207 @@
+//! Pseudo-terminal support for `run --pty`.
+//!
+//! The command gets the slave side of a fresh PTY as its controlling terminal, so
+//! `isatty()` checks pass and colors, progress bars, and interactive prompts work.
+//! memwatch relays bytes between the master side and its own terminal on background
+//! threads, leaving the sampling loop untouched.
+
+use anyhow::{Context, Result};
+use crossterm::terminal;
+use std::fs::File;
+use std::io::{IsTerminal, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How often the terminal size is checked for changes
+const RESIZE_POLL: Duration = Duration::from_millis(100);
+
+/// How long to wait for buffered output after the command exits. Orphans that keep
+/// the terminal open would otherwise hold up the summary indefinitely
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Both ends of a newly allocated pseudo-terminal
+pub struct Pty {
+    master: OwnedFd,
+    slave: OwnedFd,
+}
+
+impl Pty {
+    /// Allocate a PTY sized like memwatch's own terminal (or 80x24 without one)
+    pub fn open() -> Result<Self> {
+        let mut size = terminal_size().unwrap_or(libc::winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        });
+        let mut master = -1;
+        let mut slave = -1;
+
+        // SAFETY: openpty writes two new descriptors, which we take ownership of
+        let result = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                // *const on Linux, *mut on macOS
+                &raw mut size as _,
+            )
+        };
+        if result != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to allocate a PTY");
+        }
+
+        Ok(Self {
+            master: unsafe { OwnedFd::from_raw_fd(master) },
+            slave: unsafe { OwnedFd::from_raw_fd(slave) },
+        })
+    }
+
+    /// Make the slave side the command's stdio and controlling terminal
+    pub fn attach(&self, cmd: &mut Command) -> Result<()> {
+        cmd.stdin(Stdio::from(self.slave.try_clone()?));
+        cmd.stdout(Stdio::from(self.slave.try_clone()?));
+        cmd.stderr(Stdio::from(self.slave.try_clone()?));
+
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            cmd.pre_exec(|| {
+                // A new session has no controlling terminal; stdin (the slave) becomes it
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Start relaying between the PTY and memwatch's terminal. Call after spawning so
+    /// the parent's copy of the slave is closed and the output relay sees EOF once the
+    /// command (and anything it left holding the terminal) exits
+    pub fn relay(self) -> Result<PtyRelay> {
+        let Pty { master, slave } = self;
+        drop(slave);
+
+        let mut output = File::from(master.try_clone()?);
+        let input = File::from(master.try_clone()?);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // Output: PTY -> stdout, until the slave side is closed (EIO on Linux)
+        let (done_tx, done_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdout = std::io::stdout();
+            let mut buf = [0u8; 8192];
+            while let Ok(n) = output.read(&mut buf) {
+                if n == 0 || stdout.write_all(&buf[..n]).is_err() {
+                    break;
+                }
+                let _ = stdout.flush();
+            }
+            let _ = done_tx.send(());
+        });
+
+        // Input and resizes only make sense when memwatch itself is on a terminal
+        let raw_mode = if std::io::stdin().is_terminal() {
+            let guard = RawModeGuard::enter()?;
+            spawn_input_relay(input);
+            spawn_resize_relay(master, Arc::clone(&stop));
+            Some(guard)
+        } else {
+            None
+        };
+
+        Ok(PtyRelay {
+            output_done: done_rx,
+            stop,
+            _raw_mode: raw_mode,
+        })
+    }
+}
+
+/// Running relay threads; dropping it restores the terminal
+pub struct PtyRelay {
+    output_done: mpsc::Receiver<()>,
+    stop: Arc<AtomicBool>,
+    _raw_mode: Option<RawModeGuard>,
+}
+
+impl PtyRelay {
+    /// Wait briefly for the command's remaining output, then restore the terminal
+    pub fn finish(self) {
+        let _ = self.output_done.recv_timeout(DRAIN_TIMEOUT);
+    }
+}
+
+impl Drop for PtyRelay {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Puts memwatch's terminal in raw mode so keystrokes (including Ctrl-C) go to the
+/// command untranslated, and restores it on drop, including when the command crashes
+/// or memwatch bails out with an error
+struct RawModeGuard;
+
+impl RawModeGuard {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode().context("Failed to put the terminal in raw mode")?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// stdin -> PTY. The thread blocks on stdin, so it is left running rather than joined
+fn spawn_input_relay(mut input: File) {
+    thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; 1024];
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 || input.write_all(&buf[..n]).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Copy memwatch's window size to the PTY whenever it changes. Setting the size on the
+/// master makes the kernel send SIGWINCH to the command's foreground process group
+fn spawn_resize_relay(master: OwnedFd, stop: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        let mut last = terminal_size().map(|s| (s.ws_row, s.ws_col));
+        while !stop.load(Ordering::Relaxed) {
+            thread::sleep(RESIZE_POLL);
+            let Some(size) = terminal_size() else {
+                continue;
+            };
+            if last != Some((size.ws_row, size.ws_col)) {
+                last = Some((size.ws_row, size.ws_col));
+                // SAFETY: TIOCSWINSZ only reads the winsize struct we pass
+                unsafe {
+                    libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size);
+                }
+            }
+        }
+    });
+}
+
+/// Size of the terminal on memwatch's stdin, if it is one
+fn terminal_size() -> Option<libc::winsize> {
+    // SAFETY: TIOCGWINSZ only writes into the winsize struct we pass
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_col > 0 && size.ws_row > 0).then_some(size)
+}
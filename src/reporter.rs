@@ -1,25 +1,76 @@
-use crate::types::{JobProfile, ProcessStats, memory};
-use anyhow::Result;
-use colored_json::ToColoredJson;
-use std::collections::HashMap;
-use std::io::IsTerminal;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
-
-/// Format bytes in KiB to human-readable format (KiB, MiB, GiB)
-fn format_memory(kib: u64) -> String {
+use crate::compare;
+use crate::config;
+use crate::error::MemwatchError;
+use crate::types::{
+    FilterConfig, JobProfile, KillReason, MemoryAlert, PeakMode, ProcessOrder, ProcessSample,
+    ProcessSort, ProcessStats, Units, memory, percent_of,
+};
+use anyhow::{Context, Result};
+use colored_json::{ColorMode, ToColoredJson};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write};
+use termcolor::{Color, ColorChoice, ColorSpec, NoColor, StandardStream, WriteColor};
+
+/// Format a size in KiB for display, in the `--units` setting
+pub(crate) fn format_memory(kib: u64) -> String {
+    format_memory_in(kib, config::units())
+}
+
+/// Format a KiB count in the given units
+fn format_memory_in(kib: u64, units: Units) -> String {
     let kib_f64 = kib as f64;
 
-    if kib_f64 >= memory::KIB_PER_GIB {
-        format!("{:.1} GiB", kib_f64 / memory::KIB_PER_GIB)
-    } else if kib_f64 >= memory::KIB_PER_MIB {
-        format!("{:.1} MiB", kib_f64 / memory::KIB_PER_MIB)
+    match units {
+        Units::Binary => {
+            if kib_f64 >= memory::KIB_PER_GIB {
+                format!("{:.1} GiB", kib_f64 / memory::KIB_PER_GIB)
+            } else if kib_f64 >= memory::KIB_PER_MIB {
+                format!("{:.1} MiB", kib_f64 / memory::KIB_PER_MIB)
+            } else {
+                format!("{} KiB", kib)
+            }
+        }
+        Units::Si => format_si(kib_f64 * memory::BYTES_PER_KIB),
+        Units::Kib => format!("{} KiB", kib),
+        Units::Mib => format!("{:.2} MiB", kib_f64 / memory::KIB_PER_MIB),
+        Units::Gib => format!("{:.3} GiB", kib_f64 / memory::KIB_PER_GIB),
+        Units::Bytes => format!("{} B", kib * 1024),
+    }
+}
+
+/// Format a byte count in powers of 1000
+fn format_si(bytes: f64) -> String {
+    // Step up a unit when rounding would print 1000 of the smaller one
+    if (bytes / memory::BYTES_PER_MB * 10.0).round() >= 10_000.0 {
+        format!("{:.1} GB", bytes / memory::BYTES_PER_GB)
+    } else if (bytes / memory::BYTES_PER_KB).round() >= 1000.0 {
+        format!("{:.1} MB", bytes / memory::BYTES_PER_MB)
     } else {
-        format!("{} KiB", kib)
+        format!("{:.0} kB", bytes / memory::BYTES_PER_KB)
+    }
+}
+
+/// Format a byte count with the same units as `format_memory`
+fn format_bytes(bytes: u64) -> String {
+    format_bytes_in(bytes, config::units())
+}
+
+/// Format a byte count in the given units; SI keeps the exact count rather than
+/// rounding down to whole KiB first
+fn format_bytes_in(bytes: u64, units: Units) -> String {
+    match units {
+        Units::Bytes => format!("{} B", bytes),
+        _ if bytes < 1024 => format!("{} B", bytes),
+        Units::Si => format_si(bytes as f64),
+        _ => format_memory_in(bytes / 1024, units),
     }
 }
 
 /// Format duration in seconds to HH:MM:SS
-fn format_duration(seconds: f64) -> String {
+pub(crate) fn format_duration(seconds: f64) -> String {
     let total_secs = seconds as u64;
     let hours = total_secs / 3600;
     let minutes = (total_secs % 3600) / 60;
@@ -28,260 +79,1508 @@ fn format_duration(seconds: f64) -> String {
     format!("{:02}:{:02}:{:02}", hours, minutes, secs)
 }
 
-/// Extract command name from full command line
-fn extract_command_name(command: &str) -> String {
-    // Take first word (command name)
-    let first_word = command.split_whitespace().next().unwrap_or(command);
+/// Format a sampling interval, falling back to µs below one millisecond
+fn format_interval(profile: &JobProfile) -> String {
+    // Profiles written before interval_us existed only carry milliseconds
+    let interval_us = if profile.interval_us > 0 {
+        profile.interval_us
+    } else {
+        profile.interval_ms * 1000
+    };
+
+    if interval_us < 1000 {
+        format!("{}µs", interval_us)
+    } else {
+        format!("{}ms", interval_us / 1000)
+    }
+}
+
+/// Rows shown per table in the summary unless `--top` says otherwise
+pub const DEFAULT_TOP: usize = 20;
+
+/// Processes listed in an intermediate summary (SIGUSR1)
+const PARTIAL_SUMMARY_TOP: usize = 5;
+
+/// Decorative glyphs in reports and what `--ascii` output shows instead
+const ASCII_GLYPHS: [(char, &str); 6] = [
+    ('⚠', "!"),
+    ('•', "-"),
+    ('✖', "x"),
+    ('✔', "+"),
+    ('…', "..."),
+    ('→', "->"),
+];
+
+/// Replace the glyphs in `ASCII_GLYPHS` with their ASCII equivalents
+pub fn to_ascii(text: &str) -> Cow<'_, str> {
+    if !text.contains(|c| ASCII_GLYPHS.iter().any(|&(glyph, _)| glyph == c)) {
+        return Cow::Borrowed(text);
+    }
+    let mut ascii = String::with_capacity(text.len());
+    for c in text.chars() {
+        match ASCII_GLYPHS.iter().find(|&&(glyph, _)| glyph == c) {
+            Some((_, replacement)) => ascii.push_str(replacement),
+            None => ascii.push(c),
+        }
+    }
+    Cow::Owned(ascii)
+}
+
+/// Passes what is written through `to_ascii` when `ascii` is set, so report code
+/// writes its glyphs unconditionally
+pub struct Glyphs<W> {
+    inner: W,
+    ascii: bool,
+}
+
+impl<W> Glyphs<W> {
+    pub fn new(inner: W, ascii: bool) -> Self {
+        Self { inner, ascii }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for Glyphs<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // Formatting hands over whole pieces of text; anything else passes unchanged
+        match std::str::from_utf8(buf) {
+            Ok(text) if self.ascii => {
+                self.inner.write_all(to_ascii(text).as_bytes())?;
+                Ok(buf.len())
+            }
+            _ => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for Glyphs<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> std::io::Result<()> {
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> std::io::Result<()> {
+        self.inner.reset()
+    }
+}
+
+/// Stdout for human-readable reports, with colors and glyphs as configured
+fn stdout_stream() -> Glyphs<StandardStream> {
+    Glyphs::new(
+        StandardStream::stdout(color_choice()),
+        config::ascii_for(std::io::stdout().is_terminal()),
+    )
+}
+
+/// Stderr for notices while a job runs, with colors and glyphs as configured
+fn stderr_stream() -> Glyphs<StandardStream> {
+    Glyphs::new(
+        StandardStream::stderr(stderr_color_choice()),
+        config::ascii_for(std::io::stderr().is_terminal()),
+    )
+}
+
+/// Layout choices for the human-readable summary
+#[derive(Debug, Clone, Copy)]
+pub struct SummaryOptions {
+    /// Rows shown per table (`None` for all)
+    pub top: Option<usize>,
+    /// Order of the per-process table
+    pub order: ProcessOrder,
+    /// Show executable paths in place of program names (`--resolve-exe`)
+    pub resolve_exe: bool,
+    /// Width to fit table rows into by shortening their commands, usually
+    /// `summary_width()`; `None`, the default, prints them in full (`--full-command`)
+    pub width: Option<usize>,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        Self {
+            top: Some(DEFAULT_TOP),
+            order: ProcessOrder::default(),
+            resolve_exe: false,
+            width: None,
+        }
+    }
+}
+
+/// Width the summary fits into when stdout is not a terminal
+const PIPED_WIDTH: usize = 100;
 
-    // Get basename from path
-    if let Some(pos) = first_word.rfind('/') {
-        first_word[pos + 1..].to_string()
+/// A shortened command is never cut below this many characters, however narrow the
+/// terminal
+const MIN_COMMAND_WIDTH: usize = 24;
+
+/// Per-process table layout: rows are indented, and every column before COMMAND is
+/// followed by a gap
+const TABLE_INDENT: usize = 2;
+const COLUMN_GAP: usize = 2;
+const PID_WIDTH: usize = 5;
+/// MEMORY, % JOB PEAK, GROWTH, READ, WRITE, PRIVATE, and SHARED
+const VALUE_WIDTH: usize = 10;
+/// TIME and LIFETIME
+const TIME_WIDTH: usize = 8;
+/// SHARE in the AT PEAK table
+const SHARE_WIDTH: usize = 6;
+
+/// Columns taken by the indent and by columns of the given widths with their gaps
+const fn table_columns(widths: &[usize]) -> usize {
+    let mut used = TABLE_INDENT;
+    let mut i = 0;
+    while i < widths.len() {
+        used += widths[i] + COLUMN_GAP;
+        i += 1;
+    }
+    used
+}
+
+/// Width of the terminal stdout is, or `PIPED_WIDTH` when it is redirected
+pub fn summary_width() -> usize {
+    if std::io::stdout().is_terminal() {
+        crate::plot::terminal_width()
     } else {
-        first_word.to_string()
+        PIPED_WIDTH
+    }
+}
+
+/// Shorten `command` to `width` characters by replacing its middle with `…`, keeping
+/// both the program and the last arguments in view
+pub fn truncate_middle(command: &str, width: usize) -> Cow<'_, str> {
+    let length = command.chars().count();
+    if length <= width {
+        return Cow::Borrowed(command);
+    }
+    let kept = width.saturating_sub(1);
+    let head: String = command.chars().take(kept.div_ceil(2)).collect();
+    let tail: String = command.chars().skip(length - kept / 2).collect();
+    Cow::Owned(format!("{}…{}", head, tail))
+}
+
+/// `command` shortened to what is left of `options.width` after `used` columns
+fn fit_command<'a>(command: &'a str, used: usize, options: &SummaryOptions) -> Cow<'a, str> {
+    match options.width {
+        Some(width) => truncate_middle(command, width.saturating_sub(used).max(MIN_COMMAND_WIDTH)),
+        None => Cow::Borrowed(command),
+    }
+}
+
+/// The command line to show for a process: with `resolve_exe`, the program name is
+/// replaced by the executable path when it is known
+fn display_command(proc: &ProcessStats, resolve_exe: bool) -> Cow<'_, str> {
+    match (&proc.exe_path, resolve_exe) {
+        (Some(exe), true) => match proc.command.split_once(' ') {
+            Some((_, args)) => Cow::Owned(format!("{} {}", exe, args)),
+            None => Cow::Borrowed(exe),
+        },
+        _ => Cow::Borrowed(&proc.command),
     }
 }
 
 /// Print human-readable summary with colors and compact formatting
-pub fn print_summary(profile: &JobProfile) {
-    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
+pub fn print_summary(profile: &JobProfile, options: SummaryOptions) {
+    let mut stdout = stdout_stream();
+    // A closed stdout (e.g. piped into `head`) is not worth failing the run over
+    let _ = write_summary(&mut stdout, profile, options);
+}
+
+/// Print the processes `memwatch probe` found, marking which a profile would show
+pub fn print_probe(root_pid: i32, processes: &[ProcessSample], profile: &JobProfile) {
+    let mut stdout = stdout_stream();
+    let _ = write_probe(&mut stdout, root_pid, processes, profile);
+}
+
+/// Render the probe table: every tracked process, largest first, and whether the
+/// filters in `profile` (built from the same snapshot) keep it
+pub fn write_probe(
+    out: &mut impl WriteColor,
+    root_pid: i32,
+    processes: &[ProcessSample],
+    profile: &JobProfile,
+) -> std::io::Result<()> {
+    let shown: HashSet<i32> = profile.displayed_processes().map(|p| p.pid).collect();
+    let mut processes: Vec<&ProcessSample> = processes.iter().collect();
+    processes.sort_by_key(|p| (std::cmp::Reverse(p.rss_kib), p.pid));
+
+    writeln!(
+        out,
+        "\nJob rooted at PID {}: {} processes, {}",
+        root_pid,
+        processes.len(),
+        format_memory(profile.max_total_rss_kib)
+    )?;
+    if let Some(ref filter) = profile.filter {
+        for line in describe_filter(filter) {
+            writeln!(out, "  {}", line)?;
+        }
+    }
+
+    out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+    writeln!(
+        out,
+        "\n  {:>5}  {:>5}  {:>10}  {:6}  COMMAND",
+        "PID", "PPID", "MEMORY", "STATUS"
+    )?;
+    out.reset()?;
+
+    for proc in &processes {
+        let is_shown = shown.contains(&proc.pid);
+        let mut spec = ColorSpec::new();
+        if !is_shown {
+            spec.set_fg(Some(Color::White)).set_dimmed(true);
+        }
+        out.set_color(&spec)?;
+        writeln!(
+            out,
+            "  {:>5}  {:>5}  {:>10}  {:6}  {}",
+            proc.pid,
+            proc.ppid,
+            format_memory(proc.rss_kib),
+            if is_shown { "shown" } else { "hidden" },
+            proc.command
+        )?;
+        out.reset()?;
+    }
+
+    writeln!(
+        out,
+        "\n{} shown, {} hidden",
+        shown.len(),
+        processes.len() - shown.len()
+    )?;
+    Ok(())
+}
+
+/// Print `memwatch snapshot`: the processes the filters keep, largest first
+pub fn print_snapshot(profile: &JobProfile, process_count: usize, top: Option<usize>) {
+    let mut stdout = stdout_stream();
+    let _ = write_snapshot(&mut stdout, profile, process_count, top);
+}
+
+/// Render the snapshot table. `profile` holds the single sample after filtering;
+/// `process_count` is how many processes were running before it
+pub fn write_snapshot(
+    out: &mut impl WriteColor,
+    profile: &JobProfile,
+    process_count: usize,
+    top: Option<usize>,
+) -> std::io::Result<()> {
+    let processes = profile.sorted_processes(ProcessOrder::default());
+    let total_kib: u64 = processes.iter().map(|p| p.max_rss_kib).sum();
+
+    writeln!(
+        out,
+        "\n{} of {} processes, {}",
+        processes.len(),
+        process_count,
+        format_memory(total_kib)
+    )?;
+    if let Some(ref filter) = profile.filter {
+        for line in describe_filter(filter) {
+            writeln!(out, "  {}", line)?;
+        }
+    }
+
+    out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+    writeln!(
+        out,
+        "\n  {:>7}  {:>7}  {:>10}  COMMAND",
+        "PID", "PPID", "MEMORY"
+    )?;
+    out.reset()?;
+
+    let limit = top.unwrap_or(usize::MAX);
+    for proc in processes.iter().take(limit) {
+        writeln!(
+            out,
+            "  {:>7}  {:>7}  {:>10}  {}",
+            proc.pid,
+            proc.ppid,
+            format_memory(proc.max_rss_kib),
+            proc.command
+        )?;
+    }
+    let hidden = &processes[limit.min(processes.len())..];
+    write_hidden_rows(
+        out,
+        hidden.len(),
+        "processes",
+        hidden.iter().map(|p| p.max_rss_kib).sum(),
+    )?;
+
+    if let Some(count @ 1..) = profile.filtered_process_count {
+        writeln!(
+            out,
+            "\n{} filtered out ({})",
+            count,
+            format_memory(profile.filtered_total_rss_kib.unwrap_or(0))
+        )?;
+    }
+    if profile.below_min_rss_count > 0 {
+        writeln!(
+            out,
+            "{} below --min-rss ({})",
+            profile.below_min_rss_count,
+            format_memory(profile.below_min_rss_total_kib)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write a change against `--baseline` as ` (<change> vs baseline)`, red for an increase
+fn write_change(out: &mut impl WriteColor, increased: bool, change: &str) -> std::io::Result<()> {
+    let color = if increased { Color::Red } else { Color::Green };
+    out.set_color(ColorSpec::new().set_fg(Some(color)))?;
+    write!(out, " ({} vs baseline)", change)?;
+    out.reset()
+}
+
+/// Render the human-readable summary into any sink; wrap plain writers in
+/// `termcolor::NoColor` to drop the colors
+pub fn write_summary(
+    out: &mut impl WriteColor,
+    profile: &JobProfile,
+    options: SummaryOptions,
+) -> std::io::Result<()> {
+    let limit = options.top.unwrap_or(usize::MAX);
 
     // Job header
-    println!("\nJob: {}", profile.command.join(" "));
-    print!(
-        "Duration: {}  |  Samples: {}",
-        format_duration(profile.duration_seconds),
-        profile.samples
-    );
-    println!();
+    // A label names the job; the full command line is still shown below it
+    writeln!(out, "\nJob: {}", profile.name())?;
+    if profile.label.is_some() {
+        writeln!(out, "Command: {}", profile.command.join(" "))?;
+    }
+    if !profile.tags.is_empty() {
+        writeln!(out, "Tags: {}", profile.format_tags())?;
+    }
+    if let Some(shell) = &profile.shell {
+        let counted = if profile.self_excluded {
+            " (not counted)"
+        } else {
+            ""
+        };
+        writeln!(out, "Shell: {} -c{}", shell, counted)?;
+    }
+    if let Some(dir) = &profile.working_dir {
+        writeln!(out, "Directory: {}", dir)?;
+    }
+    if let Some(depth) = profile.filter.as_ref().and_then(|f| f.max_depth) {
+        writeln!(out, "Depth: {}", describe_depth(depth))?;
+    }
+    write!(
+        out,
+        "Duration: {}",
+        format_duration(profile.duration_seconds)
+    )?;
+    if let Some(baseline) = &profile.baseline_comparison {
+        write_change(
+            out,
+            profile.duration_seconds > baseline.duration_seconds,
+            &compare::format_duration_delta(profile.duration_seconds, baseline.duration_seconds),
+        )?;
+    }
+    write!(out, "  |  Samples: {}", profile.samples)?;
+    if !profile.pauses.is_empty() {
+        write!(
+            out,
+            "  |  Paused: {} ({} pause{})",
+            format_duration(profile.paused_seconds()),
+            profile.pauses.len(),
+            if profile.pauses.len() == 1 { "" } else { "s" }
+        )?;
+    }
+    writeln!(out)?;
+    if let Some(seconds) = profile.drain_seconds {
+        writeln!(
+            out,
+            "Followed processes left behind for {:.1}s after the command exited",
+            seconds
+        )?;
+    }
+    if let Some(cpu) = profile.total_cpu_seconds {
+        write!(
+            out,
+            "CPU time: {:.1}s over {:.1}s wall",
+            cpu, profile.duration_seconds
+        )?;
+        if profile.duration_seconds > 0.0 {
+            write!(out, " → {:.1}x parallelism", cpu / profile.duration_seconds)?;
+        }
+        writeln!(out)?;
+    }
+    if let (Some(read), Some(written)) = (profile.io_read_bytes, profile.io_write_bytes) {
+        writeln!(
+            out,
+            "I/O: {} read, {} written",
+            format_bytes(read),
+            format_bytes(written)
+        )?;
+    }
+    if let Some(private) = profile.private_total_kib {
+        writeln!(
+            out,
+            "Private total: {} (memory no other process maps, each process at its peak)",
+            format_memory(private)
+        )?;
+    }
+
+    if let Some(phase) = &profile.build_phase {
+        write!(
+            out,
+            "Build phase: peak {}",
+            format_memory(phase.build_peak_kib)
+        )?;
+        match phase.build_end_seconds {
+            Some(end) => write!(
+                out,
+                " until {:.1}s  |  Run phase: peak {}",
+                end,
+                format_memory(phase.run_peak_kib)
+            )?,
+            None => write!(out, " (nothing but the build ran)")?,
+        }
+        if phase.build_excluded {
+            write!(out, " (build excluded from the total peak)")?;
+        }
+        writeln!(out)?;
+    }
+
+    if profile.sampling_truncated {
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        write!(
+            out,
+            "\n⚠ Sampling stopped after {} samples; peak may be underestimated",
+            profile.samples
+        )?;
+        if profile.detached {
+            write!(out, " (memwatch detached; the command is still running)")?;
+        }
+        if profile.killed_reason == Some(KillReason::SampleLimit) {
+            write!(out, " (memwatch stopped the command)")?;
+        }
+        out.reset()?;
+        writeln!(out)?;
+    }
+
+    if profile.interrupted {
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        let by = match profile.interrupt_signal.as_deref() {
+            Some("SIGTERM") => "SIGTERM",
+            _ => "Ctrl-C",
+        };
+        write!(
+            out,
+            "\n⚠ Interrupted by {}; the profile covers the run up to then",
+            by
+        )?;
+        if profile.detached && !profile.sampling_truncated {
+            write!(out, " (the command is still running)")?;
+        }
+        out.reset()?;
+        writeln!(out)?;
+    }
+
+    if profile.idle_stopped {
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        write!(
+            out,
+            "\n⚠ Memory stayed within {} for {}; {}",
+            format_memory(profile.idle_epsilon_kib.unwrap_or(0)),
+            format_duration(profile.idle_stop_seconds.unwrap_or(0.0)),
+            if profile.detached {
+                "memwatch detached and the command is still running"
+            } else {
+                "memwatch stopped the command"
+            }
+        )?;
+        out.reset()?;
+        writeln!(out)?;
+    }
+
+    // Stops at --max-samples and --idle-stop were asked for, and are noted above
+    if let Some(reason) = profile
+        .killed_reason
+        .filter(|&reason| !matches!(reason, KillReason::SampleLimit | KillReason::Idle))
+    {
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+        match reason {
+            KillReason::MemoryLimit => write!(
+                out,
+                "\n✖ KILLED: job exceeded {}",
+                describe_memory_limit(profile)
+            )?,
+            KillReason::Timeout => write!(
+                out,
+                "\n✖ TIMED OUT: job killed after the {} limit; results cover the run until then",
+                format_duration(profile.timeout_seconds.unwrap_or(0.0))
+            )?,
+            KillReason::SampleLimit | KillReason::Idle => {}
+        }
+        out.reset()?;
+        writeln!(out)?;
+    }
+
+    // An interrupted run already says why it ended
+    if let Some(signal) = profile
+        .terminated_by_signal_name
+        .as_deref()
+        .filter(|_| !profile.interrupted)
+    {
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+        write!(out, "\n✖ Terminated by {}", signal)?;
+        // The kernel's OOM killer is the usual sender
+        if signal == "SIGKILL" {
+            write!(out, " (possibly OOM)")?;
+        }
+        out.reset()?;
+        writeln!(out)?;
+    }
 
-    // Filter out processes with 0 RSS for display
-    let valid_processes: Vec<_> = profile
-        .processes
-        .iter()
-        .filter(|p| p.max_rss_kib > 0)
-        .collect();
+    // Filter out processes with 0 RSS for display (unless --include-zero)
+    let valid_processes = profile.sorted_processes(options.order);
 
-    if profile.max_total_rss_kib == 0 {
+    if profile.exited_during_delay {
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        writeln!(
+            out,
+            "\n⚠ The command exited before --delay ({}) elapsed, so nothing was sampled.",
+            format_duration(profile.delay_seconds.unwrap_or(0.0))
+        )?;
+        out.reset()?;
+        writeln!(out, "\nSuggestions:")?;
+        writeln!(
+            out,
+            "  • Use a shorter --delay, or drop it to profile the whole run"
+        )?;
+    } else if profile.max_total_rss_kib == 0 {
         // No data captured at all
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
-        println!(
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        writeln!(
+            out,
             "\nMax total RSS: {} (no data captured)",
             format_memory(profile.max_total_rss_kib)
-        );
-        let _ = stdout.reset();
+        )?;
+        out.reset()?;
 
-        println!("\n⚠ Warning: The command completed too quickly to capture memory usage.");
-        println!("\nPossible reasons:");
-        println!(
-            "  • Command executed in < {}ms (sampling interval)",
-            profile.interval_ms
-        );
-        println!("  • Process spawned child and immediately exited");
-        println!("  • Command failed or was killed immediately");
-        println!("\nSuggestions:");
-        println!("  • Use a shorter interval: memwatch run -i 50 -- <command>");
-        println!("  • Check if the command actually ran: echo $?");
-        println!("  • For instant commands (like 'echo'), memory profiling may not be useful");
+        writeln!(
+            out,
+            "\n⚠ Warning: The command completed too quickly to capture memory usage."
+        )?;
+        writeln!(out, "\nPossible reasons:")?;
+        writeln!(
+            out,
+            "  • Command executed in < {} (sampling interval)",
+            format_interval(profile)
+        )?;
+        writeln!(out, "  • Process spawned child and immediately exited")?;
+        writeln!(out, "  • Command failed or was killed immediately")?;
+        writeln!(out, "\nSuggestions:")?;
+        writeln!(
+            out,
+            "  • Use a shorter interval: memwatch run -i 50ms -- <command>"
+        )?;
+        writeln!(
+            out,
+            "  • Chase sub-millisecond peaks: memwatch run --interval-us 200 -- <command>"
+        )?;
+        writeln!(out, "  • Check if the command actually ran: echo $?")?;
+        writeln!(
+            out,
+            "  • For instant commands (like 'echo'), memory profiling may not be useful"
+        )?;
     } else if valid_processes.is_empty() && profile.filter.is_some() {
         // All processes were filtered out
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
-        println!("\n⚠ Warning: All processes were filtered out.");
-        let _ = stdout.reset();
-        println!(
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        writeln!(out, "\n⚠ Warning: All processes were filtered out.")?;
+        out.reset()?;
+        writeln!(
+            out,
             "\nTotal job memory was {}, but no processes match the filter criteria.",
             format_memory(profile.max_total_rss_kib)
-        );
+        )?;
 
         if let Some(ref filter) = profile.filter {
-            println!("\nActive filters:");
-            for line in filter.display_patterns() {
-                println!("  • {}", line);
+            writeln!(out, "\nActive filters:")?;
+            for line in describe_filter(filter) {
+                writeln!(out, "  • {}", line)?;
             }
         }
 
-        println!("\nSuggestions:");
-        println!("  • Check your filter patterns for typos");
-        println!("  • Use broader patterns (e.g., 'test' instead of '^test$')");
-        println!("  • Run without filters to see all processes: memwatch run --json -- <command>");
+        writeln!(out, "\nSuggestions:")?;
+        writeln!(out, "  • Check your filter patterns for typos")?;
+        writeln!(
+            out,
+            "  • Use broader patterns (e.g., 'test' instead of '^test$')"
+        )?;
+        writeln!(
+            out,
+            "  • Run without filters to see all processes: memwatch run --json -- <command>"
+        )?;
     } else if valid_processes.is_empty() {
         // No valid processes (no filter applied)
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
-        println!(
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        writeln!(
+            out,
             "\nMax total RSS: {} (no data captured)",
             format_memory(profile.max_total_rss_kib)
-        );
-        let _ = stdout.reset();
+        )?;
+        out.reset()?;
 
-        println!("\n⚠ Warning: The command completed too quickly to capture memory usage.");
-        println!("\nPossible reasons:");
-        println!(
-            "  • Command executed in < {}ms (sampling interval)",
-            profile.interval_ms
-        );
-        println!("  • Process spawned child and immediately exited");
-        println!("  • Command failed or was killed immediately");
-        println!("\nSuggestions:");
-        println!("  • Use a shorter interval: memwatch run -i 50 -- <command>");
-        println!("  • Check if the command actually ran: echo $?");
-        println!("  • For instant commands (like 'echo'), memory profiling may not be useful");
+        writeln!(
+            out,
+            "\n⚠ Warning: The command completed too quickly to capture memory usage."
+        )?;
+        writeln!(out, "\nPossible reasons:")?;
+        writeln!(
+            out,
+            "  • Command executed in < {} (sampling interval)",
+            format_interval(profile)
+        )?;
+        writeln!(out, "  • Process spawned child and immediately exited")?;
+        writeln!(out, "  • Command failed or was killed immediately")?;
+        writeln!(out, "\nSuggestions:")?;
+        writeln!(
+            out,
+            "  • Use a shorter interval: memwatch run -i 50ms -- <command>"
+        )?;
+        writeln!(
+            out,
+            "  • Chase sub-millisecond peaks: memwatch run --interval-us 200 -- <command>"
+        )?;
+        writeln!(out, "  • Check if the command actually ran: echo $?")?;
+        writeln!(
+            out,
+            "  • For instant commands (like 'echo'), memory profiling may not be useful"
+        )?;
     } else {
         // Memory summary section
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true));
-        print!("\nMEMORY SUMMARY");
-        let _ = stdout.reset();
-        println!();
-
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
-        print!(
-            "  Total peak:    {}",
-            format_memory(profile.max_total_rss_kib)
-        );
-        let _ = stdout.reset();
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))?;
+        write!(out, "\nMEMORY SUMMARY")?;
+        out.reset()?;
+        writeln!(out)?;
+
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+        match (
+            profile.max_total_above_baseline_kib,
+            profile.baseline_rss_kib,
+        ) {
+            (Some(above), Some(baseline)) => {
+                write!(
+                    out,
+                    "  Total peak:    {} above baseline",
+                    format_memory(above)
+                )?;
+                out.reset()?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+                write!(
+                    out,
+                    " (raw {}, baseline {})",
+                    format_memory(profile.max_total_rss_kib),
+                    format_memory(baseline)
+                )?;
+            }
+            _ => write!(
+                out,
+                "  Total peak:    {}",
+                format_memory(profile.max_total_rss_kib)
+            )?,
+        }
+        out.reset()?;
+
+        if let Some(baseline) = &profile.baseline_comparison {
+            write_change(
+                out,
+                profile.max_total_rss_kib > baseline.peak_kib,
+                &compare::format_memory_delta(profile.max_total_rss_kib, baseline.peak_kib),
+            )?;
+        }
 
         // Show filtering info if applicable
-        if profile.filter.is_some() {
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
-            print!(" (all processes)");
-            let _ = stdout.reset();
+        if profile.filter.as_ref().is_some_and(|f| f.hides_processes()) {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            write!(out, " (all processes)")?;
+            out.reset()?;
         }
-        println!();
+
+        // Name the peak definition behind the headline and give the other one.
+        // Profiles from before --peak-mode have no sum recorded
+        if profile.sum_of_peaks_kib > 0 {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            match profile.peak_mode {
+                PeakMode::Simultaneous => write!(
+                    out,
+                    " (simultaneous; sum of per-process peaks {})",
+                    format_memory(profile.sum_of_peaks_kib)
+                )?,
+                PeakMode::SumOfPeaks => write!(
+                    out,
+                    " (sum of per-process peaks; simultaneous {})",
+                    format_memory(profile.simultaneous_peak_kib)
+                )?,
+            }
+            out.reset()?;
+        }
+        writeln!(out)?;
 
         if let Some(max_process) = valid_processes.first() {
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
-            print!(
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+            write!(
+                out,
                 "  Process peak:  {} ",
                 format_memory(max_process.max_rss_kib)
-            );
-            let _ = stdout.reset();
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
-            print!("(pid {})", max_process.pid);
-            let _ = stdout.reset();
-            println!();
+            )?;
+            out.reset()?;
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            write!(out, "(pid {})", max_process.pid)?;
+            out.reset()?;
+            writeln!(out)?;
+
+            if let Some(ref numa) = max_process.numa_rss_kib {
+                let mut nodes: Vec<_> = numa.iter().collect();
+                nodes.sort();
+                let breakdown: Vec<String> = nodes
+                    .into_iter()
+                    .map(|(node, kib)| format!("N{} {}", node, format_memory(*kib)))
+                    .collect();
+                writeln!(out, "  NUMA nodes:    {}", breakdown.join("  "))?;
+            }
+        }
+
+        // What the peak means on this machine
+        if let Some(total) = profile.system_total_kib
+            && let Some(pct) = percent_of(profile.max_total_rss_kib, total)
+        {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            write!(
+                out,
+                "  Peak {} of {} system RAM ({:.0}%)",
+                format_memory(profile.max_total_rss_kib),
+                format_memory(total),
+                pct
+            )?;
+            if let Some(available) = profile.system_available_kib {
+                write!(
+                    out,
+                    "; {} was available at the start",
+                    format_memory(available)
+                )?;
+            }
+            out.reset()?;
+            writeln!(out)?;
         }
 
         // Per-process peaks table
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true));
-        print!("\nPER-PROCESS PEAKS");
-        let _ = stdout.reset();
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))?;
+        write!(out, "\nPER-PROCESS PEAKS")?;
+        out.reset()?;
 
         // Show filter annotation in header if applicable
         if let (Some(filtered_count), Some(filtered_rss)) = (
             profile.filtered_process_count,
             profile.filtered_total_rss_kib,
         ) {
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
-            print!(
-                " ({} processes filtered out, totaling {})",
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            let wrappers = match profile.hidden_wrapper_count {
+                0 => String::new(),
+                1 => ", including 1 wrapper".to_string(),
+                n => format!(", including {} wrappers", n),
+            };
+            write!(
+                out,
+                " ({} processes filtered out{}, totaling {})",
                 filtered_count,
+                wrappers,
                 format_memory(filtered_rss)
-            );
-            let _ = stdout.reset();
+            )?;
+            out.reset()?;
         }
-        println!();
+        writeln!(out)?;
+
+        let shown = valid_processes.len().min(limit);
+        let (shown, hidden) = valid_processes.split_at(shown);
+        let hwm_corrected = shown.iter().any(|p| p.sampled_max_rss_kib.is_some());
+        let show_io = shown.iter().any(|p| p.io_read_bytes.is_some());
+        let show_shared = shown.iter().any(|p| p.private_kib.is_some());
+        let show_growth = options.order.key == ProcessSort::Growth;
+        // Which process dominates only needs telling when there is more than one
+        let show_pct = valid_processes.len() > 1;
+        // Columns ahead of COMMAND: PID, MEMORY, TIME, and LIFETIME, then the optional ones
+        let used = table_columns(&[PID_WIDTH, VALUE_WIDTH, TIME_WIDTH, TIME_WIDTH])
+            + (VALUE_WIDTH + COLUMN_GAP)
+                * (show_pct as usize
+                    + show_growth as usize
+                    + 2 * (show_io as usize + show_shared as usize));
 
         // Table header
-        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
-        println!(
-            "  {:>5}  {:>10}  {:>8}  {}",
-            "PID", "MEMORY", "TIME", "COMMAND"
-        );
-        let _ = stdout.reset();
+        out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+        write!(out, "  {:>PID_WIDTH$}  {:>VALUE_WIDTH$}  ", "PID", "MEMORY")?;
+        if show_pct {
+            write!(out, "{:>VALUE_WIDTH$}  ", "% JOB PEAK")?;
+        }
+        write!(
+            out,
+            "{:>TIME_WIDTH$}  {:>TIME_WIDTH$}  ",
+            "TIME", "LIFETIME"
+        )?;
+        if show_growth {
+            write!(out, "{:>VALUE_WIDTH$}  ", "GROWTH")?;
+        }
+        if show_io {
+            write!(out, "{:>VALUE_WIDTH$}  {:>VALUE_WIDTH$}  ", "READ", "WRITE")?;
+        }
+        if show_shared {
+            write!(
+                out,
+                "{:>VALUE_WIDTH$}  {:>VALUE_WIDTH$}  ",
+                "PRIVATE", "SHARED"
+            )?;
+        }
+        writeln!(out, "COMMAND")?;
+        out.reset()?;
 
         // Table rows
-        for proc in valid_processes {
+        for proc in shown {
             let elapsed_secs =
                 (proc.peak_time - profile.start_time).num_milliseconds() as f64 / 1000.0;
 
             // PID (dimmed)
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
-            print!("  {:>5}  ", proc.pid);
-            let _ = stdout.reset();
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            write!(out, "  {:>PID_WIDTH$}  ", proc.pid)?;
+            out.reset()?;
+
+            // Memory (green), starred when raised to the kernel's high-water mark
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+            match proc.sampled_max_rss_kib {
+                Some(_) => write!(
+                    out,
+                    "{:>width$}* ",
+                    format_memory(proc.max_rss_kib),
+                    width = VALUE_WIDTH - 1
+                )?,
+                None => write!(out, "{:>VALUE_WIDTH$}  ", format_memory(proc.max_rss_kib))?,
+            }
+            out.reset()?;
 
-            // Memory (green)
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
-            print!("{:>10}  ", format_memory(proc.max_rss_kib));
-            let _ = stdout.reset();
+            // Share of the job peak (default), approximate since peaks need not coincide
+            if show_pct {
+                match percent_of(proc.max_rss_kib, profile.max_total_rss_kib) {
+                    Some(pct) => write!(out, "{:>width$.1}%  ", pct, width = VALUE_WIDTH - 1)?,
+                    None => write!(out, "{:>VALUE_WIDTH$}  ", "")?,
+                }
+            }
 
             // Time (yellow)
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
-            print!("@ {:5.1}s  ", elapsed_secs);
-            let _ = stdout.reset();
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+            write!(
+                out,
+                "@ {:width$.1}s  ",
+                elapsed_secs,
+                width = TIME_WIDTH - 3
+            )?;
+            out.reset()?;
+
+            // Lifetime (dimmed): kernel start time when known, else what we observed
+            let lifetime = proc.age_seconds.unwrap_or(proc.lifetime_seconds);
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            write!(out, "{:>width$.1}s  ", lifetime, width = TIME_WIDTH - 1)?;
+            out.reset()?;
 
-            // Command (default)
-            println!("{}", proc.command);
+            // Growth since first seen (magenta), blank for profiles that predate it
+            if show_growth {
+                let growth = proc
+                    .rss_growth_kib
+                    .map(|kib| format!("+{}", format_memory(kib)))
+                    .unwrap_or_default();
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
+                write!(out, "{:>VALUE_WIDTH$}  ", growth)?;
+                out.reset()?;
+            }
+
+            // I/O (cyan), blank where the counters were unreadable
+            if show_io {
+                let column = |bytes: Option<u64>| bytes.map(format_bytes).unwrap_or_default();
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+                write!(
+                    out,
+                    "{:>VALUE_WIDTH$}  {:>VALUE_WIDTH$}  ",
+                    column(proc.io_read_bytes),
+                    column(proc.io_write_bytes)
+                )?;
+                out.reset()?;
+            }
+
+            // Private/shared split (dimmed), blank where smaps_rollup was unreadable
+            if show_shared {
+                let column = |kib: Option<u64>| kib.map(format_memory).unwrap_or_default();
+                out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+                write!(
+                    out,
+                    "{:>VALUE_WIDTH$}  {:>VALUE_WIDTH$}  ",
+                    column(proc.private_kib),
+                    column(proc.shared_kib)
+                )?;
+                out.reset()?;
+            }
+
+            // Command (default), prefixed with its source for merged profiles
+            let command = display_command(proc, options.resolve_exe);
+            let command = match proc.source {
+                Some(ref source) => Cow::Owned(format!("[{}] {}", source, command)),
+                None => command,
+            };
+            writeln!(out, "{}", fit_command(&command, used, &options))?;
         }
 
-        // Process groups table
-        let groups = compute_process_groups(&profile.processes);
+        write_hidden_rows(
+            out,
+            hidden.len(),
+            "processes",
+            hidden.iter().map(|p| p.max_rss_kib).sum(),
+        )?;
+
+        let min_rss = profile.filter.as_ref().and_then(|f| f.min_rss_kib);
+        if let (Some(min), 1..) = (min_rss, profile.below_min_rss_count) {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            writeln!(
+                out,
+                "  {} {} below {} (total {}) omitted",
+                profile.below_min_rss_count,
+                if profile.below_min_rss_count == 1 {
+                    "process"
+                } else {
+                    "processes"
+                },
+                format_memory(min),
+                format_memory(profile.below_min_rss_total_kib)
+            )?;
+            out.reset()?;
+        }
+
+        let min_lifetime = profile.filter.as_ref().and_then(|f| f.min_lifetime_seconds);
+        if let (Some(min), 1..) = (min_lifetime, profile.short_lived_count) {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            writeln!(
+                out,
+                "  {} {} shorter than {}s (total {}) omitted",
+                profile.short_lived_count,
+                if profile.short_lived_count == 1 {
+                    "process"
+                } else {
+                    "processes"
+                },
+                min,
+                format_memory(profile.short_lived_total_kib)
+            )?;
+            out.reset()?;
+        }
+
+        if hwm_corrected {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            write!(
+                out,
+                "  * kernel high-water mark (VmHWM); the peak fell between samples, TIME is the highest sample"
+            )?;
+            out.reset()?;
+            writeln!(out)?;
+        }
+
+        // Breakdown of the total at the moment it peaked
+        if let Some(ref peak) = profile.peak_snapshot {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))?;
+            write!(out, "\nAT PEAK")?;
+            out.reset()?;
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            write!(
+                out,
+                " (t={:.1}s, total {})",
+                peak.elapsed_seconds,
+                format_memory(peak.total_rss_kib)
+            )?;
+            out.reset()?;
+            writeln!(out)?;
+
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            writeln!(
+                out,
+                "  {:>PID_WIDTH$}  {:>VALUE_WIDTH$}  {:>SHARE_WIDTH$}  COMMAND",
+                "PID", "MEMORY", "SHARE"
+            )?;
+            out.reset()?;
+
+            let hidden = &peak.processes[peak.processes.len().min(limit)..];
+            for proc in peak.processes.iter().take(limit) {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+                write!(out, "  {:>PID_WIDTH$}  ", proc.pid)?;
+                out.reset()?;
+
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+                write!(out, "{:>VALUE_WIDTH$}  ", format_memory(proc.rss_kib))?;
+                out.reset()?;
+
+                let share = proc.rss_kib as f64 * 100.0 / peak.total_rss_kib as f64;
+                let used = table_columns(&[PID_WIDTH, VALUE_WIDTH, SHARE_WIDTH]);
+                writeln!(
+                    out,
+                    "{:>width$.1}%  {}",
+                    share,
+                    fit_command(&proc.command, used, &options),
+                    width = SHARE_WIDTH - 1
+                )?;
+            }
+            write_hidden_rows(
+                out,
+                hidden.len(),
+                "processes",
+                hidden.iter().map(|p| p.rss_kib).sum(),
+            )?;
+        }
+
+        // Per-source table for merged profiles
+        if let Some(ref sources) = profile.sources {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))?;
+            write!(out, "\nPER-SOURCE PEAKS")?;
+            out.reset()?;
+            writeln!(out)?;
+
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            writeln!(
+                out,
+                "  {:24}  {:>12}  {:>8}  {:>9}  {:>4}",
+                "SOURCE", "TOTAL PEAK", "DURATION", "PROCESSES", "EXIT"
+            )?;
+            out.reset()?;
+
+            let worst = sources.iter().map(|s| s.max_total_rss_kib).max();
+            for source in sources {
+                write!(out, "  {:24}  ", source.label)?;
+
+                // Highlight the source with the largest peak
+                let color = if Some(source.max_total_rss_kib) == worst {
+                    Color::Red
+                } else {
+                    Color::Green
+                };
+                out.set_color(ColorSpec::new().set_fg(Some(color)))?;
+                write!(out, "{:>12}  ", format_memory(source.max_total_rss_kib))?;
+                out.reset()?;
+
+                write!(out, "{:>8}  ", format_duration(source.duration_seconds))?;
+
+                out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+                write!(out, "{:>9}  ", source.process_count)?;
+                out.reset()?;
+
+                match source.exit_code {
+                    Some(code) => writeln!(out, "{:>4}", code)?,
+                    None => writeln!(out, "{:>4}", "-")?,
+                }
+            }
+        }
+
+        // Process groups table (already sorted by total RSS, descending)
+        let groups = &profile.process_groups;
         if groups.len() > 1 {
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true));
-            print!("\nPROCESS GROUPS");
-            let _ = stdout.reset();
-            println!();
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))?;
+            write!(out, "\nPROCESS GROUPS")?;
+            out.reset()?;
+            if !profile.group_by.is_basename() {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+                write!(out, " (by {})", profile.group_by)?;
+                out.reset()?;
+            }
+            writeln!(out)?;
 
             // Table header
-            let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
-            println!(
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            writeln!(
+                out,
                 "  {:24}  {:>9}  {:>12}",
                 "COMMAND", "PROCESSES", "TOTAL PEAK"
-            );
-            let _ = stdout.reset();
-
-            // Sort by total RSS (descending)
-            let mut group_vec: Vec<_> = groups.into_iter().collect();
-            group_vec.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+            )?;
+            out.reset()?;
 
             // Table rows
-            for (cmd_name, (count, total_rss)) in group_vec {
-                print!("  {:24}  ", cmd_name);
-
-                let _ =
-                    stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
-                print!("{:>9}  ", count);
-                let _ = stdout.reset();
-
-                let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
-                print!("{:>12}", format_memory(total_rss));
-                let _ = stdout.reset();
-                println!();
+            for group in groups.iter().take(limit) {
+                write!(out, "  {:24}  ", group.command_name)?;
+
+                out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+                write!(out, "{:>9}  ", group.count)?;
+                out.reset()?;
+
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+                write!(out, "{:>12}", format_memory(group.total_peak_rss_kib))?;
+                out.reset()?;
+                if let Some(baseline) = &profile.baseline_comparison {
+                    match baseline.group_peaks_kib.get(&group.command_name) {
+                        Some(&before) => write_change(
+                            out,
+                            group.total_peak_rss_kib > before,
+                            &compare::format_memory_delta(group.total_peak_rss_kib, before),
+                        )?,
+                        None => write_change(out, true, "new")?,
+                    }
+                }
+                writeln!(out)?;
             }
+
+            let hidden = &groups[groups.len().min(limit)..];
+            write_hidden_rows(
+                out,
+                hidden.len(),
+                "groups",
+                hidden.iter().map(|g| g.total_peak_rss_kib).sum(),
+            )?;
         }
     }
-    println!();
+
+    if profile.escaped_process_count > 0 {
+        let count = profile.escaped_process_count;
+        let rss: u64 = profile.escaped_processes.iter().map(|p| p.rss_kib).sum();
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        write!(
+            out,
+            "\n⚠ {} process{} possibly escaped tracking (reparented to init; {} when last seen)",
+            count,
+            if count == 1 { "" } else { "es" },
+            format_memory(rss)
+        )?;
+        out.reset()?;
+        writeln!(out)?;
+        out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+        write!(
+            out,
+            "  Totals may undercount daemonized work; try --drain or --track-by pgid"
+        )?;
+        out.reset()?;
+        writeln!(out)?;
+    }
+
+    if !profile.sampling_errors.is_empty() {
+        let count = profile.sampling_errors.len();
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        write!(
+            out,
+            "\n⚠ {} sampling error{} occurred; the profile may have gaps",
+            count,
+            if count == 1 { "" } else { "s" }
+        )?;
+        out.reset()?;
+        writeln!(out)?;
+        out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+        write!(
+            out,
+            "  Last: {}",
+            profile.sampling_errors[count - 1].message
+        )?;
+        out.reset()?;
+        writeln!(out)?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// The memory limit the job ran under, as given: `--max-memory 4GiB`, or
+/// `--max-rss-pct 75 (12.0 GiB of 16.0 GiB)`
+pub(crate) fn describe_memory_limit(profile: &JobProfile) -> String {
+    let limit = format_memory(profile.memory_limit_kib.unwrap_or(0));
+    match (profile.memory_limit_pct, profile.system_total_kib) {
+        (Some(pct), Some(total)) => format!(
+            "--max-rss-pct {} ({} of {})",
+            pct,
+            limit,
+            format_memory(total)
+        ),
+        _ => format!("--max-memory {}", limit),
+    }
+}
+
+/// Filter settings as human-readable lines for display
+pub(crate) fn describe_filter(filter: &FilterConfig) -> Vec<String> {
+    let mut lines = Vec::new();
+    for exclude in &filter.exclude_patterns {
+        lines.push(format!("Exclude pattern: '{}'", exclude));
+    }
+    for include in &filter.include_patterns {
+        lines.push(format!("Include pattern: '{}'", include));
+    }
+    if filter.hide_wrappers {
+        lines.push("Wrapper processes hidden (--hide-wrappers)".to_string());
+    }
+    if let Some(min) = filter.min_rss_kib {
+        lines.push(format!("Minimum peak: {}", format_memory(min)));
+    }
+    if let Some(min) = filter.min_lifetime_seconds {
+        lines.push(format!("Minimum lifetime: {}s", min));
+    }
+    for user in &filter.users {
+        lines.push(format!("User: '{}'", user));
+    }
+    for user in &filter.exclude_users {
+        lines.push(format!("Exclude user: '{}'", user));
+    }
+    for pid in &filter.pids {
+        lines.push(format!("PID subtree: {}", pid));
+    }
+    for pid in &filter.exclude_pids {
+        lines.push(format!("Exclude PID subtree: {}", pid));
+    }
+    if let Some(depth) = filter.max_depth {
+        lines.push(format!("Depth: {}", depth));
+    }
+    lines
+}
+
+/// What `--depth N` kept, for the summary header
+fn describe_depth(depth: usize) -> String {
+    match depth {
+        0 => "command only (--depth 0); its children were not counted".to_string(),
+        1 => "command and its direct children (--depth 1); deeper processes were not counted"
+            .to_string(),
+        n => format!(
+            "command and {} levels of descendants (--depth {}); deeper processes were not counted",
+            n, n
+        ),
+    }
+}
+
+/// Trailer for a table cut short by `--top`, so the hidden rows still count
+fn write_hidden_rows(
+    out: &mut impl WriteColor,
+    count: usize,
+    noun: &str,
+    total_kib: u64,
+) -> std::io::Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+    out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+    write!(
+        out,
+        "  … and {} more {} ({} combined)",
+        count,
+        noun,
+        format_memory(total_kib)
+    )?;
+    out.reset()?;
+    writeln!(out)
+}
+
+/// Write the summary to a file, without colors and, as it is not a terminal, in ASCII
+pub fn export_summary(profile: &JobProfile, path: &str, options: SummaryOptions) -> Result<()> {
+    let file = File::create(path).context(MemwatchError::export_failed(
+        "Failed to create summary file",
+        path,
+    ))?;
+    let mut out = Glyphs::new(NoColor::new(BufWriter::new(file)), config::ascii_for(false));
+    write_summary(&mut out, profile, options)
+        .and_then(|_| out.flush())
+        .context(MemwatchError::export_failed(
+            "Failed to write summary file",
+            path,
+        ))
+}
+
+/// Color setting for stdout, from `--color` and the environment
+pub fn color_choice() -> ColorChoice {
+    config::color_choice_for(std::io::stdout().is_terminal())
+}
+
+/// Color setting for stderr, from `--color` and the environment
+pub fn stderr_color_choice() -> ColorChoice {
+    config::color_choice_for(std::io::stderr().is_terminal())
+}
+
+/// Print an `--alert` crossing on stderr as it happens
+pub fn print_alert(alert: &MemoryAlert) {
+    let mut stderr = stderr_stream();
+    let _ = stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)).set_bold(true));
+    let _ = write!(
+        stderr,
+        "⚠ crossed {} at t={:.1}s (total {})",
+        format_memory(alert.level_kib),
+        alert.elapsed_seconds,
+        format_memory(alert.total_rss_kib)
+    );
+    let _ = stderr.reset();
+    let _ = writeln!(stderr);
+}
+
+/// Print on stderr that SIGUSR2 paused sampling, or resumed it after `resumed_after`
+/// seconds
+pub fn print_pause(elapsed_seconds: f64, resumed_after: Option<f64>) {
+    let mut stderr = stderr_stream();
+    let _ = stderr.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)));
+    let _ = match resumed_after {
+        None => write!(
+            stderr,
+            "Sampling paused at t={:.1}s; send SIGUSR2 again to resume",
+            elapsed_seconds
+        ),
+        Some(paused) => write!(
+            stderr,
+            "Sampling resumed at t={:.1}s after {:.1}s paused",
+            elapsed_seconds, paused
+        ),
+    };
+    let _ = stderr.reset();
+    let _ = writeln!(stderr);
+}
+
+/// Print the intermediate summary SIGUSR1 asks for on stderr
+pub fn print_partial_summary(
+    elapsed_seconds: f64,
+    current_kib: u64,
+    peak_kib: u64,
+    processes: &[ProcessSample],
+) {
+    let mut stderr = stderr_stream();
+    let _ = write_partial_summary(
+        &mut stderr,
+        elapsed_seconds,
+        current_kib,
+        peak_kib,
+        processes,
+    );
 }
 
-/// Compute process groups by command name
-fn compute_process_groups(processes: &[ProcessStats]) -> HashMap<String, (usize, u64)> {
-    let mut groups: HashMap<String, (usize, u64)> = HashMap::new();
+/// Render an intermediate summary: the current total and peak so far, then the
+/// largest of `processes` (the job's current sample)
+pub fn write_partial_summary(
+    out: &mut impl WriteColor,
+    elapsed_seconds: f64,
+    current_kib: u64,
+    peak_kib: u64,
+    processes: &[ProcessSample],
+) -> std::io::Result<()> {
+    out.set_color(ColorSpec::new().set_bold(true))?;
+    write!(out, "memwatch {}", format_duration(elapsed_seconds))?;
+    out.reset()?;
+    writeln!(
+        out,
+        "  current {}  peak {}  ({} processes)",
+        format_memory(current_kib),
+        format_memory(peak_kib),
+        processes.len()
+    )?;
 
-    for proc in processes.iter().filter(|p| p.max_rss_kib > 0) {
-        let cmd_name = extract_command_name(&proc.command);
-        groups
-            .entry(cmd_name)
-            .and_modify(|(count, total)| {
-                *count += 1;
-                *total += proc.max_rss_kib;
-            })
-            .or_insert((1, proc.max_rss_kib));
+    let mut largest: Vec<&ProcessSample> = processes.iter().collect();
+    largest.sort_by(|a, b| b.rss_kib.cmp(&a.rss_kib).then(a.pid.cmp(&b.pid)));
+    for process in largest.iter().take(PARTIAL_SUMMARY_TOP) {
+        writeln!(
+            out,
+            "  {:>7}  {:>10}  {}",
+            process.pid,
+            format_memory(process.rss_kib),
+            process.command
+        )?;
     }
+    Ok(())
+}
+
+/// Single-line summary for `--oneline`. The format is part of the output schema
+/// (`SCHEMA_VERSION`): fields keep their names and order, and new ones are only
+/// ever appended before `command`, which always comes last.
+pub fn format_oneline(profile: &JobProfile) -> String {
+    let exit = profile
+        .exit_code
+        .map(|code| code.to_string())
+        .unwrap_or_else(|| "none".to_string());
+    // Only present for labeled runs, so unlabeled lines are unchanged
+    let label = profile
+        .label
+        .as_deref()
+        .map(|label| format!(" label=\"{}\"", escape_oneline(label)))
+        .unwrap_or_default();
+
+    format!(
+        "memwatch: peak={} duration={:.3} samples={} exit={}{} command=\"{}\"",
+        profile.max_total_rss_kib,
+        profile.duration_seconds,
+        profile.samples,
+        exit,
+        label,
+        escape_oneline(&profile.command.join(" "))
+    )
+}
+
+/// Escape a quoted `--oneline` value: backslashes, quotes and control characters
+/// (which would otherwise break the line) are written as escape sequences
+fn escape_oneline(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:04x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write the profile as JSON to `path` (`--json-out`), pretty-printed unless `compact`.
+/// Like the CSV exports the file is written in place, so `/dev/stdout`, FIFOs and
+/// symlinks work
+pub fn export_json(profile: &JobProfile, path: &str, compact: bool) -> Result<()> {
+    let file = File::create(path).context(MemwatchError::export_failed(
+        "Failed to create JSON file",
+        path,
+    ))?;
+    let mut out = BufWriter::new(file);
+    write_profile_json(profile, compact, &mut out)?;
+    out.flush().context(MemwatchError::export_failed(
+        "Failed to write JSON file",
+        path,
+    ))
+}
 
-    groups
+/// Write the profile as JSON to `out`, pretty-printed unless `compact`
+pub fn write_profile_json<W: Write>(
+    profile: &JobProfile,
+    compact: bool,
+    out: &mut W,
+) -> Result<()> {
+    if compact {
+        serde_json::to_writer(&mut *out, profile)?;
+    } else {
+        serde_json::to_writer_pretty(&mut *out, profile)?;
+    }
+    writeln!(out)?;
+    Ok(())
 }
 
 /// Print JSON output with optional colorization
 ///
-/// Automatically detects if output is to a terminal and colorizes accordingly.
-/// Colors are disabled when piping to files or when stdout is not a TTY.
-pub fn print_json(profile: &JobProfile) -> Result<()> {
+/// Colorized under the same rules as the summary (`--color`), so by default only
+/// when stdout is a terminal. `compact` prints a single uncolored line for machines.
+pub fn print_json<T: Serialize + ?Sized>(value: &T, compact: bool) -> Result<()> {
+    if compact {
+        println!("{}", serde_json::to_string(value)?);
+        return Ok(());
+    }
+
     // First, serialize to pretty JSON
-    let json_value = serde_json::to_value(profile)?;
+    let json_value = serde_json::to_value(value)?;
     let json_string = serde_json::to_string_pretty(&json_value)?;
 
-    // Check if stdout is a terminal (TTY)
-    // If not (e.g., piping to file), disable colors
-    if std::io::stdout().is_terminal() {
+    // Colors follow --color: by default only on a terminal, so pipes and files get
+    // plain JSON
+    if color_choice() != ColorChoice::Never {
         // Colorize the JSON output
-        match json_string.to_colored_json_auto() {
+        match json_string.to_colored_json(ColorMode::On) {
             Ok(colored) => println!("{}", colored),
             Err(_) => {
                 // Fallback to plain JSON if colorization fails
@@ -300,6 +1599,13 @@ pub fn print_json(profile: &JobProfile) -> Result<()> {
 mod tests {
     use super::*;
 
+    /// The uncolored summary of `profile` with default options
+    fn render_summary(profile: &JobProfile) -> String {
+        let mut out = NoColor::new(Vec::new());
+        write_summary(&mut out, profile, SummaryOptions::default()).unwrap();
+        String::from_utf8(out.into_inner()).unwrap()
+    }
+
     #[test]
     fn test_format_memory() {
         assert_eq!(format_memory(512), "512 KiB");
@@ -310,6 +1616,400 @@ mod tests {
         assert_eq!(format_memory(1536 * 1024), "1.5 GiB");
     }
 
+    #[test]
+    fn test_describe_memory_limit() {
+        let mut profile = JobProfile {
+            memory_limit_kib: Some(4 * 1024 * 1024),
+            ..Default::default()
+        };
+        assert_eq!(describe_memory_limit(&profile), "--max-memory 4.0 GiB");
+
+        profile.memory_limit_pct = Some(25.0);
+        profile.system_total_kib = Some(16 * 1024 * 1024);
+        assert_eq!(
+            describe_memory_limit(&profile),
+            "--max-rss-pct 25 (4.0 GiB of 16.0 GiB)"
+        );
+    }
+
+    #[test]
+    fn test_format_memory_in_units() {
+        assert_eq!(format_memory_in(1536 * 1024, Units::Binary), "1.5 GiB");
+
+        // 999.4998 MB stays in MB; 999.9995 MB would round to 1000.0 MB
+        assert_eq!(format_memory_in(976_074, Units::Si), "999.5 MB");
+        assert_eq!(format_memory_in(976_562, Units::Si), "1.0 GB");
+        assert_eq!(format_memory_in(976, Units::Si), "999 kB");
+        assert_eq!(format_memory_in(977, Units::Si), "1.0 MB");
+        assert_eq!(format_memory_in(0, Units::Si), "0 kB");
+        assert_eq!(format_memory_in(2 * 1024 * 1024, Units::Si), "2.1 GB");
+
+        assert_eq!(format_memory_in(1536 * 1024, Units::Kib), "1572864 KiB");
+        assert_eq!(format_memory_in(512, Units::Mib), "0.50 MiB");
+        assert_eq!(format_memory_in(3 * 1024 * 1024, Units::Mib), "3072.00 MiB");
+        assert_eq!(format_memory_in(1024, Units::Gib), "0.001 GiB");
+        assert_eq!(format_memory_in(1536, Units::Bytes), "1572864 B");
+        assert_eq!(format_memory_in(0, Units::Bytes), "0 B");
+    }
+
+    #[test]
+    fn test_format_bytes_in_unit_bases() {
+        assert_eq!(format_bytes_in(1_000_000, Units::Si), "1.0 MB");
+        assert_eq!(format_bytes_in(1_000_000, Units::Binary), "976 KiB");
+        assert_eq!(format_bytes_in(1_048_576, Units::Si), "1.0 MB");
+        assert_eq!(format_bytes_in(1_048_576, Units::Binary), "1.0 MiB");
+        assert_eq!(format_bytes_in(2_500_000_000, Units::Si), "2.5 GB");
+        assert_eq!(format_bytes_in(999, Units::Si), "999 B");
+        assert_eq!(format_bytes_in(1_000_000, Units::Bytes), "1000000 B");
+    }
+
+    #[test]
+    fn test_write_summary_snapshot() {
+        use crate::types::{GroupBy, ProcessStats, compute_process_groups};
+        use chrono::{TimeZone, Utc};
+
+        let start = Utc.with_ymd_and_hms(2025, 11, 20, 18, 0, 0).unwrap();
+        let process = |pid: i32, command: &str, max_rss_kib: u64, peak_secs: i64| ProcessStats {
+            pid,
+            ppid: 1,
+            command: command.to_string(),
+            max_rss_kib,
+            first_seen: start,
+            last_seen: start + chrono::Duration::seconds(10),
+            peak_time: start + chrono::Duration::seconds(peak_secs),
+            lifetime_seconds: 10.0,
+            ..Default::default()
+        };
+        let processes = vec![
+            process(101, "cc1plus big.cpp", 1024 * 1024, 8),
+            process(102, "cc1plus small.cpp", 512 * 1024, 3),
+            process(100, "make -j2", 4096, 0),
+        ];
+        let profile = JobProfile {
+            command: vec!["make".to_string(), "-j2".to_string()],
+            start_time: start,
+            duration_seconds: 12.0,
+            interval_ms: 500,
+            max_total_rss_kib: 1200 * 1024,
+            simultaneous_peak_kib: 1200 * 1024,
+            sum_of_peaks_kib: 1536 * 1024 + 4096,
+            samples: 24,
+            process_groups: compute_process_groups(&processes, false, &GroupBy::Basename),
+            processes,
+            exit_code: Some(0),
+            ..Default::default()
+        };
+
+        let mut out = NoColor::new(Vec::new());
+        let options = SummaryOptions {
+            top: Some(2),
+            ..Default::default()
+        };
+        write_summary(&mut out, &profile, options).unwrap();
+        let summary = String::from_utf8(out.into_inner()).unwrap();
+        assert_eq!(
+            summary,
+            "
+Job: make -j2
+Duration: 00:00:12  |  Samples: 24
+
+MEMORY SUMMARY
+  Total peak:    1.2 GiB (simultaneous; sum of per-process peaks 1.5 GiB)
+  Process peak:  1.0 GiB (pid 101)
+
+PER-PROCESS PEAKS
+    PID      MEMORY  % JOB PEAK      TIME  LIFETIME  COMMAND
+    101     1.0 GiB       85.3%  @   8.0s     10.0s  cc1plus big.cpp
+    102   512.0 MiB       42.7%  @   3.0s     10.0s  cc1plus small.cpp
+  … and 1 more processes (4.0 MiB combined)
+
+PROCESS GROUPS
+  COMMAND                   PROCESSES    TOTAL PEAK
+  cc1plus                           2       1.5 GiB
+  make                              1       4.0 MiB
+
+"
+        );
+    }
+
+    #[test]
+    fn test_write_snapshot() {
+        use crate::types::{FilterConfig, SystemSnapshot};
+
+        let process = |pid: i32, command: &str, max_rss_kib: u64| ProcessStats {
+            pid,
+            ppid: 1,
+            command: command.to_string(),
+            max_rss_kib,
+            ..Default::default()
+        };
+        let profile = JobProfile {
+            processes: vec![
+                process(300, "postgres: checkpointer", 8 * 1024),
+                process(200, "postgres -D /var/lib/postgres", 64 * 1024),
+                process(400, "postgres: walwriter", 4 * 1024),
+            ],
+            filter: Some(FilterConfig {
+                include_patterns: vec!["postgres".to_string()],
+                min_rss_kib: Some(1024),
+                ..Default::default()
+            }),
+            filtered_process_count: Some(120),
+            filtered_total_rss_kib: Some(2048 * 1024),
+            below_min_rss_count: 5,
+            below_min_rss_total_kib: 2048,
+            ..Default::default()
+        };
+
+        let mut out = NoColor::new(Vec::new());
+        write_snapshot(&mut out, &profile, 128, Some(2)).unwrap();
+        let table = String::from_utf8(out.into_inner()).unwrap();
+        assert!(table.starts_with(
+            "\n3 of 128 processes, 76.0 MiB\n  Include pattern: 'postgres'\n  Minimum peak: 1.0 MiB\n"
+        ));
+        let rows: Vec<&str> = table
+            .lines()
+            .filter(|l| l.contains("MiB  postgres"))
+            .collect();
+        assert!(rows[0].ends_with("64.0 MiB  postgres -D /var/lib/postgres"));
+        assert!(rows[1].ends_with("8.0 MiB  postgres: checkpointer"));
+        assert!(table.contains("  … and 1 more processes (4.0 MiB combined)\n"));
+        assert!(table.ends_with("\n120 filtered out (2.0 GiB)\n5 below --min-rss (2.0 MiB)\n"));
+
+        let json = serde_json::to_value(SystemSnapshot::from_profile(&profile, 128)).unwrap();
+        assert_eq!(json["process_count"], 128);
+        assert_eq!(json["total_rss_kib"], 76 * 1024);
+        // --top only shortens the table
+        assert_eq!(json["processes"].as_array().unwrap().len(), 3);
+        assert_eq!(json["processes"][0]["pid"], 200);
+        assert_eq!(json["processes"][0]["exe_path"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!(
+            to_ascii("\n⚠ Interrupted by SIGINT\n  • Use a shorter --delay"),
+            "\n! Interrupted by SIGINT\n  - Use a shorter --delay"
+        );
+        assert_eq!(
+            to_ascii("✖ KILLED … 3.0 → 4.0x"),
+            "x KILLED ... 3.0 -> 4.0x"
+        );
+        // Nothing to replace borrows, and other non-ASCII text is left alone
+        assert!(matches!(
+            to_ascii("250µs  ünïcödé"),
+            Cow::Borrowed("250µs  ünïcödé")
+        ));
+    }
+
+    #[test]
+    fn test_glyphs_writer() {
+        let summary = |ascii: bool| {
+            let mut out = Glyphs::new(NoColor::new(Vec::new()), ascii);
+            let profile = JobProfile {
+                interrupted: true,
+                interrupt_signal: Some("SIGINT".to_string()),
+                ..Default::default()
+            };
+            write_summary(&mut out, &profile, SummaryOptions::default()).unwrap();
+            String::from_utf8(out.into_inner().into_inner()).unwrap()
+        };
+        assert!(summary(false).contains("\n⚠ Interrupted by Ctrl-C"));
+        assert!(summary(true).contains("\n! Interrupted by Ctrl-C"));
+        assert!(!summary(true).contains('⚠'));
+    }
+
+    #[test]
+    fn test_write_partial_summary() {
+        let sample = |pid: i32, rss_kib: u64| {
+            ProcessSample::for_test(pid, 1, rss_kib, &format!("worker{}", pid))
+        };
+        let processes: Vec<ProcessSample> =
+            (1..=7).map(|pid| sample(pid, pid as u64 * 1024)).collect();
+
+        let mut out = NoColor::new(Vec::new());
+        write_partial_summary(&mut out, 83.0, 28 * 1024, 40 * 1024, &processes).unwrap();
+        let summary = String::from_utf8(out.into_inner()).unwrap();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(
+            lines[0],
+            "memwatch 00:01:23  current 28.0 MiB  peak 40.0 MiB  (7 processes)"
+        );
+        // The five largest, largest first
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].ends_with("7.0 MiB  worker7"));
+        assert!(lines[5].ends_with("3.0 MiB  worker3"));
+    }
+
+    #[test]
+    fn test_write_summary_puts_the_peak_in_context() {
+        let mut profile = JobProfile {
+            command: vec!["./train.sh".to_string()],
+            max_total_rss_kib: 4 * 1024 * 1024,
+            system_total_kib: Some(8 * 1024 * 1024),
+            system_available_kib: Some(6 * 1024 * 1024),
+            ..Default::default()
+        };
+        assert!(render_summary(&profile).contains(
+            "\n  Peak 4.0 GiB of 8.0 GiB system RAM (50%); 6.0 GiB was available at the start\n"
+        ));
+
+        // Omitted where the platform did not say
+        profile.system_total_kib = None;
+        assert!(!render_summary(&profile).contains("system RAM"));
+    }
+
+    #[test]
+    fn test_write_summary_names_the_fatal_signal() {
+        let mut profile = JobProfile {
+            command: vec!["./solver".to_string()],
+            exit_code: Some(137),
+            terminated_by_signal: Some(9),
+            terminated_by_signal_name: Some("SIGKILL".to_string()),
+            ..Default::default()
+        };
+        assert!(render_summary(&profile).contains("\n✖ Terminated by SIGKILL (possibly OOM)\n"));
+
+        profile.exit_code = Some(139);
+        profile.terminated_by_signal = Some(11);
+        profile.terminated_by_signal_name = Some("SIGSEGV".to_string());
+        assert!(render_summary(&profile).contains("\n✖ Terminated by SIGSEGV\n"));
+    }
+
+    #[test]
+    fn test_write_summary_reports_paused_time() {
+        use crate::types::PausePeriod;
+
+        let pause = |start_elapsed_seconds: f64, duration_seconds: f64| PausePeriod {
+            start: chrono::Utc::now(),
+            start_elapsed_seconds,
+            duration_seconds,
+        };
+        let mut profile = JobProfile {
+            command: vec!["./fetch-and-train.sh".to_string()],
+            duration_seconds: 95.0,
+            samples: 120,
+            pauses: vec![pause(5.0, 40.0)],
+            ..Default::default()
+        };
+        assert!(
+            render_summary(&profile)
+                .contains("Duration: 00:01:35  |  Samples: 120  |  Paused: 00:00:40 (1 pause)\n")
+        );
+
+        profile.pauses.push(pause(60.0, 21.0));
+        assert!(render_summary(&profile).contains("|  Paused: 00:01:01 (2 pauses)\n"));
+    }
+
+    #[test]
+    fn test_write_summary_counts_sampling_errors() {
+        use crate::types::SamplingError;
+
+        let error = |message: &str| SamplingError {
+            timestamp: chrono::Utc::now(),
+            message: message.to_string(),
+        };
+        let profile = JobProfile {
+            command: vec!["true".to_string()],
+            sampling_errors: vec![
+                error("Failed to sample processes: EMFILE"),
+                error("Failed to sample processes: EAGAIN"),
+            ],
+            ..Default::default()
+        };
+
+        let mut out = NoColor::new(Vec::new());
+        write_summary(&mut out, &profile, SummaryOptions::default()).unwrap();
+        let summary = String::from_utf8(out.into_inner()).unwrap();
+        assert!(summary.contains("⚠ 2 sampling errors occurred; the profile may have gaps"));
+        assert!(summary.contains("  Last: Failed to sample processes: EAGAIN"));
+    }
+
+    #[test]
+    fn test_export_json_round_trips() {
+        use crate::types::ProcessStats;
+
+        let profile = JobProfile {
+            command: vec!["make".to_string(), "-j4".to_string()],
+            max_total_rss_kib: 4096,
+            processes: vec![ProcessStats {
+                pid: 42,
+                command: "cc1".to_string(),
+                max_rss_kib: 4096,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let path =
+            std::env::temp_dir().join(format!("memwatch-json-out-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        for compact in [false, true] {
+            export_json(&profile, path, compact).unwrap();
+            let contents = std::fs::read_to_string(path).unwrap();
+            assert_eq!(contents.trim_end().lines().count() == 1, compact);
+
+            let parsed: JobProfile = serde_json::from_str(&contents).unwrap();
+            assert_eq!(parsed.command, profile.command);
+            assert_eq!(parsed.max_total_rss_kib, 4096);
+            assert_eq!(parsed.processes[0].pid, 42);
+        }
+
+        // A symlink is written through, not replaced
+        let link = format!("{}.link", path);
+        std::os::unix::fs::symlink(path, &link).unwrap();
+        export_json(&profile, &link, false).unwrap();
+        assert!(
+            std::fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert!(std::fs::read_to_string(path).unwrap().lines().count() > 1);
+        std::fs::remove_file(&link).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_format_oneline() {
+        let profile = JobProfile {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo \"hi\"".to_string(),
+            ],
+            duration_seconds: 1.5,
+            max_total_rss_kib: 2048,
+            samples: 4,
+            exit_code: Some(0),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            format_oneline(&profile),
+            r#"memwatch: peak=2048 duration=1.500 samples=4 exit=0 command="sh -c echo \"hi\"""#
+        );
+
+        let labeled = JobProfile {
+            label: Some("nightly".to_string()),
+            ..profile
+        };
+        assert!(format_oneline(&labeled).contains(r#"exit=0 label="nightly" command="sh -c"#));
+
+        let multiline = JobProfile {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo a\r\necho\tb\x1b".to_string(),
+            ],
+            label: Some("two\nlines".to_string()),
+            ..Default::default()
+        };
+        let line = format_oneline(&multiline);
+        assert!(!line.contains(['\n', '\r', '\t', '\x1b']));
+        assert!(line.ends_with(r#"label="two\nlines" command="sh -c echo a\r\necho\tb\u{001b}""#));
+    }
+
     #[test]
     fn test_format_duration() {
         assert_eq!(format_duration(0.0), "00:00:00");
@@ -318,4 +2018,54 @@ mod tests {
         assert_eq!(format_duration(3661.0), "01:01:01");
         assert_eq!(format_duration(7384.0), "02:03:04");
     }
+
+    #[test]
+    fn test_truncate_middle() {
+        let command = "python train.py --epochs 3 --lr 0.1";
+        assert_eq!(truncate_middle(command, 40), command);
+        assert_eq!(truncate_middle(command, 35), command);
+        assert_eq!(truncate_middle(command, 20), "python tra… --lr 0.1");
+        assert_eq!(truncate_middle(command, 20).chars().count(), 20);
+        // Counted in characters, not bytes
+        assert_eq!(truncate_middle("ünïcödé-prögräm", 6), "ünï…äm");
+
+        let options = |width| SummaryOptions {
+            width,
+            ..Default::default()
+        };
+        assert_eq!(fit_command(command, 41, &options(None)), command);
+        assert_eq!(SummaryOptions::default().width, None);
+        assert_eq!(
+            table_columns(&[PID_WIDTH, VALUE_WIDTH, TIME_WIDTH, TIME_WIDTH]),
+            41
+        );
+        assert_eq!(
+            fit_command(command, 40, &options(Some(60))),
+            "python tra… --lr 0.1"
+        );
+        // Never below MIN_COMMAND_WIDTH, however little room is left
+        assert_eq!(
+            fit_command(command, 41, &options(Some(50))).chars().count(),
+            MIN_COMMAND_WIDTH
+        );
+    }
+
+    #[test]
+    fn test_display_command_resolves_exe() {
+        let mut proc = ProcessStats {
+            command: "python train.py --epochs 3".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(display_command(&proc, true), "python train.py --epochs 3");
+
+        proc.exe_path = Some("/opt/venv/bin/python3.12".to_string());
+        assert_eq!(display_command(&proc, false), "python train.py --epochs 3");
+        assert_eq!(
+            display_command(&proc, true),
+            "/opt/venv/bin/python3.12 train.py --epochs 3"
+        );
+
+        proc.command = "python".to_string();
+        assert_eq!(display_command(&proc, true), "/opt/venv/bin/python3.12");
+    }
 }
@@ -1,46 +1,499 @@
+use crate::cli::TrackBy;
+use crate::csv_writer::write_timeline_csv;
+use crate::error::MemwatchError;
+use crate::history::write_atomic;
 use crate::inspector::ProcessInspector;
-use crate::types::{JobProfile, JobSnapshot, JobState, ProcessSample};
+use crate::pty::Pty;
+use crate::reporter::{format_memory, print_alert, write_profile_json};
+use crate::signals::{self, InterruptGuard};
+use crate::types::{
+    CapturedOutput, FilterConfig, GroupBy, JobProfile, JobSnapshot, JobState, KillReason,
+    ProcessSample, SystemMemory, compute_process_groups, extract_command_name,
+};
+use crate::webhook::{Notifier, WebhookConfig};
 use anyhow::{Context, Result};
 use chrono::Utc;
+use log::{debug, log_enabled, trace, warn};
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::IsTerminal;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// Shortest interval we sample at; anything lower just burns CPU re-reading process tables
+pub const MIN_INTERVAL: Duration = Duration::from_micros(100);
+
+/// Clamp the requested interval to `MIN_INTERVAL`, returning a warning for
+/// intervals that were raised or that will noticeably increase overhead
+fn effective_interval(requested: Duration) -> (Duration, Option<String>) {
+    if requested < MIN_INTERVAL {
+        (
+            MIN_INTERVAL,
+            Some(format!(
+                "Sampling interval of {}µs is too small; using the minimum of {}µs",
+                requested.as_micros(),
+                MIN_INTERVAL.as_micros()
+            )),
+        )
+    } else if requested < Duration::from_millis(1) {
+        (
+            requested,
+            Some(format!(
+                "Sub-millisecond sampling ({}µs) significantly raises memwatch's own CPU overhead; each sample scans the full process table",
+                requested.as_micros()
+            )),
+        )
+    } else {
+        (requested, None)
+    }
+}
+
+/// Maximum number of runs (including the first) attempted by `--auto-interval`
+pub const AUTO_INTERVAL_MAX_ATTEMPTS: usize = 3;
+
+/// Each `--auto-interval` retry samples this many times more frequently
+const AUTO_INTERVAL_DIVISOR: u32 = 5;
+
+/// Intervals tried by `--auto-interval`: the requested interval, then progressively
+/// shorter ones (e.g. 500ms → 100ms → 20ms), never going below `MIN_INTERVAL`
+pub fn auto_interval_schedule(initial: Duration) -> Vec<Duration> {
+    let mut schedule = vec![initial];
+    let mut current = initial;
+
+    while schedule.len() < AUTO_INTERVAL_MAX_ATTEMPTS && current > MIN_INTERVAL {
+        current = (current / AUTO_INTERVAL_DIVISOR).max(MIN_INTERVAL);
+        schedule.push(current);
+    }
+
+    schedule
+}
+
+/// Check that `dir` can be used as the command's working directory, returning its
+/// absolute path
+pub fn resolve_working_dir(dir: &str) -> Result<PathBuf, String> {
+    let path = Path::new(dir);
+    if !path.exists() {
+        return Err(format!("directory '{}' does not exist", dir));
+    }
+    if !path.is_dir() {
+        return Err(format!("'{}' is not a directory", dir));
+    }
+    path.canonicalize()
+        .map_err(|e| format!("cannot resolve '{}': {}", dir, e))
+}
+
+/// Pick the files in `dir` that a command's output is saved to, creating `dir` if needed.
+/// Names combine the command name and a timestamp so runs sharing a directory don't collide
+fn capture_paths(dir: &Path, command: &[String]) -> Result<CapturedOutput> {
+    std::fs::create_dir_all(dir).context(MemwatchError::export_failed(
+        "Failed to create output directory",
+        dir.display(),
+    ))?;
+
+    let name = extract_command_name(&command.join(" "));
+    let stem = format!("{}-{}", name, Utc::now().format("%Y%m%dT%H%M%S%.3f"));
+    let path = |ext: &str| dir.join(format!("{}.{}", stem, ext)).display().to_string();
+    Ok(CapturedOutput {
+        stdout: path("stdout"),
+        stderr: path("stderr"),
+    })
+}
+
+/// When and how `--idle-stop` ends a run whose memory has settled
+#[derive(Debug, Clone, Copy)]
+pub struct IdleStop {
+    /// How long the total must stay flat
+    pub after: Duration,
+    /// How far it may move and still count as flat
+    pub epsilon_kib: u64,
+    /// Leave the job running instead of killing it
+    pub detach: bool,
+}
+
+/// Follows the job total for `--idle-stop`: it is idle once it has stayed within
+/// `epsilon_kib` of the level it settled at for `after`
+struct IdleWatch {
+    stop: IdleStop,
+    /// The level of the current flat stretch and when it began
+    settled: Option<(u64, Instant)>,
+}
+
+impl IdleWatch {
+    fn new(stop: IdleStop) -> Self {
+        Self {
+            stop,
+            settled: None,
+        }
+    }
+
+    /// Record a sample of the total; true once it has been flat for long enough
+    fn observe(&mut self, total_rss_kib: u64, at: Instant) -> bool {
+        match self.settled {
+            Some((level, since)) if total_rss_kib.abs_diff(level) <= self.stop.epsilon_kib => {
+                at.duration_since(since) >= self.stop.after
+            }
+            _ => {
+                self.settled = Some((total_rss_kib, at));
+                false
+            }
+        }
+    }
+}
+
+/// Environment changes applied to the profiled command
+#[derive(Debug, Clone, Default)]
+pub struct ChildEnv {
+    /// Start from an empty environment instead of inheriting memwatch's
+    pub clear: bool,
+    /// Variables to set, applied in order
+    pub vars: Vec<(String, String)>,
+}
+
+/// Files an intermediate summary (SIGUSR1) also writes the data so far to, next to
+/// the final `--json-out` and `--timeline` files
+#[derive(Debug, Clone, Default)]
+pub struct PartialOutputs {
+    pub json: Option<String>,
+    pub json_compact: bool,
+    pub timeline: Option<String>,
+}
+
+/// How a command is sampled and how the resulting profile is built
+#[derive(Debug, Clone)]
+pub struct SamplerOptions {
+    pub interval: Duration,
+    pub track_timeline: bool,
+    /// Also record every process's RSS at each sample (implies `track_timeline`)
+    pub per_process_timeline: bool,
+    /// Keep every snapshot unaggregated in the profile
+    pub raw_samples: bool,
+    /// Discard the command's stdout/stderr
+    pub silent: bool,
+    /// Run the command on a pseudo-terminal relayed to memwatch's own
+    pub pty: bool,
+    /// Write the command's stdout/stderr to files in this directory
+    pub capture_output: Option<PathBuf>,
+    /// Keep processes that never showed any RSS in reports and groups
+    pub include_zero: bool,
+    /// How `process_groups` is keyed
+    pub group_by: GroupBy,
+    pub env: ChildEnv,
+    /// Run the joined command line through this shell as `<shell> -c '<command>'`
+    pub shell: Option<String>,
+    /// Leave memwatch and the `shell` wrapper out of the job, while following their children
+    pub exclude_self: bool,
+    /// Run the command in this directory instead of memwatch's own
+    pub cwd: Option<PathBuf>,
+    /// Patterns and thresholds deciding which processes the profile lists
+    pub filter: FilterConfig,
+    /// Report the total peak relative to the job's RSS at the first sample
+    pub subtract_baseline: bool,
+    /// Record the per-NUMA-node breakdown whenever a process reaches a new peak
+    pub numa: bool,
+    /// Record every process's cumulative storage I/O at each sample
+    pub show_io: bool,
+    /// Record the private/shared split whenever a process reaches a new peak
+    pub show_shared: bool,
+    /// Keep sampling failures out of stderr; they are still recorded in the profile
+    pub quiet_errors: bool,
+    /// Also count the root's session or process group as part of the job
+    pub track_by: TrackBy,
+    /// Kill the job tree once its total RSS exceeds this many KiB
+    pub max_memory_kib: Option<u64>,
+    /// Kill the job tree once it has run for this long
+    pub timeout: Option<Duration>,
+    /// End the run once memory has stopped changing (`--idle-stop`)
+    pub idle_stop: Option<IdleStop>,
+    /// Start sampling only after the command has run this long
+    pub delay: Duration,
+    /// After the root exits, keep sampling the processes it left behind for up to this long
+    pub drain: Option<Duration>,
+    /// Without `drain`, keep sampling them for as long as they run, within `timeout`
+    pub wait_for_children: bool,
+    /// Stop sampling after this many samples
+    pub max_samples: Option<usize>,
+    /// Once `max_samples` is reached, return without waiting for the command to exit
+    pub detach_after_samples: bool,
+    /// Once `max_samples` is reached, stop the job tree the way `timeout` does
+    pub kill_after_samples: bool,
+    /// How long killed processes get to exit after SIGTERM before SIGKILL
+    pub kill_grace: Duration,
+    /// Run the command in its own process group (unless stdin is a terminal) and pass
+    /// SIGINT and SIGTERM on to it: after SIGINT it gets `kill_grace` to exit, while
+    /// SIGTERM stops it the way `timeout` does. Without this the command is left running
+    pub forward_signals: bool,
+    /// Notify a URL when the job's total RSS crosses a threshold
+    pub webhook: Option<WebhookConfig>,
+    /// Print a line the first time the job's total RSS rises above each of these (KiB)
+    pub alert_levels_kib: Vec<u64>,
+    /// Where an intermediate summary writes the partial profile and timeline
+    pub partial_outputs: PartialOutputs,
+}
+
+/// The `run` defaults: a 500ms interval and forwarded signals, with everything else off
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(500),
+            track_timeline: false,
+            per_process_timeline: false,
+            raw_samples: false,
+            silent: false,
+            pty: false,
+            capture_output: None,
+            include_zero: false,
+            group_by: GroupBy::default(),
+            env: ChildEnv::default(),
+            shell: None,
+            exclude_self: false,
+            cwd: None,
+            filter: FilterConfig::default(),
+            subtract_baseline: false,
+            numa: false,
+            show_io: false,
+            show_shared: false,
+            quiet_errors: false,
+            track_by: TrackBy::Tree,
+            max_memory_kib: None,
+            timeout: None,
+            idle_stop: None,
+            delay: Duration::ZERO,
+            drain: None,
+            wait_for_children: false,
+            max_samples: None,
+            detach_after_samples: false,
+            kill_after_samples: false,
+            kill_grace: Duration::from_secs(5),
+            forward_signals: true,
+            webhook: None,
+            alert_levels_kib: Vec::new(),
+            partial_outputs: PartialOutputs::default(),
+        }
+    }
+}
+
+/// Something that happened while a job was sampled, passed to a `SampleObserver`
+pub enum SampleEvent<'a> {
+    /// A sample was recorded: its total RSS and the peak so far
+    Sample {
+        elapsed: Duration,
+        total_rss_kib: u64,
+        peak_kib: u64,
+    },
+    /// SIGUSR1 asked where the job stands; `processes` come from a sample taken for
+    /// it and not recorded. `written` names each partial output written for it and
+    /// its path, e.g. `("JSON profile", "profile.json.partial")`
+    Summary {
+        elapsed: Duration,
+        current_kib: u64,
+        peak_kib: u64,
+        processes: &'a [ProcessSample],
+        written: &'a [(&'static str, &'a str)],
+    },
+    /// SIGUSR2 paused sampling, or resumed it after `resumed_after` seconds
+    Pause {
+        elapsed_seconds: f64,
+        resumed_after: Option<f64>,
+    },
+}
+
+/// Follows a run while it is sampled, e.g. to draw progress or print notices
+pub trait SampleObserver {
+    fn observe(&mut self, event: SampleEvent<'_>);
+
+    /// Sampling has stopped; called before waiting for the command to exit
+    fn finish(&mut self) {}
+}
+
+/// Ignores everything
+impl SampleObserver for () {
+    fn observe(&mut self, _event: SampleEvent<'_>) {}
+}
 
 /// Run a command and profile its memory usage
 pub fn run_and_profile(
     command: Vec<String>,
-    interval_ms: u64,
-    track_timeline: bool,
-    silent: bool,
-    exclude_pattern: Option<String>,
-    include_pattern: Option<String>,
+    options: &SamplerOptions,
+    inspector: &impl ProcessInspector,
+) -> Result<JobProfile> {
+    run_and_observe(command, options, inspector, &mut ())
+}
+
+/// `run_and_profile`, telling `observer` about every sample and signal as it happens
+pub fn run_and_observe(
+    command: Vec<String>,
+    options: &SamplerOptions,
     inspector: &impl ProcessInspector,
+    observer: &mut impl SampleObserver,
 ) -> Result<JobProfile> {
     if command.is_empty() {
         anyhow::bail!("Command cannot be empty");
     }
 
+    let (interval, interval_warning) = effective_interval(options.interval);
+    if let Some(warning) = interval_warning {
+        warn!("{}", warning);
+    }
+
+    // The machine's memory before the job takes any, for context in the report
+    let system_at_start = read_system_memory(inspector);
+
     // Spawn the command
-    let mut child = spawn_command(&command, silent).context("Failed to start command")?;
+    // Installed before the spawn so a Ctrl-C at any point still yields a profile
+    let _interrupts = InterruptGuard::install();
+    let pty = options.pty.then(Pty::open).transpose()?;
+    let captured_output = match &options.capture_output {
+        Some(dir) => Some(capture_paths(dir, &command)?),
+        None => None,
+    };
+    // Under a pty the command leads a session of its own anyway
+    let own_group = pty.is_some() || in_own_group(options);
+    let mut child = spawn_command(&command, options, pty.as_ref(), captured_output.as_ref())
+        .context("Failed to start command")?;
+    let pty_relay = pty.map(Pty::relay).transpose()?;
 
     let root_pid = child.id() as i32;
-    let mut state = JobState::new(track_timeline);
+    let own_pid = std::process::id();
+    debug!(
+        "Started {} as pid {}; memwatch is pid {} (`kill -USR1 {}` prints an intermediate summary)",
+        command.join(" "),
+        root_pid,
+        own_pid,
+        own_pid
+    );
+    // The group a job in its own process group is signalled through, so processes that
+    // left the tree are reached as well
+    let group = own_group.then_some(root_pid);
+    let mut job = JobMembership::new(root_pid, options.track_by, &options.filter, inspector);
+    if options.exclude_self {
+        job.exclude_self(options.shell.as_deref());
+    }
+    let mut notifier = options
+        .webhook
+        .clone()
+        .map(|config| Notifier::new(config, &command, root_pid));
+    let started = Instant::now();
+    let exited_during_delay = !options.delay.is_zero() && wait_out_delay(&mut child, options.delay);
+
+    // Created after the delay so the profile's start time marks when sampling began
+    let mut state = JobState::new(options.track_timeline, options.per_process_timeline);
+    if options.raw_samples {
+        state.raw_samples = Some(Vec::new());
+    }
+    let mut killed_reason = None;
+    let mut idle_watch = options.idle_stop.map(IdleWatch::new);
+    let mut idle_stopped = false;
+    let mut sampling_truncated = false;
+    let mut interrupted = None;
+    let mut left_running = false;
+    let mut drain_timed_out = false;
+    let mut drain_seconds = None;
+    let sample_limit_reached =
+        |state: &JobState| options.max_samples.is_some_and(|max| state.samples >= max);
 
     // Take an immediate first sample to catch quick-exit processes
-    // This happens as fast as possible after spawn
-    if let Ok(snapshot) = sample_job_tree(inspector, root_pid) {
-        state.update(snapshot);
+    // This happens as fast as possible after spawn (or after the delay)
+    if !exited_during_delay && let Ok(snapshot) = sample_job_tree(inspector, &job) {
+        record_snapshot(&mut state, snapshot, inspector, options);
     }
 
     // Sampling loop
     loop {
+        // Nothing left to sample if the command finished during the delay
+        if exited_during_delay {
+            break;
+        }
+
+        if let Some(signal) = signals::take_interrupt() {
+            interrupted = Some(signal);
+            // The job as it was when interrupted, before it starts shutting down
+            if !state.is_paused()
+                && let Ok(snapshot) = sample_job_tree(inspector, &job)
+            {
+                record_snapshot(&mut state, snapshot, inspector, options);
+            }
+            left_running = !forward_interrupt(&mut child, &job, group, signal, inspector, options);
+            break;
+        }
+
+        if signals::take_dump_request() {
+            let current = sample_job_tree(inspector, &job).ok();
+            dump_partial(
+                &state,
+                current,
+                &command,
+                (interval, started.elapsed()),
+                options,
+                observer,
+            );
+        }
+
+        if signals::take_pause_toggle() {
+            let now = Utc::now();
+            let resumed_after = if state.toggle_pause(now) {
+                None
+            } else {
+                // How long memory was flat before the pause says nothing about after it
+                idle_watch = options.idle_stop.map(IdleWatch::new);
+                state.pauses.last().map(|pause| pause.duration_seconds)
+            };
+            observer.observe(SampleEvent::Pause {
+                elapsed_seconds: (now - state.start_time).num_milliseconds() as f64 / 1000.0,
+                resumed_after,
+            });
+        }
+
+        // Checked before the exit check so the final sample cannot exceed the limit
+        if sample_limit_reached(&state) {
+            sampling_truncated = true;
+            if options.kill_after_samples && matches!(child.try_wait(), Ok(None)) {
+                let job_pids = live_job_pids(inspector, &job, root_pid);
+                kill_job_tree(
+                    &mut child,
+                    &job,
+                    group,
+                    job_pids,
+                    inspector,
+                    options.kill_grace,
+                );
+                killed_reason = Some(KillReason::SampleLimit);
+            }
+            break;
+        }
+
         // Check if the root process is still alive
         match child.try_wait() {
             Ok(Some(_status)) => {
-                // Process has exited, do one final sample and break
-                if let Ok(snapshot) = sample_job_tree(inspector, root_pid) {
-                    state.update(snapshot);
+                // Process has exited, do one final sample (unless paused) and break
+                if !state.is_paused()
+                    && let Ok(snapshot) = sample_job_tree(inspector, &job)
+                {
+                    record_snapshot(&mut state, snapshot, inspector, options);
+                }
+                // --wait-for-children has no deadline of its own, only what is left
+                // of --timeout
+                let drain_limit = match (options.drain, options.timeout) {
+                    (Some(timeout), _) => Some(DrainLimit::DrainTimeout(timeout)),
+                    (None, Some(timeout)) if options.wait_for_children => {
+                        Some(DrainLimit::RestOfTimeout {
+                            timeout,
+                            remaining: timeout.saturating_sub(started.elapsed()),
+                        })
+                    }
+                    (None, None) if options.wait_for_children => Some(DrainLimit::Unlimited),
+                    (None, _) => None,
+                };
+                if let Some(limit) = drain_limit {
+                    let drain_started = Instant::now();
+                    drain_timed_out = drain_orphans(
+                        &mut state, root_pid, group, inspector, interval, limit, options,
+                    );
+                    drain_seconds = Some(drain_started.elapsed().as_secs_f64());
+                    // drain_orphans stops early on an interrupt
+                    interrupted = signals::take_interrupt();
                 }
                 break;
             }
@@ -48,63 +501,1044 @@ pub fn run_and_profile(
                 // Process still running, continue sampling
             }
             Err(e) => {
-                eprintln!("Warning: Failed to check process status: {}", e);
+                report_error(
+                    &mut state,
+                    options,
+                    format!("Failed to check process status: {}", e),
+                );
                 break;
             }
         }
 
-        // Take a snapshot
-        match sample_job_tree(inspector, root_pid) {
-            Ok(snapshot) => {
-                state.update(snapshot);
+        // Take a snapshot. While paused nothing is recorded, so the timeline shows a
+        // gap; the command's exit and --timeout are still checked
+        let mut job_pids = Vec::new();
+        let mut total_rss_kib = 0;
+        let sample = (!state.is_paused()).then(|| sample_job_tree(inspector, &job));
+        match sample {
+            None => {}
+            Some(Ok(snapshot)) => {
+                job_pids = snapshot.processes.iter().map(|p| p.pid).collect();
+                total_rss_kib = snapshot.total_rss_kib;
+                if let Some(watch) = idle_watch.as_mut() {
+                    idle_stopped = watch.observe(total_rss_kib, Instant::now());
+                }
+                if let Some(notifier) = notifier.as_mut() {
+                    notifier.observe(total_rss_kib, snapshot.timestamp);
+                }
+                record_snapshot(&mut state, snapshot, inspector, options);
+                observer.observe(SampleEvent::Sample {
+                    elapsed: started.elapsed(),
+                    total_rss_kib,
+                    peak_kib: state.max_total_rss_kib,
+                });
             }
-            Err(e) => {
-                eprintln!("Warning: Failed to sample processes: {}", e);
+            Some(Err(e)) => {
+                report_error(
+                    &mut state,
+                    options,
+                    format!("Failed to sample processes: {}", e),
+                );
             }
         }
 
-        // Sleep for the interval
-        thread::sleep(Duration::from_millis(interval_ms));
+        if let Some(limit) = options
+            .max_memory_kib
+            .filter(|&limit| total_rss_kib > limit)
+        {
+            warn!(
+                "Job RSS {} exceeded --max-memory {}; killing {} process(es)",
+                format_memory(total_rss_kib),
+                format_memory(limit),
+                job_pids.len()
+            );
+            killed_reason = Some(KillReason::MemoryLimit);
+        } else if let Some(timeout) = options
+            .timeout
+            .filter(|&timeout| started.elapsed() >= timeout)
+        {
+            // Nothing was sampled if sampling is paused
+            if job_pids.is_empty() {
+                job_pids = live_job_pids(inspector, &job, root_pid);
+            }
+            warn!(
+                "Job exceeded --timeout {:?}; killing {} process(es)",
+                timeout,
+                job_pids.len()
+            );
+            killed_reason = Some(KillReason::Timeout);
+        } else if idle_stopped && let Some(idle) = options.idle_stop {
+            debug!(
+                "Job RSS stayed within {} for {:?}; {}",
+                format_memory(idle.epsilon_kib),
+                idle.after,
+                if idle.detach {
+                    "detaching"
+                } else {
+                    "stopping it"
+                }
+            );
+            if idle.detach {
+                left_running = true;
+                break;
+            }
+            killed_reason = Some(KillReason::Idle);
+        }
+
+        if killed_reason.is_some() {
+            kill_job_tree(
+                &mut child,
+                &job,
+                group,
+                job_pids,
+                inspector,
+                options.kill_grace,
+            );
+            break;
+        }
+
+        if sample_limit_reached(&state) {
+            continue;
+        }
+
+        // Sleep for the interval, waking up early enough to enforce --timeout
+        let sleep = match options.timeout {
+            Some(timeout) => interval.min(timeout.saturating_sub(started.elapsed())),
+            None => interval,
+        };
+        thread::sleep(sleep);
     }
 
-    // Wait for the process to fully exit and capture exit code
-    let exit_status = child.wait().ok();
-    let exit_code = exit_status.and_then(|s| s.code());
+    observer.finish();
+
+    // Sampling has stopped, but --timeout and Ctrl-C still apply while waiting for
+    // the exit
+    if sampling_truncated && !options.detach_after_samples {
+        while let Ok(None) = child.try_wait() {
+            if let Some(signal) = signals::take_interrupt() {
+                interrupted = Some(signal);
+                left_running =
+                    !forward_interrupt(&mut child, &job, group, signal, inspector, options);
+                break;
+            }
+            let mut wait = Duration::from_millis(100);
+            if let Some(timeout) = options.timeout {
+                let remaining = timeout.saturating_sub(started.elapsed());
+                if remaining.is_zero() {
+                    let job_pids = live_job_pids(inspector, &job, root_pid);
+                    warn!(
+                        "Job exceeded --timeout {:?}; killing {} process(es)",
+                        timeout,
+                        job_pids.len()
+                    );
+                    kill_job_tree(
+                        &mut child,
+                        &job,
+                        group,
+                        job_pids,
+                        inspector,
+                        options.kill_grace,
+                    );
+                    killed_reason = Some(KillReason::Timeout);
+                    break;
+                }
+                wait = wait.min(remaining);
+            }
+            thread::sleep(wait);
+        }
+    }
+
+    // Wait for the process to fully exit and capture exit code. A detached command
+    // (including one left running after Ctrl-C) keeps running after memwatch exits,
+    // so it has no exit code yet
+    let detached = (sampling_truncated && options.detach_after_samples) || left_running;
+    let status = if detached { None } else { child.wait().ok() };
+    // A kill memwatch decided on is reported as `killed_reason` instead, and one it
+    // passed on as `interrupted`. Otherwise a signal death still gets an exit code,
+    // 128 + the signal, as shells report it
+    let terminated_by_signal = status
+        .and_then(|s| s.signal())
+        .filter(|_| killed_reason.is_none() && interrupted.is_none());
+    let exit_code = status
+        .and_then(|s| s.code())
+        .or(terminated_by_signal.map(|signal| 128 + signal));
+    let system_at_end = read_system_memory(inspector);
+    if let Some(relay) = pty_relay {
+        relay.finish();
+    }
+    if let Some(notifier) = notifier {
+        notifier.finish();
+    }
 
     // Convert state to profile
-    state.into_profile(
-        command,
-        interval_ms,
-        exit_code,
-        exclude_pattern,
-        include_pattern,
-    )
+    let baseline_kib = state.first_total_rss_kib;
+    let mut profile = state.into_profile(command, interval, exit_code, options.filter.clone())?;
+
+    if options.subtract_baseline {
+        profile.apply_baseline(baseline_kib.unwrap_or(0));
+    }
+    profile.killed_reason = killed_reason;
+    profile.memory_limit_kib = options.max_memory_kib;
+    profile.timed_out = killed_reason == Some(KillReason::Timeout);
+    profile.timeout_seconds = options.timeout.map(|t| t.as_secs_f64());
+    profile.idle_stopped = idle_stopped;
+    profile.idle_stop_seconds = options.idle_stop.map(|idle| idle.after.as_secs_f64());
+    profile.idle_epsilon_kib = options.idle_stop.map(|idle| idle.epsilon_kib);
+    profile.delay_seconds = (!options.delay.is_zero()).then_some(options.delay.as_secs_f64());
+    profile.exited_during_delay = exited_during_delay;
+    profile.sampling_truncated = sampling_truncated;
+    profile.drain_timed_out = drain_timed_out;
+    profile.drain_seconds = drain_seconds;
+    profile.detached = detached;
+    profile.interrupted = interrupted.is_some();
+    profile.interrupt_signal = interrupted.map(|signal| signals::name(signal).to_string());
+    profile.terminated_by_signal = terminated_by_signal;
+    profile.terminated_by_signal_name =
+        terminated_by_signal.map(|signal| signals::name(signal).to_string());
+    profile.system_total_kib = system_at_start.or(system_at_end).map(|m| m.total_kib);
+    profile.system_available_kib = system_at_start.and_then(|m| m.available_kib);
+    profile.system_available_end_kib = system_at_end.and_then(|m| m.available_kib);
+    profile.captured_output = captured_output;
+    profile.include_zero_rss = options.include_zero;
+    profile.group_by = options.group_by.clone();
+    if options.include_zero || !options.group_by.is_basename() {
+        profile.process_groups =
+            compute_process_groups(&profile.processes, options.include_zero, &options.group_by);
+    }
+    profile.working_dir = options.cwd.as_ref().map(|dir| dir.display().to_string());
+    profile.shell = options.shell.clone();
+    profile.self_excluded = options.exclude_self;
+
+    Ok(profile)
+}
+
+/// How long `drain_orphans` waits for the processes the root left behind
+#[derive(Clone, Copy, Debug)]
+enum DrainLimit {
+    /// `--drain-timeout`, counted from the root's exit
+    DrainTimeout(Duration),
+    /// `--wait-for-children` under `--timeout`: whatever of `timeout` was left
+    /// when the root exited
+    RestOfTimeout {
+        timeout: Duration,
+        remaining: Duration,
+    },
+    /// `--wait-for-children` alone: as long as they run
+    Unlimited,
+}
+
+impl DrainLimit {
+    fn duration(self) -> Option<Duration> {
+        match self {
+            DrainLimit::DrainTimeout(timeout) => Some(timeout),
+            DrainLimit::RestOfTimeout { remaining, .. } => Some(remaining),
+            DrainLimit::Unlimited => None,
+        }
+    }
+}
+
+impl fmt::Display for DrainLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrainLimit::DrainTimeout(timeout) => write!(f, "after --drain-timeout {:?}", timeout),
+            DrainLimit::RestOfTimeout { timeout, remaining } => write!(
+                f,
+                "at --timeout {:?} ({:?} after the command exited)",
+                timeout, remaining
+            ),
+            DrainLimit::Unlimited => write!(f, "with no limit"),
+        }
+    }
+}
+
+/// Keep sampling the processes the root left behind until they have all exited or
+/// `limit` runs out, returning true if some were still running at the deadline.
+///
+/// Orphans are reparented (usually to init), so they are no longer found under the
+/// root; instead every process seen so far is tracked by PID, along with anything
+/// they spawn. A tracked PID only matches while its start time is unchanged, so a
+/// recycled PID is not mistaken for a job process.
+fn drain_orphans(
+    state: &mut JobState,
+    root_pid: i32,
+    group: Option<i32>,
+    inspector: &impl ProcessInspector,
+    interval: Duration,
+    limit: DrainLimit,
+    options: &SamplerOptions,
+) -> bool {
+    let deadline = limit.duration().map(|timeout| Instant::now() + timeout);
+    let mut tracked: HashMap<i32, Option<chrono::DateTime<Utc>>> = state
+        .process_stats
+        .values()
+        .filter(|stats| stats.pid != root_pid)
+        .map(|stats| (stats.pid, stats.start_time))
+        .collect();
+
+    loop {
+        if options.max_samples.is_some_and(|max| state.samples >= max) {
+            return false;
+        }
+        // Left for the caller to take; the orphans get the signal when forwarding
+        if let Some(signal) = signals::interrupt_pending() {
+            if options.forward_signals {
+                let pids: Vec<i32> = tracked.keys().copied().collect();
+                signal_job(group, &pids, signal);
+            }
+            return false;
+        }
+
+        // Under --depth, children of the orphans are not followed: their distance from
+        // the original root is no longer known
+        let max_depth = options.filter.max_depth.map(|_| 0);
+        let snapshot = match sample_descendants(
+            inspector,
+            |p| {
+                tracked
+                    .get(&p.pid)
+                    .is_some_and(|start| start.is_none() || *start == p.start_time)
+            },
+            max_depth,
+        ) {
+            Ok(mut snapshot) => {
+                if options.filter.narrows_sampling() {
+                    select_processes(&mut snapshot, &options.filter);
+                }
+                snapshot
+            }
+            Err(e) => {
+                report_error(state, options, format!("Failed to sample processes: {}", e));
+                return false;
+            }
+        };
+
+        // Zombies waiting to be reaped by their new parent have no memory left
+        if snapshot.processes.iter().all(|p| p.rss_kib == 0) {
+            return false;
+        }
+        for proc in &snapshot.processes {
+            tracked.insert(proc.pid, proc.start_time);
+        }
+        let remaining = snapshot.processes.len();
+        record_snapshot(state, snapshot, inspector, options);
+
+        let Some(deadline) = deadline else {
+            thread::sleep(interval);
+            continue;
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            warn!(
+                "{} process(es) left behind by the command were still running {}; they are no longer sampled",
+                remaining, limit
+            );
+            return true;
+        }
+        thread::sleep(interval.min(left));
+    }
+}
+
+/// Wait out `delay` without sampling, returning true if the command exited meanwhile
+fn wait_out_delay(child: &mut Child, delay: Duration) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        if let Ok(Some(_)) = child.try_wait() {
+            return true;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return false;
+        }
+        thread::sleep(remaining.min(Duration::from_millis(50)));
+    }
+}
+
+/// Pass an interrupt on to the job when `forward_signals` is set. SIGTERM stops the
+/// job the way `--timeout` does; after SIGINT the command gets up to `kill_grace` to
+/// exit, and a second interrupt kills it at once. A SIGINT is not sent again to
+/// processes the terminal already delivered it to. Returns false if it is left running
+fn forward_interrupt(
+    child: &mut Child,
+    job: &JobMembership,
+    group: Option<i32>,
+    signal: libc::c_int,
+    inspector: &impl ProcessInspector,
+    options: &SamplerOptions,
+) -> bool {
+    if !options.forward_signals {
+        debug!(
+            "Interrupted by {}; leaving the command running (--no-forward-signals)",
+            signals::name(signal)
+        );
+        return false;
+    }
+    let processes = sample_job_tree(inspector, job).map(|snapshot| snapshot.processes);
+    let job_pids: Vec<i32> = match &processes {
+        Ok(processes) => processes.iter().map(|p| p.pid).collect(),
+        Err(_) => vec![child.id() as i32],
+    };
+    if signal == libc::SIGTERM {
+        debug!(
+            "Interrupted by SIGTERM; stopping {} process(es)",
+            job_pids.len()
+        );
+        kill_job_tree(child, job, group, job_pids, inspector, options.kill_grace);
+        return true;
+    }
+    // A Ctrl-C on the terminal already reached every process in its foreground group,
+    // so only the ones that left that group still need it
+    let reached = group.is_none().then(terminal_foreground_group).flatten();
+    let forward_to: Vec<i32> = match (reached, &processes) {
+        (None, _) => job_pids.clone(),
+        (Some(reached), Ok(processes)) => processes
+            .iter()
+            .filter(|p| p.process_group != Some(reached))
+            .map(|p| p.pid)
+            .collect(),
+        // Only the command itself is known, and it is in the group
+        (Some(_), Err(_)) => Vec::new(),
+    };
+    debug!(
+        "Interrupted by {}; forwarding it to {} process(es)",
+        signals::name(signal),
+        forward_to.len()
+    );
+    signal_job(group, &forward_to, signal);
+    let deadline = Instant::now() + options.kill_grace;
+    while Instant::now() < deadline {
+        if let Ok(Some(_)) = child.try_wait() {
+            return true;
+        }
+        // Pressing Ctrl-C again means not waiting any longer
+        if signals::take_interrupt().is_some() {
+            debug!("Interrupted again; killing the job");
+            let job_pids = match sample_job_tree(inspector, job) {
+                Ok(snapshot) => snapshot.processes.iter().map(|p| p.pid).collect(),
+                Err(_) => job_pids,
+            };
+            signal_job(group, &job_pids, libc::SIGKILL);
+            let _ = child.wait();
+            return true;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    warn!(
+        "The command was still running {:?} after the interrupt; leaving it running",
+        options.kill_grace
+    );
+    false
+}
+
+/// Send SIGTERM to every process in the job, then SIGKILL whatever is still alive
+/// after `grace`, or as soon as an interrupt arrives. Descendants spawned after
+/// `job_pids` was sampled are picked up from the live tree before returning or
+/// escalating, and a job in its own process `group` is signalled through the group too.
+fn kill_job_tree(
+    child: &mut Child,
+    job: &JobMembership,
+    group: Option<i32>,
+    job_pids: Vec<i32>,
+    inspector: &impl ProcessInspector,
+    grace: Duration,
+) {
+    signal_job(group, &job_pids, libc::SIGTERM);
+
+    let deadline = Instant::now() + grace;
+    let mut remaining: HashSet<i32> = job_pids.into_iter().collect();
+    while Instant::now() < deadline {
+        // Reap the root so it does not linger as a zombie that still answers kill(pid, 0)
+        let _ = child.try_wait();
+        remaining.retain(|&pid| process_exists(pid));
+        if remaining.is_empty() {
+            // Children forked after `job_pids` was sampled may still be alive
+            let stragglers: Vec<i32> = match sample_job_tree(inspector, job) {
+                Ok(snapshot) => snapshot
+                    .processes
+                    .iter()
+                    .map(|p| p.pid)
+                    .filter(|&pid| process_exists(pid))
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+            if stragglers.is_empty() {
+                return;
+            }
+            signal_pids(&stragglers, libc::SIGTERM);
+            remaining.extend(stragglers);
+        }
+        if signals::interrupt_pending().is_some() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    if let Ok(snapshot) = sample_job_tree(inspector, job) {
+        remaining.extend(snapshot.processes.iter().map(|p| p.pid));
+    }
+    let survivors: Vec<i32> = remaining.into_iter().collect();
+    signal_job(group, &survivors, libc::SIGKILL);
+}
+
+/// The PIDs in the job's live tree, or just the root if it cannot be sampled
+fn live_job_pids(
+    inspector: &impl ProcessInspector,
+    job: &JobMembership,
+    root_pid: i32,
+) -> Vec<i32> {
+    match sample_job_tree(inspector, job) {
+        Ok(snapshot) => snapshot.processes.iter().map(|p| p.pid).collect(),
+        Err(_) => vec![root_pid],
+    }
+}
+
+/// Whether the command is started in a process group of its own. A command on the
+/// terminal stays in memwatch's group, the terminal's foreground group, so it can
+/// still read from the terminal (and gets Ctrl-C from it directly), unless signals
+/// are not forwarded: then it must not get Ctrl-C at all
+fn in_own_group(options: &SamplerOptions) -> bool {
+    !options.forward_signals || !std::io::stdin().is_terminal()
+}
+
+/// memwatch's process group, when it is the foreground group of the terminal on
+/// stdin and so gets that terminal's Ctrl-C
+fn terminal_foreground_group() -> Option<i32> {
+    // SAFETY: both only query; tcgetpgrp returns -1 when stdin is not our terminal
+    let (own, foreground) = unsafe { (libc::getpgrp(), libc::tcgetpgrp(libc::STDIN_FILENO)) };
+    (foreground == own).then_some(own)
+}
+
+/// Signal the process `group` (when the job has its own) and then `pids`, which also
+/// covers processes that moved to another group or session
+fn signal_job(group: Option<i32>, pids: &[i32], signal: libc::c_int) {
+    if let Some(group) = group {
+        // SAFETY: a negative pid addresses the group; it may already be gone (ESRCH)
+        unsafe {
+            libc::kill(-group, signal);
+        }
+    }
+    signal_pids(pids, signal);
+}
+
+fn signal_pids(pids: &[i32], signal: libc::c_int) {
+    for &pid in pids {
+        // The process may already be gone (ESRCH), which is exactly what we want
+        unsafe {
+            libc::kill(pid, signal);
+        }
+    }
+}
+
+fn process_exists(pid: i32) -> bool {
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+/// Record a sampling failure in the profile, and warn about it unless `quiet_errors`
+/// is set (it is still logged at debug level then)
+fn report_error(state: &mut JobState, options: &SamplerOptions, message: String) {
+    if options.quiet_errors {
+        debug!("{}", message);
+    } else {
+        warn!("{}", message);
+    }
+    state.record_error(message);
+}
+
+/// Fold a snapshot into the job state, capturing the NUMA breakdown and the
+/// private/shared split of every process that just reached a new peak when `numa`
+/// and `show_shared` are enabled, and every process's I/O counters when `show_io`
+/// is. `--alert` crossings are printed here
+fn record_snapshot(
+    state: &mut JobState,
+    snapshot: JobSnapshot,
+    inspector: &impl ProcessInspector,
+    options: &SamplerOptions,
+) {
+    let pids: Vec<i32> = if options.show_io {
+        snapshot.processes.iter().map(|p| p.pid).collect()
+    } else {
+        Vec::new()
+    };
+    if log_enabled!(log::Level::Debug) {
+        log_membership(state, &snapshot);
+    }
+    let (total_rss_kib, timestamp) = (snapshot.total_rss_kib, snapshot.timestamp);
+    let new_peaks = state.update(snapshot);
+
+    for alert in state.record_alerts(&options.alert_levels_kib, total_rss_kib, timestamp) {
+        print_alert(alert);
+    }
+
+    for pid in pids {
+        if let Some(io) = inspector.io_bytes(pid) {
+            state.record_io(pid, io);
+        }
+    }
+
+    for pid in new_peaks {
+        if options.numa
+            && let Some(nodes) = inspector.numa_rss_kib(pid)
+        {
+            state.record_numa(pid, nodes);
+        }
+        if options.show_shared
+            && let Some(split) = inspector.memory_split(pid)
+        {
+            state.record_memory_split(pid, split);
+        }
+    }
+}
+
+/// Log the processes that joined the job since the previous sample and those that
+/// left it. The previous sample is the latest `last_seen` of any process
+fn log_membership(state: &JobState, snapshot: &JobSnapshot) {
+    for process in &snapshot.processes {
+        if !state.process_stats.contains_key(&process.pid) {
+            debug!(
+                "pid {} joined the job (parent {}): {}",
+                process.pid, process.ppid, process.command
+            );
+        }
+    }
+
+    let Some(previous) = state.process_stats.values().map(|p| p.last_seen).max() else {
+        return;
+    };
+    let current: HashSet<i32> = snapshot.processes.iter().map(|p| p.pid).collect();
+    for stats in state.process_stats.values() {
+        if stats.last_seen == previous && !current.contains(&stats.pid) {
+            debug!(
+                "pid {} left the job after {:.1}s: {}",
+                stats.pid, stats.lifetime_seconds, stats.command
+            );
+        }
+    }
+}
+
+/// Total and available system memory, or None where the platform does not say
+fn read_system_memory(inspector: &impl ProcessInspector) -> Option<SystemMemory> {
+    inspector
+        .system_memory()
+        .map_err(|e| debug!("System memory unavailable: {:#}", e))
+        .ok()
+}
+
+/// Answer SIGUSR1: report where the job stands and write the partial outputs, leaving
+/// `state` as it was so sampling carries on undisturbed. `current` is a sample taken
+/// for the summary and not recorded
+fn dump_partial(
+    state: &JobState,
+    current: Option<JobSnapshot>,
+    command: &[String],
+    (interval, elapsed): (Duration, Duration),
+    options: &SamplerOptions,
+    observer: &mut impl SampleObserver,
+) {
+    let (current_kib, processes) = match current {
+        Some(snapshot) => (snapshot.total_rss_kib, snapshot.processes),
+        None => (0, Vec::new()),
+    };
+    let outputs = &options.partial_outputs;
+    let mut written = Vec::new();
+    if outputs.json.is_some() || outputs.timeline.is_some() {
+        let result = state
+            .clone()
+            .into_profile(command.to_vec(), interval, None, options.filter.clone())
+            .and_then(|profile| write_partial_outputs(&profile, outputs, &mut written));
+        if let Err(e) = result {
+            warn!("Failed to write partial results: {:#}", e);
+        }
+    }
+    observer.observe(SampleEvent::Summary {
+        elapsed,
+        current_kib,
+        peak_kib: state.max_total_rss_kib.max(current_kib),
+        processes: &processes,
+        written: &written,
+    });
+}
+
+/// Write the profile so far to the partial files, adding each to `written`. Each
+/// replaces the previous one atomically, so a reader never sees a half-written file
+fn write_partial_outputs<'a>(
+    profile: &JobProfile,
+    outputs: &'a PartialOutputs,
+    written: &mut Vec<(&'static str, &'a str)>,
+) -> Result<()> {
+    if let Some(ref path) = outputs.json {
+        let mut json = Vec::new();
+        write_profile_json(profile, outputs.json_compact, &mut json)?;
+        write_atomic(Path::new(path), &json)?;
+        written.push(("JSON profile", path.as_str()));
+    }
+    if let Some(ref path) = outputs.timeline {
+        let mut csv = Vec::new();
+        write_timeline_csv(profile, &mut csv)?;
+        write_atomic(Path::new(path), &csv)?;
+        written.push(("timeline CSV", path.as_str()));
+    }
+    Ok(())
 }
 
-fn spawn_command(command: &[String], silent: bool) -> Result<Child> {
+/// Run a command to completion the way `run_and_profile` would start it, without
+/// sampling, returning its exit code (None if a signal ended it). Used for `--warmup`.
+/// `--timeout` and interrupts stop the job as they would while sampling, and are
+/// errors, since the run did not finish
+pub fn run_unsampled(
+    command: &[String],
+    options: &SamplerOptions,
+    inspector: &impl ProcessInspector,
+) -> Result<Option<i32>> {
+    let _interrupts = InterruptGuard::install();
+    let mut child =
+        spawn_command(command, options, None, None).context("Failed to start command")?;
+    let root_pid = child.id() as i32;
+    let group = in_own_group(options).then_some(root_pid);
+    let job = JobMembership::new(root_pid, options.track_by, &options.filter, inspector);
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().context("Failed to wait for command")? {
+            return Ok(status.code());
+        }
+        if let Some(signal) = signals::take_interrupt() {
+            forward_interrupt(&mut child, &job, group, signal, inspector, options);
+            anyhow::bail!(
+                "Warmup run interrupted by {}; not measuring",
+                signals::name(signal)
+            );
+        }
+        if let Some(timeout) = options.timeout
+            && started.elapsed() >= timeout
+        {
+            let job_pids = live_job_pids(inspector, &job, root_pid);
+            kill_job_tree(
+                &mut child,
+                &job,
+                group,
+                job_pids,
+                inspector,
+                options.kill_grace,
+            );
+            let _ = child.wait();
+            anyhow::bail!("Warmup run exceeded --timeout {:?}; not measuring", timeout);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn spawn_command(
+    command: &[String],
+    options: &SamplerOptions,
+    pty: Option<&Pty>,
+    captured_output: Option<&CapturedOutput>,
+) -> Result<Child> {
     if command.is_empty() {
         anyhow::bail!("Command is empty");
     }
 
-    let program = &command[0];
-    let args = &command[1..];
+    // The shell gets the command line as a single argument, so its quoting is kept
+    let (program, args) = match &options.shell {
+        Some(shell) => (shell, vec!["-c".to_string(), command.join(" ")]),
+        None => (&command[0], command[1..].to_vec()),
+    };
 
     let mut cmd = Command::new(program);
     cmd.args(args);
 
-    // Suppress output if silent flag is set
-    if silent {
+    if options.env.clear {
+        cmd.env_clear();
+    }
+    cmd.envs(options.env.vars.iter().map(|(key, value)| (key, value)));
+
+    if let Some(dir) = &options.cwd {
+        cmd.current_dir(dir);
+    }
+
+    // A group of its own is what lets an interrupt reach the whole job (a pty session
+    // already is one)
+    if pty.is_none() && in_own_group(options) {
+        cmd.process_group(0);
+    }
+
+    if let Some(pty) = pty {
+        pty.attach(&mut cmd)?;
+    } else if let Some(captured) = captured_output {
+        let create = |path: &str| {
+            std::fs::File::create(path)
+                .context(MemwatchError::export_failed("Failed to create", path))
+        };
+        cmd.stdout(create(&captured.stdout)?);
+        cmd.stderr(create(&captured.stderr)?);
+    } else if options.silent {
+        // Suppress output if silent flag is set
         cmd.stdout(std::process::Stdio::null());
         cmd.stderr(std::process::Stdio::null());
     }
 
-    cmd.spawn()
-        .context(format!("Failed to execute: {}", program))
+    cmd.spawn().map_err(|e| {
+        // Told apart the way a shell does, with its exit codes (127 and 126)
+        let failure = match e.kind() {
+            std::io::ErrorKind::NotFound => MemwatchError::command_not_found(
+                program,
+                similar_command(program, child_path(&options.env).as_deref()),
+            ),
+            std::io::ErrorKind::PermissionDenied => MemwatchError::permission_denied(program),
+            _ => MemwatchError::spawn_failed(format!("Failed to execute: {}", program), program),
+        };
+        anyhow::Error::new(e).context(failure)
+    })
+}
+
+/// PATH as the command would see it, which is where it was looked up
+fn child_path(env: &ChildEnv) -> Option<std::ffi::OsString> {
+    match env.vars.iter().rev().find(|(key, _)| key == "PATH") {
+        Some((_, value)) => Some(value.into()),
+        None if env.clear => None,
+        None => std::env::var_os("PATH"),
+    }
+}
+
+/// The executable on `path` whose name is closest to `program`, if any is close
+/// enough to be the intended one. Paths (`./tool`) are taken literally
+fn similar_command(program: &str, path: Option<&std::ffi::OsStr>) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if program.contains('/') {
+        return None;
+    }
+    // Two edits catch a swapped pair of letters even in short names
+    let max_distance = (program.chars().count() / 2).max(2);
+    let mut best: Option<(usize, String)> = None;
+    for dir in std::env::split_paths(path?) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let distance = edit_distance(program, &name);
+            if distance > max_distance
+                || best
+                    .as_ref()
+                    .is_some_and(|b| (distance, &name) >= (b.0, &b.1))
+            {
+                continue;
+            }
+            // Only stat the names that would win
+            let executable = std::fs::metadata(entry.path())
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+            if executable {
+                best = Some((distance, name));
+            }
+        }
+    }
+    best.map(|(_, name)| name)
+}
+
+/// Levenshtein distance between `a` and `b`, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Take a single snapshot of the job rooted at an already running `pid`, for
+/// `memwatch probe`. memwatch itself is left out, as it would be during a run
+pub fn probe_job(
+    pid: i32,
+    track_by: TrackBy,
+    filter: &FilterConfig,
+    inspector: &impl ProcessInspector,
+) -> Result<JobSnapshot> {
+    let job = JobMembership::new(pid, track_by, filter, inspector);
+    let mut snapshot = sample_job_tree(inspector, &job)?;
+    if !snapshot.processes.iter().any(|p| p.pid == pid) {
+        match job.selection {
+            Some(_) => anyhow::bail!(
+                "No running process with PID {} among the selected users and PIDs",
+                pid
+            ),
+            None => anyhow::bail!("No running process with PID {}", pid),
+        }
+    }
+
+    let own_pid = std::process::id() as i32;
+    snapshot.processes.retain(|p| p.pid != own_pid);
+    snapshot.total_rss_kib = snapshot.processes.iter().map(|p| p.rss_kib).sum();
+    Ok(snapshot)
+}
+
+/// Sample all processes and filter to those in the job tree (down to `--depth`), then
+/// apply the `--user`/`--exclude-user` and `--pid`/`--exclude-pid` selection
+fn sample_job_tree(inspector: &impl ProcessInspector, job: &JobMembership) -> Result<JobSnapshot> {
+    let started = Instant::now();
+    let mut snapshot = sample_descendants(inspector, |p| job.is_root(p), job.max_depth)?;
+    if let Some(ref selection) = job.selection {
+        select_processes(&mut snapshot, selection);
+    }
+    if job.uncounted.is_some() {
+        snapshot.processes.retain(|p| !job.is_uncounted(p));
+        snapshot.total_rss_kib = snapshot.processes.iter().map(|p| p.rss_kib).sum();
+    }
+    debug!(
+        "Sampled {} job process(es), {} in total, in {:.2?}",
+        snapshot.processes.len(),
+        format_memory(snapshot.total_rss_kib),
+        started.elapsed()
+    );
+    for process in &snapshot.processes {
+        trace!(
+            "pid {}: {} {}",
+            process.pid,
+            format_memory(process.rss_kib),
+            process.command
+        );
+    }
+    Ok(snapshot)
+}
+
+/// Keep the processes `selection` allows. PID subtrees are resolved from this
+/// snapshot's parent links, so children that appear later are covered too
+fn select_processes(snapshot: &mut JobSnapshot, selection: &FilterConfig) {
+    let ppid_map: HashMap<i32, i32> = snapshot.processes.iter().map(|p| (p.pid, p.ppid)).collect();
+    let wanted = (!selection.pids.is_empty())
+        .then(|| find_job_pids(selection.pids.iter().copied(), &ppid_map, None));
+    let unwanted = find_job_pids(selection.exclude_pids.iter().copied(), &ppid_map, None);
+
+    snapshot.processes.retain(|p| {
+        selection.selects_owner(p.uid)
+            && wanted.as_ref().is_none_or(|w| w.contains(&p.pid))
+            && !unwanted.contains(&p.pid)
+    });
+    snapshot.total_rss_kib = snapshot.processes.iter().map(|p| p.rss_kib).sum();
+}
+
+/// Which processes make up the job: the root's descendants, plus everything in its
+/// session or process group under `--track-by session|pgid`
+struct JobMembership {
+    root_pid: i32,
+    group: Option<(TrackBy, i32)>,
+    /// memwatch and its ancestors, which usually share the root's session and
+    /// process group but are never part of the job
+    excluded: HashSet<i32>,
+    /// The filter holding the user and PID selection, when any is given
+    selection: Option<FilterConfig>,
+    /// Levels of descendants to follow below each root (`--depth`)
+    max_depth: Option<usize>,
+    /// Processes found in the tree but left out of the job (`--exclude-self`)
+    uncounted: Option<Uncounted>,
+}
+
+/// memwatch itself, and the root while it is still the `--shell` wrapper. A shell that
+/// `exec`s its last command hands the root PID over to the workload, so the root is
+/// only left out while its command line is the shell's
+struct Uncounted {
+    own_pid: i32,
+    shell_prefix: Option<String>,
+}
+
+impl JobMembership {
+    fn new(
+        root_pid: i32,
+        track_by: TrackBy,
+        filter: &FilterConfig,
+        inspector: &impl ProcessInspector,
+    ) -> Self {
+        // SAFETY: getsid and getpgid have no preconditions
+        let id = match track_by {
+            TrackBy::Tree => -1,
+            TrackBy::Session => unsafe { libc::getsid(root_pid) },
+            TrackBy::Pgid => unsafe { libc::getpgid(root_pid) },
+        };
+
+        let excluded = if id >= 0 {
+            let ppids: HashMap<i32, i32> = inspector
+                .snapshot_all()
+                .map(|all| all.iter().map(|p| (p.pid, p.ppid)).collect())
+                .unwrap_or_default();
+            memwatch_ancestry(std::process::id() as i32, &ppids)
+        } else {
+            HashSet::new()
+        };
+
+        Self {
+            root_pid,
+            group: (id >= 0).then_some((track_by, id)),
+            excluded,
+            selection: filter.narrows_sampling().then(|| filter.clone()),
+            max_depth: filter.max_depth,
+            uncounted: None,
+        }
+    }
+
+    /// Leave memwatch and, when the command runs through `shell`, the shell out of every
+    /// snapshot. The tree is still walked through them, so their children stay in the job
+    fn exclude_self(&mut self, shell: Option<&str>) {
+        self.uncounted = Some(Uncounted {
+            own_pid: std::process::id() as i32,
+            shell_prefix: shell.map(|shell| format!("{} -c ", shell)),
+        });
+    }
+
+    fn is_uncounted(&self, proc: &ProcessSample) -> bool {
+        self.uncounted.as_ref().is_some_and(|uncounted| {
+            proc.pid == uncounted.own_pid
+                || (proc.pid == self.root_pid
+                    && uncounted
+                        .shell_prefix
+                        .as_ref()
+                        .is_some_and(|prefix| proc.command.starts_with(prefix.as_str())))
+        })
+    }
+
+    fn is_root(&self, proc: &ProcessSample) -> bool {
+        if proc.pid == self.root_pid {
+            return true;
+        }
+        let in_group = match self.group {
+            Some((TrackBy::Session, sid)) => proc.session == Some(sid),
+            Some((TrackBy::Pgid, pgid)) => proc.process_group == Some(pgid),
+            _ => false,
+        };
+        in_group && !self.excluded.contains(&proc.pid)
+    }
+}
+
+/// `pid` and every process above it in the tree
+fn memwatch_ancestry(pid: i32, ppid_map: &HashMap<i32, i32>) -> HashSet<i32> {
+    let mut ancestry = HashSet::from([pid]);
+    let mut current = pid;
+    while let Some(&ppid) = ppid_map.get(&current) {
+        if ppid <= 0 || !ancestry.insert(ppid) {
+            break;
+        }
+        current = ppid;
+    }
+    ancestry
 }
 
-/// Sample all processes and filter to those in the job tree
-fn sample_job_tree(inspector: &impl ProcessInspector, root_pid: i32) -> Result<JobSnapshot> {
+/// Sample every process matching `is_root`, plus their descendants up to `max_depth`
+/// levels down (all of them when None)
+fn sample_descendants(
+    inspector: &impl ProcessInspector,
+    is_root: impl Fn(&ProcessSample) -> bool,
+    max_depth: Option<usize>,
+) -> Result<JobSnapshot> {
     let all_processes = inspector.snapshot_all()?;
+    let roots: Vec<i32> = all_processes
+        .iter()
+        .filter(|p| is_root(p))
+        .map(|p| p.pid)
+        .collect();
 
     // Build PID -> ProcessSample map and PID -> PPID map
     let mut pid_map: HashMap<i32, ProcessSample> = HashMap::new();
@@ -116,7 +1550,7 @@ fn sample_job_tree(inspector: &impl ProcessInspector, root_pid: i32) -> Result<J
     }
 
     // Find all PIDs that belong to the job tree
-    let job_pids = find_job_pids(root_pid, &ppid_map);
+    let job_pids = find_job_pids(roots, &ppid_map, max_depth);
 
     // Collect processes in the job (move out of HashMap to avoid cloning)
     let mut job_processes = Vec::new();
@@ -133,24 +1567,31 @@ fn sample_job_tree(inspector: &impl ProcessInspector, root_pid: i32) -> Result<J
         timestamp: Utc::now(),
         total_rss_kib,
         processes: job_processes,
+        reparented: pid_map.into_values().filter(|p| p.ppid == 1).collect(),
     })
 }
 
-/// Find all PIDs that are descendants of the root PID (including root itself)
-fn find_job_pids(root_pid: i32, ppid_map: &HashMap<i32, i32>) -> HashSet<i32> {
-    let mut job_pids = HashSet::new();
-    job_pids.insert(root_pid);
+/// Find all PIDs that are descendants of the root PIDs (including the roots themselves),
+/// at most `max_depth` levels below a root when given (0 keeps only the roots)
+fn find_job_pids(
+    roots: impl IntoIterator<Item = i32>,
+    ppid_map: &HashMap<i32, i32>,
+    max_depth: Option<usize>,
+) -> HashSet<i32> {
+    let mut job_pids: HashSet<i32> = roots.into_iter().collect();
 
-    // Iteratively find children
-    let mut changed = true;
-    while changed {
-        changed = false;
-        for (&pid, &ppid) in ppid_map {
-            if !job_pids.contains(&pid) && job_pids.contains(&ppid) {
-                job_pids.insert(pid);
-                changed = true;
-            }
-        }
+    // Find children one level at a time, so each PID's distance from a root is known
+    let mut frontier = job_pids.clone();
+    let mut depth = 0;
+    while !frontier.is_empty() && max_depth.is_none_or(|max| depth < max) {
+        let children: HashSet<i32> = ppid_map
+            .iter()
+            .filter(|(pid, ppid)| frontier.contains(ppid) && !job_pids.contains(pid))
+            .map(|(&pid, _)| pid)
+            .collect();
+        job_pids.extend(&children);
+        frontier = children;
+        depth += 1;
     }
 
     job_pids
@@ -160,6 +1601,39 @@ fn find_job_pids(root_pid: i32, ppid_map: &HashMap<i32, i32>) -> HashSet<i32> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_auto_interval_schedule() {
+        assert_eq!(
+            auto_interval_schedule(Duration::from_millis(500)),
+            vec![
+                Duration::from_millis(500),
+                Duration::from_millis(100),
+                Duration::from_millis(20)
+            ]
+        );
+
+        // Stops at the minimum instead of repeating it
+        assert_eq!(
+            auto_interval_schedule(Duration::from_micros(300)),
+            vec![Duration::from_micros(300), MIN_INTERVAL]
+        );
+    }
+
+    #[test]
+    fn test_effective_interval() {
+        let (interval, warning) = effective_interval(Duration::from_millis(500));
+        assert_eq!(interval, Duration::from_millis(500));
+        assert!(warning.is_none());
+
+        let (interval, warning) = effective_interval(Duration::from_micros(250));
+        assert_eq!(interval, Duration::from_micros(250));
+        assert!(warning.unwrap().contains("overhead"));
+
+        let (interval, warning) = effective_interval(Duration::ZERO);
+        assert_eq!(interval, MIN_INTERVAL);
+        assert!(warning.unwrap().contains("minimum"));
+    }
+
     #[test]
     fn test_find_job_pids_simple() {
         let mut ppid_map = HashMap::new();
@@ -169,7 +1643,7 @@ mod tests {
         ppid_map.insert(400, 200); // grandchild
         ppid_map.insert(500, 50); // unrelated process
 
-        let job_pids = find_job_pids(100, &ppid_map);
+        let job_pids = find_job_pids([100], &ppid_map, None);
 
         assert!(job_pids.contains(&100));
         assert!(job_pids.contains(&200));
@@ -187,7 +1661,7 @@ mod tests {
         ppid_map.insert(30, 20);
         ppid_map.insert(40, 30);
 
-        let job_pids = find_job_pids(10, &ppid_map);
+        let job_pids = find_job_pids([10], &ppid_map, None);
 
         assert!(job_pids.contains(&10));
         assert!(job_pids.contains(&20));
@@ -195,4 +1669,675 @@ mod tests {
         assert!(job_pids.contains(&40));
         assert!(!job_pids.contains(&1));
     }
+
+    #[test]
+    fn test_find_job_pids_depth_limited() {
+        let mut ppid_map = HashMap::new();
+        ppid_map.insert(1, 0);
+        ppid_map.insert(10, 1); // root
+        ppid_map.insert(20, 10); // depth 1
+        ppid_map.insert(21, 10); // depth 1
+        ppid_map.insert(30, 20); // depth 2
+        ppid_map.insert(40, 30); // depth 3
+        ppid_map.insert(50, 1); // unrelated
+
+        let at_depth = |depth| {
+            let mut pids: Vec<_> = find_job_pids([10], &ppid_map, Some(depth))
+                .into_iter()
+                .collect();
+            pids.sort();
+            pids
+        };
+
+        assert_eq!(at_depth(0), vec![10]);
+        assert_eq!(at_depth(1), vec![10, 20, 21]);
+        assert_eq!(at_depth(2), vec![10, 20, 21, 30]);
+        assert_eq!(at_depth(10), vec![10, 20, 21, 30, 40]);
+
+        // Several roots each get the full depth
+        let mut pids: Vec<_> = find_job_pids([20, 50], &ppid_map, Some(1))
+            .into_iter()
+            .collect();
+        pids.sort();
+        assert_eq!(pids, vec![20, 30, 50]);
+    }
+
+    /// Reports every process at 1 GiB so any job immediately breaches a smaller limit
+    struct InflatingInspector(crate::inspector::PlatformInspector);
+
+    impl ProcessInspector for InflatingInspector {
+        fn snapshot_all(&self) -> Result<Vec<ProcessSample>> {
+            let mut processes = self.0.snapshot_all()?;
+            for proc in &mut processes {
+                proc.rss_kib = 1024 * 1024;
+            }
+            Ok(processes)
+        }
+    }
+
+    #[test]
+    fn test_max_memory_kills_job() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            max_memory_kib: Some(512 * 1024),
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let inspector = InflatingInspector(crate::inspector::create_inspector());
+
+        let started = Instant::now();
+        let profile = run_and_profile(
+            vec!["sleep".to_string(), "30".to_string()],
+            &options,
+            &inspector,
+        )
+        .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(profile.killed_reason, Some(KillReason::MemoryLimit));
+        assert_eq!(profile.memory_limit_kib, Some(512 * 1024));
+        // Killed by a signal, so there is no exit code of its own
+        assert_eq!(profile.exit_code, None);
+    }
+
+    #[test]
+    fn test_timeout_kills_job() {
+        let options = SamplerOptions {
+            silent: true,
+            timeout: Some(Duration::from_secs(1)),
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let profile = run_and_profile(
+            vec!["sleep".to_string(), "60".to_string()],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["timed_out"], true);
+        assert_eq!(json["killed_reason"], "timeout");
+        assert_eq!(json["timeout_seconds"], 1.0);
+        assert!(profile.duration_seconds >= 1.0);
+    }
+
+    #[test]
+    fn test_idle_stop_ends_a_steady_job() {
+        let options = SamplerOptions {
+            silent: true,
+            idle_stop: Some(IdleStop {
+                after: Duration::from_secs(1),
+                epsilon_kib: 1024,
+                detach: false,
+            }),
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let profile = run_and_profile(
+            vec!["sleep".to_string(), "60".to_string()],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        // sleep's memory does not move, so it is stopped long before it would exit
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(profile.killed_reason, Some(KillReason::Idle));
+        assert!(profile.idle_stopped);
+        assert_eq!(profile.exit_code, None);
+        assert!(profile.duration_seconds >= 1.0);
+    }
+
+    #[test]
+    fn test_idle_watch() {
+        let stop = IdleStop {
+            after: Duration::from_secs(10),
+            epsilon_kib: 100,
+            detach: false,
+        };
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        // Climbing, then settled around 5000 KiB with jitter under the epsilon
+        let mut watch = IdleWatch::new(stop);
+        let series = [1000, 3000, 5000, 5040, 4960, 5100, 5020, 5000, 4990];
+        let idle: Vec<bool> = series
+            .iter()
+            .enumerate()
+            .map(|(i, &kib)| watch.observe(kib, at(2 * i as u64)))
+            .collect();
+        // Flat from t=4s, so idle from t=14s
+        assert_eq!(idle.iter().position(|&idle| idle), Some(7));
+
+        // A move beyond the epsilon starts the wait over
+        let mut watch = IdleWatch::new(stop);
+        assert!(!watch.observe(5000, at(0)));
+        assert!(!watch.observe(5000, at(8)));
+        assert!(!watch.observe(5200, at(9)));
+        assert!(!watch.observe(5200, at(18)));
+        assert!(watch.observe(5150, at(19)));
+    }
+
+    #[test]
+    fn test_observer_follows_the_samples() {
+        #[derive(Default)]
+        struct Recorder {
+            peaks: Vec<u64>,
+            finished: bool,
+        }
+        impl SampleObserver for Recorder {
+            fn observe(&mut self, event: SampleEvent<'_>) {
+                if let SampleEvent::Sample { peak_kib, .. } = event {
+                    self.peaks.push(peak_kib);
+                }
+            }
+            fn finish(&mut self) {
+                self.finished = true;
+            }
+        }
+
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            max_samples: Some(3),
+            ..Default::default()
+        };
+        let mut recorder = Recorder::default();
+        let profile = run_and_observe(
+            vec!["sleep".to_string(), "0.3".to_string()],
+            &options,
+            &crate::inspector::create_inspector(),
+            &mut recorder,
+        )
+        .unwrap();
+
+        // The samples taken on the way out are recorded without being reported
+        assert!(!recorder.peaks.is_empty());
+        assert!(recorder.peaks.len() <= profile.samples);
+        assert!(recorder.peaks.is_sorted());
+        assert!(recorder.finished);
+    }
+
+    #[test]
+    fn test_max_samples_stops_sampling_but_waits() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            track_timeline: true,
+            silent: true,
+            max_samples: Some(3),
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        let profile = run_and_profile(
+            vec!["sleep".to_string(), "0.5".to_string()],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert_eq!(profile.samples, 3);
+        assert_eq!(profile.timeline.as_ref().unwrap().len(), 3);
+        assert!(profile.sampling_truncated);
+        assert!(!profile.detached);
+        // Still waited for the command to finish
+        assert_eq!(profile.exit_code, Some(0));
+        assert!(profile.duration_seconds >= 0.5);
+    }
+
+    #[test]
+    fn test_kill_after_samples_stops_a_long_running_child() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            track_timeline: true,
+            silent: true,
+            max_samples: Some(3),
+            kill_after_samples: true,
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        let profile = run_and_profile(
+            vec!["sleep".to_string(), "30".to_string()],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert_eq!(profile.samples, 3);
+        assert!(profile.sampling_truncated);
+        assert_eq!(profile.killed_reason, Some(KillReason::SampleLimit));
+        assert!(!profile.timed_out);
+        // Stopped right away rather than after the command's 30 seconds
+        assert!(profile.duration_seconds < 10.0);
+    }
+
+    #[test]
+    fn test_delay_skips_startup() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            track_timeline: true,
+            silent: true,
+            delay: Duration::from_millis(300),
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let inspector = crate::inspector::create_inspector();
+
+        let before = Utc::now();
+        let profile = run_and_profile(
+            vec!["sleep".to_string(), "0.6".to_string()],
+            &options,
+            &inspector,
+        )
+        .unwrap();
+        assert_eq!(profile.delay_seconds, Some(0.3));
+        assert!(!profile.exited_during_delay);
+        assert!((profile.start_time - before).num_milliseconds() >= 300);
+        assert!(profile.samples > 0);
+
+        // A command that finishes during the delay is never sampled
+        let profile = run_and_profile(vec!["true".to_string()], &options, &inspector).unwrap();
+        assert!(profile.exited_during_delay);
+        assert_eq!(profile.samples, 0);
+        assert_eq!(profile.exit_code, Some(0));
+    }
+
+    #[test]
+    fn test_drain_samples_orphans() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            drain: Some(Duration::from_secs(5)),
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        // The shell exits first, leaving the background sleep behind
+        let profile = run_and_profile(
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "sleep 0.8 & sleep 0.2".to_string(),
+            ],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert!(!profile.drain_timed_out);
+        let sleep = profile
+            .processes
+            .iter()
+            .find(|p| p.command == "sleep 0.8")
+            .unwrap();
+        assert!(sleep.lifetime_seconds >= 0.5);
+    }
+
+    #[test]
+    fn test_wait_for_children_follows_orphans_until_they_exit() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            timeout: Some(Duration::from_secs(10)),
+            wait_for_children: true,
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        // The shell exits first, leaving the background sleep behind
+        let profile = run_and_profile(
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "sleep 0.8 & sleep 0.2; exit 3".to_string(),
+            ],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert!(!profile.drain_timed_out);
+        assert!(profile.drain_seconds.unwrap() >= 0.4);
+        // The exit code is still the root's
+        assert_eq!(profile.exit_code, Some(3));
+        let sleep = profile
+            .processes
+            .iter()
+            .find(|p| p.command == "sleep 0.8")
+            .unwrap();
+        assert!(sleep.lifetime_seconds >= 0.5);
+    }
+
+    #[test]
+    fn test_track_by_pgid_follows_daemonized_children() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            track_by: TrackBy::Pgid,
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        // The subshell exits at once, so the sleep is reparented out of the tree
+        let profile = run_and_profile(
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "(sleep 0.6 &); sleep 0.4".to_string(),
+            ],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert!(profile.processes.iter().any(|p| p.command == "sleep 0.6"));
+        let own_pid = std::process::id() as i32;
+        assert!(profile.processes.iter().all(|p| p.pid != own_pid));
+    }
+
+    #[test]
+    fn test_probe_job_lists_running_tree() {
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 2 & wait"])
+            .spawn()
+            .unwrap();
+        let root = child.id() as i32;
+        let inspector = crate::inspector::create_inspector();
+
+        // Give the shell a moment to start its child
+        let mut snapshot =
+            probe_job(root, TrackBy::Tree, &FilterConfig::default(), &inspector).unwrap();
+        for _ in 0..50 {
+            if snapshot.processes.len() >= 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+            snapshot =
+                probe_job(root, TrackBy::Tree, &FilterConfig::default(), &inspector).unwrap();
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert!(snapshot.processes.iter().any(|p| p.pid == root));
+        assert!(snapshot.processes.iter().any(|p| p.command == "sleep 2"));
+        assert!(
+            probe_job(
+                i32::MAX,
+                TrackBy::Tree,
+                &FilterConfig::default(),
+                &inspector
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_select_processes_by_pid_subtree() {
+        let sample = |pid: i32, ppid: i32, uid: u32| ProcessSample {
+            uid: Some(uid),
+            ..ProcessSample::for_test(pid, ppid, 100, &format!("p{}", pid))
+        };
+        // 10 -> {20 -> 21 -> 22, 30 -> 31}, with 31 owned by another user
+        let snapshot = || JobSnapshot {
+            timestamp: Utc::now(),
+            total_rss_kib: 700,
+            processes: vec![
+                sample(10, 1, 1000),
+                sample(20, 10, 1000),
+                sample(21, 20, 1000),
+                sample(22, 21, 1000),
+                sample(30, 10, 1000),
+                sample(31, 30, 0),
+            ],
+            reparented: Vec::new(),
+        };
+        let selected = |selection: FilterConfig| {
+            let mut snapshot = snapshot();
+            select_processes(&mut snapshot, &selection);
+            let mut pids: Vec<_> = snapshot.processes.iter().map(|p| p.pid).collect();
+            pids.sort();
+            (pids, snapshot.total_rss_kib)
+        };
+
+        assert_eq!(
+            selected(FilterConfig {
+                pids: vec![20],
+                ..Default::default()
+            }),
+            (vec![20, 21, 22], 300)
+        );
+        assert_eq!(
+            selected(FilterConfig {
+                exclude_pids: vec![21, 30],
+                ..Default::default()
+            }),
+            (vec![10, 20], 200)
+        );
+        // Composes with the owner filter and with each other
+        assert_eq!(
+            selected(FilterConfig {
+                pids: vec![10],
+                exclude_pids: vec![22],
+                exclude_uids: vec![0],
+                ..Default::default()
+            }),
+            (vec![10, 20, 21, 30], 400)
+        );
+    }
+
+    #[test]
+    fn test_memwatch_ancestry() {
+        let ppid_map = HashMap::from([(300, 200), (200, 100), (100, 1), (1, 0), (400, 100)]);
+
+        assert_eq!(
+            memwatch_ancestry(300, &ppid_map),
+            HashSet::from([300, 200, 100, 1])
+        );
+        assert_eq!(memwatch_ancestry(999, &ppid_map), HashSet::from([999]));
+    }
+
+    #[test]
+    fn test_child_env() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            env: ChildEnv {
+                clear: true,
+                vars: vec![
+                    ("GREETING".to_string(), "hi".to_string()),
+                    ("GREETING".to_string(), "hello".to_string()),
+                ],
+            },
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        // The later value wins, and nothing is inherited (HOME is unset)
+        let profile = run_and_profile(
+            vec![
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                "test \"$GREETING\" = hello && test -z \"$HOME\"".to_string(),
+            ],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert_eq!(profile.exit_code, Some(0));
+    }
+
+    #[test]
+    fn test_cwd() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            cwd: Some(resolve_working_dir("/tmp").unwrap()),
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        let profile = run_and_profile(
+            vec!["pwd".to_string()],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert_eq!(profile.exit_code, Some(0));
+        assert!(profile.samples >= 1);
+        assert_eq!(
+            profile.working_dir.as_deref(),
+            options.cwd.as_ref().and_then(|d| d.to_str())
+        );
+
+        assert!(
+            resolve_working_dir("/nonexistent/memwatch")
+                .unwrap_err()
+                .contains("does not exist")
+        );
+        assert!(
+            resolve_working_dir("/bin/sh")
+                .unwrap_err()
+                .contains("not a directory")
+        );
+    }
+
+    #[test]
+    fn test_shell_runs_the_joined_command_line() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            shell: Some("/bin/sh".to_string()),
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        // A pipeline with quoting that only works if the string reaches the shell intact
+        let profile = run_and_profile(
+            vec!["printf '%s\\n' 'a  b' | grep -qx 'a  b'".to_string()],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+        assert_eq!(profile.exit_code, Some(0));
+        assert_eq!(profile.shell.as_deref(), Some("/bin/sh"));
+        assert_eq!(profile.command.len(), 1);
+
+        // Separate arguments are joined with spaces
+        let profile = run_and_profile(
+            vec![
+                "test".to_string(),
+                "1".to_string(),
+                "-eq".to_string(),
+                "2".to_string(),
+            ],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+        assert_eq!(profile.exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_exclude_self_drops_the_shell_but_keeps_its_children() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            silent: true,
+            shell: Some("/bin/sh".to_string()),
+            exclude_self: true,
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        // Two commands, so the shell waits for sleep instead of exec'ing it
+        let profile = run_and_profile(
+            vec!["sleep 0.3; true".to_string()],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert_eq!(profile.exit_code, Some(0));
+        assert!(profile.self_excluded);
+        assert!(
+            profile
+                .processes
+                .iter()
+                .any(|p| p.command.starts_with("sleep"))
+        );
+        assert!(
+            !profile
+                .processes
+                .iter()
+                .any(|p| p.command.starts_with("/bin/sh -c "))
+        );
+    }
+
+    #[test]
+    fn test_pty_gives_child_a_terminal() {
+        let options = SamplerOptions {
+            interval: Duration::from_millis(20),
+            pty: true,
+            kill_grace: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        let profile = run_and_profile(
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "test -t 0 && test -t 1 && test -t 2".to_string(),
+            ],
+            &options,
+            &crate::inspector::create_inspector(),
+        )
+        .unwrap();
+
+        assert_eq!(profile.exit_code, Some(0));
+    }
+
+    #[test]
+    fn test_similar_command() {
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_eq!(edit_distance("pytohn", "python"), 2);
+        assert_eq!(edit_distance("", "ls"), 2);
+        assert_eq!(edit_distance("cargo", "cargo"), 0);
+
+        /// Removes the directory even when an assertion fails
+        struct TempDir(std::path::PathBuf);
+        impl Drop for TempDir {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_dir_all(&self.0);
+            }
+        }
+
+        let dir =
+            TempDir(std::env::temp_dir().join(format!("memwatch-path-{}", std::process::id())));
+        std::fs::create_dir_all(&dir.0).unwrap();
+        for (name, mode) in [("python3", 0o755), ("pytest", 0o755), ("python", 0o644)] {
+            let path = dir.0.join(name);
+            std::fs::write(&path, "").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+        let path = dir.0.clone().into_os_string();
+
+        // `python` is closer but not executable
+        assert_eq!(
+            similar_command("pytohn", Some(&path)),
+            Some("python3".to_string())
+        );
+        assert_eq!(similar_command("cmake", Some(&path)), None);
+        assert_eq!(similar_command("./pytohn", Some(&path)), None);
+        assert_eq!(similar_command("pytohn", None), None);
+    }
 }
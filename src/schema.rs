@@ -0,0 +1,129 @@
+//! JSON Schemas of memwatch's JSON output (`memwatch schema`), derived from
+//! `JobProfile` and `SystemSnapshot` so they cannot drift from what `--json` writes.
+
+use crate::types::{JobProfile, SCHEMA_VERSION, SystemSnapshot};
+use schemars::schema::RootSchema;
+use serde_json::Value;
+
+/// `$id` of the profile schema for `SCHEMA_VERSION`
+pub fn schema_id() -> String {
+    format!(
+        "https://github.com/AlvinKuruvilla/memwatch/schema/profile-v{}.json",
+        SCHEMA_VERSION
+    )
+}
+
+/// `$id` of the `snapshot --json` schema for `SCHEMA_VERSION`
+pub fn snapshot_schema_id() -> String {
+    format!(
+        "https://github.com/AlvinKuruvilla/memwatch/schema/snapshot-v{}.json",
+        SCHEMA_VERSION
+    )
+}
+
+/// The JSON Schema a profile written by this memwatch conforms to
+pub fn profile_schema() -> Value {
+    with_id(
+        schemars::schema_for!(JobProfile),
+        schema_id(),
+        "memwatch job profile",
+    )
+}
+
+/// The JSON Schema of what `memwatch snapshot --json` prints
+pub fn snapshot_schema() -> Value {
+    with_id(
+        schemars::schema_for!(SystemSnapshot),
+        snapshot_schema_id(),
+        "memwatch process snapshot",
+    )
+}
+
+/// `schema` as JSON, with its `$id` and a description naming the schema version
+fn with_id(schema: RootSchema, id: String, what: &str) -> Value {
+    let mut schema = serde_json::to_value(schema).expect("schemas serialize");
+    if let Value::Object(ref mut object) = schema {
+        object.insert("$id".to_string(), Value::String(id));
+        object.insert(
+            "description".to_string(),
+            Value::String(format!("{}, schema_version {}", what, SCHEMA_VERSION)),
+        );
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FilterConfig, ProcessStats, TimelinePoint};
+    use chrono::Utc;
+
+    #[test]
+    fn test_schema_validates_a_profile() {
+        let schema = profile_schema();
+        assert_eq!(schema["$id"], schema_id());
+        let validator = jsonschema::validator_for(&schema).unwrap();
+
+        let profile = JobProfile {
+            schema_version: SCHEMA_VERSION,
+            command: vec!["make".to_string(), "-j8".to_string()],
+            max_total_rss_kib: 4096,
+            processes: vec![ProcessStats {
+                pid: 42,
+                command: "cc1plus big.cpp".to_string(),
+                max_rss_kib: 4096,
+                ..Default::default()
+            }],
+            timeline: Some(vec![TimelinePoint {
+                timestamp: Utc::now(),
+                elapsed_seconds: 0.5,
+                total_rss_kib: 4096,
+                process_count: 1,
+                process_rss_kib: None,
+                smoothed_rss_kib: None,
+            }]),
+            // A single pattern is written as a plain string
+            filter: Some(FilterConfig {
+                exclude_patterns: vec!["cargo".to_string()],
+                include_patterns: vec!["cc1".to_string(), "ld".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let instance = serde_json::to_value(&profile).unwrap();
+        assert!(validator.is_valid(&instance));
+
+        // The schema does constrain the profile
+        let mut broken = instance;
+        broken["max_total_rss_kib"] = Value::String("4 MiB".to_string());
+        assert!(!validator.is_valid(&broken));
+    }
+
+    #[test]
+    fn test_snapshot_schema_validates_a_snapshot() {
+        let schema = snapshot_schema();
+        assert_eq!(schema["$id"], snapshot_schema_id());
+        let validator = jsonschema::validator_for(&schema).unwrap();
+
+        let profile = JobProfile {
+            processes: vec![ProcessStats {
+                pid: 42,
+                ppid: 1,
+                command: "postgres -D /var/lib/postgres".to_string(),
+                max_rss_kib: 4096,
+                ..Default::default()
+            }],
+            filter: Some(FilterConfig {
+                include_patterns: vec!["postgres".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let instance = serde_json::to_value(SystemSnapshot::from_profile(&profile, 10)).unwrap();
+        assert!(validator.is_valid(&instance));
+
+        let mut broken = instance;
+        broken["processes"][0]["rss_kib"] = Value::String("4 MiB".to_string());
+        assert!(!validator.is_valid(&broken));
+    }
+}
@@ -0,0 +1,170 @@
+//! Signals memwatch handles itself while it profiles a command.
+//!
+//! A handler only records which signal arrived; the sampling loop polls for it
+//! between samples, so nothing but an atomic store happens in signal context.
+//! Besides the interrupts, SIGUSR1 asks for an intermediate summary and SIGUSR2
+//! pauses or resumes sampling.
+
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+/// Signals that end sampling early: Ctrl-C, and the SIGTERM a CI runner or
+/// orchestrator sends when it gives up on a step
+pub const INTERRUPTS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/// The last interrupt that arrived and was not taken yet, or 0
+static PENDING: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn record_interrupt(signal: libc::c_int) {
+    PENDING.store(signal, Ordering::SeqCst);
+}
+
+/// Signal asking for an intermediate summary without stopping the run
+pub const DUMP: libc::c_int = libc::SIGUSR1;
+
+/// Whether a `DUMP` arrived and was not taken yet. Several before the loop gets to
+/// it collapse into one summary
+static DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn record_dump_request(_signal: libc::c_int) {
+    DUMP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Signal that pauses sampling, or resumes it when paused
+pub const PAUSE: libc::c_int = libc::SIGUSR2;
+
+/// A flag a signal handler flips and the sampling loop takes. Two flips before the
+/// loop gets to it cancel out, as pausing and resuming at once would
+pub(crate) struct SignalToggle(AtomicBool);
+
+impl SignalToggle {
+    pub(crate) const fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    /// Flip the flag; safe in signal context
+    pub(crate) fn flip(&self) {
+        self.0.fetch_xor(true, Ordering::SeqCst);
+    }
+
+    /// Whether the flag flipped an odd number of times since the last call
+    pub(crate) fn take(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+}
+
+static PAUSE_TOGGLE: SignalToggle = SignalToggle::new();
+
+extern "C" fn record_pause_toggle(_signal: libc::c_int) {
+    PAUSE_TOGGLE.flip();
+}
+
+/// Catches `INTERRUPTS`, `DUMP`, and `PAUSE` for as long as it is alive, then
+/// restores the previous dispositions
+pub struct InterruptGuard {
+    previous: Vec<(libc::c_int, libc::sighandler_t)>,
+}
+
+impl InterruptGuard {
+    pub fn install() -> Self {
+        PENDING.store(0, Ordering::SeqCst);
+        DUMP_REQUESTED.store(false, Ordering::SeqCst);
+        PAUSE_TOGGLE.take();
+        let interrupt = record_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        let dump = record_dump_request as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        let pause = record_pause_toggle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        let previous = INTERRUPTS
+            .iter()
+            .map(|&signal| (signal, interrupt))
+            .chain([(DUMP, dump), (PAUSE, pause)])
+            // SAFETY: the handlers only store to an atomic, which is async-signal-safe
+            .map(|(signal, handler)| (signal, unsafe { libc::signal(signal, handler) }))
+            .collect();
+        Self { previous }
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        for &(signal, previous) in &self.previous {
+            // SAFETY: puts back the disposition `install` replaced
+            unsafe {
+                libc::signal(signal, previous);
+            }
+        }
+    }
+}
+
+/// The interrupt that arrived since the last call (or since `InterruptGuard::install`)
+pub fn take_interrupt() -> Option<libc::c_int> {
+    match PENDING.swap(0, Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// The interrupt that arrived, without consuming it
+pub fn interrupt_pending() -> Option<libc::c_int> {
+    match PENDING.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// Whether a `DUMP` arrived since the last call (or since `InterruptGuard::install`)
+pub fn take_dump_request() -> bool {
+    DUMP_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Whether sampling should switch between paused and running, after a `PAUSE`
+pub fn take_pause_toggle() -> bool {
+    PAUSE_TOGGLE.take()
+}
+
+/// Conventional name of a signal, as recorded in profiles
+pub fn name(signal: libc::c_int) -> &'static str {
+    match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        _ => "signal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_toggle() {
+        let toggle = SignalToggle::new();
+        assert!(!toggle.take());
+
+        toggle.flip();
+        assert!(toggle.take());
+        // Taking resets it
+        assert!(!toggle.take());
+
+        // A pause and a resume before the loop looks cancel out, a third flip does not
+        toggle.flip();
+        toggle.flip();
+        assert!(!toggle.take());
+        for _ in 0..3 {
+            toggle.flip();
+        }
+        assert!(toggle.take());
+    }
+}
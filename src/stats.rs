@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+/// Descriptive statistics over a set of measurements
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Sample standard deviation (0 for a single value)
+    pub stddev: f64,
+}
+
+impl Stats {
+    /// Compute statistics, returning None for an empty input
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let mean = sorted.iter().sum::<f64>() / sorted.len() as f64;
+        let squares: f64 = sorted.iter().map(|v| (v - mean).powi(2)).sum();
+        Some(Self {
+            min: sorted[0],
+            median: median_of_sorted(&sorted),
+            max: sorted[sorted.len() - 1],
+            mean,
+            stddev: (squares / (sorted.len() - 1).max(1) as f64).sqrt(),
+        })
+    }
+}
+
+/// Median of a set of values (average of the two middle values for even counts)
+pub fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    Some(median_of_sorted(&sorted))
+}
+
+/// Centered moving average over `window` points (which must be odd).
+///
+/// Near the ends the window shrinks symmetrically to the points available, so the
+/// output has the same length as the input and the first and last values are kept.
+pub fn moving_average(values: &[u64], window: usize) -> Vec<u64> {
+    let half = window / 2;
+
+    (0..values.len())
+        .map(|i| {
+            let reach = half.min(i).min(values.len() - 1 - i);
+            let slice = &values[i - reach..=i + reach];
+            let sum: u64 = slice.iter().sum();
+            (sum as f64 / slice.len() as f64).round() as u64
+        })
+        .collect()
+}
+
+fn median_of_sorted(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_odd_count() {
+        let stats = Stats::from_values(&[3.0, 1.0, 2.0]).unwrap();
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.median, 2.0);
+        assert_eq!(stats.max, 3.0);
+        assert_eq!(stats.mean, 2.0);
+        assert_eq!(stats.stddev, 1.0);
+        assert_eq!(Stats::from_values(&[5.0]).unwrap().stddev, 0.0);
+    }
+
+    #[test]
+    fn test_stats_even_count_and_empty() {
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), Some(2.5));
+        assert_eq!(median(&[]), None);
+        assert!(Stats::from_values(&[]).is_none());
+    }
+
+    #[test]
+    fn test_moving_average() {
+        let values = [10, 20, 60, 20, 10, 40];
+        // Edges shrink the window: index 1 averages 3 points, index 0 only itself
+        assert_eq!(moving_average(&values, 3), vec![10, 30, 33, 30, 23, 40]);
+        assert_eq!(moving_average(&values, 5), vec![10, 30, 24, 30, 23, 40]);
+        assert_eq!(moving_average(&values, 1), values.to_vec());
+        assert!(moving_average(&[], 3).is_empty());
+    }
+}
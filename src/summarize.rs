@@ -0,0 +1,290 @@
+use crate::error::MemwatchError;
+use crate::reporter::{color_choice, format_duration, format_memory};
+use crate::stats::Stats;
+use crate::types::JobProfile;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+/// A profile file that could not be used
+#[derive(Debug, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub error: String,
+}
+
+/// Aggregate statistics for all runs of one command
+#[derive(Debug, Serialize)]
+pub struct GroupSummary {
+    pub key: String,
+    pub runs: usize,
+    pub peak_rss_kib: Stats,
+    pub duration_seconds: Stats,
+    pub worst_file: String,
+    pub worst_peak_rss_kib: u64,
+}
+
+/// Result of summarizing a set of profiles
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub groups: Vec<GroupSummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// Expand the given inputs into profile paths.
+///
+/// Directories contribute every `*.json` file directly inside them, and arguments
+/// containing glob metacharacters are expanded (for quoted patterns the shell left alone).
+pub fn expand_inputs(inputs: &[String]) -> (Vec<PathBuf>, Vec<SkippedFile>) {
+    let mut paths = Vec::new();
+    let mut skipped = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+
+        if path.is_dir() {
+            match list_json_files(path) {
+                Ok(mut files) => paths.append(&mut files),
+                Err(e) => skipped.push(SkippedFile {
+                    path: input.clone(),
+                    error: format!("{:#}", e),
+                }),
+            }
+        } else if input.contains(['*', '?', '[']) {
+            match glob::glob(input) {
+                Ok(matches) => {
+                    let mut files: Vec<PathBuf> = matches.filter_map(|m| m.ok()).collect();
+                    files.sort();
+                    paths.append(&mut files);
+                }
+                Err(e) => skipped.push(SkippedFile {
+                    path: input.clone(),
+                    error: format!("Invalid glob pattern: {}", e),
+                }),
+            }
+        } else {
+            paths.push(path.to_path_buf());
+        }
+    }
+
+    (paths, skipped)
+}
+
+fn list_json_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .context(MemwatchError::read_failed(
+            "Failed to read directory",
+            dir.display(),
+        ))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Load every profile, skipping (and recording) files that fail to parse
+pub fn load_profiles(paths: &[PathBuf]) -> (Vec<(String, JobProfile)>, Vec<SkippedFile>) {
+    let mut profiles = Vec::new();
+    let mut skipped = Vec::new();
+
+    for path in paths {
+        let path = path.to_string_lossy().to_string();
+        match JobProfile::load_json(&path) {
+            Ok(profile) => profiles.push((path, profile)),
+            Err(e) => skipped.push(SkippedFile {
+                path,
+                error: format!("{:#}", e),
+            }),
+        }
+    }
+
+    (profiles, skipped)
+}
+
+/// Group profiles by label, or by command for unlabeled runs, and compute per-group
+/// statistics
+pub fn summarize(profiles: &[(String, JobProfile)]) -> Vec<GroupSummary> {
+    let mut groups: BTreeMap<String, Vec<&(String, JobProfile)>> = BTreeMap::new();
+    for entry in profiles {
+        groups.entry(entry.1.name()).or_default().push(entry);
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, runs)| {
+            let peaks: Vec<f64> = runs
+                .iter()
+                .map(|(_, p)| p.max_total_rss_kib as f64)
+                .collect();
+            let durations: Vec<f64> = runs.iter().map(|(_, p)| p.duration_seconds).collect();
+            let (worst_file, worst) = runs
+                .iter()
+                .max_by_key(|(_, p)| p.max_total_rss_kib)
+                .expect("groups are never empty");
+
+            GroupSummary {
+                key,
+                runs: runs.len(),
+                peak_rss_kib: Stats::from_values(&peaks).expect("groups are never empty"),
+                duration_seconds: Stats::from_values(&durations).expect("groups are never empty"),
+                worst_file: worst_file.clone(),
+                worst_peak_rss_kib: worst.max_total_rss_kib,
+            }
+        })
+        .collect()
+}
+
+/// Print the aggregate table for every group
+pub fn print_run_summary(summary: &RunSummary) {
+    let mut stdout = StandardStream::stdout(color_choice());
+    let runs: usize = summary.groups.iter().map(|g| g.runs).sum();
+
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true));
+    print!("\nRUN SUMMARY");
+    let _ = stdout.reset();
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    print!(" ({} profiles, {} commands)", runs, summary.groups.len());
+    let _ = stdout.reset();
+    println!();
+
+    for group in &summary.groups {
+        println!("\n{}  ({} runs)", group.key, group.runs);
+
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+        println!(
+            "  {:8}  {:>10}  {:>10}  {:>10}  {:>10}",
+            "", "MIN", "MEDIAN", "MAX", "MEAN"
+        );
+        let _ = stdout.reset();
+
+        let peak = &group.peak_rss_kib;
+        print!("  {:8}  ", "Peak");
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
+        println!(
+            "{:>10}  {:>10}  {:>10}  {:>10}",
+            format_memory(peak.min as u64),
+            format_memory(peak.median as u64),
+            format_memory(peak.max as u64),
+            format_memory(peak.mean as u64)
+        );
+        let _ = stdout.reset();
+
+        let duration = &group.duration_seconds;
+        print!("  {:8}  ", "Duration");
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
+        println!(
+            "{:>10}  {:>10}  {:>10}  {:>10}",
+            format_duration(duration.min),
+            format_duration(duration.median),
+            format_duration(duration.max),
+            format_duration(duration.mean)
+        );
+        let _ = stdout.reset();
+
+        print!("  Worst:    {} ", group.worst_file);
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)));
+        print!("({})", format_memory(group.worst_peak_rss_kib));
+        let _ = stdout.reset();
+        println!();
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn profile(command: &str, peak: u64, duration: f64) -> JobProfile {
+        JobProfile {
+            command: command.split(' ').map(String::from).collect(),
+            start_time: Utc::now(),
+            end_time: Utc::now(),
+            duration_seconds: duration,
+            max_total_rss_kib: peak,
+            exit_code: Some(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_summarize_groups_by_command() {
+        let profiles = vec![
+            ("a.json".to_string(), profile("cargo test", 100, 10.0)),
+            ("b.json".to_string(), profile("cargo test", 300, 20.0)),
+            ("c.json".to_string(), profile("cargo test", 200, 30.0)),
+            ("d.json".to_string(), profile("make", 50, 1.0)),
+        ];
+
+        let groups = summarize(&profiles);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key, "cargo test");
+        assert_eq!(groups[0].runs, 3);
+        assert_eq!(groups[0].peak_rss_kib.median, 200.0);
+        assert_eq!(groups[0].peak_rss_kib.mean, 200.0);
+        assert_eq!(groups[0].duration_seconds.max, 30.0);
+        assert_eq!(groups[0].worst_file, "b.json");
+        assert_eq!(groups[1].key, "make");
+    }
+
+    #[test]
+    fn test_summarize_groups_by_label() {
+        let labeled = |command: &str, label: &str, peak: u64| JobProfile {
+            label: Some(label.to_string()),
+            ..profile(command, peak, 1.0)
+        };
+        // Differently spelled commands with one label form one group
+        let profiles = vec![
+            (
+                "a.json".to_string(),
+                labeled("sh -c ./bench --seed 1", "bench", 100),
+            ),
+            (
+                "b.json".to_string(),
+                labeled("sh -c ./bench --seed 2", "bench", 300),
+            ),
+            (
+                "c.json".to_string(),
+                profile("sh -c ./bench --seed 1", 200, 1.0),
+            ),
+        ];
+
+        let groups = summarize(&profiles);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key, "bench");
+        assert_eq!(groups[0].runs, 2);
+        assert_eq!(groups[1].key, "sh -c ./bench --seed 1");
+    }
+
+    #[test]
+    fn test_load_profiles_skips_unparseable_files() {
+        let dir = std::env::temp_dir().join(format!("memwatch-summarize-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.json");
+        let bad = dir.join("bad.json");
+        fs::write(
+            &good,
+            serde_json::to_string(&profile("ls", 10, 1.0)).unwrap(),
+        )
+        .unwrap();
+        fs::write(&bad, "{ not json").unwrap();
+
+        let (paths, skipped) = expand_inputs(&[dir.to_string_lossy().to_string()]);
+        assert!(skipped.is_empty());
+        assert_eq!(paths.len(), 2);
+
+        let (profiles, skipped) = load_profiles(&paths);
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].path.ends_with("bad.json"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
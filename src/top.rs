@@ -0,0 +1,262 @@
+use crate::cli::TopSort;
+use crate::inspector::ProcessInspector;
+use crate::reporter::format_memory;
+use crate::types::{ProcessSample, extract_command_name};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use std::collections::HashMap;
+use std::io::{Write, stdout};
+use std::time::Duration;
+
+/// A process tree: a root process and all of its descendants
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeGroup {
+    pub root_pid: i32,
+    pub command: String,
+    pub process_count: usize,
+    pub total_rss_kib: u64,
+}
+
+/// Group processes into trees rooted at children of PID 1 (or of the kernel, PID 0).
+///
+/// Processes whose parent is not in the snapshot (it exited and they have not been
+/// reparented yet) root their own tree.
+pub fn group_trees(processes: &[ProcessSample]) -> Vec<TreeGroup> {
+    let by_pid: HashMap<i32, &ProcessSample> = processes.iter().map(|p| (p.pid, p)).collect();
+
+    let root_of = |mut pid: i32| {
+        // Bounded walk so a corrupt (cyclic) ppid chain cannot hang the viewer
+        for _ in 0..by_pid.len() {
+            match by_pid.get(&pid) {
+                Some(p) if p.ppid > 1 && by_pid.contains_key(&p.ppid) => pid = p.ppid,
+                _ => break,
+            }
+        }
+        pid
+    };
+
+    let mut groups: HashMap<i32, TreeGroup> = HashMap::new();
+    for proc in processes {
+        let root = root_of(proc.pid);
+        let group = groups.entry(root).or_insert_with(|| TreeGroup {
+            root_pid: root,
+            command: by_pid
+                .get(&root)
+                .map(|p| p.command.clone())
+                .unwrap_or_default(),
+            process_count: 0,
+            total_rss_kib: 0,
+        });
+        group.process_count += 1;
+        group.total_rss_kib += proc.rss_kib;
+    }
+
+    groups.into_values().collect()
+}
+
+/// Order trees by the chosen key (largest first for numeric keys)
+pub fn sort_trees(trees: &mut [TreeGroup], sort: TopSort) {
+    match sort {
+        TopSort::Rss => trees.sort_by(|a, b| {
+            b.total_rss_kib
+                .cmp(&a.total_rss_kib)
+                .then(a.root_pid.cmp(&b.root_pid))
+        }),
+        TopSort::Procs => trees.sort_by(|a, b| {
+            b.process_count
+                .cmp(&a.process_count)
+                .then(b.total_rss_kib.cmp(&a.total_rss_kib))
+        }),
+        TopSort::Name => trees.sort_by(|a, b| {
+            extract_command_name(&a.command)
+                .cmp(&extract_command_name(&b.command))
+                .then(a.root_pid.cmp(&b.root_pid))
+        }),
+    }
+}
+
+fn next_sort(sort: TopSort) -> TopSort {
+    match sort {
+        TopSort::Rss => TopSort::Procs,
+        TopSort::Procs => TopSort::Name,
+        TopSort::Name => TopSort::Rss,
+    }
+}
+
+/// Puts the terminal in raw mode on the alternate screen and restores it on drop,
+/// including when rendering fails or panics
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        let guard = Self;
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Run the live viewer until the user quits (q, Esc, or Ctrl-C)
+pub fn run(inspector: &impl ProcessInspector, interval: Duration, sort: TopSort) -> Result<()> {
+    let _guard = TerminalGuard::enter()?;
+    let mut sort = sort;
+
+    loop {
+        let mut trees = group_trees(&inspector.snapshot_all()?);
+        sort_trees(&mut trees, sort);
+        render(&trees, sort)?;
+
+        // Wait out the interval, reacting to keys as they arrive
+        let deadline = std::time::Instant::now() + interval;
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() || !event::poll(remaining)? {
+                break;
+            }
+            if let Event::Key(key) = event::read()? {
+                match handle_key(key, sort) {
+                    KeyAction::Quit => return Ok(()),
+                    KeyAction::Sort(new_sort) => {
+                        sort = new_sort;
+                        sort_trees(&mut trees, sort);
+                        render(&trees, sort)?;
+                    }
+                    KeyAction::None => {}
+                }
+            }
+        }
+    }
+}
+
+enum KeyAction {
+    Quit,
+    Sort(TopSort),
+    None,
+}
+
+fn handle_key(key: KeyEvent, sort: TopSort) -> KeyAction {
+    if key.kind != KeyEventKind::Press {
+        return KeyAction::None;
+    }
+
+    match key.code {
+        // Raw mode swallows SIGINT, so Ctrl-C arrives as a key
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => KeyAction::Quit,
+        KeyCode::Char('q') | KeyCode::Esc => KeyAction::Quit,
+        KeyCode::Char('s') => KeyAction::Sort(next_sort(sort)),
+        KeyCode::Char('m') => KeyAction::Sort(TopSort::Rss),
+        KeyCode::Char('p') => KeyAction::Sort(TopSort::Procs),
+        KeyCode::Char('n') => KeyAction::Sort(TopSort::Name),
+        _ => KeyAction::None,
+    }
+}
+
+fn render(trees: &[TreeGroup], sort: TopSort) -> Result<()> {
+    let (width, height) = match terminal::size() {
+        Ok((w, h)) if w > 0 && h > 0 => (w, h),
+        _ => (80, 24),
+    };
+    let mut out = stdout();
+
+    let total_rss: u64 = trees.iter().map(|t| t.total_rss_kib).sum();
+    let sort_name = match sort {
+        TopSort::Rss => "rss",
+        TopSort::Procs => "procs",
+        TopSort::Name => "name",
+    };
+
+    queue!(
+        out,
+        cursor::MoveTo(0, 0),
+        terminal::Clear(terminal::ClearType::All),
+        SetForegroundColor(Color::Cyan),
+        SetAttribute(crossterm::style::Attribute::Bold),
+        Print("memwatch top"),
+        SetAttribute(crossterm::style::Attribute::Reset),
+        ResetColor,
+        Print(format!(
+            "  {} trees, {} total  |  sort: {}  |  s: cycle sort  m/p/n: rss/procs/name  q: quit",
+            trees.len(),
+            format_memory(total_rss),
+            sort_name
+        )),
+        cursor::MoveTo(0, 2),
+        SetForegroundColor(Color::DarkGrey),
+        Print(format!(
+            "  {:>7}  {:>5}  {:>10}  COMMAND",
+            "ROOT", "PROCS", "TREE RSS"
+        )),
+        ResetColor,
+    )?;
+
+    let rows = (height as usize).saturating_sub(4);
+    for (i, tree) in trees.iter().take(rows).enumerate() {
+        let prefix = format!(
+            "  {:>7}  {:>5}  {:>10}  ",
+            tree.root_pid,
+            tree.process_count,
+            format_memory(tree.total_rss_kib)
+        );
+        let room = (width as usize).saturating_sub(prefix.chars().count());
+        let command: String = tree.command.chars().take(room).collect();
+
+        queue!(
+            out,
+            cursor::MoveTo(0, 3 + i as u16),
+            Print(format!(
+                "  {:>7}  {:>5}  ",
+                tree.root_pid, tree.process_count
+            )),
+            SetForegroundColor(Color::Green),
+            Print(format!("{:>10}  ", format_memory(tree.total_rss_kib))),
+            ResetColor,
+            Print(command),
+        )?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_trees_by_child_of_init() {
+        let processes = vec![
+            ProcessSample::for_test(1, 0, 10, "init"),
+            ProcessSample::for_test(100, 1, 50, "sshd"),
+            ProcessSample::for_test(101, 100, 20, "bash"),
+            ProcessSample::for_test(102, 101, 500, "cargo build"),
+            ProcessSample::for_test(200, 1, 300, "postgres"),
+            ProcessSample::for_test(201, 200, 100, "postgres: writer"),
+            // Parent already gone
+            ProcessSample::for_test(300, 299, 40, "orphan"),
+        ];
+
+        let mut trees = group_trees(&processes);
+        sort_trees(&mut trees, TopSort::Rss);
+
+        assert_eq!(
+            trees
+                .iter()
+                .map(|t| (t.root_pid, t.process_count, t.total_rss_kib))
+                .collect::<Vec<_>>(),
+            vec![(100, 3, 570), (200, 2, 400), (300, 1, 40), (1, 1, 10)]
+        );
+        assert_eq!(trees[0].command, "sshd");
+
+        sort_trees(&mut trees, TopSort::Name);
+        assert_eq!(trees[0].command, "init");
+    }
+}
@@ -0,0 +1,246 @@
+use crate::reporter::{color_choice, format_memory};
+use crate::stats;
+use crate::types::JobProfile;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Outcome of comparing the latest run against its history
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Ok,
+    Regression,
+    InsufficientData,
+}
+
+/// Peak memory trend for one command
+#[derive(Debug, Serialize)]
+pub struct CommandTrend {
+    /// The runs' label, or their command line when unlabeled
+    pub command: String,
+    pub runs: usize,
+    /// Total peaks in chronological order
+    pub peaks_kib: Vec<u64>,
+    /// Least-squares slope of peak over run index
+    pub slope_kib_per_run: f64,
+    pub latest_kib: u64,
+    /// Median of up to `window` runs preceding the latest one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline_kib: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_percent: Option<f64>,
+    pub verdict: Verdict,
+}
+
+/// Group profiles by label (or command, for unlabeled runs), order each group by start
+/// time, and evaluate the trend.
+///
+/// The baseline is the median of the previous `window` runs rather than the mean, so a
+/// single outlier run in the history does not mask or fake a regression.
+pub fn analyze(
+    profiles: Vec<JobProfile>,
+    window: usize,
+    threshold_percent: f64,
+) -> Vec<CommandTrend> {
+    let mut groups: BTreeMap<String, Vec<JobProfile>> = BTreeMap::new();
+    for profile in profiles {
+        groups.entry(profile.name()).or_default().push(profile);
+    }
+
+    groups
+        .into_iter()
+        .map(|(command, mut runs)| {
+            runs.sort_by_key(|p| p.start_time);
+            let peaks_kib: Vec<u64> = runs.iter().map(|p| p.max_total_rss_kib).collect();
+            let latest_kib = *peaks_kib.last().expect("groups are never empty");
+
+            let history: Vec<f64> = peaks_kib[..peaks_kib.len() - 1]
+                .iter()
+                .rev()
+                .take(window)
+                .map(|&p| p as f64)
+                .collect();
+            let baseline_kib = stats::median(&history);
+
+            let change_percent = baseline_kib
+                .filter(|&b| b > 0.0)
+                .map(|b| (latest_kib as f64 - b) / b * 100.0);
+
+            let verdict = match change_percent {
+                None => Verdict::InsufficientData,
+                Some(pct) if pct > threshold_percent => Verdict::Regression,
+                Some(_) => Verdict::Ok,
+            };
+
+            CommandTrend {
+                command,
+                runs: runs.len(),
+                slope_kib_per_run: linear_slope(&peaks_kib),
+                peaks_kib,
+                latest_kib,
+                baseline_kib,
+                change_percent,
+                verdict,
+            }
+        })
+        .collect()
+}
+
+/// Least-squares slope of the values against their index (0 for fewer than two values)
+fn linear_slope(values: &[u64]) -> f64 {
+    let n = values.len() as f64;
+    if values.len() < 2 {
+        return 0.0;
+    }
+
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().map(|&v| v as f64).sum::<f64>() / n;
+
+    let (mut num, mut den) = (0.0, 0.0);
+    for (i, &v) in values.iter().enumerate() {
+        let dx = i as f64 - mean_x;
+        num += dx * (v as f64 - mean_y);
+        den += dx * dx;
+    }
+
+    num / den
+}
+
+/// Render values as a unicode sparkline scaled between their min and max
+pub fn sparkline(values: &[u64]) -> String {
+    let (Some(&min), Some(&max)) = (values.iter().min(), values.iter().max()) else {
+        return String::new();
+    };
+
+    values
+        .iter()
+        .map(|&v| {
+            if max == min {
+                SPARK_CHARS[SPARK_CHARS.len() / 2]
+            } else {
+                let idx = (v - min) as f64 / (max - min) as f64 * (SPARK_CHARS.len() - 1) as f64;
+                SPARK_CHARS[idx.round() as usize]
+            }
+        })
+        .collect()
+}
+
+/// Print the trend for every command
+pub fn print_trends(trends: &[CommandTrend], threshold_percent: f64) {
+    let mut stdout = StandardStream::stdout(color_choice());
+
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true));
+    print!("\nPEAK MEMORY TREND");
+    let _ = stdout.reset();
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true));
+    print!(" (regression threshold: +{:.1}%)", threshold_percent);
+    let _ = stdout.reset();
+    println!();
+
+    for trend in trends {
+        println!("\n{}  ({} runs)", trend.command, trend.runs);
+        println!("  History:  {}", sparkline(&trend.peaks_kib));
+
+        print!("  Latest:   ");
+        let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
+        print!("{}", format_memory(trend.latest_kib));
+        let _ = stdout.reset();
+        if let Some(baseline) = trend.baseline_kib {
+            print!("  (baseline median {})", format_memory(baseline as u64));
+        }
+        println!();
+
+        let sign = if trend.slope_kib_per_run < 0.0 {
+            "-"
+        } else {
+            "+"
+        };
+        println!(
+            "  Slope:    {}{} per run",
+            sign,
+            format_memory(trend.slope_kib_per_run.abs() as u64)
+        );
+
+        print!("  Verdict:  ");
+        match (trend.verdict, trend.change_percent) {
+            (Verdict::Regression, Some(pct)) => {
+                let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true));
+                print!("REGRESSION ({:+.1}%)", pct);
+            }
+            (Verdict::Ok, Some(pct)) => {
+                let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
+                print!("ok ({:+.1}%)", pct);
+            }
+            _ => {
+                let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)));
+                print!("not enough history");
+            }
+        }
+        let _ = stdout.reset();
+        println!();
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn runs(command: &str, peaks: &[u64]) -> Vec<JobProfile> {
+        let t0 = Utc::now();
+        peaks
+            .iter()
+            .enumerate()
+            .map(|(i, &peak)| JobProfile {
+                command: vec![command.to_string()],
+                start_time: t0 + Duration::days(i as i64),
+                max_total_rss_kib: peak,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_regression_detected_against_median() {
+        let mut profiles = runs("bench", &[100, 100, 100, 100, 130]);
+        // Chronological order must not depend on input order
+        profiles.reverse();
+
+        let trends = analyze(profiles, 5, 10.0);
+
+        assert_eq!(trends.len(), 1);
+        assert_eq!(trends[0].peaks_kib, vec![100, 100, 100, 100, 130]);
+        assert_eq!(trends[0].baseline_kib, Some(100.0));
+        assert_eq!(trends[0].verdict, Verdict::Regression);
+        assert!(trends[0].slope_kib_per_run > 0.0);
+    }
+
+    #[test]
+    fn test_single_outlier_does_not_skew_baseline() {
+        // One huge historical run would drag a mean-based baseline above the latest run
+        let trends = analyze(runs("bench", &[100, 1000, 100, 100, 125]), 4, 10.0);
+        assert_eq!(trends[0].baseline_kib, Some(100.0));
+        assert_eq!(trends[0].verdict, Verdict::Regression);
+
+        let trends = analyze(runs("bench", &[100, 1000, 100, 100, 105]), 4, 10.0);
+        assert_eq!(trends[0].verdict, Verdict::Ok);
+    }
+
+    #[test]
+    fn test_single_run_has_insufficient_data() {
+        let trends = analyze(runs("bench", &[100]), 5, 10.0);
+        assert_eq!(trends[0].verdict, Verdict::InsufficientData);
+        assert_eq!(trends[0].slope_kib_per_run, 0.0);
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0, 7]), "▁█");
+        assert_eq!(sparkline(&[5, 5]), "▅▅");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
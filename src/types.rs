@@ -1,48 +1,343 @@
+use crate::error::MemwatchError;
 use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Memory unit conversion constants
 pub mod memory {
     pub const KIB_PER_MIB: f64 = 1024.0;
     pub const MIB_PER_GIB: f64 = 1024.0;
     pub const KIB_PER_GIB: f64 = KIB_PER_MIB * MIB_PER_GIB;
+    pub const KIB_PER_TIB: f64 = KIB_PER_GIB * 1024.0;
+    pub const BYTES_PER_KIB: f64 = 1024.0;
+
+    /// SI (powers of 1000) counterparts, for `--units si` and `--unit-base 1000`
+    pub const BYTES_PER_KB: f64 = 1000.0;
+    pub const BYTES_PER_MB: f64 = BYTES_PER_KB * 1000.0;
+    pub const BYTES_PER_GB: f64 = BYTES_PER_MB * 1000.0;
+    pub const BYTES_PER_TB: f64 = BYTES_PER_GB * 1000.0;
+
+    /// Parse a human-readable size into KiB.
+    ///
+    /// Binary suffixes (`K`, `KiB`, `M`, `MiB`, `G`, `GiB`, `T`, `TiB`) use powers of 1024,
+    /// SI suffixes (`KB`, `MB`, `GB`, `TB`) use powers of 1000, `B` is bytes, and a bare
+    /// number is KiB (memwatch's internal unit). Suffixes are case-insensitive and
+    /// fractional values like `1.5GiB` are allowed.
+    pub fn parse_size_kib(input: &str) -> Result<u64, String> {
+        let trimmed = input.trim();
+        let split = trimmed
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(trimmed.len());
+        let (number, suffix) = trimmed.split_at(split);
+
+        let value: f64 = number
+            .parse()
+            .map_err(|_| format!("invalid size '{}': expected e.g. 512MiB or 4GB", input))?;
+
+        let kib_per_unit = match suffix.trim().to_ascii_lowercase().as_str() {
+            "" | "k" | "kib" => 1.0,
+            "b" => 1.0 / BYTES_PER_KIB,
+            "kb" => BYTES_PER_KB / BYTES_PER_KIB,
+            "m" | "mib" => KIB_PER_MIB,
+            "mb" => BYTES_PER_MB / BYTES_PER_KIB,
+            "g" | "gib" => KIB_PER_GIB,
+            "gb" => BYTES_PER_GB / BYTES_PER_KIB,
+            "t" | "tib" => KIB_PER_TIB,
+            "tb" => BYTES_PER_TB / BYTES_PER_KIB,
+            other => {
+                return Err(format!(
+                    "invalid size '{}': unknown unit '{}' (use B, KiB, MiB, GiB, TiB, KB, MB, GB, or TB)",
+                    input, other
+                ));
+            }
+        };
+
+        Ok((value * kib_per_unit).round() as u64)
+    }
 }
 
-/// Process filtering configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FilterConfig {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub exclude_pattern: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub include_pattern: Option<String>,
+/// Version of memwatch's machine-readable output: the JSON profile and the `--oneline`
+/// summary. Bumped whenever a field is removed, renamed, or changes meaning; adding an
+/// optional JSON field does not change it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Exit codes memwatch returns for its own outcomes (otherwise the child's code is used)
+pub mod exit_codes {
+    /// memwatch itself failed (bad arguments, command could not start, export failed)
+    pub const ERROR: i32 = 1;
+    /// Arguments were rejected, as by clap for unknown flags (`--error-format` codes
+    /// `invalid_argument` and `invalid_regex`)
+    pub const USAGE: i32 = 2;
+    /// A memory threshold check failed (e.g. `trend --fail-on-regression`)
+    pub const THRESHOLD_EXCEEDED: i32 = 3;
+    /// `--max-memory`: memwatch killed the job for exceeding its memory limit
+    pub const MEMORY_LIMIT_EXCEEDED: i32 = 10;
+    /// `--timeout`: memwatch killed the job for running too long (same as timeout(1))
+    pub const TIMED_OUT: i32 = 124;
+    /// `--fail-on-empty`: no memory data was captured (sysexits EX_NOINPUT)
+    pub const NO_DATA: i32 = 66;
+    /// Ctrl-C ended the run and the command had not exited (128 + SIGINT, as shells use)
+    pub const INTERRUPTED: i32 = 130;
+    /// SIGTERM ended the run and the command had not exited on its own (128 + SIGTERM)
+    pub const TERMINATED: i32 = 143;
+    /// The command does not exist (`--error-format` code `command_not_found`), as in shells
+    pub const COMMAND_NOT_FOUND: i32 = 127;
+    /// The command exists but is not executable (code `permission_denied`), as in shells
+    pub const NOT_EXECUTABLE: i32 = 126;
 }
 
-impl FilterConfig {
-    /// Format patterns as human-readable lines for display
-    pub fn display_patterns(&self) -> Vec<String> {
-        let mut lines = Vec::new();
-        if let Some(ref exclude) = self.exclude_pattern {
-            lines.push(format!("Exclude pattern: '{}'", exclude));
-        }
-        if let Some(ref include) = self.include_pattern {
-            lines.push(format!("Include pattern: '{}'", include));
+/// Outcome of comparing the peak against `--warn-above` / `--fail-above`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Peak thresholds a run was checked against, and the result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ThresholdCheck {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warn_above_kib: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fail_above_kib: Option<u64>,
+    pub verdict: Verdict,
+}
+
+/// An environment variable set for the profiled command via `--env`/`--env-file`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EnvOverride {
+    pub key: String,
+    /// Only recorded with `--capture-env`, since values may hold secrets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// Where the command's output was saved (`--capture-output`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CapturedOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Why memwatch ended a job itself instead of letting it run to completion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum KillReason {
+    /// Total job RSS exceeded `--max-memory`
+    #[serde(rename = "memory limit")]
+    MemoryLimit,
+    /// The job ran longer than `--timeout`
+    #[serde(rename = "timeout")]
+    Timeout,
+    /// `--max-samples` was reached under `--kill-after-samples`
+    #[serde(rename = "sample limit")]
+    SampleLimit,
+    /// Memory stayed flat for `--idle-stop`
+    #[serde(rename = "idle")]
+    Idle,
+}
+
+impl std::fmt::Display for KillReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KillReason::MemoryLimit => write!(f, "memory limit"),
+            KillReason::Timeout => write!(f, "timeout"),
+            KillReason::SampleLimit => write!(f, "sample limit"),
+            KillReason::Idle => write!(f, "idle"),
         }
-        lines
     }
+}
+
+/// How memory sizes are displayed (`--units`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// KiB, MiB, or GiB, whichever fits the value
+    #[default]
+    Binary,
+    /// kB, MB, or GB (powers of 1000), whichever fits the value
+    Si,
+    Kib,
+    Mib,
+    Gib,
+    /// Exact byte counts
+    Bytes,
+}
+
+/// What `max_total_rss_kib` reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum PeakMode {
+    /// Largest total RSS seen in a single sample
+    #[default]
+    Simultaneous,
+    /// Every process's own peak added up, as if they had all coincided (a worst case)
+    SumOfPeaks,
+}
+
+/// Process filtering configuration.
+///
+/// A single pattern is serialized as a plain string, as before patterns were
+/// repeatable, and several as an array
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FilterConfig {
+    #[serde(
+        rename = "exclude_pattern",
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        with = "one_or_many"
+    )]
+    #[schemars(with = "one_or_many::OneOrMany")]
+    pub exclude_patterns: Vec<String>,
+    #[serde(
+        rename = "include_pattern",
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        with = "one_or_many"
+    )]
+    #[schemars(with = "one_or_many::OneOrMany")]
+    pub include_patterns: Vec<String>,
+    /// Built-in wrapper patterns (`WRAPPER_PATTERNS`) were applied as well
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hide_wrappers: bool,
+    /// Processes whose peak stayed below this (KiB) were dropped after pattern filtering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_rss_kib: Option<u64>,
+    /// Processes observed for less than this many seconds were dropped after `min_rss_kib`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_lifetime_seconds: Option<f64>,
+    /// Only processes owned by these users were sampled (`--user`), as given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<String>,
+    /// Processes owned by these users were not sampled (`--exclude-user`), as given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_users: Vec<String>,
+    /// `users` resolved to UIDs at startup
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uids: Vec<u32>,
+    /// `exclude_users` resolved to UIDs at startup
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_uids: Vec<u32>,
+    /// Only these processes and their descendants were sampled (`--pid`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pids: Vec<i32>,
+    /// These processes and their descendants were not sampled (`--exclude-pid`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_pids: Vec<i32>,
+    /// Only processes this many levels below the root were followed (`--depth`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+}
 
+impl FilterConfig {
     /// Format patterns for CSV comment
     pub fn to_csv_comment(&self) -> String {
         let mut parts = Vec::new();
-        if let Some(ref exclude) = self.exclude_pattern {
+        for exclude in &self.exclude_patterns {
             parts.push(format!("exclude='{}'", exclude));
         }
-        if let Some(ref include) = self.include_pattern {
+        for include in &self.include_patterns {
             parts.push(format!("include='{}'", include));
         }
+        if self.hide_wrappers {
+            parts.push("hide_wrappers".to_string());
+        }
+        if let Some(min) = self.min_rss_kib {
+            parts.push(format!("min_rss_kib={}", min));
+        }
+        if let Some(min) = self.min_lifetime_seconds {
+            parts.push(format!("min_lifetime_seconds={}", min));
+        }
+        for user in &self.users {
+            parts.push(format!("user='{}'", user));
+        }
+        for user in &self.exclude_users {
+            parts.push(format!("exclude_user='{}'", user));
+        }
+        for pid in &self.pids {
+            parts.push(format!("pid={}", pid));
+        }
+        for pid in &self.exclude_pids {
+            parts.push(format!("exclude_pid={}", pid));
+        }
+        if let Some(depth) = self.max_depth {
+            parts.push(format!("max_depth={}", depth));
+        }
         parts.join(" ")
     }
+
+    /// Whether any pattern or wrapper filter is set (the size threshold aside)
+    fn has_patterns(&self) -> bool {
+        self.hide_wrappers || !self.exclude_patterns.is_empty() || !self.include_patterns.is_empty()
+    }
+
+    /// Whether processes may be left out of the report while still counting in the
+    /// totals. User and PID filters do not: they decide what is sampled at all
+    pub fn hides_processes(&self) -> bool {
+        self.has_patterns() || self.min_rss_kib.is_some() || self.min_lifetime_seconds.is_some()
+    }
+
+    /// Whether a process owned by `uid` is sampled under `--user`/`--exclude-user`.
+    /// An unknown owner only passes when no `--user` is given
+    pub fn selects_owner(&self, uid: Option<u32>) -> bool {
+        let included = self.uids.is_empty() || uid.is_some_and(|u| self.uids.contains(&u));
+        let excluded = uid.is_some_and(|u| self.exclude_uids.contains(&u));
+        included && !excluded
+    }
+
+    /// Whether user, PID or depth filters narrow which job processes are sampled
+    pub fn narrows_sampling(&self) -> bool {
+        !self.users.is_empty()
+            || !self.exclude_users.is_empty()
+            || !self.uids.is_empty()
+            || !self.exclude_uids.is_empty()
+            || !self.pids.is_empty()
+            || !self.exclude_pids.is_empty()
+            || self.max_depth.is_some()
+    }
+}
+
+/// Command-line patterns for launcher and wrapper processes hidden by `--hide-wrappers`:
+/// shells running `-c`, MPI launchers and their per-node daemons, cargo (which only
+/// builds and spawns the target), and nested memwatch instances
+pub const WRAPPER_PATTERNS: &[&str] = &[
+    r"^(\S*/)?(ba|da|z|k|fi)?sh -c(\s|$)",
+    r"^(\S*/)?(mpirun|mpiexec|mpiexec\.hydra|orterun|orted|prterun|prted|hydra_pmi_proxy)(\s|$)",
+    r"^(\S*/)?cargo(\s|$)",
+    r"^(\S*/)?memwatch(\s|$)",
+];
+
+/// Serde helpers for a list that is written as a bare value when it has one element
+mod one_or_many {
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(untagged)]
+    pub(super) enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    pub fn serialize<S: Serializer>(values: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        match values {
+            [one] => one.serialize(serializer),
+            _ => values.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        Ok(match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        })
+    }
 }
 
 /// One snapshot of a single process at a point in time
@@ -52,52 +347,721 @@ pub struct ProcessSample {
     pub ppid: i32,
     pub rss_kib: u64,
     pub command: String,
+    /// When the process started, as reported by the kernel (None if unavailable)
+    pub start_time: Option<DateTime<Utc>>,
+    /// Peak RSS the kernel has recorded for the process (`VmHWM`, Linux only)
+    pub hwm_kib: Option<u64>,
+    /// User plus system CPU time the process has used so far (None if unavailable)
+    pub cpu_seconds: Option<f64>,
+    /// Process group ID (None if unavailable)
+    pub process_group: Option<i32>,
+    /// Session ID (None if unavailable)
+    pub session: Option<i32>,
+    /// Real user ID of the owner (None if unavailable)
+    pub uid: Option<u32>,
+    /// Path of the running executable (`/proc/[pid]/exe`, Linux only; None if unreadable)
+    pub exe_path: Option<String>,
+}
+
+#[cfg(test)]
+impl ProcessSample {
+    /// A sample with only the tree position, RSS and command set
+    pub(crate) fn for_test(pid: i32, ppid: i32, rss_kib: u64, command: &str) -> Self {
+        ProcessSample {
+            pid,
+            ppid,
+            rss_kib,
+            command: command.to_string(),
+            start_time: None,
+            hwm_kib: None,
+            cpu_seconds: None,
+            process_group: None,
+            session: None,
+            uid: None,
+            exe_path: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl JobSnapshot {
+    /// A snapshot taken now of `processes`, whose RSS make up the total
+    pub(crate) fn for_test(processes: Vec<ProcessSample>) -> Self {
+        JobSnapshot {
+            timestamp: Utc::now(),
+            total_rss_kib: processes.iter().map(|p| p.rss_kib).sum(),
+            processes,
+            reparented: Vec::new(),
+        }
+    }
+}
+
+/// Cumulative bytes a process has caused to be read from or written to storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoBytes {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+/// Resident memory of a process split by whether other processes map it too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemorySplit {
+    pub private_kib: u64,
+    pub shared_kib: u64,
+}
+
+/// Physical memory of the whole machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemMemory {
+    pub total_kib: u64,
+    /// Memory available to new work without swapping, where the platform reports it
+    pub available_kib: Option<u64>,
 }
 
 /// Per-process statistics tracked across the job lifetime
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessStats {
     pub pid: i32,
     pub ppid: i32,
     pub command: String,
+    /// Path of the executable, which tells apart processes with the same command name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe_path: Option<String>,
     pub max_rss_kib: u64,
     pub first_seen: DateTime<Utc>,
     pub last_seen: DateTime<Utc>,
     pub peak_time: DateTime<Utc>,
+    /// RSS at the first sample that observed the process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_rss_kib: Option<u64>,
+    /// `max_rss_kib - first_rss_kib`: how much the process grew while memwatch watched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rss_growth_kib: Option<u64>,
+    /// Mean RSS over the samples that observed the process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_rss_kib: Option<u64>,
+    /// Kernel-reported process start time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<DateTime<Utc>>,
+    /// Seconds from the kernel start time to the last sample; unlike `lifetime_seconds`
+    /// this includes time the process was alive before memwatch first saw it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_seconds: Option<f64>,
+    /// Seconds between the first and last sample that observed the process
+    #[serde(default)]
+    pub lifetime_seconds: f64,
+    /// Resident KiB per NUMA node, captured at the process's peak (`--numa`, Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numa_rss_kib: Option<HashMap<u32, u64>>,
+    /// Highest RSS memwatch actually sampled, present only when `max_rss_kib` was raised
+    /// to the kernel's high-water mark (`VmHWM`) because sampling missed the true peak.
+    /// `peak_time` still refers to this sampled peak.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampled_max_rss_kib: Option<u64>,
+    /// Bytes read from storage as of the last sample (`--show-io`, Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_read_bytes: Option<u64>,
+    /// Bytes written to storage as of the last sample (`--show-io`, Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_write_bytes: Option<u64>,
+    /// Resident KiB mapped only by this process, at its peak (`--show-shared`, Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_kib: Option<u64>,
+    /// Resident KiB also mapped by other processes, at its peak (`--show-shared`, Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_kib: Option<u64>,
+    /// Label of the profile this process came from (set by `memwatch merge`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Peak as a percentage of the job's total peak (`max_total_rss_kib`). Process
+    /// peaks need not coincide, so this is approximate and can add up past 100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pct_of_job_peak: Option<f64>,
+}
+
+impl ProcessStats {
+    /// Recompute `lifetime_seconds` and `age_seconds` from the recorded timestamps.
+    ///
+    /// Kernel start times only have second precision on Linux (boot time is whole
+    /// seconds), so they can land slightly after `last_seen`; both values are clamped
+    /// to be non-negative.
+    pub fn update_lifetime(&mut self) {
+        self.lifetime_seconds = seconds_between(self.first_seen, self.last_seen);
+        self.age_seconds = self
+            .start_time
+            .map(|start| seconds_between(start, self.last_seen));
+    }
+
+    /// Recompute `rss_growth_kib` after `max_rss_kib` changed
+    pub fn update_growth(&mut self) {
+        self.rss_growth_kib = self
+            .first_rss_kib
+            .map(|first| self.max_rss_kib.saturating_sub(first));
+    }
+}
+
+/// Non-negative seconds elapsed from `from` to `to`
+fn seconds_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    ((to - from).num_milliseconds() as f64 / 1000.0).max(0.0)
+}
+
+/// Aggregated peak memory for all processes sharing a command name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ProcessGroup {
+    pub command_name: String,
+    pub count: usize,
+    pub total_peak_rss_kib: u64,
 }
 
 /// Timeline data point for time-series export
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TimelinePoint {
     pub timestamp: DateTime<Utc>,
     pub elapsed_seconds: f64,
     pub total_rss_kib: u64,
     pub process_count: usize,
+    /// RSS of every job process at this sample, keyed by PID (`--per-process-timeline`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_rss_kib: Option<BTreeMap<i32, u64>>,
+    /// Moving average of `total_rss_kib` (`--smooth`); peaks are always reported from raw data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smoothed_rss_kib: Option<u64>,
+}
+
+/// One process as seen in a raw sample
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RawProcessSample {
+    pub pid: i32,
+    pub ppid: i32,
+    pub rss_kib: u64,
+}
+
+/// Every job process at one sample, unaggregated (`--raw-samples`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RawSample {
+    pub timestamp: DateTime<Utc>,
+    pub elapsed_seconds: f64,
+    pub total_rss_kib: u64,
+    pub processes: Vec<RawProcessSample>,
+}
+
+/// One process's share of the job total at the peak sample
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PeakProcess {
+    pub pid: i32,
+    pub rss_kib: u64,
+    pub command: String,
+}
+
+/// The job tree at the sample where the total RSS peaked
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PeakSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub elapsed_seconds: f64,
+    pub total_rss_kib: u64,
+    /// Largest first
+    pub processes: Vec<PeakProcess>,
+}
+
+/// `memwatch snapshot --json`: every running process the filters keep, whatever
+/// `--top` says
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SystemSnapshot {
+    pub timestamp: DateTime<Utc>,
+    /// Processes read, before filtering
+    pub process_count: usize,
+    /// RSS of the processes kept
+    pub total_rss_kib: u64,
+    pub filter: Option<FilterConfig>,
+    /// Largest first
+    pub processes: Vec<SnapshotProcess>,
+}
+
+/// One process in a `SystemSnapshot`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SnapshotProcess {
+    pub pid: i32,
+    pub ppid: i32,
+    pub rss_kib: u64,
+    pub command: String,
+    pub exe_path: Option<String>,
+}
+
+impl SystemSnapshot {
+    /// The snapshot held by `profile`, built from a single sample of all `process_count`
+    /// processes
+    pub fn from_profile(profile: &JobProfile, process_count: usize) -> Self {
+        Self {
+            timestamp: profile.start_time,
+            process_count,
+            total_rss_kib: profile.processes.iter().map(|p| p.max_rss_kib).sum(),
+            filter: profile.filter.clone(),
+            processes: profile
+                .sorted_processes(ProcessOrder::default())
+                .into_iter()
+                .map(|p| SnapshotProcess {
+                    pid: p.pid,
+                    ppid: p.ppid,
+                    rss_kib: p.max_rss_kib,
+                    command: p.command.clone(),
+                    exe_path: p.exe_path.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A job process that was later seen reparented to init, outside the job tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EscapedProcess {
+    pub pid: i32,
+    pub command: String,
+    /// RSS when it was last seen outside the job
+    pub rss_kib: u64,
+}
+
+/// A transient failure while sampling, such as an unreadable process table
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SamplingError {
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
+}
+
+/// The moment the job's total RSS first rose above an `--alert` level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MemoryAlert {
+    pub level_kib: u64,
+    /// Total RSS of the sample that crossed the level
+    pub total_rss_kib: u64,
+    pub timestamp: DateTime<Utc>,
+    pub elapsed_seconds: f64,
+}
+
+/// A stretch of the run during which sampling was paused with SIGUSR2. The timeline
+/// has no points inside it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PausePeriod {
+    pub start: DateTime<Utc>,
+    /// Seconds into the run, on the same clock as the timeline's `elapsed_seconds`
+    pub start_elapsed_seconds: f64,
+    pub duration_seconds: f64,
+}
+
+/// Compile and run phases of a `cargo memwatch` job, split where the first process
+/// that is not part of the build appears
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BuildPhase {
+    /// Seconds into the run when the first non-build process was sampled (absent when
+    /// the job never got past building)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_end_seconds: Option<f64>,
+    /// Peak total RSS before that point
+    pub build_peak_kib: u64,
+    /// Peak RSS of the non-build processes from that point on; cargo and the compilers
+    /// are left out
+    pub run_peak_kib: u64,
+    /// The headline peak is `run_peak_kib` (`--exclude-build`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub build_excluded: bool,
+}
+
+/// The figures of the profile given to `run --baseline`, kept so the summary can show
+/// the changes and archived results stay interpretable
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BaselineComparison {
+    /// The baseline profile's path, as given
+    pub path: String,
+    pub peak_kib: u64,
+    pub duration_seconds: f64,
+    /// Each process group's total peak in the baseline, by command name
+    #[serde(default)]
+    pub group_peaks_kib: BTreeMap<String, u64>,
+}
+
+/// Per-source overview of a merged profile
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SourceSummary {
+    pub label: String,
+    pub command: Vec<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub duration_seconds: f64,
+    pub max_total_rss_kib: u64,
+    pub process_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
 }
 
 /// Complete job memory profile
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct JobProfile {
+    /// `SCHEMA_VERSION` of the memwatch that wrote this profile (0 for older files)
+    #[serde(default)]
+    pub schema_version: u32,
     pub command: Vec<String>,
+    /// User-chosen name for the run (`--label`), e.g. a commit SHA or benchmark name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Free-form metadata from `--tag KEY=VALUE`, e.g. the commit, dataset, or node
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
     pub duration_seconds: f64,
     pub interval_ms: u64,
+    /// Exact sampling interval; `interval_ms` rounds sub-millisecond intervals down to 0
+    #[serde(default)]
+    pub interval_us: u64,
+    /// Number of runs `--auto-interval` needed; the interval fields describe the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_interval_attempts: Option<usize>,
+    /// Unmeasured `--warmup` runs of the command before this one
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub warmup_runs: usize,
+    /// The headline peak, as chosen by `peak_mode`
     pub max_total_rss_kib: u64,
+    #[serde(default)]
+    pub peak_mode: PeakMode,
+    /// Units the run was displayed in (`--units`); sizes in the profile are always KiB
+    #[serde(default)]
+    pub units: Units,
+    /// Largest total RSS seen in a single sample
+    #[serde(default)]
+    pub simultaneous_peak_kib: u64,
+    /// Sum of every process's peak (before filtering); never less than the simultaneous peak
+    #[serde(default)]
+    pub sum_of_peaks_kib: u64,
+    /// User plus system CPU time of every job process (before filtering), as of the
+    /// last sample that saw each one; absent when the platform does not report it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_cpu_seconds: Option<f64>,
+    /// Bytes read from storage by every job process (before filtering; `--show-io`).
+    /// Parents include children they reaped, so this can count some I/O twice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_read_bytes: Option<u64>,
+    /// Bytes written to storage by every job process (before filtering; `--show-io`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_write_bytes: Option<u64>,
+    /// Private memory of every job process (before filtering; `--show-shared`), each
+    /// at its own peak. Unlike the RSS totals, this counts shared libraries and other
+    /// shared pages of the job not at all, so it never counts them twice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_total_kib: Option<u64>,
+    /// Job tree RSS at the first sample (`--subtract-baseline`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline_rss_kib: Option<u64>,
+    /// Peak total RSS minus the baseline, clamped at zero (`--subtract-baseline`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_above_baseline_kib: Option<u64>,
     pub samples: usize,
     pub processes: Vec<ProcessStats>,
+    /// Per-command aggregation of `processes`, sorted by total peak (descending)
+    #[serde(default)]
+    pub process_groups: Vec<ProcessGroup>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeline: Option<Vec<TimelinePoint>>,
+    /// Which processes made up the total at its peak (absent when nothing was captured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_snapshot: Option<PeakSnapshot>,
+    /// Every sample's processes before any aggregation or filtering (`--raw-samples`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_samples: Option<Vec<RawSample>>,
+    /// The command's exit code, or 128 + N when signal N ended it
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exit_code: Option<i32>,
+    /// Set when memwatch killed the job tree before it finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub killed_reason: Option<KillReason>,
+    /// Total RSS limit the job ran under (`--max-memory`, or `--max-rss-pct` resolved
+    /// against `system_total_kib`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit_kib: Option<u64>,
+    /// `--max-rss-pct`: the limit as a percent of the system's total memory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit_pct: Option<f64>,
+    /// Total physical memory of the machine the job ran on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_total_kib: Option<u64>,
+    /// Memory the system had available when the job started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_available_kib: Option<u64>,
+    /// Memory the system had available once the job had finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_available_end_kib: Option<u64>,
+    /// True when the job was killed for exceeding `--timeout`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// True when memwatch stopped the run because memory stayed flat (`--idle-stop`);
+    /// `killed_reason` says whether the job was killed, `detached` whether it was left running
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub idle_stopped: bool,
+    /// How long memory had to stay flat (`--idle-stop`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_stop_seconds: Option<f64>,
+    /// How far it could move and still count as flat (`--idle-epsilon`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_epsilon_kib: Option<u64>,
+    /// Wall-clock limit the job ran under (`--timeout`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<f64>,
+    /// How long after the command started sampling began (`--delay`); `start_time`
+    /// is when sampling began, not when the command was spawned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_seconds: Option<f64>,
+    /// The command exited before `--delay` elapsed, so nothing was sampled
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exited_during_delay: bool,
+    /// Processes left behind by the command were still running when `--drain-timeout`
+    /// (or `--timeout`, under `--wait-for-children`) elapsed, so they were not sampled
+    /// to the end
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub drain_timed_out: bool,
+    /// How long sampling went on after the command exited, following the processes it
+    /// left behind (`--drain`, `--wait-for-children`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drain_seconds: Option<f64>,
+    /// Sampling stopped at `--max-samples` before the command exited
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sampling_truncated: bool,
+    /// memwatch returned without waiting for the command (`--detach-after-samples`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub detached: bool,
+    /// Ctrl-C or SIGTERM ended sampling; `detached` is also set if the command was
+    /// left running
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// Which of them it was (`SIGINT` or `SIGTERM`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interrupt_signal: Option<String>,
+    /// Signal that ended the command, when memwatch did not send it to stop the job
+    /// (then `exit_code` is 128 + the signal)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminated_by_signal: Option<i32>,
+    /// Name of `terminated_by_signal`, such as `SIGKILL`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminated_by_signal_name: Option<String>,
+    /// `--warn-above` / `--fail-above` thresholds and the verdict against the peak
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thresholds: Option<ThresholdCheck>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_output: Option<CapturedOutput>,
+    /// Keep processes that never showed any RSS in reports and groups (`--include-zero`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_zero_rss: bool,
+    /// How `process_groups` was keyed (`--group-by`)
+    #[serde(default, skip_serializing_if = "GroupBy::is_basename")]
+    #[schemars(with = "String")]
+    pub group_by: GroupBy,
+    /// Directory the command ran in (`--cwd`), as an absolute path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    /// Shell the command line ran through (`--shell`), as `<shell> -c '<command>'`; the
+    /// shell is the root of the job tree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// memwatch and the `--shell` wrapper were left out of the job (`--exclude-self`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub self_excluded: bool,
+    /// The command started from an empty environment (`--env-clear`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub env_cleared: bool,
+    /// Variables set for the command, in the order they were applied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env_overrides: Vec<EnvOverride>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filter: Option<FilterConfig>,
+    /// Heuristic: job processes later seen reparented to init (PID 1), which memwatch
+    /// then stopped following. Work they did afterwards is not in the totals
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub escaped_process_count: usize,
+    /// The possibly escaped processes, largest last-known RSS first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub escaped_processes: Vec<EscapedProcess>,
+    /// Sampling failures during the run; the profile may have gaps around them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sampling_errors: Vec<SamplingError>,
+    /// `--alert` levels the total crossed, in the order they were crossed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<MemoryAlert>,
+    /// When sampling was paused (SIGUSR2), in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pauses: Vec<PausePeriod>,
+    /// How many of the filtered-out processes were wrappers hidden by `--hide-wrappers`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hidden_wrapper_count: usize,
+    /// Processes dropped by `--min-rss`; their memory still counts in the totals
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub below_min_rss_count: usize,
+    /// Sum of the peaks of the processes dropped by `--min-rss` (KiB)
+    #[serde(default, skip_serializing_if = "is_zero_u64")]
+    pub below_min_rss_total_kib: u64,
+    /// Processes dropped by `--min-lifetime`; their memory still counts in the totals
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub short_lived_count: usize,
+    /// Sum of the peaks of the processes dropped by `--min-lifetime` (KiB)
+    #[serde(default, skip_serializing_if = "is_zero_u64")]
+    pub short_lived_total_kib: u64,
     /// Number of processes that were filtered out
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filtered_process_count: Option<usize>,
     /// Total RSS of filtered processes (KiB)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filtered_total_rss_kib: Option<u64>,
+    /// Per-source breakdown when this profile was produced by `memwatch merge`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<Vec<SourceSummary>>,
+    /// Compile versus run split, for profiles recorded by `cargo memwatch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_phase: Option<BuildPhase>,
+    /// The profile this run was compared with (`--baseline`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline_comparison: Option<BaselineComparison>,
+}
+
+/// Build the tag map from `--tag` pairs; a repeated key keeps its last value and is
+/// returned so the caller can warn about it
+pub fn collect_tags(pairs: &[(String, String)]) -> (BTreeMap<String, String>, Vec<String>) {
+    let mut tags = BTreeMap::new();
+    let mut repeated = Vec::new();
+    for (key, value) in pairs {
+        if tags.insert(key.clone(), value.clone()).is_some() && !repeated.contains(key) {
+            repeated.push(key.clone());
+        }
+    }
+    (tags, repeated)
+}
+
+impl JobProfile {
+    /// Whether every `KEY=VALUE` pair is among this profile's tags (`--tag` filters)
+    pub fn has_tags(&self, pairs: &[(String, String)]) -> bool {
+        pairs
+            .iter()
+            .all(|(key, value)| self.tags.get(key) == Some(value))
+    }
+
+    /// The run's name: its `--label`, or the command line for unlabeled runs
+    pub fn name(&self) -> String {
+        self.label.clone().unwrap_or_else(|| self.command.join(" "))
+    }
+
+    /// File name stem for exports written into a directory: the label, or else the
+    /// program's name, with anything but letters, digits, `.`, `-` and `_` replaced by `-`
+    pub fn file_stem(&self) -> String {
+        let name = match &self.label {
+            Some(label) => label.as_str(),
+            None => self
+                .command
+                .first()
+                .and_then(|program| std::path::Path::new(program).file_name())
+                .and_then(|name| name.to_str())
+                .unwrap_or("memwatch"),
+        };
+        let stem: String = name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        // Never produce a hidden file or an empty name
+        match stem.trim_start_matches('.') {
+            "" => "memwatch".to_string(),
+            trimmed => trimmed.to_string(),
+        }
+    }
+
+    /// Tags as space-separated `key=value` pairs, in key order
+    pub fn format_tags(&self) -> String {
+        self.tags
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Exit code of an interrupted run whose command did not exit on its own:
+    /// 128 + the signal memwatch got, as shells report it
+    pub fn interrupt_exit_code(&self) -> i32 {
+        match self.interrupt_signal.as_deref() {
+            Some("SIGTERM") => exit_codes::TERMINATED,
+            _ => exit_codes::INTERRUPTED,
+        }
+    }
+
+    /// Total time sampling was paused (SIGUSR2)
+    pub fn paused_seconds(&self) -> f64 {
+        self.pauses.iter().map(|pause| pause.duration_seconds).sum()
+    }
+
+    /// Processes shown in reports: those with a nonzero peak, or all of them when
+    /// `include_zero_rss` is set
+    pub fn displayed_processes(&self) -> impl Iterator<Item = &ProcessStats> {
+        self.processes
+            .iter()
+            .filter(|p| self.include_zero_rss || p.max_rss_kib > 0)
+    }
+
+    /// `displayed_processes` in the given order
+    pub fn sorted_processes(&self, order: ProcessOrder) -> Vec<&ProcessStats> {
+        let mut processes: Vec<_> = self.displayed_processes().collect();
+        order.sort(&mut processes);
+        processes
+    }
+
+    /// Load a profile previously written with `--json`
+    pub fn load_json(path: &str) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let content = std::fs::read_to_string(path)
+            .context(MemwatchError::read_failed("Failed to read profile", path))?;
+        serde_json::from_str(&content).context(MemwatchError::read_failed(
+            "Failed to parse profile JSON",
+            path,
+        ))
+    }
+
+    /// Annotate every timeline point with a `window`-point moving average of its total RSS
+    pub fn apply_smoothing(&mut self, window: usize) {
+        if let Some(timeline) = &mut self.timeline {
+            let totals: Vec<u64> = timeline.iter().map(|p| p.total_rss_kib).collect();
+            for (point, smoothed) in timeline
+                .iter_mut()
+                .zip(crate::stats::moving_average(&totals, window))
+            {
+                point.smoothed_rss_kib = Some(smoothed);
+            }
+        }
+    }
+
+    /// Report the peak chosen by `mode` as `max_total_rss_kib`
+    pub fn apply_peak_mode(&mut self, mode: PeakMode) {
+        self.peak_mode = mode;
+        self.max_total_rss_kib = match mode {
+            PeakMode::Simultaneous => self.simultaneous_peak_kib,
+            PeakMode::SumOfPeaks => self.sum_of_peaks_kib,
+        };
+        self.update_peak_shares();
+        if let Some(baseline) = self.baseline_rss_kib {
+            self.apply_baseline(baseline);
+        }
+    }
+
+    /// Recompute each process's `pct_of_job_peak` after `max_total_rss_kib` changed
+    pub fn update_peak_shares(&mut self) {
+        for proc in &mut self.processes {
+            proc.pct_of_job_peak = percent_of(proc.max_rss_kib, self.max_total_rss_kib);
+        }
+    }
+
+    /// Report the total peak relative to `baseline_kib`, keeping the raw peak intact
+    pub fn apply_baseline(&mut self, baseline_kib: u64) {
+        self.baseline_rss_kib = Some(baseline_kib);
+        self.max_total_above_baseline_kib =
+            Some(self.max_total_rss_kib.saturating_sub(baseline_kib));
+    }
 }
 
 /// Snapshot of all processes in the job at a point in time
@@ -106,50 +1070,170 @@ pub struct JobSnapshot {
     pub timestamp: DateTime<Utc>,
     pub total_rss_kib: u64,
     pub processes: Vec<ProcessSample>,
+    /// Processes outside the job whose parent is init, checked against earlier samples
+    /// for job processes that were reparented away
+    pub reparented: Vec<ProcessSample>,
 }
 
 /// Accumulated job state during sampling
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct JobState {
     pub start_time: DateTime<Utc>,
     pub max_total_rss_kib: u64,
+    /// Total RSS of the first sample that saw any memory
+    pub first_total_rss_kib: Option<u64>,
     pub samples: usize,
     pub process_stats: HashMap<i32, ProcessStats>,
     pub timeline: Option<Vec<TimelinePoint>>,
+    /// Record each process's RSS in every timeline point (implies a timeline)
+    pub track_process_timeline: bool,
+    /// Latest kernel high-water mark seen for each process
+    pub hwm_kib: HashMap<i32, u64>,
+    /// Latest CPU time seen for each process
+    pub cpu_seconds: HashMap<i32, f64>,
+    /// Sum of sampled RSS and number of samples for each process, for averages
+    pub rss_sums: HashMap<i32, (u64, u64)>,
+    /// The snapshot that set `max_total_rss_kib`
+    pub peak_snapshot: Option<PeakSnapshot>,
+    /// Every snapshot, unaggregated, when raw samples were requested
+    pub raw_samples: Option<Vec<RawSample>>,
+    /// Sampling failures, in the order they happened
+    pub sampling_errors: Vec<SamplingError>,
+    /// `--alert` levels crossed so far
+    pub alerts: Vec<MemoryAlert>,
+    /// Pauses that have ended
+    pub pauses: Vec<PausePeriod>,
+    /// When the current pause began, while paused
+    pub paused_since: Option<DateTime<Utc>>,
+    /// Job processes seen reparented to init, by PID
+    pub escaped: HashMap<i32, EscapedProcess>,
 }
 
 impl JobState {
-    pub fn new(track_timeline: bool) -> Self {
+    pub fn new(track_timeline: bool, track_process_timeline: bool) -> Self {
         Self {
             start_time: Utc::now(),
             max_total_rss_kib: 0,
+            first_total_rss_kib: None,
             samples: 0,
             process_stats: HashMap::new(),
-            timeline: if track_timeline {
+            timeline: if track_timeline || track_process_timeline {
                 Some(Vec::new())
             } else {
                 None
             },
+            track_process_timeline,
+            hwm_kib: HashMap::new(),
+            cpu_seconds: HashMap::new(),
+            rss_sums: HashMap::new(),
+            peak_snapshot: None,
+            raw_samples: None,
+            sampling_errors: Vec::new(),
+            alerts: Vec::new(),
+            pauses: Vec::new(),
+            paused_since: None,
+            escaped: HashMap::new(),
         }
     }
 
-    pub fn update(&mut self, snapshot: JobSnapshot) {
+    /// Fold a snapshot into the state, returning the PIDs whose peak RSS was raised
+    /// (including processes seen for the first time)
+    pub fn update(&mut self, snapshot: JobSnapshot) -> Vec<i32> {
         self.samples += 1;
+        let mut new_peaks = Vec::new();
+        let elapsed_seconds =
+            (snapshot.timestamp - self.start_time).num_milliseconds() as f64 / 1000.0;
+
+        if snapshot.total_rss_kib > self.max_total_rss_kib {
+            let mut processes: Vec<PeakProcess> = snapshot
+                .processes
+                .iter()
+                .filter(|p| p.rss_kib > 0)
+                .map(|p| PeakProcess {
+                    pid: p.pid,
+                    rss_kib: p.rss_kib,
+                    command: p.command.clone(),
+                })
+                .collect();
+            processes.sort_by(|a, b| b.rss_kib.cmp(&a.rss_kib).then(a.pid.cmp(&b.pid)));
+            self.peak_snapshot = Some(PeakSnapshot {
+                timestamp: snapshot.timestamp,
+                elapsed_seconds,
+                total_rss_kib: snapshot.total_rss_kib,
+                processes,
+            });
+        }
         self.max_total_rss_kib = self.max_total_rss_kib.max(snapshot.total_rss_kib);
+        if self.first_total_rss_kib.is_none() && snapshot.total_rss_kib > 0 {
+            self.first_total_rss_kib = Some(snapshot.total_rss_kib);
+        }
 
         // Track timeline if requested
         if let Some(timeline) = &mut self.timeline {
-            let elapsed_seconds =
-                (snapshot.timestamp - self.start_time).num_milliseconds() as f64 / 1000.0;
             timeline.push(TimelinePoint {
                 timestamp: snapshot.timestamp,
                 elapsed_seconds,
                 total_rss_kib: snapshot.total_rss_kib,
                 process_count: snapshot.processes.len(),
+                process_rss_kib: self.track_process_timeline.then(|| {
+                    snapshot
+                        .processes
+                        .iter()
+                        .map(|p| (p.pid, p.rss_kib))
+                        .collect()
+                }),
+                smoothed_rss_kib: None,
             });
         }
 
+        if let Some(raw_samples) = &mut self.raw_samples {
+            raw_samples.push(RawSample {
+                timestamp: snapshot.timestamp,
+                elapsed_seconds,
+                total_rss_kib: snapshot.total_rss_kib,
+                processes: snapshot
+                    .processes
+                    .iter()
+                    .map(|p| RawProcessSample {
+                        pid: p.pid,
+                        ppid: p.ppid,
+                        rss_kib: p.rss_kib,
+                    })
+                    .collect(),
+            });
+        }
+
+        for proc in &snapshot.reparented {
+            if let Some(stats) = self.process_stats.get(&proc.pid)
+                && stats.ppid != 1
+                && (stats.start_time.is_none() || stats.start_time == proc.start_time)
+            {
+                // Only a newly escaped process needs its command copied
+                self.escaped
+                    .entry(proc.pid)
+                    .and_modify(|escaped| escaped.rss_kib = proc.rss_kib)
+                    .or_insert_with(|| EscapedProcess {
+                        pid: proc.pid,
+                        command: stats.command.clone(),
+                        rss_kib: proc.rss_kib,
+                    });
+            }
+        }
+
         for proc in snapshot.processes {
+            // Back in the job, e.g. followed by --drain
+            self.escaped.remove(&proc.pid);
+
+            if let Some(hwm) = proc.hwm_kib {
+                self.hwm_kib.insert(proc.pid, hwm);
+            }
+            if let Some(cpu) = proc.cpu_seconds {
+                self.cpu_seconds.insert(proc.pid, cpu);
+            }
+            let (sum, count) = self.rss_sums.entry(proc.pid).or_default();
+            *sum += proc.rss_kib;
+            *count += 1;
+
             self.process_stats
                 .entry(proc.pid)
                 .and_modify(|stats| {
@@ -157,79 +1241,535 @@ impl JobState {
                     if proc.rss_kib > stats.max_rss_kib {
                         stats.max_rss_kib = proc.rss_kib;
                         stats.peak_time = snapshot.timestamp;
+                        stats.update_growth();
+                        new_peaks.push(proc.pid);
+                    }
+                    if stats.exe_path.is_none() {
+                        stats.exe_path = proc.exe_path.clone();
                     }
                     stats.last_seen = snapshot.timestamp;
+                    stats.update_lifetime();
                 })
-                .or_insert_with(|| ProcessStats {
-                    pid: proc.pid,
-                    ppid: proc.ppid,
-                    command: proc.command,
-                    max_rss_kib: proc.rss_kib,
-                    first_seen: snapshot.timestamp,
-                    last_seen: snapshot.timestamp,
-                    peak_time: snapshot.timestamp,
+                .or_insert_with(|| {
+                    new_peaks.push(proc.pid);
+                    let mut stats = ProcessStats {
+                        pid: proc.pid,
+                        ppid: proc.ppid,
+                        command: proc.command,
+                        exe_path: proc.exe_path,
+                        max_rss_kib: proc.rss_kib,
+                        first_seen: snapshot.timestamp,
+                        last_seen: snapshot.timestamp,
+                        peak_time: snapshot.timestamp,
+                        first_rss_kib: Some(proc.rss_kib),
+                        rss_growth_kib: Some(0),
+                        start_time: proc.start_time,
+                        ..Default::default()
+                    };
+                    stats.update_lifetime();
+                    stats
+                });
+        }
+
+        new_peaks
+    }
+
+    /// Note a sampling failure at the current time
+    pub fn record_error(&mut self, message: String) {
+        self.sampling_errors.push(SamplingError {
+            timestamp: Utc::now(),
+            message,
+        });
+    }
+
+    /// Record every level in `levels_kib` that `total_rss_kib` is above for the first
+    /// time, returning the new crossings
+    pub fn record_alerts(
+        &mut self,
+        levels_kib: &[u64],
+        total_rss_kib: u64,
+        timestamp: DateTime<Utc>,
+    ) -> &[MemoryAlert] {
+        let fired = self.alerts.len();
+        for &level_kib in levels_kib {
+            if total_rss_kib > level_kib && !self.alerts.iter().any(|a| a.level_kib == level_kib) {
+                self.alerts.push(MemoryAlert {
+                    level_kib,
+                    total_rss_kib,
+                    timestamp,
+                    elapsed_seconds: seconds_between(self.start_time, timestamp),
                 });
+            }
+        }
+        &self.alerts[fired..]
+    }
+
+    /// Pause sampling, or resume it if paused, returning whether it is now paused.
+    /// A pause is only recorded once it ends
+    pub fn toggle_pause(&mut self, now: DateTime<Utc>) -> bool {
+        match self.paused_since.take() {
+            Some(start) => {
+                self.pauses.push(PausePeriod {
+                    start,
+                    start_elapsed_seconds: seconds_between(self.start_time, start),
+                    duration_seconds: seconds_between(start, now),
+                });
+                false
+            }
+            None => {
+                self.paused_since = Some(now);
+                true
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_since.is_some()
+    }
+
+    /// Record a process's latest cumulative I/O counters
+    pub fn record_io(&mut self, pid: i32, io: IoBytes) {
+        if let Some(stats) = self.process_stats.get_mut(&pid) {
+            stats.io_read_bytes = Some(io.read_bytes);
+            stats.io_write_bytes = Some(io.write_bytes);
+        }
+    }
+
+    /// Attach a private/shared split to a process's current peak
+    pub fn record_memory_split(&mut self, pid: i32, split: MemorySplit) {
+        if let Some(stats) = self.process_stats.get_mut(&pid) {
+            stats.private_kib = Some(split.private_kib);
+            stats.shared_kib = Some(split.shared_kib);
+        }
+    }
+
+    /// Attach a NUMA breakdown to a process's current peak
+    pub fn record_numa(&mut self, pid: i32, nodes: HashMap<u32, u64>) {
+        if let Some(stats) = self.process_stats.get_mut(&pid) {
+            stats.numa_rss_kib = Some(nodes);
+        }
+    }
+
+    pub fn into_profile(
+        mut self,
+        command: Vec<String>,
+        interval: std::time::Duration,
+        exit_code: Option<i32>,
+        filter: FilterConfig,
+    ) -> anyhow::Result<JobProfile> {
+        let end_time = Utc::now();
+        let duration_seconds = (end_time - self.start_time).num_milliseconds() as f64 / 1000.0;
+        // A run that ends paused ends its pause too
+        if self.is_paused() {
+            self.toggle_pause(end_time);
+        }
+
+        let mut all_processes: Vec<ProcessStats> = self.process_stats.into_values().collect();
+
+        // The high-water mark only grows, so the last one seen is the process's true
+        // peak; use it where sampling fell short. The job total stays sampled, since
+        // per-process peaks need not have coincided in time.
+        for stats in &mut all_processes {
+            if let Some(&hwm) = self.hwm_kib.get(&stats.pid)
+                && hwm > stats.max_rss_kib
+            {
+                stats.sampled_max_rss_kib = Some(stats.max_rss_kib);
+                stats.max_rss_kib = hwm;
+                stats.update_growth();
+            }
+            if let Some(&(sum, count)) = self.rss_sums.get(&stats.pid) {
+                stats.avg_rss_kib = Some(sum / count);
+            }
+        }
+
+        // Ties in peak RSS go by PID, so the order does not depend on the HashMap
+        all_processes.sort_by_key(|p| (std::cmp::Reverse(p.max_rss_kib), p.pid));
+        let sum_of_peaks_kib = all_processes.iter().map(|p| p.max_rss_kib).sum();
+        // CPU time only grows, so each process's last reading is its total
+        let total_cpu_seconds =
+            (!self.cpu_seconds.is_empty()).then(|| self.cpu_seconds.values().sum());
+        let io_read_bytes = sum_present(all_processes.iter().map(|p| p.io_read_bytes));
+        let io_write_bytes = sum_present(all_processes.iter().map(|p| p.io_write_bytes));
+        let private_total_kib = sum_present(all_processes.iter().map(|p| p.private_kib));
+
+        // Apply filtering if patterns are provided
+        // Wrappers go first so user patterns only ever see the remaining processes
+        let (all_processes, hidden_wrapper_count, hidden_wrapper_rss) = if filter.hide_wrappers {
+            let (kept, wrapper_info) = apply_filter(all_processes, WRAPPER_PATTERNS, &[])?;
+            let (count, rss) =
+                wrapper_info.expect("filter_info must be Some when patterns provided");
+            (kept, count, rss)
+        } else {
+            (all_processes, 0, 0)
+        };
+
+        let (mut processes, filtered_process_count, filtered_total_rss_kib) =
+            if filter.has_patterns() {
+                let (filtered_processes, filter_info) = apply_filter(
+                    all_processes,
+                    &filter.exclude_patterns,
+                    &filter.include_patterns,
+                )?;
+
+                // No user patterns when only wrappers are hidden
+                let (filtered_count, filtered_rss) = filter_info.unwrap_or((0, 0));
+
+                (
+                    filtered_processes,
+                    Some(filtered_count + hidden_wrapper_count),
+                    Some(filtered_rss + hidden_wrapper_rss),
+                )
+            } else {
+                (all_processes, None, None)
+            };
+
+        // The size threshold applies to whatever the patterns kept
+        let (mut below_min_rss_count, mut below_min_rss_total_kib) = (0, 0);
+        if let Some(min) = filter.min_rss_kib {
+            processes.retain(|p| {
+                let keep = p.max_rss_kib >= min;
+                if !keep {
+                    below_min_rss_count += 1;
+                    below_min_rss_total_kib += p.max_rss_kib;
+                }
+                keep
+            });
+        }
+
+        // A process seen in a single sample has an observed lifetime of zero
+        let (mut short_lived_count, mut short_lived_total_kib) = (0, 0);
+        if let Some(min) = filter.min_lifetime_seconds {
+            processes.retain(|p| {
+                let keep = p.lifetime_seconds >= min;
+                if !keep {
+                    short_lived_count += 1;
+                    short_lived_total_kib += p.max_rss_kib;
+                }
+                keep
+            });
+        }
+
+        let filter = (filter.hides_processes() || filter.narrows_sampling()).then_some(filter);
+
+        let process_groups = compute_process_groups(&processes, false, &GroupBy::Basename);
+        for proc in &mut processes {
+            proc.pct_of_job_peak = percent_of(proc.max_rss_kib, self.max_total_rss_kib);
+        }
+
+        let mut escaped_processes: Vec<EscapedProcess> = self.escaped.into_values().collect();
+        escaped_processes.sort_by_key(|p| (std::cmp::Reverse(p.rss_kib), p.pid));
+
+        Ok(JobProfile {
+            schema_version: SCHEMA_VERSION,
+            command,
+            label: None,
+            tags: BTreeMap::new(),
+            start_time: self.start_time,
+            end_time,
+            duration_seconds,
+            interval_ms: interval.as_millis() as u64,
+            interval_us: interval.as_micros() as u64,
+            auto_interval_attempts: None,
+            warmup_runs: 0,
+            max_total_rss_kib: self.max_total_rss_kib,
+            peak_mode: PeakMode::Simultaneous,
+            units: Units::Binary,
+            simultaneous_peak_kib: self.max_total_rss_kib,
+            sum_of_peaks_kib,
+            total_cpu_seconds,
+            io_read_bytes,
+            io_write_bytes,
+            private_total_kib,
+            baseline_rss_kib: None,
+            max_total_above_baseline_kib: None,
+            samples: self.samples,
+            processes,
+            process_groups,
+            timeline: self.timeline,
+            peak_snapshot: self.peak_snapshot,
+            raw_samples: self.raw_samples,
+            exit_code,
+            killed_reason: None,
+            memory_limit_kib: None,
+            memory_limit_pct: None,
+            system_total_kib: None,
+            system_available_kib: None,
+            system_available_end_kib: None,
+            timed_out: false,
+            idle_stopped: false,
+            idle_stop_seconds: None,
+            idle_epsilon_kib: None,
+            timeout_seconds: None,
+            delay_seconds: None,
+            exited_during_delay: false,
+            drain_timed_out: false,
+            drain_seconds: None,
+            sampling_truncated: false,
+            detached: false,
+            interrupted: false,
+            interrupt_signal: None,
+            terminated_by_signal: None,
+            terminated_by_signal_name: None,
+            thresholds: None,
+            captured_output: None,
+            include_zero_rss: false,
+            group_by: GroupBy::Basename,
+            working_dir: None,
+            shell: None,
+            self_excluded: false,
+            env_cleared: false,
+            env_overrides: Vec::new(),
+            escaped_process_count: escaped_processes.len(),
+            escaped_processes,
+            sampling_errors: self.sampling_errors,
+            alerts: self.alerts,
+            pauses: self.pauses,
+            hidden_wrapper_count,
+            below_min_rss_count,
+            below_min_rss_total_kib,
+            short_lived_count,
+            short_lived_total_kib,
+            filter,
+            filtered_process_count,
+            filtered_total_rss_kib,
+            sources: None,
+            build_phase: None,
+            baseline_comparison: None,
+        })
+    }
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+fn is_zero_u64(n: &u64) -> bool {
+    *n == 0
+}
+
+/// `part` as a percentage of `total`, rounded to two decimals; None for a zero total
+pub fn percent_of(part: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| (part as f64 * 10_000.0 / total as f64).round() / 100.0)
+}
+
+/// Sum the values that are present, or None if there are none
+pub(crate) fn sum_present(values: impl Iterator<Item = Option<u64>>) -> Option<u64> {
+    values.flatten().reduce(|a, b| a + b)
+}
+
+/// Extract command name from full command line
+pub fn extract_command_name(command: &str) -> String {
+    // Take first word (command name)
+    let first_word = command.split_whitespace().next().unwrap_or(command);
+
+    // Get basename from path
+    if let Some(pos) = first_word.rfind('/') {
+        first_word[pos + 1..].to_string()
+    } else {
+        first_word.to_string()
+    }
+}
+
+/// How processes are keyed into `process_groups` (`--group-by`). Written in
+/// profiles as it is given on the command line
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum GroupBy {
+    /// The executable's basename (`extract_command_name`)
+    #[default]
+    Basename,
+    /// The basename of the first argument that is not an option, such as the script
+    /// an interpreter runs; commands without arguments fall back to the basename
+    FirstArg,
+    /// The first capture group (or the whole match, without groups) of a pattern
+    /// matched against the full command line; commands it does not match fall back
+    /// to the basename. Given as `regex:<pattern>` or as a bare pattern
+    Regex(Regex),
+}
+
+impl GroupBy {
+    pub fn is_basename(&self) -> bool {
+        matches!(self, GroupBy::Basename)
+    }
+
+    /// Group key of a process's command line
+    pub fn key(&self, command: &str) -> String {
+        match self {
+            GroupBy::Basename => extract_command_name(command),
+            GroupBy::FirstArg => match command
+                .split_whitespace()
+                .skip(1)
+                .find(|arg| !arg.starts_with('-'))
+            {
+                Some(arg) => extract_command_name(arg),
+                None => extract_command_name(command),
+            },
+            GroupBy::Regex(regex) => regex
+                .captures(command)
+                .and_then(|captures| captures.get(1).or_else(|| captures.get(0)))
+                .map(|key| key.as_str().to_string())
+                .unwrap_or_else(|| extract_command_name(command)),
         }
     }
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = MemwatchError;
+
+    /// `basename`, `first-arg`, or a pattern, optionally prefixed `regex:`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "basename" => Ok(GroupBy::Basename),
+            "first-arg" => Ok(GroupBy::FirstArg),
+            _ => {
+                let pattern = s.strip_prefix("regex:").unwrap_or(s);
+                Regex::new(pattern).map(GroupBy::Regex).map_err(|e| {
+                    MemwatchError::invalid_regex(
+                        format!("Invalid --group-by pattern '{}': {}", pattern, e),
+                        pattern,
+                    )
+                })
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupBy::Basename => write!(f, "basename"),
+            GroupBy::FirstArg => write!(f, "first-arg"),
+            GroupBy::Regex(regex) => write!(f, "regex:{}", regex.as_str()),
+        }
+    }
+}
+
+impl PartialEq for GroupBy {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
 
-    pub fn into_profile(
-        self,
-        command: Vec<String>,
-        interval_ms: u64,
-        exit_code: Option<i32>,
-        exclude_pattern: Option<String>,
-        include_pattern: Option<String>,
-    ) -> anyhow::Result<JobProfile> {
-        let end_time = Utc::now();
-        let duration_seconds = (end_time - self.start_time).num_milliseconds() as f64 / 1000.0;
+impl TryFrom<String> for GroupBy {
+    type Error = MemwatchError;
 
-        let mut all_processes: Vec<ProcessStats> = self.process_stats.into_values().collect();
-        all_processes.sort_by_key(|p| std::cmp::Reverse(p.max_rss_kib));
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
 
-        // Apply filtering if patterns are provided
-        let has_filter = exclude_pattern.is_some() || include_pattern.is_some();
-
-        let (processes, filter, filtered_process_count, filtered_total_rss_kib) = if has_filter {
-            let (filtered_processes, filter_info) = apply_filter(
-                all_processes,
-                exclude_pattern.as_deref(),
-                include_pattern.as_deref(),
-            )?;
-
-            let (filtered_count, filtered_rss) =
-                filter_info.expect("filter_info must be Some when patterns provided");
-
-            (
-                filtered_processes,
-                Some(FilterConfig {
-                    exclude_pattern,
-                    include_pattern,
-                }),
-                Some(filtered_count),
-                Some(filtered_rss),
-            )
-        } else {
-            (all_processes, None, None, None)
-        };
+impl From<GroupBy> for String {
+    fn from(group_by: GroupBy) -> Self {
+        group_by.to_string()
+    }
+}
 
-        Ok(JobProfile {
-            command,
-            start_time: self.start_time,
-            end_time,
-            duration_seconds,
-            interval_ms,
-            max_total_rss_kib: self.max_total_rss_kib,
-            samples: self.samples,
-            processes,
-            timeline: self.timeline,
-            exit_code,
-            filter,
-            filtered_process_count,
-            filtered_total_rss_kib,
-        })
+/// Sort keys for per-process tables; ties are broken by pid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProcessSort {
+    /// Peak RSS, largest first
+    #[default]
+    #[value(alias = "peak")]
+    Rss,
+    /// Average RSS over the samples that saw the process, largest first
+    Avg,
+    /// Time of the peak, earliest first
+    Time,
+    /// Process start time, earliest first (follows pipeline order)
+    Start,
+    /// Time the process was observed, longest first
+    Lifetime,
+    /// Process ID, lowest first
+    Pid,
+    /// Command line, alphabetically
+    Command,
+    /// Growth from the first observed RSS to the peak, largest first
+    Growth,
+}
+
+/// How per-process tables are ordered (`--sort-by`, `--reverse`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessOrder {
+    pub key: ProcessSort,
+    pub reverse: bool,
+}
+
+impl ProcessOrder {
+    /// Stable sort by `key` (reversed if asked), then by ascending pid so equal
+    /// values always come out in the same order
+    pub fn sort(&self, processes: &mut [&ProcessStats]) {
+        processes.sort_by(|a, b| {
+            let primary = match self.key {
+                ProcessSort::Rss => b.max_rss_kib.cmp(&a.max_rss_kib),
+                ProcessSort::Time => a.peak_time.cmp(&b.peak_time),
+                ProcessSort::Start => a
+                    .start_time
+                    .unwrap_or(a.first_seen)
+                    .cmp(&b.start_time.unwrap_or(b.first_seen)),
+                ProcessSort::Lifetime => b.lifetime_seconds.total_cmp(&a.lifetime_seconds),
+                ProcessSort::Pid => a.pid.cmp(&b.pid),
+                ProcessSort::Command => a.command.cmp(&b.command),
+                ProcessSort::Growth => b.rss_growth_kib.cmp(&a.rss_growth_kib),
+                ProcessSort::Avg => b.avg_rss_kib.cmp(&a.avg_rss_kib),
+            };
+            let primary = if self.reverse {
+                primary.reverse()
+            } else {
+                primary
+            };
+            primary.then(a.pid.cmp(&b.pid))
+        });
+    }
+}
+
+/// Compute process groups by `group_by` key, sorted by total peak RSS (descending).
+///
+/// Processes with 0 RSS are skipped unless `include_zero` is set, matching what the
+/// summary table displays.
+pub fn compute_process_groups(
+    processes: &[ProcessStats],
+    include_zero: bool,
+    group_by: &GroupBy,
+) -> Vec<ProcessGroup> {
+    let mut groups: HashMap<String, (usize, u64)> = HashMap::new();
+
+    for proc in processes
+        .iter()
+        .filter(|p| include_zero || p.max_rss_kib > 0)
+    {
+        let cmd_name = group_by.key(&proc.command);
+        groups
+            .entry(cmd_name)
+            .and_modify(|(count, total)| {
+                *count += 1;
+                *total += proc.max_rss_kib;
+            })
+            .or_insert((1, proc.max_rss_kib));
     }
+
+    let mut groups: Vec<ProcessGroup> = groups
+        .into_iter()
+        .map(|(command_name, (count, total_peak_rss_kib))| ProcessGroup {
+            command_name,
+            count,
+            total_peak_rss_kib,
+        })
+        .collect();
+
+    // Ties are broken by name so the output is deterministic
+    groups.sort_by(|a, b| {
+        b.total_peak_rss_kib
+            .cmp(&a.total_peak_rss_kib)
+            .then_with(|| a.command_name.cmp(&b.command_name))
+    });
+
+    groups
 }
 
+/// Result of filtering: processes that passed, plus (filtered_count, filtered_rss_kib)
+/// when any pattern was provided
+type FilterResult = (Vec<ProcessStats>, Option<(usize, u64)>);
+
 /// Apply include/exclude filters to process list.
 ///
 /// Takes ownership of the process list to avoid cloning. Processes that pass the filter
@@ -237,35 +1777,35 @@ impl JobState {
 ///
 /// # Arguments
 /// * `processes` - Owned vector of processes to filter
-/// * `exclude_pattern` - Regex pattern to exclude (optional)
-/// * `include_pattern` - Regex pattern to include (optional)
+/// * `exclude_patterns` - Regex patterns to exclude; matching any one excludes
+/// * `include_patterns` - Regex patterns to include; matching any one includes
 ///
 /// # Returns
 /// Tuple of (filtered_processes, Option<(filtered_count, filtered_rss_kib)>)
 ///
 /// # Errors
 /// Returns error if regex patterns are invalid
-fn apply_filter(
+fn apply_filter<S: AsRef<str>>(
     processes: Vec<ProcessStats>,
-    exclude_pattern: Option<&str>,
-    include_pattern: Option<&str>,
-) -> anyhow::Result<(Vec<ProcessStats>, Option<(usize, u64)>)> {
+    exclude_patterns: &[S],
+    include_patterns: &[S],
+) -> anyhow::Result<FilterResult> {
     use anyhow::Context;
 
-    let exclude_regex = match exclude_pattern {
-        Some(p) => Some(Regex::new(p).context(format!(
-            "Invalid exclude pattern '{}': must be valid regex",
-            p
-        ))?),
-        None => None,
-    };
-    let include_regex = match include_pattern {
-        Some(p) => Some(Regex::new(p).context(format!(
-            "Invalid include pattern '{}': must be valid regex",
-            p
-        ))?),
-        None => None,
+    let compile = |patterns: &[S], kind: &str| {
+        patterns
+            .iter()
+            .map(|p| {
+                let p = p.as_ref();
+                Regex::new(p).context(MemwatchError::invalid_regex(
+                    format!("Invalid {} pattern '{}': must be valid regex", kind, p),
+                    p,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
     };
+    let exclude_regexes = compile(exclude_patterns, "exclude")?;
+    let include_regexes = compile(include_patterns, "include")?;
 
     let mut filtered = Vec::new();
     let mut filtered_count = 0;
@@ -275,17 +1815,28 @@ fn apply_filter(
         let mut should_include = true;
 
         // Apply include filter first
-        if let Some(ref include) = include_regex {
-            should_include = include.is_match(&proc.command);
+        if !include_regexes.is_empty() {
+            should_include = include_regexes.iter().any(|r| r.is_match(&proc.command));
+            if !should_include {
+                log::debug!(
+                    "Filtered out pid {} (matches no include pattern): {}",
+                    proc.pid,
+                    proc.command
+                );
+            }
         }
 
         // Then apply exclude filter
-        if should_include {
-            if let Some(ref exclude) = exclude_regex {
-                if exclude.is_match(&proc.command) {
-                    should_include = false;
-                }
-            }
+        if should_include
+            && let Some(r) = exclude_regexes.iter().find(|r| r.is_match(&proc.command))
+        {
+            log::debug!(
+                "Filtered out pid {} (matches exclude pattern '{}'): {}",
+                proc.pid,
+                r.as_str(),
+                proc.command
+            );
+            should_include = false;
         }
 
         if should_include {
@@ -298,7 +1849,7 @@ fn apply_filter(
     }
 
     // Always track filter info if patterns were provided, even if nothing was filtered
-    let filter_info = if exclude_pattern.is_some() || include_pattern.is_some() {
+    let filter_info = if !exclude_patterns.is_empty() || !include_patterns.is_empty() {
         Some((filtered_count, filtered_rss))
     } else {
         None
@@ -320,6 +1871,7 @@ mod tests {
             first_seen: Utc::now(),
             last_seen: Utc::now(),
             peak_time: Utc::now(),
+            ..Default::default()
         }
     }
 
@@ -330,7 +1882,7 @@ mod tests {
             create_test_process(2, "rustc", 200),
         ];
 
-        let (filtered, info) = apply_filter(processes, None, None).unwrap();
+        let (filtered, info) = apply_filter::<&str>(processes, &[], &[]).unwrap();
 
         assert_eq!(filtered.len(), 2);
         assert_eq!(info, None);
@@ -344,7 +1896,7 @@ mod tests {
             create_test_process(3, "sleep", 50),
         ];
 
-        let (filtered, info) = apply_filter(processes, Some("cargo|rustc"), None).unwrap();
+        let (filtered, info) = apply_filter(processes, &["cargo|rustc"], &[]).unwrap();
 
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].command, "sleep");
@@ -359,7 +1911,7 @@ mod tests {
             create_test_process(3, "sleep", 50),
         ];
 
-        let (filtered, info) = apply_filter(processes, None, Some("^sleep")).unwrap();
+        let (filtered, info) = apply_filter(processes, &[], &["^sleep"]).unwrap();
 
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].command, "sleep");
@@ -376,7 +1928,7 @@ mod tests {
         ];
 
         // Include test*, exclude test_helper
-        let (filtered, info) = apply_filter(processes, Some("helper"), Some("test")).unwrap();
+        let (filtered, info) = apply_filter(processes, &["helper"], &["test"]).unwrap();
 
         assert_eq!(filtered.len(), 2);
         assert_eq!(filtered[0].command, "test_worker_1");
@@ -391,7 +1943,7 @@ mod tests {
             create_test_process(2, "rustc", 200),
         ];
 
-        let (filtered, info) = apply_filter(processes, Some(".*"), None).unwrap();
+        let (filtered, info) = apply_filter(processes, &[".*"], &[]).unwrap();
 
         assert_eq!(filtered.len(), 0);
         assert_eq!(info, Some((2, 300)));
@@ -404,7 +1956,7 @@ mod tests {
             create_test_process(2, "test2", 200),
         ];
 
-        let (filtered, info) = apply_filter(processes, Some("nonexistent"), None).unwrap();
+        let (filtered, info) = apply_filter(processes, &["nonexistent"], &[]).unwrap();
 
         assert_eq!(filtered.len(), 2);
         assert_eq!(info, Some((0, 0))); // Filter was provided but nothing matched
@@ -414,7 +1966,7 @@ mod tests {
     fn test_apply_filter_invalid_exclude_regex() {
         let processes = vec![create_test_process(1, "test", 100)];
 
-        let result = apply_filter(processes, Some("[[invalid"), None);
+        let result = apply_filter(processes, &["[[invalid"], &[]);
 
         assert!(result.is_err());
         let err_msg = format!("{}", result.unwrap_err());
@@ -426,7 +1978,7 @@ mod tests {
     fn test_apply_filter_invalid_include_regex() {
         let processes = vec![create_test_process(1, "test", 100)];
 
-        let result = apply_filter(processes, None, Some("[[invalid"));
+        let result = apply_filter(processes, &[], &["[[invalid"]);
 
         assert!(result.is_err());
         let err_msg = format!("{}", result.unwrap_err());
@@ -438,7 +1990,7 @@ mod tests {
     fn test_apply_filter_preserves_process_data() {
         let processes = vec![create_test_process(42, "test_app", 12345)];
 
-        let (filtered, _) = apply_filter(processes, None, Some("test")).unwrap();
+        let (filtered, _) = apply_filter(processes, &[], &["test"]).unwrap();
 
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].pid, 42);
@@ -446,13 +1998,816 @@ mod tests {
         assert_eq!(filtered[0].command, "test_app");
     }
 
+    #[test]
+    fn test_parse_size_kib() {
+        use memory::parse_size_kib;
+
+        assert_eq!(parse_size_kib("512"), Ok(512));
+        assert_eq!(parse_size_kib("1KiB"), Ok(1));
+        assert_eq!(parse_size_kib("10MiB"), Ok(10 * 1024));
+        assert_eq!(parse_size_kib("4GiB"), Ok(4 * 1024 * 1024));
+        assert_eq!(parse_size_kib("4g"), Ok(4 * 1024 * 1024));
+        assert_eq!(parse_size_kib("1.5 GiB"), Ok(1536 * 1024));
+        assert_eq!(parse_size_kib("1GB"), Ok(976_563));
+        assert_eq!(parse_size_kib("2048B"), Ok(2));
+        assert!(parse_size_kib("").is_err());
+        assert!(parse_size_kib("lots").is_err());
+        assert!(parse_size_kib("4 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_compute_process_groups_forked_workers() {
+        let processes = vec![
+            create_test_process(1, "/usr/bin/python3 main.py", 300),
+            create_test_process(2, "python3 worker.py --rank 0", 100),
+            create_test_process(3, "python3 worker.py --rank 1", 120),
+            create_test_process(4, "/usr/local/bin/python3 worker.py --rank 2", 110),
+            create_test_process(5, "/bin/sh -c run.sh", 500),
+            create_test_process(6, "zombie", 0),
+        ];
+
+        let groups = compute_process_groups(&processes, false, &GroupBy::Basename);
+
+        assert_eq!(
+            groups,
+            vec![
+                ProcessGroup {
+                    command_name: "python3".to_string(),
+                    count: 4,
+                    total_peak_rss_kib: 630,
+                },
+                ProcessGroup {
+                    command_name: "sh".to_string(),
+                    count: 1,
+                    total_peak_rss_kib: 500,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_by_modes() {
+        let python = "/usr/bin/python3 -u /srv/jobs/train.py --epochs 3";
+        let java = "java -jar build/app.jar";
+        let bare = "/usr/bin/node";
+
+        let basename = GroupBy::Basename;
+        assert_eq!(basename.key(python), "python3");
+        assert_eq!(basename.key(java), "java");
+
+        let first_arg: GroupBy = "first-arg".parse().unwrap();
+        assert_eq!(first_arg.key(python), "train.py");
+        assert_eq!(first_arg.key(java), "app.jar");
+        assert_eq!(first_arg.key(bare), "node");
+
+        let regex: GroupBy = r"regex:--rank (\d+)".parse().unwrap();
+        assert_eq!(regex.key("python3 worker.py --rank 2"), "2");
+        // Unmatched commands fall back to the basename
+        assert_eq!(regex.key(python), "python3");
+        // Without a capture group, the whole match is the key
+        let whole: GroupBy = r"regex:\w+\.py".parse().unwrap();
+        assert_eq!(whole.key(python), "train.py");
+
+        assert!("regex:(".parse::<GroupBy>().is_err());
+        assert!("(".parse::<GroupBy>().is_err());
+        // The prefix is optional
+        let unprefixed: GroupBy = r"python3 \S*/(\w+)\.py".parse().unwrap();
+        assert_eq!(unprefixed.key("python3 /srv/jobs/train.py"), "train");
+        assert_eq!(unprefixed, r"regex:python3 \S*/(\w+)\.py".parse().unwrap());
+
+        // Written the way it was given
+        assert_eq!(regex.to_string(), r"regex:--rank (\d+)");
+        let json = serde_json::to_string(&first_arg).unwrap();
+        assert_eq!(json, "\"first-arg\"");
+        assert_eq!(serde_json::from_str::<GroupBy>(&json).unwrap(), first_arg);
+    }
+
+    #[test]
+    fn test_group_by_regex_groups_processes() {
+        let processes = vec![
+            create_test_process(1, "python3 worker.py --rank 0", 100),
+            create_test_process(2, "python3 worker.py --rank 1", 120),
+            create_test_process(3, "python3 reducer.py", 300),
+            create_test_process(4, "/bin/sh -c run.sh", 50),
+        ];
+
+        let group_by: GroupBy = r"regex:python3 (\w+)\.py".parse().unwrap();
+        let groups = compute_process_groups(&processes, false, &group_by);
+        let keys: Vec<(&str, usize)> = groups
+            .iter()
+            .map(|g| (g.command_name.as_str(), g.count))
+            .collect();
+        assert_eq!(keys, vec![("reducer", 1), ("worker", 2), ("sh", 1)]);
+    }
+
+    #[test]
+    fn test_process_order_breaks_ties_by_pid() {
+        let start = Utc::now();
+        let mut processes = [
+            create_test_process(30, "cc", 200),
+            create_test_process(10, "ld", 100),
+            create_test_process(20, "cc", 200),
+            create_test_process(5, "as", 100),
+        ];
+        for (i, proc) in processes.iter_mut().enumerate() {
+            proc.start_time = Some(start + chrono::Duration::seconds(i as i64));
+        }
+
+        let pids = |key, reverse| {
+            let mut refs: Vec<_> = processes.iter().collect();
+            ProcessOrder { key, reverse }.sort(&mut refs);
+            refs.iter().map(|p| p.pid).collect::<Vec<_>>()
+        };
+
+        assert_eq!(pids(ProcessSort::Rss, false), vec![20, 30, 5, 10]);
+        assert_eq!(pids(ProcessSort::Rss, true), vec![5, 10, 20, 30]);
+        assert_eq!(pids(ProcessSort::Command, false), vec![5, 20, 30, 10]);
+        assert_eq!(pids(ProcessSort::Start, false), vec![30, 10, 20, 5]);
+        assert_eq!(pids(ProcessSort::Start, true), vec![5, 20, 10, 30]);
+        assert_eq!(pids(ProcessSort::Pid, true), vec![30, 20, 10, 5]);
+    }
+
     #[test]
     fn test_apply_filter_empty_input() {
         let processes: Vec<ProcessStats> = vec![];
 
-        let (filtered, info) = apply_filter(processes, Some("test"), None).unwrap();
+        let (filtered, info) = apply_filter(processes, &["test"], &[]).unwrap();
 
         assert_eq!(filtered.len(), 0);
         assert_eq!(info, Some((0, 0)));
     }
+
+    #[test]
+    fn test_apply_filter_multiple_patterns() {
+        let processes = vec![
+            create_test_process(1, "cargo test", 100),
+            create_test_process(2, "rustc", 200),
+            create_test_process(3, "test_worker", 50),
+            create_test_process(4, "test_helper", 25),
+            create_test_process(5, "sleep", 10),
+        ];
+
+        // Overlapping includes keep a process once; any exclude drops it
+        let (filtered, info) = apply_filter(
+            processes,
+            &["helper", "^cargo"],
+            &["test", "worker", "rustc"],
+        )
+        .unwrap();
+
+        let commands: Vec<_> = filtered.iter().map(|p| p.command.as_str()).collect();
+        assert_eq!(commands, vec!["rustc", "test_worker"]);
+        assert_eq!(info, Some((3, 135)));
+    }
+
+    #[test]
+    fn test_apply_filter_multiple_excludes_filter_everything() {
+        let processes = vec![
+            create_test_process(1, "cargo", 100),
+            create_test_process(2, "rustc", 200),
+        ];
+
+        let (filtered, info) = apply_filter(processes, &["cargo", "rustc"], &[]).unwrap();
+
+        assert!(filtered.is_empty());
+        assert_eq!(info, Some((2, 300)));
+    }
+
+    #[test]
+    fn test_filter_config_selects_owner() {
+        let all = FilterConfig::default();
+        assert!(all.selects_owner(Some(1000)));
+        assert!(all.selects_owner(None));
+
+        let mine = FilterConfig {
+            uids: vec![1000, 1001],
+            exclude_uids: vec![1001],
+            ..Default::default()
+        };
+        assert!(mine.selects_owner(Some(1000)));
+        assert!(!mine.selects_owner(Some(1001)));
+        assert!(!mine.selects_owner(Some(0)));
+        assert!(!mine.selects_owner(None));
+
+        let not_root = FilterConfig {
+            exclude_uids: vec![0],
+            ..Default::default()
+        };
+        assert!(!not_root.selects_owner(Some(0)));
+        assert!(not_root.selects_owner(None));
+    }
+
+    #[test]
+    fn test_filter_config_serializes_single_pattern_as_string() {
+        let single = FilterConfig {
+            exclude_patterns: vec!["cargo".to_string()],
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&single).unwrap();
+        assert_eq!(json, r#"{"exclude_pattern":"cargo"}"#);
+        assert_eq!(serde_json::from_str::<FilterConfig>(&json).unwrap(), single);
+
+        let many = FilterConfig {
+            exclude_patterns: vec!["cargo".to_string(), "rustc".to_string()],
+            include_patterns: vec!["test".to_string()],
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&many).unwrap();
+        assert_eq!(
+            json,
+            r#"{"exclude_pattern":["cargo","rustc"],"include_pattern":"test"}"#
+        );
+        assert_eq!(serde_json::from_str::<FilterConfig>(&json).unwrap(), many);
+    }
+
+    #[test]
+    fn test_update_lifetime_clamps_clock_skew() {
+        let now = Utc::now();
+        let mut stats = ProcessStats {
+            first_seen: now,
+            last_seen: now + chrono::Duration::milliseconds(2500),
+            // Started before memwatch first saw it
+            start_time: Some(now - chrono::Duration::seconds(10)),
+            ..Default::default()
+        };
+        stats.update_lifetime();
+        assert_eq!(stats.lifetime_seconds, 2.5);
+        assert_eq!(stats.age_seconds, Some(12.5));
+
+        // A start time rounded past the last sample must not go negative
+        stats.start_time = Some(stats.last_seen + chrono::Duration::milliseconds(400));
+        stats.update_lifetime();
+        assert_eq!(stats.age_seconds, Some(0.0));
+    }
+
+    #[test]
+    fn test_apply_baseline_clamps_at_zero() {
+        let mut profile = JobProfile {
+            max_total_rss_kib: 5000,
+            ..Default::default()
+        };
+        profile.apply_baseline(1200);
+        assert_eq!(profile.max_total_rss_kib, 5000);
+        assert_eq!(profile.baseline_rss_kib, Some(1200));
+        assert_eq!(profile.max_total_above_baseline_kib, Some(3800));
+
+        profile.apply_baseline(6000);
+        assert_eq!(profile.max_total_above_baseline_kib, Some(0));
+    }
+
+    #[test]
+    fn test_label_round_trips_through_json() {
+        let profile = JobProfile {
+            label: Some("bench-a1b2c3d".to_string()),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&profile).unwrap();
+        let loaded: JobProfile = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.label.as_deref(), Some("bench-a1b2c3d"));
+
+        // Absent labels stay out of the JSON
+        let json = serde_json::to_string(&JobProfile::default()).unwrap();
+        assert!(!json.contains("label"));
+    }
+
+    #[test]
+    fn test_peak_mode() {
+        let sample = |pid: i32, rss_kib: u64| {
+            ProcessSample::for_test(pid, 1, rss_kib, &format!("proc{}", pid))
+        };
+
+        // Each process peaks while the other is low
+        let mut state = JobState::new(false, false);
+        for (a, b) in [(100, 400), (500, 100)] {
+            state.update(JobSnapshot::for_test(vec![sample(1, a), sample(2, b)]));
+        }
+        let mut profile = state
+            .into_profile(
+                vec![],
+                std::time::Duration::from_millis(500),
+                None,
+                FilterConfig::default(),
+            )
+            .unwrap();
+        profile.apply_baseline(200);
+
+        assert_eq!(profile.simultaneous_peak_kib, 600);
+        assert_eq!(profile.sum_of_peaks_kib, 900);
+        assert_eq!(profile.max_total_rss_kib, 600);
+
+        profile.apply_peak_mode(PeakMode::SumOfPeaks);
+        assert_eq!(profile.max_total_rss_kib, 900);
+        assert_eq!(profile.max_total_above_baseline_kib, Some(700));
+
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["peak_mode"], "sum-of-peaks");
+        assert_eq!(json["simultaneous_peak_kib"], 600);
+    }
+
+    #[test]
+    fn test_pct_of_job_peak() {
+        assert_eq!(percent_of(300, 1200), Some(25.0));
+        assert_eq!(percent_of(1, 3), Some(33.33));
+        assert_eq!(percent_of(2, 3), Some(66.67));
+        // Per-process peaks can exceed a simultaneous total
+        assert_eq!(percent_of(1500, 1200), Some(125.0));
+        assert_eq!(percent_of(0, 1200), Some(0.0));
+        assert_eq!(percent_of(0, 0), None);
+        assert_eq!(percent_of(512, 0), None);
+
+        let process = |pid: i32, max_rss_kib: u64| ProcessStats {
+            pid,
+            max_rss_kib,
+            ..Default::default()
+        };
+        let mut profile = JobProfile {
+            max_total_rss_kib: 600,
+            simultaneous_peak_kib: 600,
+            sum_of_peaks_kib: 900,
+            processes: vec![process(1, 500), process(2, 400)],
+            ..Default::default()
+        };
+        profile.update_peak_shares();
+        assert_eq!(profile.processes[0].pct_of_job_peak, Some(83.33));
+        assert_eq!(profile.processes[1].pct_of_job_peak, Some(66.67));
+
+        // Follows the peak the profile reports
+        profile.apply_peak_mode(PeakMode::SumOfPeaks);
+        assert_eq!(profile.processes[0].pct_of_job_peak, Some(55.56));
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["processes"][1]["pct_of_job_peak"], 44.44);
+
+        profile.max_total_rss_kib = 0;
+        profile.update_peak_shares();
+        let json = serde_json::to_value(&profile).unwrap();
+        assert!(json["processes"][0].get("pct_of_job_peak").is_none());
+    }
+
+    #[test]
+    fn test_peak_snapshot_captures_tree_at_max_total() {
+        let sample = |pid: i32, rss_kib: u64| {
+            ProcessSample::for_test(pid, 1, rss_kib, &format!("proc{}", pid))
+        };
+        let mut state = JobState::new(false, false);
+        state.update(JobSnapshot::for_test(vec![]));
+        assert!(state.peak_snapshot.is_none());
+
+        // Process 1 peaks alone; the total peaks later with both running
+        state.update(JobSnapshot::for_test(vec![sample(1, 500)]));
+        state.update(JobSnapshot::for_test(vec![sample(1, 300), sample(2, 400)]));
+        state.update(JobSnapshot::for_test(vec![sample(2, 100)]));
+
+        let peak = state.peak_snapshot.unwrap();
+        assert_eq!(peak.total_rss_kib, 700);
+        assert_eq!(
+            peak.processes
+                .iter()
+                .map(|p| (p.pid, p.rss_kib))
+                .collect::<Vec<_>>(),
+            vec![(2, 400), (1, 300)]
+        );
+    }
+
+    #[test]
+    fn test_alerts_fire_once_per_level() {
+        let mut state = JobState::new(false, false);
+        let levels = [100, 200, 300];
+
+        assert!(state.record_alerts(&levels, 50, Utc::now()).is_empty());
+        let crossed: Vec<u64> = state
+            .record_alerts(&levels, 250, Utc::now())
+            .iter()
+            .map(|a| a.level_kib)
+            .collect();
+        assert_eq!(crossed, vec![100, 200]);
+        // Dropping back and rising again does not repeat an alert
+        state.record_alerts(&levels, 50, Utc::now());
+        assert!(state.record_alerts(&levels, 250, Utc::now()).is_empty());
+        // Reaching a level is not crossing it
+        assert!(state.record_alerts(&levels, 300, Utc::now()).is_empty());
+        assert_eq!(state.record_alerts(&levels, 301, Utc::now()).len(), 1);
+        assert_eq!(state.alerts.len(), 3);
+    }
+
+    #[test]
+    fn test_toggle_pause() {
+        let mut state = JobState::new(true, false);
+        let start = Utc::now() - chrono::Duration::seconds(10);
+        state.start_time = start;
+        let at = |seconds: i64| start + chrono::Duration::seconds(seconds);
+        let (pause, resume) = (at(2), at(5));
+
+        assert!(state.toggle_pause(pause));
+        assert!(state.is_paused());
+        // Nothing is recorded until the pause ends
+        assert!(state.pauses.is_empty());
+        assert!(!state.toggle_pause(resume));
+        assert!(!state.is_paused());
+        assert_eq!(
+            state.pauses,
+            vec![PausePeriod {
+                start: pause,
+                start_elapsed_seconds: 2.0,
+                duration_seconds: 3.0,
+            }]
+        );
+
+        // A run that ends paused closes its last pause when it ends, about 10s in
+        state.toggle_pause(at(6));
+        let profile = state
+            .into_profile(
+                vec!["sleep".to_string()],
+                std::time::Duration::from_millis(100),
+                Some(0),
+                FilterConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(profile.pauses.len(), 2);
+        assert_eq!(profile.pauses[1].start_elapsed_seconds, 6.0);
+        assert!(profile.paused_seconds() >= 6.5);
+    }
+
+    #[test]
+    fn test_reparented_job_processes_are_counted_as_escaped() {
+        let sample = |pid: i32, ppid: i32, rss_kib: u64| {
+            ProcessSample::for_test(pid, ppid, rss_kib, &format!("worker {}", pid))
+        };
+        let snapshot =
+            |processes: Vec<ProcessSample>, reparented: Vec<ProcessSample>| JobSnapshot {
+                reparented,
+                ..JobSnapshot::for_test(processes)
+            };
+
+        let mut state = JobState::new(false, false);
+        state.update(snapshot(
+            vec![sample(10, 1000, 100), sample(11, 10, 200)],
+            vec![],
+        ));
+        // 11's parent exited; 99 was never part of the job
+        state.update(snapshot(
+            vec![sample(10, 1000, 100)],
+            vec![sample(11, 1, 250), sample(99, 1, 500)],
+        ));
+        // Still outside the job; the RSS reported is the latest
+        state.update(snapshot(
+            vec![sample(10, 1000, 100)],
+            vec![sample(11, 1, 300)],
+        ));
+
+        let profile = state
+            .into_profile(
+                vec![],
+                std::time::Duration::from_millis(500),
+                None,
+                FilterConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(profile.escaped_process_count, 1);
+        assert_eq!(
+            profile.escaped_processes,
+            vec![EscapedProcess {
+                pid: 11,
+                command: "worker 11".to_string(),
+                rss_kib: 300,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_wrapper_patterns_match_launchers_only() {
+        let processes = vec![
+            create_test_process(1, "/bin/bash -c ./run.sh", 10),
+            create_test_process(2, "sh -c make", 10),
+            create_test_process(3, "/usr/bin/mpirun -n 4 ./solver", 10),
+            create_test_process(4, "orted --hnp", 10),
+            create_test_process(5, "hydra_pmi_proxy --control-port x", 10),
+            create_test_process(6, "/home/u/.cargo/bin/cargo test", 10),
+            create_test_process(7, "memwatch run -- make", 10),
+            create_test_process(8, "bash ./run.sh", 10),
+            create_test_process(9, "./solver --mpirun-compat", 10),
+            create_test_process(10, "cargo-nextest run", 10),
+            create_test_process(11, "rustc --crate-name foo", 10),
+        ];
+
+        let (kept, info) = apply_filter(processes, WRAPPER_PATTERNS, &[]).unwrap();
+
+        let pids: Vec<_> = kept.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, vec![8, 9, 10, 11]);
+        assert_eq!(info, Some((7, 70)));
+    }
+
+    #[test]
+    fn test_hide_wrappers_layers_under_user_patterns() {
+        let sample = |pid: i32, ppid: i32, command: &str, rss_kib: u64| {
+            ProcessSample::for_test(pid, ppid, rss_kib, command)
+        };
+        let processes = vec![
+            sample(10, 1, "sh -c mpirun -n 2 ./solver", 100),
+            sample(11, 10, "mpirun -n 2 ./solver", 200),
+            sample(12, 11, "./solver", 1000),
+            sample(13, 11, "./solver", 1100),
+            sample(14, 12, "gzip", 50),
+        ];
+        let mut state = JobState::new(false, false);
+        state.update(JobSnapshot::for_test(processes));
+
+        let profile = state
+            .into_profile(
+                vec![],
+                std::time::Duration::from_millis(500),
+                None,
+                FilterConfig {
+                    exclude_patterns: vec!["gzip".to_string()],
+                    hide_wrappers: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(profile.max_total_rss_kib, 2450);
+        assert_eq!(profile.processes.len(), 2);
+        assert_eq!(profile.hidden_wrapper_count, 2);
+        assert_eq!(profile.filtered_process_count, Some(3));
+        assert_eq!(profile.filtered_total_rss_kib, Some(350));
+        assert_eq!(profile.process_groups.len(), 1);
+        assert_eq!(profile.process_groups[0].count, 2);
+        assert!(profile.filter.unwrap().hide_wrappers);
+    }
+
+    #[test]
+    fn test_rss_growth_across_samples() {
+        let sample = |pid: i32, rss_kib: u64| {
+            ProcessSample::for_test(pid, 1, rss_kib, &format!("worker {}", pid))
+        };
+        let mut state = JobState::new(false, false);
+        state.update(JobSnapshot::for_test(vec![
+            sample(10, 5000),
+            sample(11, 100),
+        ]));
+        state.update(JobSnapshot::for_test(vec![
+            sample(10, 4000),
+            sample(11, 900),
+        ]));
+
+        let profile = state
+            .into_profile(
+                vec![],
+                std::time::Duration::from_millis(500),
+                None,
+                FilterConfig::default(),
+            )
+            .unwrap();
+
+        // 10 only shrank, so its peak is its first sample
+        let growth = |pid: i32| {
+            let p = profile.processes.iter().find(|p| p.pid == pid).unwrap();
+            (p.first_rss_kib, p.rss_growth_kib)
+        };
+        assert_eq!(growth(10), (Some(5000), Some(0)));
+        assert_eq!(growth(11), (Some(100), Some(800)));
+
+        let order = ProcessOrder {
+            key: ProcessSort::Growth,
+            reverse: false,
+        };
+        let pids: Vec<_> = profile
+            .sorted_processes(order)
+            .iter()
+            .map(|p| p.pid)
+            .collect();
+        assert_eq!(pids, vec![11, 10]);
+    }
+
+    #[test]
+    fn test_average_rss_across_samples() {
+        let sample = |pid: i32, rss_kib: u64| {
+            ProcessSample::for_test(pid, 1, rss_kib, &format!("worker {}", pid))
+        };
+        let mut state = JobState::new(false, false);
+        for rss in [100, 100, 1600] {
+            state.update(JobSnapshot::for_test(vec![
+                sample(10, 1000),
+                sample(11, rss),
+            ]));
+        }
+
+        let profile = state
+            .into_profile(
+                vec![],
+                std::time::Duration::from_millis(500),
+                None,
+                FilterConfig::default(),
+            )
+            .unwrap();
+
+        // 11 has the higher peak, but 10 holds more memory on average
+        let avg = |reverse: bool| {
+            profile
+                .sorted_processes(ProcessOrder {
+                    key: ProcessSort::Avg,
+                    reverse,
+                })
+                .iter()
+                .map(|p| (p.pid, p.avg_rss_kib))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(avg(false), vec![(10, Some(1000)), (11, Some(600))]);
+        assert_eq!(avg(true), vec![(11, Some(600)), (10, Some(1000))]);
+        assert_eq!(profile.processes[0].pid, 11);
+    }
+
+    #[test]
+    fn test_min_rss_applies_after_patterns() {
+        let sample = |pid: i32, command: &str, rss_kib: u64| {
+            ProcessSample::for_test(pid, 1, rss_kib, command)
+        };
+        let processes = vec![
+            sample(10, "make", 4000),
+            sample(11, "sed s/a/b/", 300),
+            sample(12, "sh -c true", 200),
+            sample(13, "cc1", 9000),
+            sample(14, "cc1", 500),
+        ];
+        let mut state = JobState::new(false, false);
+        state.update(JobSnapshot::for_test(processes));
+
+        let profile = state
+            .into_profile(
+                vec![],
+                std::time::Duration::from_millis(500),
+                None,
+                FilterConfig {
+                    exclude_patterns: vec!["^make".to_string()],
+                    min_rss_kib: Some(1000),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        // make is counted by the pattern, not the threshold
+        let pids: Vec<_> = profile.processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, vec![13]);
+        assert_eq!(profile.filtered_process_count, Some(1));
+        assert_eq!(profile.below_min_rss_count, 3);
+        assert_eq!(profile.below_min_rss_total_kib, 1000);
+        assert_eq!(profile.max_total_rss_kib, 14000);
+        assert_eq!(profile.process_groups.len(), 1);
+    }
+
+    #[test]
+    fn test_profile_name_and_file_stem() {
+        let mut profile = JobProfile {
+            command: vec!["/usr/bin/python3".to_string(), "train.py".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(profile.name(), "/usr/bin/python3 train.py");
+        assert_eq!(profile.file_stem(), "python3");
+
+        profile.label = Some("nightly run/2025 #3".to_string());
+        assert_eq!(profile.name(), "nightly run/2025 #3");
+        assert_eq!(profile.file_stem(), "nightly-run-2025--3");
+
+        profile.label = Some("..".to_string());
+        assert_eq!(profile.file_stem(), "memwatch");
+    }
+
+    #[test]
+    fn test_tags() {
+        let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+        let (tags, repeated) = collect_tags(&[
+            pair("node", "n01"),
+            pair("commit", "abc"),
+            pair("node", "n02"),
+            pair("node", "n03"),
+        ]);
+        assert_eq!(repeated, vec!["node"]);
+
+        let profile = JobProfile {
+            tags,
+            ..Default::default()
+        };
+        assert_eq!(profile.format_tags(), "commit=abc node=n03");
+        assert!(profile.has_tags(&[]));
+        assert!(profile.has_tags(&[pair("node", "n03"), pair("commit", "abc")]));
+        assert!(!profile.has_tags(&[pair("node", "n01")]));
+        assert!(!profile.has_tags(&[pair("dataset", "abc")]));
+
+        // Untagged profiles leave the field out entirely
+        let json = serde_json::to_value(JobProfile::default()).unwrap();
+        assert!(json.get("tags").is_none());
+    }
+
+    #[test]
+    fn test_min_lifetime_drops_short_lived_processes() {
+        let sample = |pid: i32, rss_kib: u64| {
+            ProcessSample::for_test(pid, 1, rss_kib, &format!("worker-{}", pid))
+        };
+        let start = Utc::now();
+        let mut state = JobState::new(false, false);
+        // 10 lives through all samples, 11 spans one second, 12 is seen only once
+        for (offset_ms, pids) in [
+            (0, vec![10, 11]),
+            (1000, vec![10, 11, 12]),
+            (3000, vec![10]),
+        ] {
+            let processes: Vec<_> = pids.into_iter().map(|pid| sample(pid, 100)).collect();
+            state.update(JobSnapshot {
+                timestamp: start + chrono::Duration::milliseconds(offset_ms),
+                ..JobSnapshot::for_test(processes)
+            });
+        }
+
+        let profile = state
+            .into_profile(
+                vec![],
+                std::time::Duration::from_millis(1000),
+                None,
+                FilterConfig {
+                    min_lifetime_seconds: Some(1.0),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let pids: Vec<_> = profile.processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, vec![10, 11]);
+        assert_eq!(profile.short_lived_count, 1);
+        assert_eq!(profile.short_lived_total_kib, 100);
+        assert_eq!(profile.max_total_rss_kib, 300);
+        assert!(profile.filter.is_some());
+    }
+
+    #[test]
+    fn test_raw_samples_kept_only_when_requested() {
+        let snapshot = || JobSnapshot::for_test(vec![ProcessSample::for_test(7, 1, 300, "worker")]);
+        let profile = |raw: bool| {
+            let mut state = JobState::new(false, false);
+            if raw {
+                state.raw_samples = Some(Vec::new());
+            }
+            state.update(snapshot());
+            state.update(snapshot());
+            state
+                .into_profile(
+                    vec![],
+                    std::time::Duration::from_millis(500),
+                    None,
+                    FilterConfig::default(),
+                )
+                .unwrap()
+        };
+
+        let raw = profile(true).raw_samples.unwrap();
+        assert_eq!(raw.len(), 2);
+        assert_eq!(
+            raw[1].processes,
+            vec![RawProcessSample {
+                pid: 7,
+                ppid: 1,
+                rss_kib: 300
+            }]
+        );
+
+        let json = serde_json::to_string(&profile(false)).unwrap();
+        assert!(!json.contains("raw_samples"));
+    }
+
+    #[test]
+    fn test_hwm_corrects_missed_peak() {
+        let sample = |pid: i32, rss_kib: u64, hwm_kib: u64| ProcessSample {
+            hwm_kib: Some(hwm_kib),
+            ..ProcessSample::for_test(pid, 1, rss_kib, &format!("proc{}", pid))
+        };
+
+        let mut state = JobState::new(false, false);
+        state.update(JobSnapshot::for_test(vec![
+            sample(1, 100, 100),
+            sample(2, 200, 200),
+        ]));
+        // Process 1 spiked to 900 KiB between samples
+        state.update(JobSnapshot::for_test(vec![
+            sample(1, 150, 900),
+            sample(2, 200, 200),
+        ]));
+
+        let profile = state
+            .into_profile(
+                vec![],
+                std::time::Duration::from_millis(500),
+                None,
+                FilterConfig::default(),
+            )
+            .unwrap();
+
+        let spiky = profile.processes.iter().find(|p| p.pid == 1).unwrap();
+        assert_eq!(spiky.max_rss_kib, 900);
+        assert_eq!(spiky.sampled_max_rss_kib, Some(150));
+
+        let steady = profile.processes.iter().find(|p| p.pid == 2).unwrap();
+        assert_eq!(steady.max_rss_kib, 200);
+        assert_eq!(steady.sampled_max_rss_kib, None);
+
+        // The job peak stays the sampled total
+        assert_eq!(profile.max_total_rss_kib, 350);
+    }
 }
@@ -0,0 +1,372 @@
+//! `--webhook`: POST a small JSON payload when the job's total RSS crosses a threshold.
+//!
+//! The client is a minimal HTTP/1.1 POST over `std::net`, so only `http://` URLs are
+//! supported; an HTTPS endpoint needs a local relay. Sending is compiled in with the
+//! `webhook` feature, which is on by default.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long a notification may take to connect, send, and get its response
+#[cfg_attr(not(feature = "webhook"), allow(dead_code))]
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A parsed `http://host[:port][/path]` URL; an IPv6 host is written in brackets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookUrl {
+    /// Host name or address, without the brackets of an IPv6 literal
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl WebhookUrl {
+    /// The host as it appears in a URL, with an IPv6 literal in brackets
+    fn url_host(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        }
+    }
+
+    /// Value of the `Host` header, which names the port unless it is a default one
+    #[cfg_attr(not(feature = "webhook"), allow(dead_code))]
+    fn host_header(&self) -> String {
+        match self.port {
+            80 | 443 => self.url_host(),
+            port => format!("{}:{}", self.url_host(), port),
+        }
+    }
+}
+
+impl fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}:{}{}", self.url_host(), self.port, self.path)
+    }
+}
+
+/// Where and when to notify
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: WebhookUrl,
+    pub threshold_kib: u64,
+    /// Notify again after the total drops back below the threshold and crosses it again
+    pub repeat: bool,
+}
+
+/// Parse a `--webhook` URL
+#[cfg(feature = "webhook")]
+pub fn parse_url(url: &str) -> Result<WebhookUrl> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        anyhow::bail!(
+            "Invalid --webhook URL '{}': only http:// URLs are supported",
+            url
+        );
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, "/"),
+    };
+    // An IPv6 literal is bracketed so its colons are not taken for the port's
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(bracketed) => match bracketed.split_once(']') {
+            Some((host, rest)) if rest.is_empty() || rest.starts_with(':') => {
+                (host, rest.strip_prefix(':'))
+            }
+            _ => anyhow::bail!("Invalid --webhook URL '{}': bad IPv6 address", url),
+        },
+        None => match authority.rsplit_once(':') {
+            Some((host, _)) if host.contains(':') => anyhow::bail!(
+                "Invalid --webhook URL '{}': put an IPv6 address in brackets, as in http://[::1]:8080/",
+                url
+            ),
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid --webhook URL '{}': bad port", url))?,
+        None => 80,
+    };
+    if host.is_empty() {
+        anyhow::bail!("Invalid --webhook URL '{}': missing host", url);
+    }
+
+    Ok(WebhookUrl {
+        host: host.to_string(),
+        port,
+        path: path.to_string(),
+    })
+}
+
+#[cfg(not(feature = "webhook"))]
+pub fn parse_url(_url: &str) -> Result<WebhookUrl> {
+    anyhow::bail!("--webhook is not available: memwatch was built without the webhook feature")
+}
+
+/// Decides when the total has crossed the threshold
+#[derive(Debug, Clone)]
+struct Trigger {
+    threshold_kib: u64,
+    repeat: bool,
+    armed: bool,
+}
+
+impl Trigger {
+    /// Whether this total is a new crossing; without `repeat` only the first one counts
+    fn fires(&mut self, total_rss_kib: u64) -> bool {
+        if total_rss_kib > self.threshold_kib {
+            std::mem::replace(&mut self.armed, false)
+        } else {
+            self.armed |= self.repeat;
+            false
+        }
+    }
+}
+
+/// Sends the notifications for one run. Requests run on their own threads so a slow
+/// endpoint never delays sampling; only the first failure is reported.
+pub struct Notifier {
+    url: WebhookUrl,
+    trigger: Trigger,
+    command: String,
+    pid: i32,
+    failed: Arc<AtomicBool>,
+    pending: Vec<JoinHandle<()>>,
+}
+
+impl Notifier {
+    pub fn new(config: WebhookConfig, command: &[String], pid: i32) -> Self {
+        Self {
+            url: config.url,
+            trigger: Trigger {
+                threshold_kib: config.threshold_kib,
+                repeat: config.repeat,
+                armed: true,
+            },
+            command: command.join(" "),
+            pid,
+            failed: Arc::new(AtomicBool::new(false)),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Check a sample's total and notify on a crossing
+    pub fn observe(&mut self, total_rss_kib: u64, timestamp: DateTime<Utc>) {
+        if !self.trigger.fires(total_rss_kib) {
+            return;
+        }
+
+        let body = payload(
+            &self.command,
+            self.pid,
+            total_rss_kib,
+            self.trigger.threshold_kib,
+            timestamp,
+        );
+        let url = self.url.clone();
+        let failed = Arc::clone(&self.failed);
+        self.pending.push(thread::spawn(move || {
+            if let Err(e) = post(&url, &body)
+                && !failed.swap(true, Ordering::Relaxed)
+            {
+                eprintln!("Warning: --webhook POST to {} failed: {}", url, e);
+            }
+        }));
+    }
+
+    /// Wait for notifications still in flight, so a crossing just before the command
+    /// exits is still delivered
+    pub fn finish(self) {
+        for handle in self.pending {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The JSON body of a notification
+fn payload(
+    command: &str,
+    pid: i32,
+    total_rss_kib: u64,
+    threshold_kib: u64,
+    timestamp: DateTime<Utc>,
+) -> String {
+    serde_json::json!({
+        "event": "memory_threshold_exceeded",
+        "command": command,
+        "pid": pid,
+        "total_rss_kib": total_rss_kib,
+        "threshold_kib": threshold_kib,
+        "timestamp": timestamp.to_rfc3339(),
+    })
+    .to_string()
+}
+
+/// POST `body` as JSON and require a 2xx response
+#[cfg(feature = "webhook")]
+fn post(url: &WebhookUrl, body: &str) -> Result<()> {
+    use anyhow::Context;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+
+    let addr = (url.host.as_str(), url.port)
+        .to_socket_addrs()?
+        .next()
+        .context("host did not resolve")?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: memwatch/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        url.path,
+        url.host_header(),
+        env!("CARGO_PKG_VERSION"),
+        body.len(),
+        body
+    )?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+    if !status.starts_with('2') {
+        anyhow::bail!("server answered '{}'", status_line.trim());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "webhook"))]
+fn post(_url: &WebhookUrl, _body: &str) -> Result<()> {
+    anyhow::bail!("memwatch was built without the webhook feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigger_fires_once_per_crossing() {
+        let fired = |repeat: bool, totals: &[u64]| {
+            let mut trigger = Trigger {
+                threshold_kib: 100,
+                repeat,
+                armed: true,
+            };
+            totals.iter().filter(|&&total| trigger.fires(total)).count()
+        };
+
+        let totals = [50, 150, 200, 80, 120, 90, 300];
+        assert_eq!(fired(false, &totals), 1);
+        assert_eq!(fired(true, &totals), 3);
+        // Reaching the threshold is not exceeding it
+        assert_eq!(fired(true, &[100, 100]), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "webhook")]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("http://hooks.local:8080/notify?x=1").unwrap(),
+            WebhookUrl {
+                host: "hooks.local".to_string(),
+                port: 8080,
+                path: "/notify?x=1".to_string(),
+            }
+        );
+        let bare = parse_url("http://example.com").unwrap();
+        assert_eq!((bare.port, bare.path.as_str()), (80, "/"));
+        assert_eq!(bare.to_string(), "http://example.com:80/");
+
+        assert!(parse_url("https://example.com/").is_err());
+        assert!(parse_url("http://:80/").is_err());
+        assert!(parse_url("http://host:port/").is_err());
+
+        let ipv6 = parse_url("http://[::1]:9000/hook").unwrap();
+        assert_eq!((ipv6.host.as_str(), ipv6.port), ("::1", 9000));
+        assert_eq!(ipv6.to_string(), "http://[::1]:9000/hook");
+        assert_eq!(parse_url("http://[fe80::1]").unwrap().port, 80);
+        assert!(parse_url("http://::1:9000/").is_err());
+        assert!(parse_url("http://[::1/").is_err());
+        assert!(parse_url("http://[::1]x/").is_err());
+        assert!(parse_url("http://[]:80/").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "webhook")]
+    fn test_host_header_names_non_default_ports() {
+        let header = |url: &str| parse_url(url).unwrap().host_header();
+        assert_eq!(header("http://example.com/"), "example.com");
+        assert_eq!(header("http://example.com:443/"), "example.com");
+        assert_eq!(header("http://example.com:8080/"), "example.com:8080");
+        assert_eq!(header("http://[::1]/"), "[::1]");
+        assert_eq!(header("http://[::1]:8080/"), "[::1]:8080");
+    }
+
+    #[test]
+    #[cfg(feature = "webhook")]
+    fn test_notifier_posts_payload() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line);
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+
+        let config = WebhookConfig {
+            url: parse_url(&format!("http://127.0.0.1:{}/hook", port)).unwrap(),
+            threshold_kib: 1024,
+            repeat: false,
+        };
+        let command = vec!["./train".to_string(), "--big".to_string()];
+        let mut notifier = Notifier::new(config, &command, 4242);
+        notifier.observe(512, Utc::now());
+        notifier.observe(2048, Utc::now());
+        notifier.observe(4096, Utc::now());
+        assert_eq!(notifier.pending.len(), 1);
+        let failed = Arc::clone(&notifier.failed);
+        notifier.finish();
+        assert!(!failed.load(Ordering::Relaxed));
+
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "POST /hook HTTP/1.1\r\n");
+        assert_eq!(head[1], format!("Host: 127.0.0.1:{}\r\n", port));
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["command"], "./train --big");
+        assert_eq!(payload["pid"], 4242);
+        assert_eq!(payload["total_rss_kib"], 2048);
+        assert_eq!(payload["threshold_kib"], 1024);
+    }
+}
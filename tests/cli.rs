@@ -0,0 +1,576 @@
+//! End-to-end checks that run the memwatch binary itself
+
+use std::process::Command;
+
+// Prints "noise-out"/"noise-err"; the words never appear in the command line itself,
+// which the report echoes
+const CHATTY: &str =
+    "for i in 1 2 3; do printf 'no%se-out\\n' is; printf 'no%se-err\\n' is >&2; done";
+
+fn memwatch(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_memwatch"))
+        .args(args)
+        .output()
+        .expect("failed to run memwatch")
+}
+
+#[test]
+fn silent_leaves_only_the_report() {
+    let output = memwatch(&["run", "--silent", "--", "sh", "-c", CHATTY]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Job: sh -c"));
+    assert!(!stdout.contains("noise"));
+    assert!(!stderr.contains("noise"));
+}
+
+#[test]
+fn run_is_the_default_subcommand() {
+    let output = memwatch(&["--color", "never", "--json", "--", "sh", "-c", "exit 0"]);
+    assert!(output.status.success());
+
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        profile["command"],
+        serde_json::json!(["sh", "-c", "exit 0"])
+    );
+}
+
+#[test]
+fn capture_output_saves_to_files() {
+    let dir = std::env::temp_dir().join(format!("memwatch-capture-{}", std::process::id()));
+    let output = memwatch(&[
+        "run",
+        "--capture-output",
+        dir.to_str().unwrap(),
+        "--json",
+        "--",
+        "sh",
+        "-c",
+        CHATTY,
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(!stdout.contains("noise"));
+
+    let profile: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let saved = |stream: &str| {
+        std::fs::read_to_string(profile["captured_output"][stream].as_str().unwrap()).unwrap()
+    };
+    assert_eq!(saved("stdout").matches("noise-out").count(), 3);
+    assert_eq!(saved("stderr").matches("noise-err").count(), 3);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn summary_with_color(color: &str, env: &[(&str, &str)]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_memwatch"))
+        .args(["run", "--color", color, "--", "sleep", "0.1"])
+        .env_remove("NO_COLOR")
+        .env_remove("CLICOLOR_FORCE")
+        .env_remove("MEMWATCH_NO_COLOR")
+        .envs(env.iter().copied())
+        .output()
+        .expect("failed to run memwatch");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn interval_comes_from_flag_then_env_then_config() {
+    let dir = std::env::temp_dir().join(format!("memwatch-interval-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("memwatch.toml");
+    std::fs::write(&config, "[defaults]\ninterval = \"1s\"\n").unwrap();
+    let interval_us = |env: Option<&str>, args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_memwatch"));
+        cmd.args(["run", "--json"])
+            .args(args)
+            .args(["--", "true"])
+            // No config file unless a test passes one
+            .env("XDG_CONFIG_HOME", &dir)
+            .env_remove("MEMWATCH_CONFIG")
+            .env_remove("MEMWATCH_INTERVAL");
+        if let Some(interval) = env {
+            cmd.env("MEMWATCH_INTERVAL", interval);
+        }
+        let output = cmd.output().expect("failed to run memwatch");
+        let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        profile["interval_us"].as_u64().unwrap()
+    };
+    let config = config.to_str().unwrap();
+
+    assert_eq!(interval_us(Some("50"), &[]), 50_000);
+    assert_eq!(interval_us(Some("50"), &["-i", "200"]), 200_000);
+    assert_eq!(interval_us(Some("50"), &["--config", config]), 50_000);
+    assert_eq!(interval_us(None, &["--config", config]), 1_000_000);
+    assert_eq!(interval_us(None, &[]), 500_000);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn color_settings_control_escape_sequences() {
+    // stdout is a pipe here, so auto means no colors
+    assert!(!summary_with_color("auto", &[]).contains("\x1b["));
+    assert!(!summary_with_color("never", &[("CLICOLOR_FORCE", "1")]).contains("\x1b["));
+    assert!(summary_with_color("always", &[("NO_COLOR", "1")]).contains("\x1b["));
+    assert!(summary_with_color("auto", &[("CLICOLOR_FORCE", "1")]).contains("\x1b["));
+    assert!(
+        !summary_with_color("auto", &[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")])
+            .contains("\x1b[")
+    );
+}
+
+#[test]
+fn verbose_logs_go_to_stderr() {
+    let output = memwatch(&["-v", "run", "--json", "--", "sh", "-c", "sleep 0.2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success());
+    assert!(stderr.contains("DEBUG"));
+    assert!(stderr.contains("joined the job"));
+    // stdout still holds nothing but the profile
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(profile["command"][0], "sh");
+}
+
+#[test]
+fn json_errors_carry_a_code() {
+    let output = memwatch(&[
+        "--error-format",
+        "json",
+        "run",
+        "--",
+        "/nonexistent/memwatch-test-binary",
+    ]);
+    assert_eq!(output.status.code(), Some(127));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["code"], "command_not_found");
+    assert_eq!(error["command"], "/nonexistent/memwatch-test-binary");
+    assert_eq!(error["exit_code"], 127);
+
+    let output = memwatch(&[
+        "--error-format",
+        "json",
+        "run",
+        "--group-by",
+        "regex:(",
+        "--",
+        "true",
+    ]);
+    // Rejected while parsing arguments, before anything runs
+    assert_eq!(output.status.code(), Some(2));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["code"], "invalid_regex");
+    assert_eq!(error["pattern"], "(");
+
+    // Malformed sizes and an even --smooth window are rejected the same way
+    for flags in [["--max-memory", "bogus"], ["--smooth", "4"]] {
+        let mut args = vec!["--error-format", "json", "run"];
+        args.extend(flags);
+        args.extend(["--", "true"]);
+        let output = memwatch(&args);
+        assert_eq!(output.status.code(), Some(2));
+        // One JSON object and nothing else
+        let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+        assert_eq!(error["code"], "invalid_argument");
+        assert!(error["message"].as_str().unwrap().contains(flags[0]));
+    }
+
+    // Another procfs would not show the command run starts here
+    let output = memwatch(&[
+        "--error-format",
+        "json",
+        "run",
+        "--proc-path",
+        "/proc",
+        "--",
+        "true",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["code"], "invalid_argument");
+    assert!(error["message"].as_str().unwrap().contains("--proc-path"));
+
+    // Humans still get the one-line message
+    let output = memwatch(&["run", "--", "/nonexistent/memwatch-test-binary"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.trim_end(),
+        "Error: command not found: /nonexistent/memwatch-test-binary"
+    );
+}
+
+#[test]
+fn unstartable_commands_exit_like_a_shell() {
+    let output = memwatch(&["run", "--", "memwatch-no-such-command"]);
+    assert_eq!(output.status.code(), Some(127));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Error: command not found: memwatch-no-such-command\n"));
+
+    // A typo of something on PATH gets a suggestion; the PATH holds only `sh`
+    let output = Command::new(env!("CARGO_BIN_EXE_memwatch"))
+        .args(["--error-format", "json", "run", "--", "sj"])
+        .env(
+            "PATH",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/path"),
+        )
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(127));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["code"], "command_not_found");
+    assert_eq!(error["suggestion"], "sh");
+
+    let script = std::env::temp_dir().join(format!("memwatch-noexec-{}.sh", std::process::id()));
+    std::fs::write(&script, "#!/bin/sh\nexit 0\n").unwrap();
+    let output = memwatch(&[
+        "--error-format",
+        "json",
+        "run",
+        "--",
+        script.to_str().unwrap(),
+    ]);
+    std::fs::remove_file(&script).unwrap();
+    assert_eq!(output.status.code(), Some(126));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["code"], "permission_denied");
+    assert_eq!(error["exit_code"], 126);
+
+    // Commands that start are untouched, down to their exit code
+    let output = memwatch(&["run", "--quiet", "--", "sh", "-c", "exit 3"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Error"));
+}
+
+/// Start `memwatch run --json -- <command>`, send memwatch each of `signals` once
+/// sampling is under way, and collect its output and how long it took after the first
+fn interrupt_memwatch(command: &[&str], signals: &[libc::c_int]) -> (std::process::Output, f64) {
+    let child = Command::new(env!("CARGO_BIN_EXE_memwatch"))
+        .args(["run", "--json", "--"])
+        .args(command)
+        // Off the terminal, so the command gets a process group of its own
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("failed to run memwatch");
+    std::thread::sleep(std::time::Duration::from_millis(700));
+    let started = std::time::Instant::now();
+    for (i, &signal) in signals.iter().enumerate() {
+        if i > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+        }
+        unsafe {
+            libc::kill(child.id() as i32, signal);
+        }
+    }
+    let output = child.wait_with_output().unwrap();
+    (output, started.elapsed().as_secs_f64())
+}
+
+/// Start `memwatch run --json <args>` leading a session of its own, with a new pty as
+/// its controlling terminal and stdin, as from an interactive shell. Writing `\x03`
+/// to the returned master is a Ctrl-C on that terminal
+fn memwatch_on_terminal(args: &[&str]) -> (std::process::Child, std::fs::File) {
+    use std::os::fd::FromRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let (mut master, mut slave) = (0, 0);
+    let opened = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    assert_eq!(opened, 0, "openpty failed");
+    let (master, slave) = unsafe {
+        (
+            std::fs::File::from_raw_fd(master),
+            std::fs::File::from_raw_fd(slave),
+        )
+    };
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_memwatch"));
+    cmd.args(["run", "--json"])
+        .args(args)
+        .stdin(slave)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null());
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    (cmd.spawn().expect("failed to run memwatch"), master)
+}
+
+#[test]
+fn ctrl_c_still_yields_a_profile() {
+    let (output, seconds) = interrupt_memwatch(&["sleep", "10"], &[libc::SIGINT]);
+
+    // The SIGINT went to memwatch alone, so sleep only stopped because it was forwarded
+    assert!(seconds < 5.0);
+    assert_eq!(output.status.code(), Some(130));
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(profile["interrupted"], true);
+    assert_eq!(profile["interrupt_signal"], "SIGINT");
+    assert!(profile.get("detached").is_none());
+}
+
+#[test]
+fn ctrl_c_on_a_terminal_reaches_the_command_once() {
+    use std::io::Write;
+
+    // On a terminal the command shares memwatch's process group, so Ctrl-C reaches it
+    // straight from the terminal and memwatch must not send it a second SIGINT
+    let traps = std::env::temp_dir().join(format!("memwatch-traps-{}", std::process::id()));
+    let script = format!(
+        "trap 'echo >> {}' INT; while :; do sleep 0.1; done",
+        traps.display()
+    );
+    let (child, mut terminal) =
+        memwatch_on_terminal(&["--kill-grace", "1", "--", "sh", "-c", &script]);
+    std::thread::sleep(std::time::Duration::from_millis(700));
+    terminal.write_all(b"\x03").unwrap();
+    let session = child.id() as i32;
+    let output = child.wait_with_output().unwrap();
+    // Left running after the grace period; it is still in memwatch's group
+    unsafe {
+        libc::kill(-session, libc::SIGKILL);
+    }
+    let trapped = std::fs::read_to_string(&traps).unwrap_or_default();
+    let _ = std::fs::remove_file(&traps);
+
+    assert_eq!(trapped.lines().count(), 1);
+    assert_eq!(output.status.code(), Some(130));
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(profile["interrupted"], true);
+}
+
+#[test]
+fn second_ctrl_c_kills_the_job_at_once() {
+    use std::io::Write;
+
+    // The command ignores SIGINT, so only the second Ctrl-C, which is not forwarded
+    // but turned into SIGKILL, ends it before --kill-grace runs out
+    let (output, seconds) = interrupt_memwatch(
+        &["sh", "-c", "trap '' INT; sleep 30"],
+        &[libc::SIGINT, libc::SIGINT],
+    );
+
+    assert!(seconds < 3.0);
+    assert_eq!(output.status.code(), Some(130));
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(profile["interrupted"], true);
+    assert!(profile["samples"].as_u64().unwrap() > 0);
+
+    // The same from a terminal, where both Ctrl-Cs also reach the command directly
+    let (child, mut terminal) = memwatch_on_terminal(&["--", "sh", "-c", "trap '' INT; sleep 30"]);
+    std::thread::sleep(std::time::Duration::from_millis(700));
+    let started = std::time::Instant::now();
+    terminal.write_all(b"\x03").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    terminal.write_all(b"\x03").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(started.elapsed().as_secs_f64() < 3.0);
+    assert_eq!(output.status.code(), Some(130));
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(profile["interrupted"], true);
+}
+
+#[test]
+fn sigterm_takes_the_job_down_with_memwatch() {
+    // The inner sleep is orphaned at once, so only its process group still ties it
+    // to the job
+    let (output, seconds) = interrupt_memwatch(
+        &["sh", "-c", "(sleep 30 &); exec sleep 30"],
+        &[libc::SIGTERM],
+    );
+
+    assert!(seconds < 5.0);
+    assert_eq!(output.status.code(), Some(143));
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(profile["interrupted"], true);
+    assert_eq!(profile["interrupt_signal"], "SIGTERM");
+    let root = profile["processes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["pid"].as_i64().unwrap() as i32)
+        .min()
+        .unwrap();
+    // Nothing is left in the command's group, once init has reaped the orphan
+    let group_gone = (0..20).any(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        unsafe { libc::kill(-root, 0) == -1 }
+    });
+    assert!(group_gone);
+}
+
+#[test]
+fn timeout_stops_a_warmup_run() {
+    let started = std::time::Instant::now();
+    let output = memwatch(&[
+        "run",
+        "--warmup",
+        "1",
+        "--timeout",
+        "1s",
+        "--",
+        "sleep",
+        "30",
+    ]);
+
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Warmup run exceeded --timeout"));
+}
+
+#[test]
+fn sigusr1_dumps_partial_results() {
+    let dir = std::env::temp_dir().join(format!("memwatch-partial-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_memwatch"))
+        .args(["run", "--json-out"])
+        .arg(dir.join("profile.json"))
+        .arg("--timeline")
+        .arg(dir.join("timeline.csv"))
+        // Short enough that each request is answered well before the next one
+        .args(["-i", "50ms", "--", "sleep", "2"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("failed to run memwatch");
+    std::thread::sleep(std::time::Duration::from_millis(700));
+    // A second request is answered on its own and rewrites the same files
+    for _ in 0..2 {
+        unsafe {
+            libc::kill(child.id() as i32, libc::SIGUSR1);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(300));
+    }
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    // Sampling carried on to the end
+    assert!(output.status.success());
+    assert_eq!(stderr.matches("  current ").count(), 2);
+    assert!(stderr.contains(" sleep 2"));
+    assert_eq!(
+        stderr.matches("Partial JSON profile written to: ").count(),
+        2
+    );
+
+    let partial: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("profile.json.partial")).unwrap())
+            .unwrap();
+    let profile: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("profile.json")).unwrap()).unwrap();
+    assert!(partial["samples"].as_u64().unwrap() < profile["samples"].as_u64().unwrap());
+    assert!(
+        std::fs::read_to_string(dir.join("timeline.csv.partial"))
+            .unwrap()
+            .contains("elapsed_seconds")
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sigusr2_pauses_and_resumes_sampling() {
+    let child = Command::new(env!("CARGO_BIN_EXE_memwatch"))
+        .args(["run", "--json", "--timeline", "/dev/null", "-i", "50ms"])
+        .args(["--", "sleep", "2.5"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("failed to run memwatch");
+    // Pause half a second in, resume a second later
+    for delay in [500, 1000] {
+        std::thread::sleep(std::time::Duration::from_millis(delay));
+        unsafe {
+            libc::kill(child.id() as i32, libc::SIGUSR2);
+        }
+    }
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success());
+    assert!(stderr.contains("Sampling paused at t="));
+    assert!(stderr.contains("Sampling resumed at t="));
+
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let pauses = profile["pauses"].as_array().unwrap();
+    assert_eq!(pauses.len(), 1);
+    let start = pauses[0]["start_elapsed_seconds"].as_f64().unwrap();
+    let end = start + pauses[0]["duration_seconds"].as_f64().unwrap();
+    assert!((0.7..=1.3).contains(&(end - start)));
+
+    // The timeline has a gap where the pause was, and wall-clock times around it
+    let times: Vec<f64> = profile["timeline"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| point["elapsed_seconds"].as_f64().unwrap())
+        .collect();
+    let (start, end) = (start + 1e-6, end - 1e-6);
+    assert!(times.iter().all(|&t| t <= start || t >= end));
+    assert!(times.iter().any(|&t| t >= end));
+}
+
+#[test]
+fn snapshot_lists_the_current_process() {
+    let output = memwatch(&["snapshot", "--json"]);
+    assert!(output.status.success());
+
+    let snapshot: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let pids: Vec<i64> = snapshot["processes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["pid"].as_i64().unwrap())
+        .collect();
+    assert!(pids.contains(&(std::process::id() as i64)));
+
+    // Filters drop everything they do not match
+    let output = memwatch(&[
+        "snapshot",
+        "--json",
+        "--include",
+        "^memwatch-no-such-process$",
+    ]);
+    let snapshot: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(snapshot["processes"], serde_json::json!([]));
+}
+
+#[test]
+fn signal_deaths_are_reported() {
+    let output = memwatch(&["run", "--json", "--", "sh", "-c", "kill -SEGV $$"]);
+    // 128 + SIGSEGV, as a shell would report it
+    assert_eq!(output.status.code(), Some(139));
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(profile["exit_code"], 139);
+    assert_eq!(profile["terminated_by_signal"], libc::SIGSEGV);
+    assert_eq!(profile["terminated_by_signal_name"], "SIGSEGV");
+
+    let output = memwatch(&["run", "--color", "never", "--", "sh", "-c", "kill -KILL $$"]);
+    assert_eq!(output.status.code(), Some(137));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Terminated by SIGKILL (possibly OOM)"));
+
+    // An ordinary exit records no signal
+    let output = memwatch(&["run", "--json", "--", "sh", "-c", "exit 1"]);
+    let profile: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(profile["exit_code"], 1);
+    assert!(profile.get("terminated_by_signal").is_none());
+}